@@ -1,5 +1,14 @@
 const TCP_PORT = 24818;
-const MW_FRONTEND_PROTO_VERSION = 8;
+const MW_FRONTEND_PROTO_VERSION = 9;
+const MW_FRONTEND_MIN_PROTO_VERSION = 9;
+// apps older than this version only send their version and require it to match ours exactly
+const MW_FRONTEND_NEGOTIATION_VERSION = 9;
+// these must match multiworld::frontend::Capabilities, which is checked by a test in that module
+const MW_FRONTEND_CAPABILITY_DELTA_QUEUES = 0x00000001;
+const MW_FRONTEND_CAPABILITY_SCENE_REPORTING = 0x00000002;
+const MW_FRONTEND_CAPABILITY_DUNGEON_REWARD_INFO = 0x00000004;
+const MW_FRONTEND_CAPABILITY_PROGRESSIVE_ITEMS = 0x00000008;
+const MW_FRONTEND_CAPABILITIES = MW_FRONTEND_CAPABILITY_DELTA_QUEUES | MW_FRONTEND_CAPABILITY_SCENE_REPORTING | MW_FRONTEND_CAPABILITY_DUNGEON_REWARD_INFO | MW_FRONTEND_CAPABILITY_PROGRESSIVE_ITEMS;
 const DEFAULT_PLAYER_NAME = [0xdf, 0xdf, 0xdf, 0xdf, 0xdf, 0xdf, 0xdf, 0xdf];
 const SRAM_START = 0xA8000000;
 const REWARD_ROWS = [0, 1, 2, 8, 3, 4, 5, 7, 6];
@@ -50,20 +59,39 @@ var potsanity3 = false;
 var gapCount = 0;
 var lastScene = null;
 
+function make_handshake() {
+    // protocol version followed by the handshake: min version, max version, capabilities
+    const handshake = new ArrayBuffer(7);
+    const view = new DataView(handshake);
+    view.setUint8(0, MW_FRONTEND_PROTO_VERSION);
+    view.setUint8(1, MW_FRONTEND_MIN_PROTO_VERSION);
+    view.setUint8(2, MW_FRONTEND_PROTO_VERSION);
+    view.setUint32(3, MW_FRONTEND_CAPABILITIES);
+    return handshake;
+}
+
 function handle_data(sock, state, buf) {
     var newBuf = new Buffer(state.readBuf.length + buf.length);
     state.readBuf.copy(newBuf);
     buf.copy(newBuf, state.readBuf.length);
     state.readBuf = newBuf;
     if (!state.versionChecked && state.readBuf.length >= 1) {
-        // check to make sure the server's protocol version matches ours
-        if (state.readBuf[0] != MW_FRONTEND_PROTO_VERSION) {
+        if (state.readBuf[0] < MW_FRONTEND_NEGOTIATION_VERSION) {
             sock.close();
-            throw 'Version mismatch';
+            throw 'Version mismatch: the multiworld app is too old for this version of the Project64 script';
+        }
+        if (state.readBuf.length >= 7) {
+            // check to make sure the app's supported protocol versions overlap with ours
+            var appMinVersion = state.readBuf.readUInt8(1);
+            var appMaxVersion = state.readBuf.readUInt8(2);
+            if (Math.min(appMaxVersion, MW_FRONTEND_PROTO_VERSION) < Math.max(appMinVersion, MW_FRONTEND_MIN_PROTO_VERSION)) {
+                sock.close();
+                throw 'Version mismatch';
+            }
+            console.log('Connected! You can now close this window and continue in the multiworld app.');
+            state.versionChecked = true;
+            state.readBuf = state.readBuf.slice(7);
         }
-        console.log('Connected! You can now close this window and continue in the multiworld app.');
-        state.versionChecked = true;
-        state.readBuf = state.readBuf.slice(1);
     }
     var eof = false;
     while (state.versionChecked && !eof) {
@@ -811,8 +839,7 @@ if (typeof PJ64_JSAPI_VERSION === 'undefined') {
     console.log("This should take less than 5 seconds. If you don't see “connected” below, make sure the app is running.");
     console.log('If you need help, you can ask in #setup-support on the OoT Randomizer Discord or in #general on the OoTR MW Tournament Discord. Feel free to ping @fenhl.');
     sock.connect({host: "127.0.0.1", port: TCP_PORT}, function() {
-        const handshake = new ArrayBuffer(1);
-        new DataView(handshake).setUint8(0, MW_FRONTEND_PROTO_VERSION);
+        const handshake = make_handshake();
         sock.write(new Buffer(new Uint8Array(handshake)), function() {
             sock.on('data', function(buf) {
                 handle_data(sock, state, new Buffer(buf));
@@ -852,16 +879,15 @@ if (typeof PJ64_JSAPI_VERSION === 'undefined') {
                 console.log('Error connecting to multiworld app:' + e);
                 throw 'Error connecting to multiworld app:' + e;
             });
-            const handshake = new ArrayBuffer(1);
-            new DataView(handshake).setUint8(0, MW_FRONTEND_PROTO_VERSION);
+            const handshake = make_handshake();
             sock.write(new Buffer(new Uint8Array(handshake)), function() {
                 sock.on('data', function(buf) {
                     handle_data(sock, state, buf);
                 });
             });
         });
-        server.listen(TCP_PORT, '127.0.0.1'); //TODO use different ports per instance of this script, see https://github.com/project64/project64/issues/2492
-        exec('PowerShell -Command "Start-Process \'' + appdata.replace('"', '""').replace("'", "''") + '\\Fenhl\\OoTR Multiworld\\cache\\gui.exe\' pj64v4"');
+        server.listen(0, '127.0.0.1'); // let the OS pick a free port so multiple instances of this script can run side by side, see https://github.com/project64/project64/issues/2492
+        exec('PowerShell -Command "Start-Process \'' + appdata.replace('"', '""').replace("'", "''") + '\\Fenhl\\OoTR Multiworld\\cache\\gui.exe\' \'pj64v4 --port=' + server.port + '\'"');
         setInterval(function() {
             if (sockets.length > 0) {
                 handle_frame(function(buf) {
@@ -0,0 +1,8 @@
+set -e
+
+cargo build --package=multiworld-gui
+cargo build --package=multiworld-everdrive-sim
+target/debug/multiworld-everdrive-sim --link=/tmp/multiworld-everdrive-sim "$@" &
+trap 'kill $!' EXIT
+sleep 1
+target/debug/multiworld-gui everdrive --port=/tmp/multiworld-everdrive-sim
@@ -3,9 +3,7 @@ use {
         borrow::Cow,
         convert::Infallible as Never,
         io::stdout,
-        time::Duration,
     },
-    async_proto::Protocol as _,
     chrono::prelude::*,
     crossterm::{
         cursor::{
@@ -29,19 +27,10 @@ use {
         },
     },
     futures::stream::StreamExt as _,
-    tokio::{
-        select,
-        time::{
-            Instant,
-            interval_at,
-            timeout,
-        },
-    },
-    tokio_tungstenite::tungstenite,
+    tokio::select,
     multiworld::{
         SessionState,
         config::Config,
-        user_agent,
         ws::latest::{
             ClientMessage,
             ServerMessage,
@@ -62,18 +51,13 @@ struct Args {
 enum Error {
     #[error(transparent)] Client(#[from] multiworld::ClientError),
     #[error(transparent)] Config(#[from] multiworld::config::Error),
-    #[error(transparent)] Elapsed(#[from] tokio::time::error::Elapsed),
+    #[error(transparent)] Connection(#[from] multiworld::client::Error),
     #[error(transparent)] FilenameParse(#[from] multiworld::FilenameParseError),
-    #[error(transparent)] Http(#[from] tungstenite::http::Error),
-    #[error(transparent)] InvalidUri(#[from] tungstenite::http::uri::InvalidUri),
     #[error(transparent)] Io(#[from] tokio::io::Error),
     #[error(transparent)] Json(#[from] serde_json::Error),
-    #[error(transparent)] Read(#[from] async_proto::ReadError),
     #[error(transparent)] Syn(#[from] syn::Error),
     #[error(transparent)] TryFromSlice(#[from] std::array::TryFromSliceError),
     #[error(transparent)] UrlParse(#[from] url::ParseError),
-    #[error(transparent)] WebSocket(#[from] tungstenite::Error),
-    #[error(transparent)] Write(#[from] async_proto::WriteError),
     #[error("expected exactly one element, got zero or multiple")]
     ExactlyOne,
     #[error("failed to parse")]
@@ -114,34 +98,26 @@ fn prompt(session_state: &SessionState<Never>) -> Cow<'static, str> {
 async fn cli(Args { api_key }: Args) -> Result<(), Error> {
     let mut cli_events = EventStream::default().fuse();
     let config = Config::load().await?;
-    let request = tungstenite::ClientRequestBuilder::new(config.websocket_url()?.to_string().try_into()?)
-        .with_header(tungstenite::http::header::USER_AGENT.to_string(), user_agent());
-    let (mut websocket, _) = tokio_tungstenite::connect_async(request).await?;
+    let (client, mut receiver) = multiworld::client::connect(&config.websocket_url()?).await?;
     if let Some(api_key) = api_key {
-        ClientMessage::LoginApiKey { api_key }.write_ws024(&mut websocket).await?;
+        client.login_api_key(api_key).await?;
     }
-    let (mut writer, reader) = websocket.split();
-    let mut session_state = SessionState::<Never>::default();
-    let mut read = Box::pin(timeout(Duration::from_secs(60), ServerMessage::read_ws_owned024(reader)));
     let mut cmd_buf = String::default();
-    let mut interval = interval_at(Instant::now() + Duration::from_secs(30), Duration::from_secs(30));
     let mut stdout = stdout();
     crossterm::execute!(stdout,
-        Print(format_args!("{}> ", prompt(&session_state))),
+        Print(format_args!("{}> ", prompt(receiver.state()))),
     )?;
     loop {
         select! {
-            res = &mut read => {
-                let (reader, msg) = res??;
-                session_state.apply(msg.clone());
+            res = receiver.recv() => {
+                let msg = res?;
                 if !matches!(msg, ServerMessage::Ping) {
                     crossterm::execute!(stdout,
                         MoveToColumn(0),
                         Clear(ClearType::UntilNewLine),
-                        Print(format_args!("{} {msg:#?}\r\n{}> {cmd_buf}", Local::now().format("%Y-%m-%d %H:%M:%S"), prompt(&session_state))),
+                        Print(format_args!("{} {msg:#?}\r\n{}> {cmd_buf}", Local::now().format("%Y-%m-%d %H:%M:%S"), prompt(receiver.state()))),
                     )?;
                 }
-                read = Box::pin(timeout(Duration::from_secs(60), ServerMessage::read_ws_owned024(reader)));
             },
             cli_event = cli_events.select_next_some() => match cli_event? {
                 Event::Key(key_event) => if key_event == KeyEvent::new(KeyCode::Char('d'), KeyModifiers::CONTROL) {
@@ -150,11 +126,11 @@ async fn cli(Args { api_key }: Args) -> Result<(), Error> {
                     match key_event.code {
                         KeyCode::Enter => if key_event.kind == KeyEventKind::Press {
                             if !cmd_buf.is_empty() {
-                                ClientMessage::from_expr(syn::parse_str(&cmd_buf)?)?.write_ws024(&mut writer).await?;
+                                client.send(ClientMessage::from_expr(syn::parse_str(&cmd_buf)?)?).await?;
                             }
                             cmd_buf.clear();
                             crossterm::execute!(stdout,
-                                Print(format_args!("\r\n{}> ", prompt(&session_state))),
+                                Print(format_args!("\r\n{}> ", prompt(receiver.state()))),
                             )?;
                         },
                         KeyCode::Backspace => if matches!(key_event.kind, KeyEventKind::Press | KeyEventKind::Repeat) && cmd_buf.pop().is_some() {
@@ -175,7 +151,6 @@ async fn cli(Args { api_key }: Args) -> Result<(), Error> {
                 Event::Paste(text) => cmd_buf.push_str(&text),
                 _ => {}
             },
-            _ = interval.tick() => ClientMessage::Ping.write_ws024(&mut writer).await?,
         }
     }
     Ok(())
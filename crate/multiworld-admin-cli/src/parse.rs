@@ -353,6 +353,61 @@ impl FromExpr for ClientMessage {
                         }
                         Ok(Self::SendAll { source_world: source_world.ok_or(Error::FromExpr)?, spoiler_log: spoiler_log.ok_or(Error::FromExpr)? })
                     }
+                    "RevokeItem" => {
+                        let mut mw_room = None;
+                        let mut source_world = None;
+                        let mut key = None;
+                        let mut target_world = None;
+                        for FieldValue { member, expr, .. } in struct_lit.fields {
+                            match member {
+                                Member::Named(member) => match &*member.to_string() {
+                                    "mw_room" => if mw_room.replace(u64::from_expr(expr)?).is_some() { return Err(Error::FromExpr) },
+                                    "source_world" => if source_world.replace(NonZeroU8::from_expr(expr)?).is_some() { return Err(Error::FromExpr) },
+                                    "key" => if key.replace(u64::from_expr(expr)?).is_some() { return Err(Error::FromExpr) },
+                                    "target_world" => if target_world.replace(NonZeroU8::from_expr(expr)?).is_some() { return Err(Error::FromExpr) },
+                                    _ => return Err(Error::FromExpr),
+                                },
+                                Member::Unnamed(_) => return Err(Error::FromExpr),
+                            }
+                        }
+                        Ok(Self::RevokeItem { mw_room: mw_room.ok_or(Error::FromExpr)?, source_world: source_world.ok_or(Error::FromExpr)?, key: key.ok_or(Error::FromExpr)?, target_world: target_world.ok_or(Error::FromExpr)? })
+                    }
+                    "GrantItem" => {
+                        let mut mw_room = None;
+                        let mut kind = None;
+                        let mut target_world = None;
+                        for FieldValue { member, expr, .. } in struct_lit.fields {
+                            match member {
+                                Member::Named(member) => match &*member.to_string() {
+                                    "mw_room" => if mw_room.replace(u64::from_expr(expr)?).is_some() { return Err(Error::FromExpr) },
+                                    "kind" => if kind.replace(u16::from_expr(expr)?).is_some() { return Err(Error::FromExpr) },
+                                    "target_world" => if target_world.replace(NonZeroU8::from_expr(expr)?).is_some() { return Err(Error::FromExpr) },
+                                    _ => return Err(Error::FromExpr),
+                                },
+                                Member::Unnamed(_) => return Err(Error::FromExpr),
+                            }
+                        }
+                        Ok(Self::GrantItem { mw_room: mw_room.ok_or(Error::FromExpr)?, kind: kind.ok_or(Error::FromExpr)?, target_world: target_world.ok_or(Error::FromExpr)? })
+                    }
+                    "MoveItem" => {
+                        let mut mw_room = None;
+                        let mut target_world = None;
+                        let mut from = None;
+                        let mut to = None;
+                        for FieldValue { member, expr, .. } in struct_lit.fields {
+                            match member {
+                                Member::Named(member) => match &*member.to_string() {
+                                    "mw_room" => if mw_room.replace(u64::from_expr(expr)?).is_some() { return Err(Error::FromExpr) },
+                                    "target_world" => if target_world.replace(NonZeroU8::from_expr(expr)?).is_some() { return Err(Error::FromExpr) },
+                                    "from" => if from.replace(u64::from_expr(expr)?).is_some() { return Err(Error::FromExpr) },
+                                    "to" => if to.replace(u64::from_expr(expr)?).is_some() { return Err(Error::FromExpr) },
+                                    _ => return Err(Error::FromExpr),
+                                },
+                                Member::Unnamed(_) => return Err(Error::FromExpr),
+                            }
+                        }
+                        Ok(Self::MoveItem { mw_room: mw_room.ok_or(Error::FromExpr)?, target_world: target_world.ok_or(Error::FromExpr)?, from: from.ok_or(Error::FromExpr)?, to: to.ok_or(Error::FromExpr)? })
+                    }
                     //TODO TrackError
                     _ => Err(Error::FromExpr),
                 }
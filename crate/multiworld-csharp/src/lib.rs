@@ -42,10 +42,11 @@ use {
         OptHintArea,
         config::Config,
         frontend::{
+            self,
             ClientMessage,
-            PROTOCOL_VERSION,
             ServerMessage,
         },
+        save_diff,
     },
     crate::util::absolute_path,
 };
@@ -211,6 +212,7 @@ impl StringHandle {
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
+    #[error(transparent)] Handshake(#[from] frontend::HandshakeError),
     #[error(transparent)] ParseInt(#[from] std::num::ParseIntError),
     #[error(transparent)] Read(#[from] async_proto::ReadError),
     #[error(transparent)] Wheel(#[from] wheel::Error),
@@ -223,14 +225,15 @@ pub enum Error {
     #[cfg(target_os = "linux")]
     #[error("could not determine BizHawk location")]
     NoCurrentExe,
-    #[error("protocol version mismatch: multiworld app is version {0} but we're version {frontend}", frontend = PROTOCOL_VERSION)]
-    VersionMismatch(u8),
 }
 
 #[derive(Debug)]
 pub struct Client {
     tcp_listener: TcpListener,
     tcp_stream: Option<TcpStream>,
+    capabilities: frontend::Capabilities,
+    /// The save data last sent over the current connection, which diffs are computed against.
+    save_data: Option<Box<[u8; oottracker::save::SIZE]>>,
     buf: Vec<u8>,
     message_queue: Vec<ClientMessage>,
 }
@@ -244,11 +247,14 @@ impl Client {
             match self.tcp_listener.accept() {
                 Ok((mut tcp_stream, _)) => {
                     tcp_stream.set_nonblocking(false).at_unknown()?;
-                    PROTOCOL_VERSION.write_sync(&mut tcp_stream)?;
-                    let frontend_version = u8::read_sync(&mut tcp_stream)?;
-                    if frontend_version != PROTOCOL_VERSION { return Err(Error::VersionMismatch(frontend_version)) }
+                    let negotiated = frontend::handshake_frontend_sync(&mut tcp_stream, frontend::Capabilities::all())?;
+                    self.capabilities = negotiated.capabilities;
+                    self.save_data = None;
                     for msg in self.message_queue.drain(..) {
                         msg.write_sync(&mut tcp_stream)?;
+                        if let ClientMessage::SaveData(save_data) = msg {
+                            self.save_data = Some(Box::new(save_data));
+                        }
                     }
                     self.tcp_stream = Some(tcp_stream);
                     Ok(None)
@@ -259,6 +265,21 @@ impl Client {
         }
     }
 
+    /// Sends save data, as a diff if the app supports that and has already received save data over this connection.
+    ///
+    /// The save data is only used as the base for later diffs once it has been written, so a failed write doesn't desynchronize the diffs.
+    fn write_save_data(&mut self, save_data: [u8; oottracker::save::SIZE]) -> Result<(), Error> {
+        let msg = match self.save_data {
+            Some(ref old_save_data) if self.tcp_stream.is_some() && self.capabilities.contains(frontend::Capabilities::SAVE_DATA_DIFFS) => ClientMessage::SaveDataDiff(save_diff::diff(old_save_data, &save_data)),
+            _ => ClientMessage::SaveData(save_data),
+        };
+        self.write(msg)?;
+        if self.tcp_stream.is_some() {
+            self.save_data = Some(Box::new(save_data));
+        }
+        Ok(())
+    }
+
     fn write(&mut self, msg: ClientMessage) -> Result<(), Error> {
         if let Some(ref mut tcp_stream) = self.tcp_stream {
             tcp_stream.set_nonblocking(false).at_unknown()?;
@@ -354,6 +375,8 @@ impl Client {
         cmd.spawn().at_command(gui_path.display().to_string())?;
         Ok(Client {
             tcp_stream: None,
+            capabilities: frontend::Capabilities::empty(),
+            save_data: None,
             buf: Vec::default(),
             message_queue: Vec::default(),
             tcp_listener,
@@ -445,7 +468,7 @@ impl Client {
 #[csharp_ffi] pub unsafe extern "C" fn client_set_save_data(client: *mut Client, save: *const u8) -> HandleOwned<Result<(), Error>> {
     let client = &mut *client;
     let save = slice::from_raw_parts(save, oottracker::save::SIZE);
-    HandleOwned::new(client.write(ClientMessage::SaveData(save.try_into().expect("incorrect save data size"))))
+    HandleOwned::new(client.write_save_data(save.try_into().expect("incorrect save data size")))
 }
 
 /// Attempts to read a frontend message from the client if one is available, without blocking if there is not.
@@ -0,0 +1,17 @@
+//! Simulates the game side of the EverDrive protocol spoken by `multiworld::everdrive` on a pseudo-terminal, so the EverDrive code path can be tested without hardware.
+//!
+//! Start the simulator, then point the app at the pseudo-terminal it prints (or at the `--link` path) using `multiworld-gui everdrive --port <path>`.
+
+#[cfg(unix)] mod sim;
+
+#[cfg(unix)]
+#[wheel::main]
+async fn main(args: sim::Args) -> Result<(), sim::Error> {
+    match sim::run(args).await? {}
+}
+
+#[cfg(not(unix))]
+fn main() {
+    eprintln!("multiworld-everdrive-sim uses pseudo-terminals, which are only available on Unix");
+    std::process::exit(1)
+}
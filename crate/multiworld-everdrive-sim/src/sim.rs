@@ -0,0 +1,367 @@
+use {
+    std::{
+        convert::Infallible as Never,
+        future,
+        num::{
+            NonZeroU8,
+            ParseIntError,
+        },
+        path::PathBuf,
+        pin::pin,
+        str::FromStr,
+        time::Duration,
+    },
+    arrayref::{
+        array_mut_ref,
+        array_ref,
+    },
+    chrono::prelude::*,
+    tokio::{
+        io::{
+            self,
+            AsyncReadExt as _,
+            AsyncWriteExt as _,
+            WriteHalf,
+        },
+        select,
+        sync::mpsc,
+        time::{
+            interval,
+            sleep,
+        },
+    },
+    tokio_serial::{
+        SerialPort as _,
+        SerialStream,
+    },
+    multiworld::{
+        Filename,
+        everdrive::{
+            HANDSHAKE,
+            PROTOCOL_VERSION,
+        },
+    },
+};
+
+macro_rules! log {
+    ($($arg:tt)*) => {
+        println!("{} {}", Local::now().format("%Y-%m-%d %H:%M:%S"), format_args!($($arg)*))
+    };
+}
+
+/// The app times out after twice this long.
+const PING_INTERVAL: Duration = Duration::from_secs(5);
+/// How long `--hang-after` stops responding, long enough for the app to time out.
+const HANG_DURATION: Duration = Duration::from_secs(15);
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+pub(crate) enum MenuReply {
+    Cmdr,
+    /// The reply sent by older versions of EverDrive OS.
+    Cmdk,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum SendItemParseError {
+    #[error(transparent)] Int(#[from] ParseIntError),
+    #[error("expected key:kind:target_world")]
+    Format,
+}
+
+#[derive(Clone, Copy)]
+pub(crate) struct SendItem {
+    key: u64,
+    kind: u16,
+    target_world: NonZeroU8,
+}
+
+impl FromStr for SendItem {
+    type Err = SendItemParseError;
+
+    fn from_str(s: &str) -> Result<Self, SendItemParseError> {
+        let (key, rest) = s.split_once(':').ok_or(SendItemParseError::Format)?;
+        let (kind, target_world) = rest.split_once(':').ok_or(SendItemParseError::Format)?;
+        Ok(Self {
+            key: key.parse()?,
+            kind: kind.parse()?,
+            target_world: target_world.parse()?,
+        })
+    }
+}
+
+#[derive(clap::Parser)]
+#[clap(version)]
+pub(crate) struct Args {
+    /// Create a symlink to the pseudo-terminal at this path, replacing any existing file.
+    #[clap(long)]
+    link: Option<PathBuf>,
+    /// Reply to handshakes like the EverDrive main menu instead of the game.
+    #[clap(long, value_enum)]
+    menu: Option<MenuReply>,
+    /// Don't reply to handshakes at all, to test the app's handshake timeout.
+    #[clap(long, conflicts_with = "menu")]
+    silent: bool,
+    /// The randomizer version reported in the handshake.
+    #[clap(long, default_value = "8.3.0")]
+    rando_version: semver::Version,
+    /// The branch identifier byte of the randomizer version.
+    #[clap(long, default_value_t = 0)]
+    branch: u8,
+    /// The supplementary version byte of the randomizer version.
+    #[clap(long, default_value_t = 0)]
+    supplementary: u8,
+    /// The world number reported in the handshake. 0 is sent as is, to test the app's error handling.
+    #[clap(long, default_value_t = 1)]
+    world: u8,
+    /// The file hash reported in the handshake, as 5 comma-separated hash icon indices.
+    #[clap(long, value_delimiter = ',', default_values_t = [0, 1, 2, 3, 4])]
+    hash: Vec<u8>,
+    /// The player name on the save file.
+    #[clap(long, default_value = "Link")]
+    name: Filename,
+    /// A raw save data dump to send when entering the game. Defaults to all zeroes.
+    #[clap(long)]
+    save: Option<PathBuf>,
+    /// An item to send after entering the game, as `key:kind:target_world`. Can be specified multiple times.
+    #[clap(long)]
+    send_item: Vec<SendItem>,
+    /// How many milliseconds the game takes to receive each item.
+    #[clap(long, default_value_t = 100)]
+    item_delay: u64,
+    /// Stop responding this many seconds after entering the game, then wait for a new handshake.
+    #[clap(long)]
+    hang_after: Option<u64>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum Error {
+    #[error(transparent)] Io(#[from] io::Error),
+    #[error(transparent)] SerialPort(#[from] tokio_serial::Error),
+    #[error(transparent)] TryFromInt(#[from] std::num::TryFromIntError),
+    #[error(transparent)] Wheel(#[from] wheel::Error),
+    #[error("--hash must have exactly 5 values")]
+    HashLength,
+    #[error("the app closed the pseudo-terminal")]
+    PortClosed,
+    #[error("pseudo-terminal has no path")]
+    PtyName,
+    #[error("save data must be {} bytes, got {0}", oottracker::save::SIZE)]
+    SaveSize(usize),
+}
+
+type Port = mpsc::Receiver<io::Result<[u8; 16]>>;
+
+async fn recv(rx: &mut Port) -> Result<[u8; 16], Error> {
+    Ok(rx.recv().await.ok_or(Error::PortClosed)??)
+}
+
+async fn send(writer: &mut WriteHalf<SerialStream>, buf: &[u8]) -> Result<(), Error> {
+    writer.write_all(buf).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+/// Sends the save data like the game does when entering it: the first 200 bytes with the “in game” state, followed by 10 segments of 500 bytes each.
+async fn send_save(writer: &mut WriteHalf<SerialStream>, save: &[u8; oottracker::save::SIZE]) -> Result<(), Error> {
+    let mut buf = [0; 16];
+    buf[0] = 0x02; // State: In Game
+    *array_mut_ref![buf, 1, 15] = *array_ref![save, 0, 15];
+    send(writer, &buf).await?;
+    send(writer, &save[15..200]).await?;
+    for segment_idx in 0..10 {
+        let offset = 200 + 500 * usize::from(segment_idx);
+        let mut buf = [0; 16];
+        buf[0] = 0x06; // Save Data Segment
+        buf[1] = segment_idx;
+        *array_mut_ref![buf, 2, 14] = *array_ref![save, offset, 14];
+        send(writer, &buf).await?;
+        send(writer, &save[offset + 14..offset + 500]).await?;
+    }
+    Ok(())
+}
+
+/// Handles one connection from the app, from the handshake until the app reconnects or `--hang-after` elapses.
+///
+/// Returns whether the handshake for the next connection has already been received.
+async fn session(args: &Args, rx: &mut Port, writer: &mut WriteHalf<SerialStream>, save: &mut [u8; oottracker::save::SIZE], mut handshake_received: bool) -> Result<bool, Error> {
+    while !handshake_received {
+        let buf = recv(rx).await?;
+        if buf == HANDSHAKE {
+            handshake_received = true;
+        } else {
+            log!("ignoring message before handshake: {buf:02x?}");
+        }
+    }
+    log!("received handshake");
+    if args.silent {
+        log!("not replying (--silent)");
+        return Ok(false)
+    }
+    if let Some(menu) = args.menu {
+        let mut buf = [0; 16];
+        *array_mut_ref![buf, 0, 4] = match menu {
+            MenuReply::Cmdr => *b"cmdr",
+            MenuReply::Cmdk => *b"cmdk",
+        };
+        send(writer, &buf).await?;
+        log!("replied as the EverDrive main menu");
+        return Ok(false)
+    }
+    let mut buf = [0; 16];
+    *array_mut_ref![buf, 0, 4] = *b"OoTR";
+    buf[4] = PROTOCOL_VERSION;
+    buf[5] = args.rando_version.major.try_into()?;
+    buf[6] = args.rando_version.minor.try_into()?;
+    buf[7] = args.rando_version.patch.try_into()?;
+    buf[8] = args.branch;
+    buf[9] = args.supplementary;
+    buf[10] = args.world;
+    buf[11..].copy_from_slice(&args.hash);
+    send(writer, &buf).await?;
+    let reply = recv(rx).await?;
+    if reply == HANDSHAKE {
+        log!("app reconnected during handshake");
+        return Ok(true)
+    }
+    if reply[..3] != [b'M', b'W', PROTOCOL_VERSION] {
+        log!("unexpected handshake reply: {reply:02x?}");
+        return Ok(false)
+    }
+    log!("handshake complete (send own items: {}, progressive items: {})", reply[3] != 0, reply[4] != 0);
+    let mut buf = [0; 16];
+    buf[0] = 0x01; // State: File Select
+    *array_mut_ref![buf, 1, 8] = args.name.0;
+    send(writer, &buf).await?;
+    send_save(writer, save).await?;
+    log!("entered the game with internal count {}", u16::from_be_bytes(*array_ref![save, 0x90, 2]));
+    for &SendItem { key, kind, target_world } in &args.send_item {
+        let mut buf = [0; 16];
+        buf[0] = 0x03; // Send Item
+        *array_mut_ref![buf, 1, 8] = key.to_be_bytes();
+        *array_mut_ref![buf, 9, 2] = kind.to_be_bytes();
+        buf[11] = target_world.get();
+        send(writer, &buf).await?;
+        log!("sent item 0x{kind:04x} with key 0x{key:016x} to world {target_world}");
+    }
+    let mut ping = interval(PING_INTERVAL);
+    let mut hang = pin!(async {
+        if let Some(hang_after) = args.hang_after {
+            sleep(Duration::from_secs(hang_after)).await
+        } else {
+            future::pending().await
+        }
+    });
+    loop {
+        select! {
+            () = &mut hang => {
+                log!("not responding for {} seconds (--hang-after)", HANG_DURATION.as_secs());
+                sleep(HANG_DURATION).await;
+                return Ok(false)
+            }
+            _ = ping.tick() => send(writer, &[0; 16]).await?,
+            res = recv(rx) => {
+                let buf = res?;
+                if buf == HANDSHAKE {
+                    log!("app reconnected");
+                    return Ok(true)
+                }
+                match buf[0] {
+                    0x01 => log!( // Player Data
+                        "player data: world {}, name {}, progressive items 0x{:08x}",
+                        buf[1],
+                        Filename(*array_ref![buf, 2, 8]),
+                        u32::from_be_bytes(*array_ref![buf, 10, 4]),
+                    ),
+                    0x02 => { // Get Item
+                        let item = u16::from_be_bytes(*array_ref![buf, 1, 2]);
+                        let internal_count = u16::from_be_bytes(*array_ref![save, 0x90, 2]).wrapping_add(1);
+                        sleep(Duration::from_millis(args.item_delay)).await;
+                        *array_mut_ref![save, 0x90, 2] = internal_count.to_be_bytes();
+                        let mut reply = [0; 16];
+                        reply[0] = 0x04; // Item Received
+                        send(writer, &reply).await?;
+                        log!("received item 0x{item:04x}, internal count is now {internal_count}");
+                    }
+                    cmd => log!("ignoring unknown message 0x{cmd:02x}: {buf:02x?}"),
+                }
+            }
+        }
+    }
+}
+
+pub(crate) async fn run(args: Args) -> Result<Never, Error> {
+    if args.hash.len() != 5 { return Err(Error::HashLength) }
+    let save = if let Some(ref path) = args.save {
+        let data = wheel::fs::read(path).await?;
+        <[u8; oottracker::save::SIZE]>::try_from(&*data).map_err(|_| Error::SaveSize(data.len()))?
+    } else {
+        [0; oottracker::save::SIZE]
+    };
+    // The slave side stays open for as long as the simulator runs, so reading from the master doesn't fail while the app isn't connected.
+    let (master, mut slave) = SerialStream::pair()?;
+    slave.set_exclusive(false)?;
+    let pty_path = slave.name().ok_or(Error::PtyName)?;
+    if let Some(ref link) = args.link {
+        match std::fs::remove_file(link) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+        std::os::unix::fs::symlink(&pty_path, link)?;
+    }
+    log!("simulating an EverDrive at {pty_path}");
+    simulate(&args, master, save).await
+}
+
+/// Plays the game side of the protocol on the master side of a pseudo-terminal, forever.
+async fn simulate(args: &Args, master: SerialStream, mut save: [u8; oottracker::save::SIZE]) -> Result<Never, Error> {
+    let (mut reader, mut writer) = io::split(master);
+    let (tx, mut rx) = mpsc::channel(1_024);
+    tokio::spawn(async move {
+        loop {
+            let mut buf = [0; 16];
+            let res = reader.read_exact(&mut buf).await.map(|_| buf);
+            let is_err = res.is_err();
+            if tx.send(res).await.is_err() || is_err { break }
+        }
+    });
+    let mut handshake_received = false;
+    loop {
+        handshake_received = session(args, &mut rx, &mut writer, &mut save, handshake_received).await?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        clap::Parser as _,
+        futures::stream::StreamExt as _,
+        multiworld::{
+            everdrive,
+            frontend,
+        },
+        super::*,
+    };
+
+    #[tokio::test]
+    async fn connect() {
+        let args = Args::parse_from(["multiworld-everdrive-sim", "--send-item", "4660:1:2", "--item-delay", "0"]);
+        let (master, mut slave) = SerialStream::pair().expect("failed to open pseudo-terminal");
+        slave.set_exclusive(false).expect("failed to make pseudo-terminal non-exclusive");
+        let pty_path = slave.name().expect("pseudo-terminal has no path");
+        tokio::spawn(async move { simulate(&args, master, [0; oottracker::save::SIZE]).await });
+        let mut events = pin!(everdrive::connect(Some(pty_path), None));
+        let Some(Ok(everdrive::Event::Connected(tx))) = events.next().await else { panic!("failed to connect to simulator") };
+        assert!(matches!(events.next().await, Some(Ok(everdrive::Event::Message(frontend::ClientMessage::PlayerId(world)))) if world.get() == 1));
+        assert!(matches!(events.next().await, Some(Ok(everdrive::Event::Message(frontend::ClientMessage::FileHash(Some(_)))))));
+        assert!(matches!(events.next().await, Some(Ok(everdrive::Event::Message(frontend::ClientMessage::PlayerName(name)))) if name.to_string() == "Link    "));
+        for _ in 0..10 {
+            assert!(matches!(events.next().await, Some(Ok(everdrive::Event::Message(frontend::ClientMessage::SaveData(_))))));
+        }
+        assert!(matches!(events.next().await, Some(Ok(everdrive::Event::Message(frontend::ClientMessage::SendItem { key: 4660, kind: 1, target_world }))) if target_world.get() == 2));
+        tx.send(frontend::ServerMessage::ItemQueue(vec![0x0001])).await.expect("connection closed");
+        // receiving an item doesn't produce any events, but the simulator's acknowledgement must not make the connection fail
+        assert!(tokio::time::timeout(Duration::from_secs(1), events.next()).await.is_err());
+        drop(slave);
+    }
+}
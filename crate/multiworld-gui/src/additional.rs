@@ -0,0 +1,394 @@
+//! Additional frontends connected to the same app instance, e.g. to play several worlds of a multiworld seed on one computer.
+//!
+//! The main session handles the frontend selected as usual. Further frontends are either connected to the Project64 3.x listener after the first one, or given on the command line using `--extra-frontend`.
+//! Each of them gets its own [`Bridge`] and connection to the server, which joins the room the main session is in and claims the world reported by that frontend.
+
+use {
+    std::{
+        any::TypeId,
+        fmt,
+        future::Future,
+        hash::Hash as _,
+        io::prelude::*,
+        pin::Pin,
+        str::FromStr,
+        sync::Arc,
+        time::Duration,
+    },
+    chrono::prelude::*,
+    futures::{
+        future::{
+            self,
+            FutureExt as _,
+        },
+        stream::{
+            self,
+            Stream,
+            StreamExt as _,
+            TryStreamExt as _,
+        },
+    },
+    iced::{
+        Task,
+        advanced::subscription::{
+            self,
+            EventStream,
+            Recipe,
+        },
+    },
+    log_lock::lock,
+    tokio::time::sleep,
+    url::Url,
+    wheel::traits::IsNetworkError as _,
+    multiworld::{
+        Filename,
+        SessionState,
+        SessionStateError,
+        bridge::{
+            self,
+            Bridge,
+            Effect,
+            Room,
+            RunError,
+        },
+        frontend::{
+            self,
+            Kind as Frontend,
+        },
+        persistent_state::PersistentState,
+        retroarch,
+        ws::latest::{
+            ClientMessage,
+            ServerMessage,
+        },
+    },
+    crate::{
+        Error,
+        FrontendWriter,
+        LoggingFrontendWriter,
+        LoggingSink,
+        Message,
+        login,
+    },
+};
+
+/// Identifies an additional frontend in [`Message::AdditionalFrontend`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) enum FrontendId {
+    /// A connection to the Project64 3.x listener, numbered in the order they were accepted.
+    Listener(u8),
+    /// An index into the `--extra-frontend` arguments.
+    Extra(usize),
+}
+
+/// A frontend given using `--extra-frontend`, in one of these formats:
+///
+/// * `bizhawk:<port>`, the port the Lua script of another BizHawk instance listens on
+/// * `everdrive` or `everdrive:<serial port>`
+/// * `pj64v4:<port>`, the port the script of another Project64 4.x instance listens on
+/// * `retroarch` or `retroarch:<port>`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) enum ExtraFrontend {
+    BizHawk(u16),
+    EverDrive(Option<String>),
+    Pj64V4(u16),
+    RetroArch(u16),
+}
+
+impl ExtraFrontend {
+    pub(crate) fn kind(&self) -> Frontend {
+        match self {
+            Self::BizHawk(_) => Frontend::BizHawk,
+            Self::EverDrive(_) => Frontend::EverDrive,
+            Self::Pj64V4(_) => Frontend::Pj64V4,
+            Self::RetroArch(_) => Frontend::RetroArch,
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum ParseError {
+    #[error(transparent)] ParseInt(#[from] std::num::ParseIntError),
+    #[error("{0} requires a port, e.g. {0}:{1}")]
+    MissingPort(&'static str, u16),
+    #[error("unknown frontend {0:?}, expected bizhawk, everdrive, pj64v4, or retroarch")]
+    UnknownFrontend(String),
+}
+
+impl FromStr for ExtraFrontend {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, ParseError> {
+        let (kind, arg) = s.split_once(':').map_or((s, None), |(kind, arg)| (kind, Some(arg)));
+        Ok(match &*kind.to_ascii_lowercase() {
+            "bizhawk" => Self::BizHawk(arg.ok_or(ParseError::MissingPort("bizhawk", frontend::PORT))?.parse()?),
+            "everdrive" => Self::EverDrive(arg.map(str::to_owned)),
+            "pj64v4" => Self::Pj64V4(arg.ok_or(ParseError::MissingPort("pj64v4", frontend::PORT))?.parse()?),
+            "retroarch" => Self::RetroArch(arg.map(str::parse).transpose()?.unwrap_or(retroarch::DEFAULT_PORT)),
+            _ => return Err(ParseError::UnknownFrontend(kind.to_owned())),
+        })
+    }
+}
+
+impl fmt::Display for ExtraFrontend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BizHawk(port) => write!(f, "BizHawk on port {port}"),
+            Self::EverDrive(Some(port)) => write!(f, "EverDrive on {port}"),
+            Self::EverDrive(None) => write!(f, "EverDrive"),
+            Self::Pj64V4(port) => write!(f, "Project64 4.x on port {port}"),
+            Self::RetroArch(port) => write!(f, "RetroArch on port {port}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum Event {
+    FrontendConnected(FrontendWriter),
+    FrontendDisconnected(Arc<Error>),
+    /// Sending a message to the frontend failed, or it sent an invalid message. Unlike errors on the server connection, this doesn't end the server session.
+    FrontendError(Arc<Error>),
+    Plugin(Box<frontend::ClientMessage>), // boxed for the same reason as Message::Plugin
+    Reconnect,
+    Server(multiworld::client::StateChange),
+    ServerConnected(multiworld::client::Client),
+    ServerError(Arc<Error>),
+}
+
+/// The room the main session is in, which additional frontends join.
+#[derive(Clone)]
+pub(crate) struct MainRoom {
+    pub(crate) id: u64,
+    pub(crate) password: String,
+    pub(crate) login_token: Option<(login::Provider, String)>,
+}
+
+pub(crate) struct AdditionalFrontend {
+    /// `None` for connections to the Project64 3.x listener, which are removed when they disconnect.
+    extra: Option<ExtraFrontend>,
+    bridge: Bridge,
+    frontend_writer: Option<LoggingFrontendWriter>,
+    /// The last error on the frontend connection, shown until the frontend connects again.
+    frontend_error: Option<Arc<Error>>,
+    server_connection: SessionState<Arc<Error>>,
+    server_writer: Option<LoggingSink>,
+    server_connection_id: u8,
+}
+
+impl AdditionalFrontend {
+    pub(crate) fn new(extra: Option<ExtraFrontend>) -> Self {
+        Self {
+            bridge: Bridge::default(),
+            frontend_writer: None,
+            frontend_error: None,
+            server_connection: SessionState::default(),
+            server_writer: None,
+            server_connection_id: 0,
+            extra,
+        }
+    }
+
+    /// Carries out effects returned by [`AdditionalFrontend::bridge`] in the background.
+    fn run_bridge(&self, id: FrontendId, persistent_state: &PersistentState, effects: Vec<Effect>) -> Task<Message> {
+        if effects.is_empty() { return Task::none() }
+        let updates_pending_items = effects.iter().any(|effect| matches!(effect, Effect::HoldItem(_) | Effect::Saved { .. } | Effect::SendHeldItems { .. }));
+        let server_writer = self.server_writer.clone();
+        let frontend_writer = self.frontend_writer.clone();
+        let persistent_state = persistent_state.clone();
+        Task::future(async move {
+            match bridge::run(effects, server_writer.as_ref(), frontend_writer.as_ref(), &persistent_state).await {
+                Ok(()) => if updates_pending_items { Message::PendingItems(persistent_state.pending_items().await) } else { Message::Nop },
+                Err(RunError::PersistentState(e)) => Message::CommandError(Arc::new(e.into())),
+                Err(RunError::Server(e)) => Message::AdditionalFrontend(id, Event::ServerError(Arc::new(e.into()))),
+                Err(RunError::Frontend(e)) => Message::AdditionalFrontend(id, Event::FrontendError(Arc::new(e))),
+            }
+        })
+    }
+
+    pub(crate) fn update(&mut self, id: FrontendId, log: bool, websocket_url: &Url, persistent_state: &PersistentState, main_room: Option<MainRoom>, event: Event) -> Task<Message> {
+        match event {
+            Event::FrontendConnected(inner) => {
+                let capabilities = inner.capabilities();
+                self.frontend_writer = Some(LoggingFrontendWriter { log, inner });
+                self.frontend_error = None;
+                let effects = self.bridge.frontend_connected(capabilities, Room::of(&self.server_connection));
+                return self.run_bridge(id, persistent_state, effects)
+            }
+            Event::FrontendDisconnected(e) => {
+                self.frontend_writer = None;
+                self.bridge.frontend_disconnected();
+                self.frontend_error = Some(e);
+            }
+            // if the connection is lost, this is followed by Event::FrontendDisconnected from the subscription
+            Event::FrontendError(e) => self.frontend_error = Some(e),
+            Event::Plugin(msg) => match self.bridge.frontend_message(websocket_url, Room::of(&self.server_connection), *msg) {
+                Ok(effects) => return self.run_bridge(id, persistent_state, effects),
+                Err(e) => return Task::done(Message::AdditionalFrontend(id, Event::FrontendError(Arc::new(e.into())))),
+            },
+            Event::Reconnect => {
+                self.server_connection = SessionState::default();
+                self.server_connection_id = self.server_connection_id.wrapping_add(1);
+            }
+            Event::Server(multiworld::client::StateChange { msg, state }) => {
+                self.server_connection = state.into_fallible();
+                let join_room = if let ServerMessage::EnterLobby { .. } = msg {
+                    main_room.map(|main_room| {
+                        let login_token = main_room.login_token.filter(|_| matches!(self.server_connection, SessionState::Lobby { login_state: None, .. }));
+                        let server_writer = self.server_writer.clone();
+                        cmd(id, async move {
+                            if let Some(server_writer) = server_writer {
+                                if let Some((provider, bearer_token)) = login_token {
+                                    server_writer.write(match provider {
+                                        login::Provider::RaceTime => ClientMessage::LoginRaceTime { bearer_token },
+                                        login::Provider::Discord => ClientMessage::LoginDiscord { bearer_token },
+                                    }).await?;
+                                }
+                                server_writer.write(ClientMessage::JoinRoom {
+                                    id: main_room.id,
+                                    password: (!main_room.password.is_empty()).then_some(main_room.password),
+                                }).await?;
+                            }
+                            Ok(())
+                        })
+                    })
+                } else {
+                    None
+                };
+                let effects = self.bridge.server_message(websocket_url, Room::of(&self.server_connection), msg);
+                let bridge_task = self.run_bridge(id, persistent_state, effects);
+                return match join_room {
+                    Some(join_room) => Task::batch([bridge_task, join_room]),
+                    None => bridge_task,
+                }
+            }
+            Event::ServerConnected(client) => {
+                // a new connection is started whenever the main session enters a different room, so any previous state is stale
+                self.server_connection = SessionState::default();
+                self.server_writer = Some(LoggingSink { log, context: "to server (additional frontend)", inner: client });
+            }
+            Event::ServerError(e) => if !matches!(self.server_connection, SessionState::Error { .. }) {
+                let auto_retry = e.is_network_error();
+                self.server_writer = None;
+                self.server_connection = SessionState::Error {
+                    maintenance: self.server_connection.maintenance(),
+                    e: SessionStateError::Connection(e),
+                    auto_retry,
+                };
+                if auto_retry {
+                    return Task::future(sleep(Duration::from_secs(5)).map(move |()| Message::AdditionalFrontend(id, Event::Reconnect)))
+                }
+            },
+        }
+        Task::none()
+    }
+
+    /// Whether this frontend should currently have a connection to the server.
+    pub(crate) fn wants_server_connection(&self) -> bool {
+        !matches!(self.server_connection, SessionState::Error { .. } | SessionState::Closed { .. })
+    }
+
+    pub(crate) fn server_subscription(&self, id: FrontendId, log: bool, room_id: u64, websocket_url: Url) -> Subscription {
+        Subscription { connection_id: self.server_connection_id, id, log, room_id, websocket_url }
+    }
+
+    /// A one-line summary of this frontend's world and connection state for the room view.
+    pub(crate) fn status(&self) -> String {
+        let player = match (self.bridge.world(), self.bridge.name() == Filename::default()) {
+            (Some(world), true) => format!("World {world}"),
+            (Some(world), false) => format!("World {world} ({})", self.bridge.name()),
+            (None, true) => if let Some(ref extra) = self.extra {
+                format!("{extra} (no world yet)")
+            } else {
+                format!("Additional frontend (no world yet)")
+            },
+            (None, false) => format!("{} (no world yet)", self.bridge.name()),
+        };
+        let state = match self.server_connection {
+            SessionState::Error { ref e, auto_retry: true, .. } => format!("reconnecting after error: {e}"),
+            SessionState::Error { ref e, auto_retry: false, .. } => format!("error: {e}"),
+            SessionState::Room { .. } if self.frontend_writer.is_none() => format!("waiting for frontend"),
+            SessionState::Room { wrong_file_hash: Some(_), .. } => format!("file hash doesn't match the room"),
+            SessionState::Room { world_taken: Some(world), .. } => format!("world {world} is already taken"),
+            SessionState::Room { .. } => format!("connected"),
+            SessionState::Closed { .. } => format!("disconnected"),
+            SessionState::Init { .. } | SessionState::InitAutoRejoin { .. } | SessionState::Lobby { .. } => format!("joining room…"),
+        };
+        if let Some(ref e) = self.frontend_error {
+            format!("{player}: {state} (frontend error: {e})")
+        } else {
+            format!("{player}: {state}")
+        }
+    }
+}
+
+/// Runs a task which writes to this frontend's server connection.
+fn cmd(id: FrontendId, future: impl Future<Output = Result<(), multiworld::client::Error>> + Send + 'static) -> Task<Message> {
+    Task::future(future.map(move |res| match res {
+        Ok(()) => Message::Nop,
+        Err(e) => Message::AdditionalFrontend(id, Event::ServerError(Arc::new(e.into()))),
+    }))
+}
+
+/// The server connection of an additional frontend.
+pub(crate) struct Subscription {
+    connection_id: u8,
+    id: FrontendId,
+    log: bool,
+    room_id: u64,
+    websocket_url: Url,
+}
+
+impl Recipe for Subscription {
+    type Output = Message;
+
+    fn hash(&self, state: &mut subscription::Hasher) {
+        TypeId::of::<Self>().hash(state);
+        self.id.hash(state);
+        self.connection_id.hash(state);
+        self.room_id.hash(state);
+        self.websocket_url.hash(state);
+    }
+
+    fn stream(self: Box<Self>, _: EventStream) -> Pin<Box<dyn Stream<Item = Message> + Send>> {
+        let Self { id, log, websocket_url, .. } = *self;
+        stream::once(async move {
+            let (client, receiver) = multiworld::client::connect(&websocket_url).await?;
+            Ok::<_, Error>(
+                stream::once(future::ok(Event::ServerConnected(client)))
+                .chain(receiver.into_stream().err_into().and_then(move |change| async move {
+                    if log {
+                        lock!(log = crate::LOG; writeln!(&*log, "{} from server (additional frontend {id:?}): {:?}", Utc::now().format("%Y-%m-%d %H:%M:%S"), change.msg))?;
+                    }
+                    Ok(Event::Server(change))
+                }))
+            )
+        })
+            .try_flatten()
+            .map(move |res| Message::AdditionalFrontend(id, res.unwrap_or_else(|e| Event::ServerError(Arc::new(e)))))
+            .chain(stream::pending())
+            .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_extra_frontend() {
+        assert_eq!("bizhawk:24819".parse::<ExtraFrontend>().expect("failed to parse extra frontend"), ExtraFrontend::BizHawk(24819));
+        assert_eq!("everdrive".parse::<ExtraFrontend>().expect("failed to parse extra frontend"), ExtraFrontend::EverDrive(None));
+        assert_eq!("everdrive:/dev/ttyUSB1".parse::<ExtraFrontend>().expect("failed to parse extra frontend"), ExtraFrontend::EverDrive(Some("/dev/ttyUSB1".to_owned())));
+        assert_eq!("everdrive:COM3".parse::<ExtraFrontend>().expect("failed to parse extra frontend"), ExtraFrontend::EverDrive(Some("COM3".to_owned())));
+        assert_eq!("pj64v4:24820".parse::<ExtraFrontend>().expect("failed to parse extra frontend"), ExtraFrontend::Pj64V4(24820));
+        assert_eq!("retroarch".parse::<ExtraFrontend>().expect("failed to parse extra frontend"), ExtraFrontend::RetroArch(retroarch::DEFAULT_PORT));
+        assert_eq!("RetroArch:55356".parse::<ExtraFrontend>().expect("failed to parse extra frontend"), ExtraFrontend::RetroArch(55356));
+    }
+
+    #[test]
+    fn parse_extra_frontend_errors() {
+        assert!(matches!("bizhawk".parse::<ExtraFrontend>(), Err(ParseError::MissingPort("bizhawk", _))));
+        assert!(matches!("pj64v4:port".parse::<ExtraFrontend>(), Err(ParseError::ParseInt(_))));
+        assert!(matches!("pj64v3".parse::<ExtraFrontend>(), Err(ParseError::UnknownFrontend(kind)) if kind == "pj64v3"));
+    }
+}
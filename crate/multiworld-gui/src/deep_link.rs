@@ -0,0 +1,135 @@
+//! Links which take a player directly into a room, in either of these formats:
+//!
+//! * `mhmw://join/<room id>`
+//! * `https://midos.house/mw/join/<room id>`
+//!
+//! Both accept the optional query parameters `password` (the room password) and `server` (the WebSocket URL of the server the room is on, defaulting to the Mido's House server).
+//! To protect players' sign-in tokens, links to servers other than the default one only work if the player has added that server in the settings.
+
+use {
+    std::{
+        fmt,
+        str::FromStr,
+    },
+    itertools::Itertools as _,
+    url::Url,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct DeepLink {
+    /// The WebSocket URL of the server the room is on. `None` for the default server.
+    pub(crate) server: Option<Url>,
+    pub(crate) room_id: u64,
+    pub(crate) password: Option<String>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum ParseError {
+    #[error(transparent)] ParseInt(#[from] std::num::ParseIntError),
+    #[error(transparent)] Url(#[from] url::ParseError),
+    #[error("the server in a room link must be a ws:// or wss:// URL")]
+    InvalidServer,
+    #[error("not a Mido's House Multiworld room link")]
+    NotAJoinLink,
+}
+
+impl FromStr for DeepLink {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, ParseError> {
+        let url = Url::parse(s)?;
+        let path = url.path_segments().map(|segments| segments.filter(|segment| !segment.is_empty()).collect_vec());
+        let room_id = match (url.scheme(), url.host_str(), path.as_deref()) {
+            ("mhmw", Some("join"), Some([room_id])) | ("https", Some("midos.house"), Some(["mw", "join", room_id])) => room_id.parse()?,
+            _ => return Err(ParseError::NotAJoinLink),
+        };
+        let mut server = None;
+        let mut password = None;
+        for (key, value) in url.query_pairs() {
+            match &*key {
+                "server" => {
+                    let url = Url::parse(&value)?;
+                    if !matches!(url.scheme(), "ws" | "wss") { return Err(ParseError::InvalidServer) }
+                    server = Some(url);
+                }
+                "password" => password = Some(value.into_owned()),
+                _ => {} // ignore unknown parameters so links made for newer versions of the app still work
+            }
+        }
+        Ok(Self { server, room_id, password })
+    }
+}
+
+impl fmt::Display for DeepLink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut url = Url::parse(&format!("mhmw://join/{}", self.room_id)).expect("room link is a valid URL");
+        {
+            let mut query = url.query_pairs_mut();
+            if let Some(ref server) = self.server {
+                query.append_pair("server", server.as_str());
+            }
+            if let Some(ref password) = self.password {
+                query.append_pair("password", password);
+            }
+        }
+        if url.query() == Some("") {
+            url.set_query(None);
+        }
+        url.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn link(server: Option<&str>, password: Option<&str>) -> DeepLink {
+        DeepLink {
+            server: server.map(|server| Url::parse(server).unwrap()),
+            room_id: 1234567890,
+            password: password.map(str::to_owned),
+        }
+    }
+
+    #[test]
+    fn parse_app_link() {
+        assert_eq!("mhmw://join/1234567890".parse::<DeepLink>().unwrap(), link(None, None));
+        assert_eq!("mhmw://join/1234567890?password=hunter2".parse::<DeepLink>().unwrap(), link(None, Some("hunter2")));
+        assert_eq!("mhmw://join/1234567890?server=wss%3A%2F%2Fexample.com%2Fv18&password=a%26b%3Dc%20d".parse::<DeepLink>().unwrap(), link(Some("wss://example.com/v18"), Some("a&b=c d")));
+    }
+
+    #[test]
+    fn parse_web_link() {
+        assert_eq!("https://midos.house/mw/join/1234567890".parse::<DeepLink>().unwrap(), link(None, None));
+        assert_eq!("https://midos.house/mw/join/1234567890/?server=ws%3A%2F%2F192.168.0.2%3A24819%2Fv18&password=hunter2".parse::<DeepLink>().unwrap(), link(Some("ws://192.168.0.2:24819/v18"), Some("hunter2")));
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(matches!("https://example.com/mw/join/1234567890".parse::<DeepLink>(), Err(ParseError::NotAJoinLink)));
+        assert!(matches!("mhmw://join/1234567890/extra".parse::<DeepLink>(), Err(ParseError::NotAJoinLink)));
+        assert!(matches!("mhmw://join/room".parse::<DeepLink>(), Err(ParseError::ParseInt(_))));
+        assert!(matches!("mhmw://join/1234567890?server=https%3A%2F%2Fexample.com".parse::<DeepLink>(), Err(ParseError::InvalidServer)));
+    }
+
+    #[test]
+    fn display_round_trip() {
+        for link in [
+            link(None, None),
+            link(None, Some("hunter2")),
+            link(Some("wss://example.com/v18"), None),
+            link(Some("ws://192.168.0.2:24819/v18"), Some("a&b=c d%e?")),
+        ] {
+            let display = link.to_string();
+            assert!(display.starts_with("mhmw://join/1234567890"));
+            assert_eq!(display.parse::<DeepLink>().unwrap(), link);
+        }
+        assert_eq!(link(None, None).to_string(), "mhmw://join/1234567890");
+    }
+
+    #[test]
+    fn web_link_round_trip() {
+        let parsed = "https://midos.house/mw/join/1234567890?server=wss%3A%2F%2Fexample.com%2Fv18&password=hunter2".parse::<DeepLink>().unwrap();
+        assert_eq!(parsed.to_string().parse::<DeepLink>().unwrap(), parsed);
+    }
+}
@@ -0,0 +1,110 @@
+//! The dummy frontend, which replays a script of messages instead of talking to an emulator or console, and optionally records the messages it receives.
+//!
+//! This is used to reproduce bug reports and to test the app and server end to end. See [`multiworld::script`] for the file formats.
+
+use {
+    std::{
+        any::TypeId,
+        hash::Hash as _,
+        path::PathBuf,
+        pin::Pin,
+        sync::Arc,
+    },
+    futures::{
+        future,
+        stream::{
+            self,
+            Stream,
+            StreamExt as _,
+            TryStreamExt as _,
+        },
+    },
+    iced::advanced::subscription::{
+        EventStream,
+        Recipe,
+    },
+    tokio::{
+        io::AsyncWriteExt as _,
+        sync::mpsc,
+        time::{
+            Instant,
+            sleep,
+        },
+    },
+    wheel::fs::{
+        self,
+        File,
+    },
+    multiworld::script::{
+        self,
+        ScriptEntry,
+    },
+    crate::{
+        FrontendWriter,
+        Message,
+    },
+};
+
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum Error {
+    #[error(transparent)] Io(#[from] tokio::io::Error),
+    #[error(transparent)] Script(#[from] script::Error),
+    #[error(transparent)] Wheel(#[from] wheel::Error),
+}
+
+pub(crate) struct Subscription {
+    pub(crate) script: Option<PathBuf>,
+    pub(crate) record: Option<PathBuf>,
+    pub(crate) connection_id: u8,
+}
+
+impl Recipe for Subscription {
+    type Output = Message;
+
+    fn hash(&self, state: &mut iced::advanced::subscription::Hasher) {
+        TypeId::of::<Self>().hash(state);
+        self.script.hash(state);
+        self.record.hash(state);
+        self.connection_id.hash(state);
+    }
+
+    fn stream(self: Box<Self>, _: EventStream) -> Pin<Box<dyn Stream<Item = Message> + Send>> {
+        let Self { script, record, .. } = *self;
+        stream::once(async move {
+            let script = if let Some(script) = script {
+                script::parse(&fs::read_to_string(script).await?)?
+            } else {
+                Vec::default()
+            };
+            let file = if let Some(record) = record {
+                let mut file = File::create(record).await?;
+                file.write_all(script::recording_header()?.as_bytes()).await?;
+                Some(file)
+            } else {
+                None
+            };
+            let (tx, rx) = mpsc::channel(1_024);
+            let start = Instant::now();
+            let replay = stream::iter(script).then(|ScriptEntry { delay, msg }| async move {
+                sleep(delay).await;
+                Ok(Message::Plugin(Box::new(msg)))
+            });
+            let record = stream::try_unfold((rx, file), move |(mut rx, mut file)| async move {
+                let Some(msg) = rx.recv().await else { return Ok(None) };
+                if let Some(ref mut file) = file {
+                    file.write_all(script::recording_line(start.elapsed(), &msg)?.as_bytes()).await?;
+                    file.flush().await?;
+                }
+                Ok::<_, Error>(Some(((), (rx, file))))
+            }).try_filter_map(|()| future::ok(None));
+            Ok::<_, Error>(
+                stream::once(future::ok(Message::FrontendConnected(FrontendWriter::Mpsc(tx))))
+                    .chain(stream::select(replay, record))
+            )
+        })
+            .try_flatten()
+            .map(|res| res.unwrap_or_else(|e| Message::FrontendSubscriptionError(Arc::new(e.into()))))
+            .chain(stream::pending())
+            .boxed()
+    }
+}
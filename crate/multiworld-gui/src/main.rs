@@ -6,13 +6,13 @@ use {
         cell::RefCell,
         collections::{
             BTreeMap,
-            HashMap,
             HashSet,
         },
         env,
         fmt,
         future::Future,
         io::prelude::*,
+        iter,
         mem,
         num::NonZeroU8,
         path::{
@@ -24,18 +24,15 @@ use {
         time::Duration,
     },
     async_proto::Protocol,
+    async_trait::async_trait,
     chrono::{
         TimeDelta,
         prelude::*,
     },
     enum_iterator::all,
-    futures::{
-        future::{
-            self,
-            FutureExt as _,
-        },
-        sink::SinkExt as _,
-        stream::Stream,
+    futures::future::{
+        self,
+        FutureExt as _,
     },
     iced::{
         Element,
@@ -64,11 +61,6 @@ use {
         reqwest::async_http_client,
     },
     once_cell::sync::Lazy,
-    ootr::model::{
-        DungeonReward,
-        Medallion,
-        Stone,
-    },
     ootr_utils::spoiler::HashIcon,
     open::that as open,
     rand::{
@@ -102,20 +94,39 @@ use {
     },
     multiworld::{
         DurationFormatter,
-        Filename,
-        HintArea,
         LobbyView,
         RoomFormatter,
+        RoomStart,
         RoomView,
         SessionState,
         SessionStateError,
-        config::Config,
+        bridge::{
+            self,
+            Bridge,
+            Effect,
+            FrontendSink,
+            Room,
+            RunError,
+            ServerSink,
+        },
+        config::{
+            Config,
+            ServerProfile,
+            UpdateChannel,
+            server_key,
+        },
+        everdrive,
         format_room_state,
         frontend::{
             self,
             Kind as Frontend,
         },
         github::Repo,
+        persistent_state::{
+            self,
+            PersistentState,
+        },
+        retroarch,
         ws::{
             ServerError,
             latest::{
@@ -125,20 +136,18 @@ use {
         },
     },
     crate::{
-        persistent_state::PersistentState,
-        subscriptions::{
-            LoggingSubscription,
-            WsSink,
-        },
+        deep_link::DeepLink,
+        subscriptions::LoggingSubscription,
     },
 };
 #[cfg(unix)] use xdg::BaseDirectories;
 #[cfg(windows)] use directories::ProjectDirs;
 #[cfg(target_os = "linux")] use std::os::unix::fs::PermissionsExt as _;
 
-mod everdrive;
+mod additional;
+mod deep_link;
+mod dummy;
 mod login;
-mod persistent_state;
 mod subscriptions;
 
 static LOG: Lazy<Mutex<std::fs::File>> = Lazy::new(|| {
@@ -155,6 +164,26 @@ static LOG: Lazy<Mutex<std::fs::File>> = Lazy::new(|| {
     Mutex::new(std::fs::File::create(path).expect("failed to create log file"))
 });
 
+/// Writes a line to [`LOG`]. Passed as a [`frontend::Logger`] to the frontends from the `multiworld` crate.
+fn log_line(args: fmt::Arguments<'_>) {
+    let _ = lock!(log = LOG; writeln!(&*log, "{} {args}", Utc::now().format("%Y-%m-%d %H:%M:%S")));
+}
+
+/// The size above which the capture file is rotated, see [`Capture::open_rotating`](multiworld::capture::Capture::open_rotating).
+const MAX_CAPTURE_LEN: u64 = 64 * 1024 * 1024;
+
+/// Where the server connection is recorded if the `capture` config option is enabled. See [`multiworld::capture`] for the format.
+fn capture_path() -> Result<PathBuf, Error> {
+    #[cfg(unix)] {
+        Ok(BaseDirectories::new().place_data_file("midos-house/multiworld-gui.capture")?)
+    }
+    #[cfg(windows)] {
+        let project_dirs = ProjectDirs::from("net", "Fenhl", "OoTR Multiworld").ok_or(Error::MissingHomeDir)?;
+        std::fs::create_dir_all(project_dirs.data_dir())?;
+        Ok(project_dirs.data_dir().join("gui.capture"))
+    }
+}
+
 struct LoggingReader {
     log: bool,
     context: &'static str,
@@ -174,25 +203,6 @@ impl LoggingReader {
     }
 }
 
-struct LoggingStream<R> {
-    log: bool,
-    context: &'static str,
-    inner: R,
-}
-
-impl<R: Stream<Item = Result<tungstenite::Message, tungstenite::Error>> + Unpin + Send> LoggingStream<R> {
-    async fn read_owned(mut self) -> Result<(Self, ServerMessage), async_proto::ReadError> {
-        let msg = ServerMessage::read_ws024(&mut self.inner).await?;
-        if self.log {
-            lock!(log = LOG; writeln!(&*log, "{} {}: {msg:?}", Utc::now().format("%Y-%m-%d %H:%M:%S"), self.context)).map_err(|e| async_proto::ReadError {
-                context: async_proto::ErrorContext::Custom(format!("multiworld-gui::LoggingStream::read_owned")),
-                kind: e.into(),
-            })?;
-        }
-        Ok((self, msg))
-    }
-}
-
 #[derive(Clone)]
 struct LoggingFrontendWriter {
     log: bool,
@@ -202,11 +212,24 @@ struct LoggingFrontendWriter {
 #[derive(Debug, Clone)]
 enum FrontendWriter {
     Mpsc(mpsc::Sender<frontend::ServerMessage>),
-    Tcp(Arc<Mutex<OwnedWriteHalf>>),
+    Tcp(Arc<Mutex<OwnedWriteHalf>>, frontend::Capabilities),
+}
+
+impl FrontendWriter {
+    /// The protocol features supported by the frontend. Frontends connected via a channel are built into the app and support everything.
+    fn capabilities(&self) -> frontend::Capabilities {
+        match self {
+            Self::Mpsc(_) => frontend::Capabilities::all(),
+            Self::Tcp(_, capabilities) => *capabilities,
+        }
+    }
 }
 
 impl LoggingFrontendWriter {
     async fn write(&self, msg: frontend::ServerMessage) -> Result<(), Error> {
+        if let frontend::ServerMessage::ProgressiveItems(..) = msg {
+            if !self.inner.capabilities().contains(frontend::Capabilities::PROGRESSIVE_ITEMS) { return Ok(()) }
+        }
         if self.log {
             lock!(log = LOG; writeln!(&*log, "{} to frontend: {msg:?}", Utc::now().format("%Y-%m-%d %H:%M:%S"))).map_err(|e| async_proto::WriteError {
                 context: async_proto::ErrorContext::Custom(format!("multiworld-gui::LoggingFrontendWriter::write")),
@@ -215,27 +238,45 @@ impl LoggingFrontendWriter {
         }
         Ok(match self.inner {
             FrontendWriter::Mpsc(ref tx) => tx.send(msg).await?,
-            FrontendWriter::Tcp(ref inner) => lock!(inner = inner; msg.write(&mut *inner).await)?,
+            FrontendWriter::Tcp(ref inner, _) => lock!(inner = inner; msg.write(&mut *inner).await)?,
         })
     }
 }
 
+#[async_trait]
+impl FrontendSink for LoggingFrontendWriter {
+    type Error = Error;
+
+    async fn send_frontend(&self, msg: frontend::ServerMessage) -> Result<(), Error> {
+        self.write(msg).await
+    }
+}
+
 #[derive(Clone)]
 struct LoggingSink {
     log: bool,
     context: &'static str,
-    inner: Arc<Mutex<WsSink>>,
+    inner: multiworld::client::Client,
 }
 
 impl LoggingSink {
-    async fn write(&self, msg: ClientMessage) -> Result<(), async_proto::WriteError> {
+    async fn write(&self, msg: ClientMessage) -> Result<(), multiworld::client::Error> {
         if self.log {
             lock!(log = LOG; writeln!(&*log, "{} {}: {msg:?}", Utc::now().format("%Y-%m-%d %H:%M:%S"), self.context)).map_err(|e| async_proto::WriteError {
                 context: async_proto::ErrorContext::Custom(format!("multiworld-gui::LoggingSink::write")),
                 kind: e.into(),
             })?;
         }
-        lock!(inner = self.inner; msg.write_ws024(&mut *inner).await)
+        self.inner.send(msg).await
+    }
+}
+
+#[async_trait]
+impl ServerSink for LoggingSink {
+    type Error = multiworld::client::Error;
+
+    async fn send_server(&self, msg: ClientMessage) -> Result<(), multiworld::client::Error> {
+        self.write(msg).await
     }
 }
 
@@ -278,21 +319,22 @@ fn hash_icon(icon: HashIcon) -> Element<'static, Message> {
 
 #[derive(Debug, thiserror::Error)]
 enum Error {
+    #[error(transparent)] Bridge(#[from] bridge::Error),
     #[error(transparent)] Client(#[from] multiworld::ClientError),
     #[error(transparent)] Config(#[from] multiworld::config::Error),
     #[error(transparent)] Elapsed(#[from] tokio::time::error::Elapsed),
+    #[error(transparent)] Dummy(#[from] dummy::Error),
     #[error(transparent)] EverDrive(#[from] everdrive::Error),
-    #[error(transparent)] Http(#[from] tungstenite::http::Error),
-    #[error(transparent)] InvalidUri(#[from] tungstenite::http::uri::InvalidUri),
     #[error(transparent)] Io(#[from] io::Error),
     #[error(transparent)] Json(#[from] serde_json::Error),
     #[error(transparent)] MpscFrontendSend(#[from] mpsc::error::SendError<frontend::ServerMessage>),
     #[error(transparent)] PersistentState(#[from] persistent_state::Error),
     #[error(transparent)] Read(#[from] async_proto::ReadError),
+    #[error(transparent)] RetroArch(Arc<retroarch::Error>),
     #[error(transparent)] Reqwest(#[from] reqwest::Error),
     #[error(transparent)] Semver(#[from] semver::Error),
+    #[error(transparent)] ServerConnection(#[from] multiworld::client::Error),
     #[error(transparent)] Url(#[from] url::ParseError),
-    #[error(transparent)] WebSocket(#[from] tungstenite::Error),
     #[error(transparent)] Wheel(#[from] wheel::Error),
     #[error(transparent)] Write(#[from] async_proto::WriteError),
     #[cfg(windows)]
@@ -300,12 +342,21 @@ enum Error {
     MissingHomeDir,
     #[error("Project64 script path is invalid, you can fix the script path by following the instructions defined in step 11 to 16 at:\nhttps://github.com/midoshouse/ootr-multiworld/blob/main/assets/doc/manual-install.md#for-project64\nor try to re-install Mido's House Multiworld using the installer")]
     InvalidPj64ScriptPath,
+    #[error("the EverDrive stopped responding")]
+    EverDriveTimeout,
     #[error("Failed to open Project64, make sure your script path is valid by following the instructions defined in step 11 to 16 at:\nhttps://github.com/midoshouse/ootr-multiworld/blob/main/assets/doc/manual-install.md#for-project64\nor try to re-install Mido's House Multiworld using the installer")]
     Pj64LaunchFailed(#[source] io::Error),
-    #[error("protocol version mismatch: {frontend} plugin is version {version} but we're version {}", frontend::PROTOCOL_VERSION)]
-    VersionMismatch {
+    #[error("the room link is for {link_server}, but the server {server} was specified on the command line")]
+    JoinServerConflict {
+        server: Url,
+        link_server: Url,
+    },
+    #[error("the room link is for {0}, which you haven't added to your servers in the settings. To protect your sign-in details, room links only work for known servers.")]
+    UnknownLinkServer(Url),
+    #[error("handshake with {frontend} plugin failed: {source}")]
+    FrontendHandshake {
         frontend: Frontend,
-        version: u8,
+        source: frontend::HandshakeError,
     },
 }
 
@@ -313,12 +364,13 @@ impl IsNetworkError for Error {
     fn is_network_error(&self) -> bool {
         match self {
             Self::Elapsed(_) => true,
-            Self::Config(_) | Self::EverDrive(_) | Self::Http(_) | Self::InvalidUri(_) | Self::Json(_) | Self::MpscFrontendSend(_) | Self::PersistentState(_) | Self::Semver(_) | Self::Url(_) | Self::InvalidPj64ScriptPath | Self::VersionMismatch { .. } => false,
+            Self::Bridge(_) | Self::Config(_) | Self::Dummy(_) | Self::EverDrive(_) | Self::Json(_) | Self::MpscFrontendSend(_) | Self::PersistentState(_) | Self::RetroArch(_) | Self::Semver(_) | Self::Url(_) | Self::EverDriveTimeout | Self::InvalidPj64ScriptPath | Self::JoinServerConflict { .. } | Self::UnknownLinkServer(_) => false,
             Self::Client(e) => e.is_network_error(),
+            Self::FrontendHandshake { source, .. } => source.is_network_error(),
             Self::Io(e) | Self::Pj64LaunchFailed(e) => e.is_network_error(),
             Self::Read(e) => e.is_network_error(),
             Self::Reqwest(e) => e.is_network_error(),
-            Self::WebSocket(e) => e.is_network_error(),
+            Self::ServerConnection(e) => e.is_network_error(),
             Self::Wheel(e) => e.is_network_error(),
             Self::Write(e) => e.is_network_error(),
             #[cfg(windows)] Self::MissingHomeDir => false,
@@ -326,19 +378,50 @@ impl IsNetworkError for Error {
     }
 }
 
+/// An entry in the server selection in the settings view. `None` is the default server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ServerProfileOption(Option<String>);
+
+impl fmt::Display for ServerProfileOption {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(ref name) => name.fmt(f),
+            None => write!(f, "Mido's House (default)"),
+        }
+    }
+}
+
+/// A config that was loaded after startup, wrapped so that [`Message`] can implement `Debug` without printing login tokens.
+#[derive(Clone)]
+struct LoadedConfig(Config);
+
+impl fmt::Debug for LoadedConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LoadedConfig").finish_non_exhaustive()
+    }
+}
+
 #[derive(Debug, Clone)]
 enum Message {
+    AdditionalFrontend(additional::FrontendId, additional::Event),
+    AddServerProfile,
     CheckForUpdates,
     CloseRequested(window::Id),
     CommandError(Arc<Error>),
+    ConfigError(Arc<multiworld::config::Error>),
+    ConfigLoaded(LoadedConfig),
     ConfirmRoomDeletion,
     CopyDebugInfo(bool),
+    /// Copies a link to the current room, including the password if the parameter is `true`.
+    CopyRoomLink(bool),
     CreateMidosHouseAccount(login::Provider),
+    DiscardPendingItems(Vec<persistent_state::PendingItem>),
     DiscordChannel,
     DiscordInvite,
     DismissConflictingItemKinds,
     DismissMaintenanceNotice,
     DismissWrongPassword,
+    EditConfig,
     EverDriveScanFailed(Arc<Vec<(tokio_serial::SerialPortInfo, everdrive::ConnectError)>>),
     EverDriveTimeout,
     Exit,
@@ -357,38 +440,106 @@ enum Message {
     NewIssue(bool),
     Nop,
     OpenLoginPage(Url),
+    OpenReleaseNotesLink(String),
+    PendingItems(Vec<persistent_state::PendingItem>),
     Plugin(Box<frontend::ClientMessage>), // boxed due to the large size of save data; if Message is too large, iced will overflow the stack on window resize
     ReconnectFrontend,
     ReconnectToLobby,
     ReconnectToRoom(u64, String),
+    RemoveServerProfile,
+    ResetConfig,
+    RetroArchError(Arc<retroarch::Error>),
+    RetryConfig,
+    #[cfg(any(target_os = "linux", target_os = "windows"))] Rollback,
     SendAll,
     SendAllBrowse,
     Server(ServerMessage),
-    ServerConnected(Arc<Mutex<WsSink>>),
+    ServerConnected(multiworld::client::Client),
     ServerSubscriptionError(Arc<Error>),
     SessionExpired {
         provider: login::Provider,
         error: Option<Arc<oauth2::basic::BasicRequestTokenError<oauth2::reqwest::HttpClientError>>>,
     },
     SetAutoDeleteDelta(DurationFormatter),
+    SetCapture(bool),
     SetCreateNewRoom(bool),
     SetExistingRoomSelection(RoomFormatter),
     SetFrontend(Frontend),
+    SetFrozen(bool),
     SetLobbyView(LobbyView),
     SetMaintenanceDontShowAgain(bool),
     SetNewRoomName(String),
+    SetNewServerProfileName(String),
+    SetNewServerProfileUrl(String),
     SetPassword(String),
     SetRoomFilter(String),
     SetRoomView(RoomView),
     SetSendAllPath(String),
     SetSendAllWorld(String),
+    SetServerProfile(ServerProfileOption),
     ShowConflictingItemKindsIssue,
     ShowLoggingInstructions,
+    StartRoom,
     ToggleRoomFilter,
     ToggleUpdateErrorDetails,
     UpToDate,
-    #[cfg(target_os = "macos")] UpdateAvailable(Version),
+    #[cfg(any(target_os = "linux", target_os = "windows"))] Update,
+    UpdateAvailable(Version, Vec<markdown::Item>),
     UpdateError(Arc<Error>),
+    #[cfg(any(target_os = "linux", target_os = "windows"))] UpdatePaused(Version, Vec<markdown::Item>),
+    WaitForStart,
+}
+
+/// Extracts the bundled updater and starts it, either to install the latest release or, if `rollback` is set, to restore the previous version.
+///
+/// Returns `false` if the updater doesn't support the current frontend.
+#[cfg(any(target_os = "linux", target_os = "windows"))]
+async fn run_updater(frontend: FrontendState, rollback: bool) -> Result<bool, Error> {
+    let updater_path = {
+        #[cfg(unix)] {
+            BaseDirectories::new().place_cache_file("midos-house/multiworld-updater")?
+        }
+        #[cfg(windows)] {
+            let project_dirs = ProjectDirs::from("net", "Fenhl", "OoTR Multiworld").ok_or(Error::MissingHomeDir)?;
+            let cache_dir = project_dirs.cache_dir();
+            fs::create_dir_all(cache_dir).await?;
+            cache_dir.join("updater.exe")
+        }
+    };
+    #[cfg(all(target_arch = "x86_64", target_os = "linux", debug_assertions))] let updater_data = include_bytes!("../../../target/debug/multiworld-updater");
+    #[cfg(all(target_arch = "x86_64", target_os = "linux", not(debug_assertions)))] let updater_data = include_bytes!("../../../target/release/multiworld-updater");
+    #[cfg(all(target_arch = "x86_64", target_os = "windows", debug_assertions))] let updater_data = include_bytes!("../../../target/debug/multiworld-updater.exe");
+    #[cfg(all(target_arch = "x86_64", target_os = "windows", not(debug_assertions)))] let updater_data = include_bytes!("../../../target/release/multiworld-updater.exe");
+    fs::write(&updater_path, updater_data).await?;
+    #[cfg(unix)] fs::set_permissions(&updater_path, fs::Permissions::from_mode(0o755)).await?;
+    let mut cmd = process::Command::new(updater_path);
+    if rollback {
+        cmd.arg("rollback");
+    }
+    match frontend.kind {
+        Frontend::Dummy => return Ok(false),
+        Frontend::EverDrive | Frontend::RetroArch => {
+            cmd.arg("everdrive");
+            cmd.arg(env::current_exe()?);
+            cmd.arg(process::id().to_string());
+        }
+        Frontend::BizHawk => if let Some(BizHawkState { path, pid, version, port: _ }) = frontend.bizhawk {
+            cmd.arg("bizhawk");
+            cmd.arg(process::id().to_string());
+            cmd.arg(path);
+            cmd.arg(pid.to_string());
+            cmd.arg(version.to_string());
+        } else {
+            return Ok(false)
+        },
+        Frontend::Pj64V3 | Frontend::Pj64V4 => {
+            cmd.arg("pj64");
+            cmd.arg(env::current_exe()?);
+            cmd.arg(process::id().to_string());
+        }
+    }
+    let _ = cmd.spawn()?;
+    Ok(true)
 }
 
 fn cmd(future: impl Future<Output = Result<Message, Error>> + Send + 'static) -> Task<Message> {
@@ -400,7 +551,8 @@ fn cmd(future: impl Future<Output = Result<Message, Error>> + Send + 'static) ->
 enum UpdateState {
     Pending,
     UpToDate,
-    #[cfg(target_os = "macos")] Available(Version),
+    Available(Version, Vec<markdown::Item>),
+    #[cfg(any(target_os = "linux", target_os = "windows"))] Paused(Version, Vec<markdown::Item>),
     Error {
         e: Arc<Error>,
         expanded: bool,
@@ -419,31 +571,61 @@ struct State {
     frontend_subscription_error: Option<Arc<Error>>,
     frontend_connection_id: u8,
     frontend_writer: Option<LoggingFrontendWriter>,
+    /// Frontends connected in addition to the one handled by the main session. See the [`additional`] module.
+    additional_frontends: BTreeMap<additional::FrontendId, additional::AdditionalFrontend>,
+    extra_frontends: Vec<additional::ExtraFrontend>,
     log: bool,
+    capture: bool,
     pj64_script_path: Option<PathBuf>,
     login_tokens: BTreeMap<login::Provider, String>,
+    /// The server each login token was obtained for, see [`Config::login_token_servers`].
+    login_token_servers: BTreeMap<login::Provider, String>,
     refresh_tokens: BTreeMap<login::Provider, String>,
     last_login_url: Option<Url>,
     websocket_url: Url,
+    default_websocket_url: Url,
+    server_profile: Option<String>,
+    server_profiles: BTreeMap<String, ServerProfile>,
+    /// Set if the server was specified on the command line, in which case it can't be changed in the settings.
+    server_locked: bool,
+    new_server_profile_name: String,
+    new_server_profile_url: String,
     server_connection: SessionState<Arc<Error>>,
     server_writer: Option<LoggingSink>,
     retry: Instant,
     wait_time: Duration,
-    last_world: Option<NonZeroU8>,
-    last_name: Filename,
-    last_hash: Option<Option<[HashIcon; 5]>>,
-    last_save: Option<oottracker::Save>,
-    last_dungeon_reward_locations: HashMap<DungeonReward, (NonZeroU8, HintArea)>,
+    bridge: Bridge,
     update_state: UpdateState,
+    update_channel: UpdateChannel,
+    #[cfg(any(target_os = "linux", target_os = "windows"))] auto_update_paused: bool,
+    #[cfg(any(target_os = "linux", target_os = "windows"))] previous_version: Option<Version>,
     maintenance_notice: bool,
     maintenance_dont_show_again: bool,
     send_all_path: String,
     send_all_world: String,
     show_room_filter: bool,
     room_filter: String,
+    pending_items: Vec<persistent_state::PendingItem>,
 }
 
 impl State {
+    /// Carries out effects returned by [`State::bridge`] in the background.
+    fn run_bridge(&self, effects: Vec<Effect>) -> Task<Message> {
+        if effects.is_empty() { return Task::none() }
+        let updates_pending_items = effects.iter().any(|effect| matches!(effect, Effect::HoldItem(_) | Effect::Saved { .. } | Effect::SendHeldItems { .. }));
+        let server_writer = self.server_writer.clone();
+        let frontend_writer = self.frontend_writer.clone();
+        let persistent_state = self.persistent_state.clone();
+        cmd(async move {
+            bridge::run(effects, server_writer.as_ref(), frontend_writer.as_ref(), &persistent_state).await.map_err(|e| match e {
+                RunError::PersistentState(e) => Error::from(e),
+                RunError::Server(e) => Error::from(e),
+                RunError::Frontend(e) => e,
+            })?;
+            Ok(if updates_pending_items { Message::PendingItems(persistent_state.pending_items().await) } else { Message::Nop })
+        })
+    }
+
     fn error_to_markdown(&self, update: bool) -> String {
         if update {
             if let UpdateState::Error { ref e, .. } = self.update_state {
@@ -590,6 +772,13 @@ struct BizHawkState {
     port: u16,
 }
 
+/// Files used by the dummy frontend, see the `dummy` module.
+#[derive(Debug, Default, Clone)]
+struct DummyState {
+    script: Option<PathBuf>,
+    record: Option<PathBuf>,
+}
+
 #[derive(Debug, Default, Clone)]
 enum EverDriveState {
     #[default]
@@ -602,9 +791,18 @@ enum EverDriveState {
 #[derive(Debug, Clone)]
 struct FrontendState {
     kind: Frontend,
+    dummy: DummyState,
     #[cfg(any(target_os = "linux", target_os = "windows"))]
     bizhawk: Option<BizHawkState>,
     everdrive: EverDriveState,
+    /// The serial port given on the command line, used instead of scanning for EverDrives.
+    everdrive_port: Option<String>,
+    /// The port the Project64 4.x script listens on, if it was passed on the command line.
+    pj64v4_port: Option<u16>,
+    /// The port of RetroArch's network command interface.
+    retroarch_port: u16,
+    /// The reason the RetroArch frontend last failed to connect or lost its connection.
+    retroarch_error: Option<Arc<retroarch::Error>>,
 }
 
 impl FrontendState {
@@ -620,13 +818,14 @@ impl FrontendState {
             #[cfg(not(any(target_os = "linux", target_os = "windows")))] Frontend::BizHawk => unreachable!("no BizHawk support on this platform"),
             Frontend::Pj64V3 => "Project64 3.x".into(),
             Frontend::Pj64V4 => "Project64 4.x".into(),
+            Frontend::RetroArch => "RetroArch".into(),
         }
     }
 
     fn is_locked(&self) -> bool {
         match self.kind {
-            Frontend::Dummy | Frontend::EverDrive | Frontend::Pj64V3 => false,
-            Frontend::Pj64V4 => false, //TODO pass port from PJ64, consider locked if present
+            Frontend::Dummy | Frontend::EverDrive | Frontend::Pj64V3 | Frontend::RetroArch => false,
+            Frontend::Pj64V4 => self.pj64v4_port.is_some(),
             #[cfg(any(target_os = "linux", target_os = "windows"))] Frontend::BizHawk => self.bizhawk.is_some(),
             #[cfg(not(any(target_os = "linux", target_os = "windows")))] Frontend::BizHawk => unreachable!("no BizHawk support on this platform"),
         }
@@ -634,11 +833,48 @@ impl FrontendState {
 }
 
 impl State {
-    fn new(icon_error: Option<icon::Error>, config: Result<Config, multiworld::config::Error>, persistent_state: Result<PersistentState, persistent_state::Error>, frontend: Option<FrontendArgs>) -> Self {
+    fn new(icon_error: Option<icon::Error>, config: Result<Config, multiworld::config::Error>, persistent_state: Result<PersistentState, persistent_state::Error>, frontend: Option<FrontendArgs>, server: Option<String>, join: Option<DeepLink>, extra_frontends: Vec<additional::ExtraFrontend>) -> Self {
         let (config, config_error) = match config {
             Ok(config) => (config, None),
             Err(e) => (Config::default(), Some(Arc::new(e))),
         };
+        let default_websocket_url = config.default_websocket_url().expect("failed to parse WebSocket URL");
+        let (mut websocket_url, mut command_error) = match server.as_deref().map_or_else(|| config.websocket_url().map_err(Error::from), |server| config.server_arg_url(server).map_err(Error::from)) {
+            Ok(websocket_url) => (websocket_url, None),
+            Err(e) => (default_websocket_url.clone(), Some(Arc::new(e))),
+        };
+        let mut server_profile = config.server_profile.clone();
+        let mut server_locked = server.is_some();
+        let server_connection = if let Some(DeepLink { server: link_server, room_id, password }) = join {
+            let link_server = link_server.unwrap_or_else(|| default_websocket_url.clone());
+            // only connect to known servers since the app sends sign-in tokens to the server
+            let known_server = iter::once(Ok((None, default_websocket_url.clone())))
+                .chain(config.server_profiles.iter().map(|(name, profile)| Ok::<_, url::ParseError>((Some(name.clone()), profile.websocket_url()?))))
+                .filter_map(Result::ok)
+                .find(|(_, known_server)| server_key(known_server) == server_key(&link_server));
+            if server.is_some() && server_key(&websocket_url) != server_key(&link_server) {
+                // neither argument takes precedence since joining the room on the wrong server would be confusing
+                command_error.get_or_insert_with(|| Arc::new(Error::JoinServerConflict { server: websocket_url.clone(), link_server }));
+                SessionState::default()
+            } else if let Some((known_profile, known_server)) = known_server {
+                if server_key(&known_server) != server_key(&websocket_url) {
+                    // the link switches servers for this session only, like the --server argument
+                    server_profile = known_profile;
+                    server_locked = true;
+                    websocket_url = known_server;
+                }
+                SessionState::InitAutoRejoin {
+                    maintenance: None,
+                    room_id,
+                    room_password: password.unwrap_or_default(),
+                }
+            } else {
+                command_error.get_or_insert_with(|| Arc::new(Error::UnknownLinkServer(link_server)));
+                SessionState::default()
+            }
+        } else {
+            SessionState::default()
+        };
         let (persistent_state, persistent_state_error) = match persistent_state {
             Ok(persistent_state) => (persistent_state, None),
             Err(e) => (PersistentState::default(), Some(Arc::new(e))),
@@ -649,12 +885,34 @@ impl State {
                     #[cfg(windows)] { Frontend::Pj64V3 }
                     #[cfg(not(windows))] { Frontend::EverDrive }
                 }),
-                Some(FrontendArgs::Dummy) => Frontend::Dummy,
-                Some(FrontendArgs::EverDrive) => Frontend::EverDrive,
+                Some(FrontendArgs::Dummy { .. }) => Frontend::Dummy,
+                Some(FrontendArgs::EverDrive { .. }) => Frontend::EverDrive,
                 Some(FrontendArgs::BizHawk { .. }) => Frontend::BizHawk,
                 Some(FrontendArgs::Pj64V3) => Frontend::Pj64V3,
-                Some(FrontendArgs::Pj64V4) => Frontend::Pj64V4,
+                Some(FrontendArgs::Pj64V4 { .. }) => Frontend::Pj64V4,
+                Some(FrontendArgs::RetroArch { .. }) => Frontend::RetroArch,
+            },
+            dummy: if let Some(FrontendArgs::Dummy { ref script, ref record }) = frontend {
+                DummyState { script: script.clone(), record: record.clone() }
+            } else {
+                DummyState::default()
+            },
+            everdrive_port: if let Some(FrontendArgs::EverDrive { ref port }) = frontend {
+                port.clone()
+            } else {
+                None
+            },
+            pj64v4_port: if let Some(FrontendArgs::Pj64V4 { port }) = frontend {
+                port
+            } else {
+                None
+            },
+            retroarch_port: if let Some(FrontendArgs::RetroArch { port }) = frontend {
+                port
+            } else {
+                retroarch::DEFAULT_PORT
             },
+            retroarch_error: None,
             #[cfg(any(target_os = "linux", target_os = "windows"))]
             bizhawk: if let Some(FrontendArgs::BizHawk { path, pid, version, port }) = frontend {
                 Some(BizHawkState { path, pid, version, port })
@@ -666,27 +924,34 @@ impl State {
         Self {
             debug_info_copied: HashSet::default(),
             icon_error: icon_error.map(Arc::new),
-            command_error: None,
+            command_error,
             login_error: None,
             frontend_subscription_error: None,
             frontend_connection_id: 0,
             frontend_writer: None,
-            websocket_url: config.websocket_url().expect("failed to parse WebSocket URL"),
+            additional_frontends: extra_frontends.iter().cloned().enumerate().map(|(idx, extra)| (additional::FrontendId::Extra(idx), additional::AdditionalFrontend::new(Some(extra)))).collect(),
+            extra_frontends,
+            server_profile, server_locked,
+            server_profiles: config.server_profiles,
+            new_server_profile_name: String::default(),
+            new_server_profile_url: String::default(),
+            websocket_url, default_websocket_url,
             log: config.log,
+            capture: config.capture,
             pj64_script_path: config.pj64_script_path,
             login_tokens: config.login_tokens,
+            login_token_servers: config.login_token_servers,
             refresh_tokens: config.refresh_tokens,
             last_login_url: None,
-            server_connection: SessionState::default(),
+            server_connection,
             server_writer: None,
             retry: Instant::now(),
             wait_time: Duration::from_secs(1),
-            last_world: None,
-            last_name: Filename::default(),
-            last_hash: None,
-            last_save: None,
-            last_dungeon_reward_locations: HashMap::default(),
+            bridge: Bridge::default(),
             update_state: UpdateState::Pending,
+            update_channel: config.update_channel,
+            #[cfg(any(target_os = "linux", target_os = "windows"))] auto_update_paused: config.auto_update_paused,
+            #[cfg(any(target_os = "linux", target_os = "windows"))] previous_version: config.previous_version,
             maintenance_notice: config.dismiss_maintenance_notices_until.is_none_or(|until| until < Utc.with_ymd_and_hms(2026, 5, 22, 0, 0, 0).single().expect("wrong hardcoded datetime"))
                 && (Utc.with_ymd_and_hms(2026, 4, 18, 0, 0, 0).single().expect("wrong hardcoded datetime")..Utc.with_ymd_and_hms(2026, 5, 22, 0, 0, 0).single().expect("wrong hardcoded datetime")).contains(&Utc::now()),
             maintenance_dont_show_again: false,
@@ -694,10 +959,48 @@ impl State {
             send_all_world: String::default(),
             show_room_filter: false,
             room_filter: String::default(),
+            pending_items: Vec::default(),
             frontend, config_error, persistent_state_error, persistent_state,
         }
     }
 
+    /// Applies a config which was loaded after the app was started, e.g. after recovering from a broken config file.
+    fn apply_config(&mut self, config: Config) {
+        if !self.frontend.is_locked() {
+            if let Some(default_frontend) = config.default_frontend {
+                self.frontend.kind = default_frontend;
+            }
+        }
+        match config.default_websocket_url() {
+            Ok(default_websocket_url) => self.default_websocket_url = default_websocket_url,
+            Err(e) => {
+                self.command_error = Some(Arc::new(e.into()));
+                return
+            }
+        }
+        if !self.server_locked {
+            match config.websocket_url() {
+                Ok(websocket_url) => self.websocket_url = websocket_url,
+                Err(e) => {
+                    self.command_error = Some(Arc::new(e.into()));
+                    return
+                }
+            }
+        }
+        self.server_profile = config.server_profile;
+        self.server_profiles = config.server_profiles;
+        self.log = config.log;
+        self.capture = config.capture;
+        self.pj64_script_path = config.pj64_script_path;
+        self.login_tokens = config.login_tokens;
+        self.login_token_servers = config.login_token_servers;
+        self.refresh_tokens = config.refresh_tokens;
+        self.update_channel = config.update_channel;
+        #[cfg(any(target_os = "linux", target_os = "windows"))] { self.auto_update_paused = config.auto_update_paused; }
+        #[cfg(any(target_os = "linux", target_os = "windows"))] { self.previous_version = config.previous_version; }
+        self.config_error = None;
+    }
+
     fn title(&self) -> String {
         if self.frontend.is_locked() {
             format!("Mido's House Multiworld for {}", self.frontend.kind)
@@ -709,14 +1012,62 @@ impl State {
     fn update(&mut self, msg: Message) -> Task<Message> {
         match msg {
             Message::SetLobbyView(new_view) => if let SessionState::Lobby { ref mut view, .. } = self.server_connection {
+                let load_pending_items = matches!(new_view, LobbyView::PendingItems);
                 *view = new_view;
+                if load_pending_items {
+                    let persistent_state = self.persistent_state.clone();
+                    return cmd(async move { Ok(Message::PendingItems(persistent_state.pending_items().await)) })
+                }
             },
             Message::SetRoomView(new_view) => if let SessionState::Room { ref mut view, .. } = self.server_connection {
                 *view = new_view;
             },
+            Message::AdditionalFrontend(id, event) => match (id, event) {
+                (additional::FrontendId::Listener(_), additional::Event::FrontendDisconnected(e)) => {
+                    self.additional_frontends.remove(&id);
+                    let frontend_closed = if let Error::Read(async_proto::ReadError { kind: async_proto::ReadErrorKind::Io(ref io_error), .. }) = *e {
+                        matches!(io_error.kind(), io::ErrorKind::ConnectionReset | io::ErrorKind::UnexpectedEof)
+                    } else {
+                        false
+                    };
+                    if !frontend_closed {
+                        self.frontend_subscription_error.get_or_insert(e);
+                    }
+                }
+                (_, event) => {
+                    if let (additional::FrontendId::Listener(_), additional::Event::FrontendConnected(_)) = (id, &event) {
+                        self.additional_frontends.insert(id, additional::AdditionalFrontend::new(None));
+                    }
+                    let main_room = if let SessionState::Room { room_id, ref room_password, .. } = self.server_connection {
+                        Some(additional::MainRoom {
+                            id: room_id,
+                            password: room_password.clone(),
+                            login_token: multiworld::config::login_token_for(&self.login_tokens, &self.login_token_servers, &self.websocket_url),
+                        })
+                    } else {
+                        None
+                    };
+                    if let Some(frontend) = self.additional_frontends.get_mut(&id) {
+                        return frontend.update(id, self.log, &self.websocket_url, &self.persistent_state, main_room, event)
+                    }
+                }
+            },
+            Message::AddServerProfile => if let Some(profile) = Url::parse(&self.new_server_profile_url).ok().as_ref().and_then(ServerProfile::from_websocket_url) {
+                let name = mem::take(&mut self.new_server_profile_name);
+                self.new_server_profile_url.clear();
+                self.server_profiles.insert(name.clone(), profile.clone());
+                return cmd(async move {
+                    let mut config = Config::load().await?;
+                    config.server_profiles.insert(name.clone(), profile);
+                    config.save().await?;
+                    Ok(Message::SetServerProfile(ServerProfileOption(Some(name))))
+                })
+            },
             Message::CheckForUpdates => {
                 self.update_state = UpdateState::Pending;
-                #[cfg(any(target_os = "linux", target_os = "windows"))] let frontend = self.frontend.clone();
+                #[cfg(any(target_os = "linux", target_os = "windows"))] let auto_update_paused = self.auto_update_paused;
+                let update_channel = self.update_channel.clone();
+                let log = self.log;
                 return cmd(async move {
                     let http_client = reqwest::Client::builder()
                         .user_agent(concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")))
@@ -725,56 +1076,21 @@ impl State {
                         .http2_prior_knowledge()
                         .build()?;
                     let repo = Repo::new("midoshouse", "ootr-multiworld");
-                    if let Some(release) = repo.latest_release(&http_client).await? {
+                    if let Some(release) = repo.release_for_channel(&http_client, &update_channel).await? {
                         let new_ver = release.version()?;
-                        if new_ver > Version::parse(env!("CARGO_PKG_VERSION"))? {
-                            #[cfg(any(target_os = "linux", target_os = "windows"))] {
-                                let updater_path = {
-                                    #[cfg(unix)] {
-                                        BaseDirectories::new().place_cache_file("midos-house/multiworld-updater")?
-                                    }
-                                    #[cfg(windows)] {
-                                        let project_dirs = ProjectDirs::from("net", "Fenhl", "OoTR Multiworld").ok_or(Error::MissingHomeDir)?;
-                                        let cache_dir = project_dirs.cache_dir();
-                                        fs::create_dir_all(cache_dir).await?;
-                                        cache_dir.join("updater.exe")
-                                    }
-                                };
-                                #[cfg(all(target_arch = "x86_64", target_os = "linux", debug_assertions))] let updater_data = include_bytes!("../../../target/debug/multiworld-updater");
-                                #[cfg(all(target_arch = "x86_64", target_os = "linux", not(debug_assertions)))] let updater_data = include_bytes!("../../../target/release/multiworld-updater");
-                                #[cfg(all(target_arch = "x86_64", target_os = "windows", debug_assertions))] let updater_data = include_bytes!("../../../target/debug/multiworld-updater.exe");
-                                #[cfg(all(target_arch = "x86_64", target_os = "windows", not(debug_assertions)))] let updater_data = include_bytes!("../../../target/release/multiworld-updater.exe");
-                                fs::write(&updater_path, updater_data).await?;
-                                #[cfg(unix)] fs::set_permissions(&updater_path, fs::Permissions::from_mode(0o755)).await?;
-                                let mut cmd = process::Command::new(updater_path);
-                                match frontend.kind {
-                                    Frontend::Dummy => return Ok(Message::UpToDate),
-                                    Frontend::EverDrive => {
-                                        cmd.arg("everdrive");
-                                        cmd.arg(env::current_exe()?);
-                                        cmd.arg(process::id().to_string());
-                                    }
-                                    Frontend::BizHawk => if let Some(BizHawkState { path, pid, version, port: _ }) = frontend.bizhawk {
-                                        cmd.arg("bizhawk");
-                                        cmd.arg(process::id().to_string());
-                                        cmd.arg(path);
-                                        cmd.arg(pid.to_string());
-                                        cmd.arg(version.to_string());
-                                    } else {
-                                        return Ok(Message::UpToDate)
-                                    },
-                                    Frontend::Pj64V3 | Frontend::Pj64V4 => {
-                                        cmd.arg("pj64");
-                                        cmd.arg(env::current_exe()?);
-                                        cmd.arg(process::id().to_string());
+                        if update_channel.should_update(&new_ver) {
+                            // the release notes are only informational, so failing to load them shouldn't hide the update
+                            let notes = match repo.release_notes(&http_client, &multiworld::version(), &new_ver).await {
+                                Ok(notes) => markdown::parse(&notes).collect(),
+                                Err(e) => {
+                                    if log {
+                                        lock!(log = LOG; writeln!(&*log, "{} failed to load release notes: {e}\ndebug info: {e:?}", Utc::now().format("%Y-%m-%d %H:%M:%S")))?;
                                     }
+                                    markdown::parse(&format!("*The release notes could not be loaded: {e}*")).collect()
                                 }
-                                let _ = cmd.spawn()?;
-                                return Ok(Message::Exit)
-                            }
-                            #[cfg(target_os = "macos")] {
-                                return Ok(Message::UpdateAvailable(new_ver))
-                            }
+                            };
+                            #[cfg(any(target_os = "linux", target_os = "windows"))] if auto_update_paused { return Ok(Message::UpdatePaused(new_ver, notes)) }
+                            return Ok(Message::UpdateAvailable(new_ver, notes))
                         }
                     }
                     Ok(Message::UpToDate)
@@ -787,23 +1103,19 @@ impl State {
                 let server_writer = self.server_writer.take();
                 return cmd(async move {
                     if let Some(frontend_writer) = frontend_writer {
-                        if let FrontendWriter::Tcp(writer) = frontend_writer.inner {
+                        if let FrontendWriter::Tcp(writer, _) = frontend_writer.inner {
                             lock!(writer = writer; writer.shutdown().await)?;
                         }
                     }
                     if let Some(server_writer) = server_writer {
-                        lock!(server_writer = server_writer.inner; {
-                            server_writer.send(tungstenite::Message::Close(Some(tungstenite::protocol::CloseFrame {
-                                code: tungstenite::protocol::frame::coding::CloseCode::Away,
-                                reason: "multiworld app exiting".into(),
-                            }))).await?;
-                            server_writer.close().await?;
-                        });
+                        server_writer.inner.close("multiworld app exiting".to_owned()).await?;
                     }
                     Ok(Message::Exit)
                 })
             },
             Message::CommandError(e) => { self.command_error.get_or_insert(e); }
+            Message::ConfigError(e) => self.config_error = Some(e),
+            Message::ConfigLoaded(LoadedConfig(config)) => self.apply_config(config),
             Message::ConfirmRoomDeletion => if let Some(writer) = self.server_writer.clone() {
                 return cmd(async move {
                     writer.write(ClientMessage::DeleteRoom).await?;
@@ -815,12 +1127,27 @@ impl State {
                 self.debug_info_copied.insert(update);
                 return clipboard::write(error_md)
             }
+            Message::CopyRoomLink(include_password) => if let SessionState::Room { room_id, ref room_password, .. } = self.server_connection {
+                let link = DeepLink {
+                    server: (self.websocket_url != self.default_websocket_url).then(|| self.websocket_url.clone()),
+                    password: include_password.then(|| room_password.clone()),
+                    room_id,
+                };
+                return clipboard::write(link.to_string())
+            },
             Message::CreateMidosHouseAccount(provider) => if let Err(e) = open(match provider {
                 login::Provider::Discord => "https://midos.house/login/discord",
                 login::Provider::RaceTime => "https://midos.house/login/racetime",
             }) {
                 return cmd(future::err(e.into()))
             },
+            Message::DiscardPendingItems(items) => {
+                let persistent_state = self.persistent_state.clone();
+                return cmd(async move {
+                    persistent_state.edit(move |state| state.discard(&items)).await?;
+                    Ok(Message::PendingItems(persistent_state.pending_items().await))
+                })
+            }
             Message::DiscordChannel => if let Err(e) = open("https://discord.com/channels/274180765816848384/476723801032491008") {
                 return cmd(future::err(e.into()))
             },
@@ -844,16 +1171,22 @@ impl State {
             Message::DismissWrongPassword => if let SessionState::Lobby { ref mut wrong_password, .. } = self.server_connection {
                 *wrong_password = false;
             },
+            Message::EditConfig => return cmd(async {
+                open(Config::path().await?)?;
+                Ok(Message::Nop)
+            }),
             Message::EverDriveScanFailed(errors) => {
                 self.frontend.everdrive = EverDriveState::Searching(errors);
                 if let Frontend::EverDrive = self.frontend.kind {
                     self.frontend_writer = None;
+                    self.bridge.frontend_disconnected();
                 }
             }
             Message::EverDriveTimeout => {
                 self.frontend.everdrive = EverDriveState::Timeout;
                 if let Frontend::EverDrive = self.frontend.kind {
                     self.frontend_writer = None;
+                    self.bridge.frontend_disconnected();
                 }
             }
             Message::Exit => return iced::exit(),
@@ -861,25 +1194,13 @@ impl State {
                 if let Frontend::EverDrive = self.frontend.kind {
                     self.frontend.everdrive = EverDriveState::Connected;
                 }
-                let writer = LoggingFrontendWriter { log: self.log, inner };
-                self.frontend_writer = Some(writer.clone());
-                if let SessionState::Room { ref players, ref item_queue, .. } = self.server_connection {
-                    let players = players.clone();
-                    let item_queue = item_queue.clone();
-                    return cmd(async move {
-                        for player in players {
-                            writer.write(frontend::ServerMessage::PlayerName(player.world, if player.name == Filename::default() {
-                                Filename::fallback(player.world)
-                            } else {
-                                player.name
-                            })).await?;
-                        }
-                        if !item_queue.is_empty() {
-                            writer.write(frontend::ServerMessage::ItemQueue(item_queue)).await?;
-                        }
-                        Ok(Message::Nop)
-                    })
+                if let Frontend::RetroArch = self.frontend.kind {
+                    self.frontend.retroarch_error = None;
                 }
+                let capabilities = inner.capabilities();
+                self.frontend_writer = Some(LoggingFrontendWriter { log: self.log, inner });
+                let effects = self.bridge.frontend_connected(capabilities, Room::of(&self.server_connection));
+                return self.run_bridge(effects)
             }
             Message::FrontendSubscriptionError(e) => {
                 if let Error::Read(async_proto::ReadError { kind: async_proto::ReadErrorKind::Io(ref e), .. }) = *e {
@@ -887,6 +1208,7 @@ impl State {
                         (Frontend::BizHawk | Frontend::Pj64V4, io::ErrorKind::ConnectionReset | io::ErrorKind::UnexpectedEof) => return iced::exit(), // frontend closed
                         (Frontend::Pj64V3, io::ErrorKind::ConnectionReset) => {
                             self.frontend_writer = None;
+                            self.bridge.frontend_disconnected();
                             return Task::none()
                         }
                         (_, _) => {}
@@ -934,7 +1256,10 @@ impl State {
             },
             Message::LoginError(e) => { self.login_error.get_or_insert(e); }
             Message::LoginTokens { provider, bearer_token, refresh_token } => {
+                // the user logged in while connected to the current server, so the token may be sent there automatically in the future
+                let server = server_key(&self.websocket_url);
                 self.login_tokens.insert(provider, bearer_token.clone());
+                self.login_token_servers.insert(provider, server.clone());
                 if let Some(ref refresh_token) = refresh_token {
                     self.refresh_tokens.insert(provider, refresh_token.clone());
                 }
@@ -943,6 +1268,7 @@ impl State {
                     return cmd(async move {
                         let mut config = Config::load().await?;
                         config.login_tokens.insert(provider, bearer_token.clone());
+                        config.login_token_servers.insert(provider, server);
                         if let Some(refresh_token) = refresh_token {
                             config.refresh_tokens.insert(provider, refresh_token.clone());
                         }
@@ -970,6 +1296,9 @@ impl State {
                 }
             }
             Message::Nop => {}
+            Message::OpenReleaseNotesLink(url) => if let Err(e) = open(url) {
+                return cmd(future::err(e.into()))
+            },
             Message::OpenLoginPage(url) => {
                 self.last_login_url = Some(url.clone());
                 return cmd(async move {
@@ -977,186 +1306,48 @@ impl State {
                     Ok(Message::Nop)
                 })
             }
-            Message::Plugin(msg) => match *msg {
-                frontend::ClientMessage::PlayerId(new_player_id) => {
-                    let (new_player_name, new_file_hash) = if self.last_world.replace(new_player_id).is_none() {
-                        (
-                            (self.last_name != Filename::default()).then_some(self.last_name),
-                            self.last_hash,
-                        )
-                    } else {
-                        (None, None)
-                    };
-                    if let Some(ref writer) = self.server_writer {
-                        if let SessionState::Room { .. } = self.server_connection {
-                            let writer = writer.clone();
-                            return cmd(async move {
-                                writer.write(ClientMessage::PlayerId(new_player_id)).await?;
-                                if let Some(new_player_name) = new_player_name {
-                                    writer.write(ClientMessage::PlayerName(new_player_name)).await?;
-                                }
-                                if let Some(new_file_hash) = new_file_hash {
-                                    writer.write(ClientMessage::FileHash(new_file_hash)).await?;
-                                }
-                                Ok(Message::Nop)
-                            })
-                        }
-                    }
-                }
-                frontend::ClientMessage::PlayerName(new_player_name) => {
-                    self.last_name = new_player_name;
-                    if self.last_world.is_some() {
-                        if let Some(ref writer) = self.server_writer {
-                            if let SessionState::Room { .. } = self.server_connection {
-                                let writer = writer.clone();
-                                return cmd(async move {
-                                    writer.write(ClientMessage::PlayerName(new_player_name)).await?;
-                                    Ok(Message::Nop)
-                                })
-                            }
-                        }
-                    }
-                }
-                frontend::ClientMessage::SendItem { key, kind, target_world } => {
-                    if let Self { server_writer: Some(writer), server_connection: SessionState::Room { .. }, .. } = self {
-                        let writer = writer.clone();
-                        return cmd(async move {
-                            writer.write(ClientMessage::SendItem { key, kind, target_world }).await?;
-                            Ok(Message::Nop)
-                        })
-                    } else {
-                        if let Some(hash) = self.last_hash {
-                            let persistent_state = self.persistent_state.clone();
-                            return cmd(async move {
-                                persistent_state.edit(move |state| state.pending_items_after_save.push(persistent_state::PendingItem { hash, key, kind, target_world })).await?;
-                                Ok(Message::Nop)
-                            })
-                        }
-                    }
-                }
-                frontend::ClientMessage::SaveData(save) => match oottracker::Save::from_save_data(&save) {
-                    Ok(save) => {
-                        self.last_save = Some(save.clone());
-                        let persistent_state = self.persistent_state.clone();
-                        let writer = if let SessionState::Room { .. } = self.server_connection {
-                            self.server_writer.clone()
-                        } else {
-                            None
-                        };
-                        return cmd(async move {
-                            persistent_state.edit(|state| state.pending_items_before_save.extend(state.pending_items_after_save.drain(..))).await?;
-                            if let Some(writer) = writer {
-                                writer.write(ClientMessage::SaveData(save)).await?;
-                            }
-                            Ok(Message::Nop)
-                        })
-                    }
-                    Err(e) => if let Some(writer) = self.server_writer.clone() {
-                        return cmd(async move {
-                            writer.write(ClientMessage::SaveDataError { debug: format!("{e:?}"), version: multiworld::version() }).await?;
-                            Ok(Message::Nop)
-                        })
-                    },
-                },
-                frontend::ClientMessage::FileHash(new_hash) => {
-                    self.last_hash = Some(new_hash);
-                    if self.last_world.is_some() {
-                        if let Some(ref writer) = self.server_writer {
-                            if let SessionState::Room { .. } = self.server_connection {
-                                let writer = writer.clone();
-                                return cmd(async move {
-                                    writer.write(ClientMessage::FileHash(new_hash)).await?;
-                                    Ok(Message::Nop)
-                                })
-                            }
-                        }
-                    }
-                }
-                frontend::ClientMessage::ResetPlayerId => {
-                    self.last_world = None;
-                    if let Some(ref writer) = self.server_writer {
-                        if let SessionState::Room { .. } = self.server_connection {
-                            let writer = writer.clone();
-                            return cmd(async move {
-                                writer.write(ClientMessage::ResetPlayerId).await?;
-                                Ok(Message::Nop)
-                            })
-                        }
-                    }
-                }
-                frontend::ClientMessage::DungeonRewardInfo { emerald, ruby, sapphire, light, forest, fire, water, shadow, spirit } => {
-                    let mut messages = Vec::default();
-                    if let Some((world, area)) = emerald {
-                        if self.last_dungeon_reward_locations.insert(DungeonReward::Stone(Stone::KokiriEmerald), (world, area)) != Some((world, area)) {
-                            messages.push(ClientMessage::DungeonRewardInfo { reward: DungeonReward::Stone(Stone::KokiriEmerald), world, area });
-                        }
-                    }
-                    if let Some((world, area)) = ruby {
-                        if self.last_dungeon_reward_locations.insert(DungeonReward::Stone(Stone::GoronRuby), (world, area)) != Some((world, area)) {
-                            messages.push(ClientMessage::DungeonRewardInfo { reward: DungeonReward::Stone(Stone::GoronRuby), world, area });
-                        }
-                    }
-                    if let Some((world, area)) = sapphire {
-                        if self.last_dungeon_reward_locations.insert(DungeonReward::Stone(Stone::ZoraSapphire), (world, area)) != Some((world, area)) {
-                            messages.push(ClientMessage::DungeonRewardInfo { reward: DungeonReward::Stone(Stone::ZoraSapphire), world, area });
-                        }
-                    }
-                    if let Some((world, area)) = light {
-                        if self.last_dungeon_reward_locations.insert(DungeonReward::Medallion(Medallion::Light), (world, area)) != Some((world, area)) {
-                            messages.push(ClientMessage::DungeonRewardInfo { reward: DungeonReward::Medallion(Medallion::Light), world, area });
-                        }
-                    }
-                    if let Some((world, area)) = forest {
-                        if self.last_dungeon_reward_locations.insert(DungeonReward::Medallion(Medallion::Forest), (world, area)) != Some((world, area)) {
-                            messages.push(ClientMessage::DungeonRewardInfo { reward: DungeonReward::Medallion(Medallion::Forest), world, area });
-                        }
-                    }
-                    if let Some((world, area)) = fire {
-                        if self.last_dungeon_reward_locations.insert(DungeonReward::Medallion(Medallion::Fire), (world, area)) != Some((world, area)) {
-                            messages.push(ClientMessage::DungeonRewardInfo { reward: DungeonReward::Medallion(Medallion::Fire), world, area });
-                        }
-                    }
-                    if let Some((world, area)) = water {
-                        if self.last_dungeon_reward_locations.insert(DungeonReward::Medallion(Medallion::Water), (world, area)) != Some((world, area)) {
-                            messages.push(ClientMessage::DungeonRewardInfo { reward: DungeonReward::Medallion(Medallion::Water), world, area });
-                        }
-                    }
-                    if let Some((world, area)) = shadow {
-                        if self.last_dungeon_reward_locations.insert(DungeonReward::Medallion(Medallion::Shadow), (world, area)) != Some((world, area)) {
-                            messages.push(ClientMessage::DungeonRewardInfo { reward: DungeonReward::Medallion(Medallion::Shadow), world, area });
-                        }
-                    }
-                    if let Some((world, area)) = spirit {
-                        if self.last_dungeon_reward_locations.insert(DungeonReward::Medallion(Medallion::Spirit), (world, area)) != Some((world, area)) {
-                            messages.push(ClientMessage::DungeonRewardInfo { reward: DungeonReward::Medallion(Medallion::Spirit), world, area });
-                        }
-                    }
-                    if let Some(ref writer) = self.server_writer {
-                        if let SessionState::Room { .. } = self.server_connection {
-                            let writer = writer.clone();
-                            return cmd(async move {
-                                for message in messages {
-                                    writer.write(message).await?;
-                                }
-                                Ok(Message::Nop)
-                            })
-                        }
-                    }
-                }
-                frontend::ClientMessage::CurrentScene(scene) => if let Some(ref writer) = self.server_writer {
-                    let writer = writer.clone();
-                    return cmd(async move {
-                        writer.write(ClientMessage::CurrentScene(scene)).await?;
-                        Ok(Message::Nop)
-                    })
-                },
+            Message::PendingItems(items) => self.pending_items = items,
+            Message::Plugin(msg) => match self.bridge.frontend_message(&self.websocket_url, Room::of(&self.server_connection), *msg) {
+                Ok(effects) => return self.run_bridge(effects),
+                Err(e) => return cmd(future::err(e.into())),
             },
             Message::ReconnectFrontend => {
                 self.frontend_subscription_error = None;
+                self.additional_frontends.retain(|id, _| matches!(id, additional::FrontendId::Extra(_)));
                 self.frontend_connection_id = self.frontend_connection_id.wrapping_add(1);
             }
             Message::ReconnectToLobby => self.server_connection = SessionState::Init { maintenance: self.server_connection.maintenance() },
             Message::ReconnectToRoom(room_id, room_password) => self.server_connection = SessionState::InitAutoRejoin { room_id, room_password, maintenance: self.server_connection.maintenance() },
+            Message::RemoveServerProfile => if let Some(name) = self.server_profile.clone() {
+                self.server_profiles.remove(&name);
+                return cmd(async move {
+                    let mut config = Config::load().await?;
+                    config.server_profiles.remove(&name);
+                    config.save().await?;
+                    Ok(Message::SetServerProfile(ServerProfileOption(None)))
+                })
+            },
+            Message::ResetConfig => return cmd(async {
+                Config::reset().await?;
+                Ok(Message::RetryConfig)
+            }),
+            Message::RetroArchError(e) => {
+                self.frontend.retroarch_error = Some(e);
+                if let Frontend::RetroArch = self.frontend.kind {
+                    self.frontend_writer = None;
+                    self.bridge.frontend_disconnected();
+                }
+            }
+            Message::RetryConfig => return Task::future(Config::load().map(|res| match res {
+                Ok(config) => Message::ConfigLoaded(LoadedConfig(config)),
+                Err(e) => Message::ConfigError(Arc::new(e)),
+            })),
+            #[cfg(any(target_os = "linux", target_os = "windows"))] Message::Rollback => {
+                let frontend = self.frontend.clone();
+                return cmd(async move {
+                    Ok(if run_updater(frontend, true).await? { Message::Exit } else { Message::Nop })
+                })
+            }
             Message::SendAll => {
                 let server_writer = self.server_writer.clone().expect("SendAll button only appears when connected to server");
                 let source_world = self.send_all_world.parse().expect("SendAll button only appears when source world is valid");
@@ -1188,6 +1379,8 @@ impl State {
                     false
                 };
                 self.server_connection.apply(msg.clone());
+                let effects = self.bridge.server_message(&self.websocket_url, Room::of(&self.server_connection), msg.clone());
+                let bridge_task = self.run_bridge(effects);
                 match msg {
                     ServerMessage::StructuredError(ServerError::RoomExists) => if let SessionState::Lobby { .. } = self.server_connection {
                         return cmd(future::ok(Message::JoinRoom))
@@ -1275,13 +1468,10 @@ impl State {
                         }
                     }
                     ServerMessage::EnterLobby { .. } => {
-                        let login_token = self.login_tokens.iter()
-                            .next()
-                            .filter(|_| matches!(self.server_connection, SessionState::Lobby { login_state: None, .. }))
-                            .map(|(&provider, bearer_token)| (provider, bearer_token.clone()));
+                        let login_token = self.auto_login_token()
+                            .filter(|_| matches!(self.server_connection, SessionState::Lobby { login_state: None, .. }));
                         let server_writer = self.server_writer.clone();
-                        let frontend_writer = self.frontend_writer.clone();
-                        return cmd(async move {
+                        return Task::batch([bridge_task, cmd(async move {
                             if let Some(server_writer) = server_writer {
                                 if let Some((provider, bearer_token)) = login_token {
                                     server_writer.write(match provider {
@@ -1290,95 +1480,12 @@ impl State {
                                     }).await?;
                                 }
                             }
-                            if let Some(frontend_writer) = frontend_writer {
-                                frontend_writer.write(frontend::ServerMessage::ItemQueue(Vec::default())).await?;
-                            }
                             Ok(if room_still_exists { Message::JoinRoom } else { Message::Nop })
-                        })
+                        })])
                     }
-                    ServerMessage::EnterRoom { players, .. } => {
-                        let persistent_state = self.persistent_state.clone();
-                        let server_writer = self.server_writer.clone().expect("join room button only appears when connected to server");
-                        let frontend_writer = self.frontend_writer.clone().expect("join room button only appears when connected to frontend");
-                        let player_id = self.last_world;
-                        let player_name = self.last_name;
-                        let file_hash = self.last_hash;
-                        let save = self.last_save.clone();
-                        let dungeon_reward_locations = self.last_dungeon_reward_locations.clone();
-                        return cmd(async move {
-                            let (pending_items_before_save, pending_items_after_save) = persistent_state.edit(|state| (
-                                mem::take(&mut state.pending_items_before_save),
-                                mem::take(&mut state.pending_items_after_save),
-                            )).await?;
-                            if let Some(player_id) = player_id {
-                                server_writer.write(ClientMessage::PlayerId(player_id)).await?;
-                                if player_name != Filename::default() {
-                                    server_writer.write(ClientMessage::PlayerName(player_name)).await?;
-                                }
-                                if let Some(hash) = file_hash {
-                                    server_writer.write(ClientMessage::FileHash(hash)).await?;
-                                }
-                            }
-                            for persistent_state::PendingItem { hash, key, kind, target_world } in pending_items_before_save {
-                                if let Some(last_hash) = file_hash {
-                                    if hash != last_hash { continue }
-                                }
-                                server_writer.write(ClientMessage::SendItem { key, kind, target_world }).await?;
-                            }
-                            if let Some(save) = save {
-                                server_writer.write(ClientMessage::SaveData(save)).await?;
-                            }
-                            for (reward, (world, area)) in dungeon_reward_locations {
-                                server_writer.write(ClientMessage::DungeonRewardInfo { reward, world, area }).await?;
-                            }
-                            for persistent_state::PendingItem { hash, key, kind, target_world } in pending_items_after_save {
-                                if let Some(last_hash) = file_hash {
-                                    if hash != last_hash { continue }
-                                }
-                                server_writer.write(ClientMessage::SendItem { key, kind, target_world }).await?;
-                            }
-                            for player in players {
-                                frontend_writer.write(frontend::ServerMessage::PlayerName(player.world, if player.name == Filename::default() {
-                                    Filename::fallback(player.world)
-                                } else {
-                                    player.name
-                                })).await?;
-                            }
-                            Ok(Message::Nop)
-                        })
-                    }
-                    ServerMessage::PlayerName(world, name) => if let Some(writer) = self.frontend_writer.clone() {
-                        return cmd(async move {
-                            writer.write(frontend::ServerMessage::PlayerName(world, name)).await?;
-                            Ok(Message::Nop)
-                        })
-                    },
-                    ServerMessage::ItemQueue(queue) => if let SessionState::Room { wrong_file_hash: None, world_taken: None, .. } = self.server_connection {
-                        if let Some(writer) = self.frontend_writer.clone() {
-                            return cmd(async move {
-                                writer.write(frontend::ServerMessage::ItemQueue(queue)).await?;
-                                Ok(Message::Nop)
-                            })
-                        }
-                    },
-                    ServerMessage::GetItem(item) => if let SessionState::Room { wrong_file_hash: None, world_taken: None, .. } = self.server_connection {
-                        if let Some(writer) = self.frontend_writer.clone() {
-                            return cmd(async move {
-                                writer.write(frontend::ServerMessage::GetItem(item)).await?;
-                                Ok(Message::Nop)
-                            })
-                        }
-                    },
-                    ServerMessage::ProgressiveItems { world, state } => if let SessionState::Room { wrong_file_hash: None, world_taken: None, .. } = self.server_connection {
-                        if let Some(writer) = self.frontend_writer.clone() {
-                            return cmd(async move {
-                                writer.write(frontend::ServerMessage::ProgressiveItems(world, state)).await?;
-                                Ok(Message::Nop)
-                            })
-                        }
-                    },
                     _ => {}
                 }
+                return bridge_task
             }
             Message::ServerConnected(sink) => self.server_writer = Some(LoggingSink { log: self.log, context: "to server", inner: sink }),
             Message::ServerSubscriptionError(e) => if !matches!(self.server_connection, SessionState::Error { .. }) {
@@ -1410,7 +1517,7 @@ impl State {
                         e: SessionStateError::Connection(e.clone()),
                         auto_retry: false,
                     };
-                    if let Error::WebSocket(tungstenite::Error::Http(ref resp)) = *e {
+                    if let Error::ServerConnection(multiworld::client::Error::WebSocket(tungstenite::Error::Http(ref resp))) = *e {
                         if resp.status() == tungstenite::http::StatusCode::GONE {
                             return cmd(future::ok(Message::CheckForUpdates))
                         }
@@ -1426,6 +1533,20 @@ impl State {
                     Ok(Message::Nop)
                 })
             },
+            Message::SetCapture(capture) => {
+                if capture != self.capture {
+                    // the server subscription is keyed on this setting, so this reconnects
+                    self.capture = capture;
+                    self.server_writer = None;
+                    self.server_connection = SessionState::Init { maintenance: None };
+                }
+                return cmd(async move {
+                    let mut config = Config::load().await?;
+                    config.capture = capture;
+                    config.save().await?;
+                    Ok(Message::Nop)
+                })
+            }
             Message::SetCreateNewRoom(new_val) => if let SessionState::Lobby { ref mut create_new_room, .. } = self.server_connection { *create_new_room = new_val },
             Message::SetExistingRoomSelection(room) => {
                 if room.is_dummy {
@@ -1435,13 +1556,46 @@ impl State {
                 }
                 self.show_room_filter = false;
             },
-            Message::SetFrontend(new_frontend) => self.frontend.kind = new_frontend,
+            Message::SetFrontend(new_frontend) => {
+                self.frontend.kind = new_frontend;
+                self.additional_frontends.retain(|id, _| matches!(id, additional::FrontendId::Extra(_)));
+            }
+            Message::SetFrozen(frozen) => if let Some(writer) = self.server_writer.clone() {
+                return cmd(async move {
+                    writer.write(ClientMessage::SetFrozen(frozen)).await?;
+                    Ok(Message::Nop)
+                })
+            },
             Message::SetMaintenanceDontShowAgain(dont_show_again) => self.maintenance_dont_show_again = dont_show_again,
             Message::SetNewRoomName(name) => if let SessionState::Lobby { ref mut new_room_name, .. } = self.server_connection { *new_room_name = name },
+            Message::SetNewServerProfileName(name) => self.new_server_profile_name = name,
+            Message::SetNewServerProfileUrl(url) => self.new_server_profile_url = url,
             Message::SetPassword(new_password) => if let SessionState::Lobby { ref mut password, .. } = self.server_connection { *password = new_password },
             Message::SetRoomFilter(new_room_filter) => self.room_filter = new_room_filter,
             Message::SetSendAllPath(new_path) => self.send_all_path = new_path,
             Message::SetSendAllWorld(new_world) => self.send_all_world = new_world,
+            Message::SetServerProfile(ServerProfileOption(profile)) => {
+                let websocket_url = match profile.as_ref().and_then(|name| self.server_profiles.get(name)) {
+                    Some(server_profile) => match server_profile.websocket_url() {
+                        Ok(websocket_url) => websocket_url,
+                        Err(e) => return cmd(future::err(e.into())),
+                    },
+                    None => self.default_websocket_url.clone(),
+                };
+                self.server_profile = profile.clone();
+                if websocket_url != self.websocket_url {
+                    // the server subscription is keyed on the URL, so this reconnects
+                    self.websocket_url = websocket_url;
+                    self.server_writer = None;
+                    self.server_connection = SessionState::Init { maintenance: None };
+                }
+                return cmd(async move {
+                    let mut config = Config::load().await?;
+                    config.server_profile = profile;
+                    config.save().await?;
+                    Ok(Message::Nop)
+                })
+            }
             Message::ShowConflictingItemKindsIssue => if let Err(e) = open("https://github.com/midoshouse/ootr-multiworld/issues/43") {
                 return cmd(future::err(e.into()))
             },
@@ -1452,6 +1606,12 @@ impl State {
             }) {
                 return cmd(future::err(e.into()))
             },
+            Message::StartRoom => if let Some(writer) = self.server_writer.clone() {
+                return cmd(async move {
+                    writer.write(ClientMessage::StartRoom { countdown: Duration::from_secs(10) }).await?;
+                    Ok(Message::Nop)
+                })
+            },
             Message::LaunchProject64 => {
                 let emulator_path = self.pj64_script_path.as_ref().expect("emulator path must be set for Project64 version 3");
                 let Some(pj64_folder_path) = Path::new(emulator_path).ancestors().nth(2) else {
@@ -1464,8 +1624,22 @@ impl State {
             }
             Message::ToggleUpdateErrorDetails => if let UpdateState::Error { ref mut expanded, .. } = self.update_state { *expanded = !*expanded },
             Message::UpToDate => self.update_state = UpdateState::UpToDate,
-            #[cfg(target_os = "macos")] Message::UpdateAvailable(new_ver) => self.update_state = UpdateState::Available(new_ver),
+            #[cfg(any(target_os = "linux", target_os = "windows"))] Message::Update => {
+                // the updater unpauses automatic updates in the config once it installs the update
+                let frontend = self.frontend.clone();
+                return cmd(async move {
+                    Ok(if run_updater(frontend, false).await? { Message::Exit } else { Message::UpToDate })
+                })
+            }
+            Message::UpdateAvailable(new_ver, notes) => self.update_state = UpdateState::Available(new_ver, notes),
             Message::UpdateError(e) => self.update_state = UpdateState::Error { e, expanded: false },
+            #[cfg(any(target_os = "linux", target_os = "windows"))] Message::UpdatePaused(new_ver, notes) => self.update_state = UpdateState::Paused(new_ver, notes),
+            Message::WaitForStart => if let Some(writer) = self.server_writer.clone() {
+                return cmd(async move {
+                    writer.write(ClientMessage::WaitForStart).await?;
+                    Ok(Message::Nop)
+                })
+            },
         }
         Task::none()
     }
@@ -1475,7 +1649,23 @@ impl State {
         let main_view = if let Some(ref e) = self.icon_error {
             error_view("An error occurred:", e, false, self.debug_info_copied.contains(&false))
         } else if let Some(ref e) = self.config_error {
-            error_view("An error occurred:", e, false, self.debug_info_copied.contains(&false))
+            Column::new()
+                .push(Text::new("Broken Config").size(24))
+                .push(Text::new(format!("Your settings file could not be read: {e}")))
+                .push("You can open the file in an editor to fix it, then retry. Or you can reset all settings to their defaults. If you reset, a backup of the current file will be kept next to it.")
+                .push(Row::new()
+                    .push(Button::new("Open in editor").on_press(Message::EditConfig))
+                    .push(Button::new("Retry").on_press(Message::RetryConfig))
+                    .push(Button::new("Reset").on_press(Message::ResetConfig))
+                    .spacing(8)
+                )
+                .push(Row::new()
+                    .push(Button::new("Copy debug info").on_press(Message::CopyDebugInfo(false)))
+                    .push(if self.debug_info_copied.contains(&false) { "Copied!" } else { "for pasting into Discord" })
+                    .align_y(iced::Alignment::Center)
+                    .spacing(8)
+                )
+                .spacing(8)
         } else if let Some(ref e) = self.persistent_state_error {
             error_view("An error occurred:", e, false, self.debug_info_copied.contains(&false))
         } else if let Some(ref e) = self.command_error {
@@ -1514,7 +1704,7 @@ impl State {
         } else if let UpdateState::Pending = self.update_state {
             let mut col = Column::new();
             if let SessionState::Error { auto_retry: false, e: SessionStateError::Connection(ref e), maintenance } = self.server_connection {
-                if let Error::WebSocket(tungstenite::Error::Http(ref resp)) = **e {
+                if let Error::ServerConnection(multiworld::client::Error::WebSocket(tungstenite::Error::Http(ref resp))) = **e {
                     if resp.status() == tungstenite::http::StatusCode::GONE {
                         if let Some((start, duration)) = maintenance {
                             col = col.push(Text::new(format!(
@@ -1593,6 +1783,16 @@ impl State {
                         .push("Waiting for Project64…")
                         .push("This should take less than 5 seconds.");
                 }
+                Frontend::RetroArch => {
+                    col = col
+                        .push("Waiting for RetroArch…")
+                        .push("Make sure the game is running in the Mupen64Plus-Next core and Network Commands are enabled in RetroArch's Network settings.");
+                    if let Some(ref e) = self.frontend.retroarch_error {
+                        col = col
+                            .push(Text::new(e.to_string()))
+                            .push("Retrying in 5 seconds…");
+                    }
+                }
             }
             col.spacing(8)
         } else {
@@ -1607,7 +1807,7 @@ impl State {
                             DurationFormatter(duration),
                         )));
                     }
-                    col
+                    col = col
                         .push("A network error occurred:")
                         .push(Text::new(e.to_string()))
                         .push(Text::new(if let Ok(retry) = TimeDelta::from_std(self.retry.duration_since(Instant::now())) {
@@ -1615,7 +1815,11 @@ impl State {
                         } else {
                             format!("Reconnecting…")
                         })) //TODO live countdown
-                        .push("If this error persists, check your internet connection or contact @fenhl on Discord for support.")
+                        .push("If this error persists, check your internet connection or contact @fenhl on Discord for support.");
+                    if self.server_profile.is_some() && !self.server_locked {
+                        col = col.push(Button::new("Connect to the default server instead").on_press(Message::SetServerProfile(ServerProfileOption(None))));
+                    }
+                    col
                         .push(Row::new()
                             .push(Button::new("Copy debug info").on_press(Message::CopyDebugInfo(false)))
                             .push(if self.debug_info_copied.contains(&false) { "Copied!" } else { "for pasting into Discord" })
@@ -1688,6 +1892,41 @@ impl State {
                             })))
                             .align_y(iced::Alignment::Center)
                         );
+                    #[cfg(any(target_os = "linux", target_os = "windows"))] if let Some(ref previous_version) = self.previous_version {
+                        if !matches!(self.frontend.kind, Frontend::Dummy) {
+                            col = col
+                                .push(Text::new(format!("If this version isn't working for you, you can go back to the version you had installed before (version {previous_version}). Automatic updates will be paused until you choose to update again.")))
+                                .push(Button::new(Text::new(format!("Roll back to version {previous_version}"))).on_press(Message::Rollback));
+                        }
+                    }
+                    if self.server_locked {
+                        col = col.push(Text::new(format!("Server: {} (set on the command line)", self.websocket_url)));
+                    } else {
+                        let options = iter::once(ServerProfileOption(None)).chain(self.server_profiles.keys().map(|name| ServerProfileOption(Some(name.clone())))).collect_vec();
+                        let mut row = Row::new()
+                            .push("Server:")
+                            .push(PickList::new(options, Some(ServerProfileOption(self.server_profile.clone())), Message::SetServerProfile))
+                            .align_y(iced::Alignment::Center)
+                            .spacing(8);
+                        if self.server_profile.is_some() {
+                            row = row.push(Button::new("Remove").on_press(Message::RemoveServerProfile));
+                        }
+                        let new_profile_valid = !self.new_server_profile_name.is_empty()
+                            && !self.server_profiles.contains_key(&self.new_server_profile_name)
+                            && Url::parse(&self.new_server_profile_url).ok().as_ref().and_then(ServerProfile::from_websocket_url).is_some();
+                        col = col
+                            .push(row)
+                            .push(Row::new()
+                                .push(TextInput::new("Name", &self.new_server_profile_name).on_input(Message::SetNewServerProfileName).on_paste(Message::SetNewServerProfileName).width(Length::FillPortion(1)))
+                                .push(TextInput::new("wss://host:port/path", &self.new_server_profile_url).on_input(Message::SetNewServerProfileUrl).on_paste(Message::SetNewServerProfileUrl).width(Length::FillPortion(2)))
+                                .push(Button::new("Add").on_press_maybe(new_profile_valid.then_some(Message::AddServerProfile)))
+                                .align_y(iced::Alignment::Center)
+                                .spacing(8)
+                            );
+                    }
+                    col = col
+                        .push(Checkbox::new(self.capture).label("Record the connection to the server for replaying with multiworld-replay").on_toggle(Message::SetCapture))
+                        .push(Button::new("Pending items").on_press(Message::SetLobbyView(LobbyView::PendingItems)));
                     if login_state.is_some() {
                         col = col.push("You are signed in."); //TODO option to sign out
                     } else {
@@ -1698,6 +1937,45 @@ impl State {
                     }
                     col.spacing(8)
                 }
+                SessionState::Lobby { view: LobbyView::PendingItems, wrong_password: false, .. } => {
+                    let mut col = Column::new()
+                        .push(Row::new()
+                            .push(Button::new("Back").on_press(Message::SetLobbyView(LobbyView::Settings)))
+                            .push(Space::default().width(Length::Fill))
+                            .push(Button::new("Discard all").on_press_maybe((!self.pending_items.is_empty()).then(|| Message::DiscardPendingItems(self.pending_items.clone()))))
+                            .align_y(iced::Alignment::Center)
+                        )
+                        .push("Items sent while you're not connected to a room are saved and sent once you join the room they were sent from. Items from other rooms or seeds stay here until you discard them.");
+                    if self.pending_items.is_empty() {
+                        col = col.push("There are no pending items.");
+                    } else {
+                        let current_server = server_key(&self.websocket_url);
+                        for item in &self.pending_items {
+                            let mut description = format!("item 0x{:04x} for world {}", item.kind, item.target_world);
+                            if let Some(world) = item.world {
+                                description.push_str(&format!(" from world {world}"));
+                            }
+                            if let Some((_, ref room_name)) = item.room {
+                                description.push_str(&format!(" in room {room_name}"));
+                            }
+                            if let Some(ref server) = item.server {
+                                if *server != current_server {
+                                    description.push_str(&format!(" on {server}"));
+                                }
+                            }
+                            if self.bridge.hash().is_some_and(|last_hash| last_hash != item.hash) {
+                                description.push_str(" (different seed)");
+                            }
+                            col = col.push(Row::new()
+                                .push(Text::new(description).width(Length::Fill))
+                                .push(Button::new("Discard").on_press(Message::DiscardPendingItems(vec![item.clone()])))
+                                .align_y(iced::Alignment::Center)
+                                .spacing(8)
+                            );
+                        }
+                    }
+                    col.spacing(8)
+                }
                 SessionState::Lobby { view: LobbyView::Login { provider, no_midos_house_account: true }, wrong_password: false, .. } => Column::new()
                     .push(Text::new(format!("This {provider} account is not associated with a Mido's House account.")))
                     .push(Row::new()
@@ -1738,6 +2016,11 @@ impl State {
                             DurationFormatter(duration),
                         )));
                     }
+                    match self.additional_frontends.len() {
+                        0 => {}
+                        1 => col = col.push("There is 1 additional frontend. It will join the room you connect to."),
+                        n => col = col.push(Text::new(format!("There are {n} additional frontends. They will join the room you connect to."))),
+                    }
                     col = col
                         .push(Radio::new("Connect to existing room", false, Some(create_new_room), Message::SetCreateNewRoom))
                         .push(Radio::new("Create new room", true, Some(create_new_room), Message::SetCreateNewRoom))
@@ -1889,7 +2172,7 @@ impl State {
                         .spacing(8)
                     )
                     .spacing(8),
-                SessionState::Room { view: RoomView::Options, wrong_file_hash: None, autodelete_delta, allow_send_all, .. } => {
+                SessionState::Room { view: RoomView::Options, wrong_file_hash: None, autodelete_delta, allow_send_all, start, frozen, can_moderate, already_started, .. } => {
                     let mut col = Column::new()
                         .push(Button::new("Back").on_press(Message::SetRoomView(RoomView::Normal)))
                         .push(rule::horizontal(1))
@@ -1904,7 +2187,29 @@ impl State {
                                 values.insert(idx, DurationFormatter(autodelete_delta));
                             }
                             PickList::new(values, Some(DurationFormatter(autodelete_delta)), Message::SetAutoDeleteDelta)
-                        });
+                        })
+                        .push(rule::horizontal(1))
+                        .push("Share a link that takes players directly into this room:")
+                        .push(Row::new()
+                            .push(Button::new("Copy link").on_press(Message::CopyRoomLink(false)))
+                            .push(Button::new("Copy link with password").on_press(Message::CopyRoomLink(true)))
+                            .spacing(8)
+                        );
+                    if let (RoomStart::Started, true) = (start, can_moderate) {
+                        col = col
+                            .push(rule::horizontal(1))
+                            .push("Hold items sent from now on until the room is started, e.g. to wait for all players to be ready before a race:")
+                            .push(Button::new("Wait for Start").on_press(Message::WaitForStart));
+                        if already_started {
+                            col = col.push("This room has already started, so it can no longer wait for a start signal.");
+                        }
+                    }
+                    if !frozen && can_moderate {
+                        col = col
+                            .push(rule::horizontal(1))
+                            .push("Pause item delivery, e.g. while a player is recovering from a crash. Items sent in the meantime will be delivered once the room is unfrozen:")
+                            .push(Button::new("Freeze").on_press(Message::SetFrozen(true)));
+                    }
                     if allow_send_all {
                         col = col
                             .push(rule::horizontal(1))
@@ -1942,8 +2247,8 @@ impl State {
                     }
                     col.spacing(8)
                 }
-                SessionState::Room { view: RoomView::Normal, wrong_file_hash: None, ref players, num_unassigned_clients, maintenance, .. } => {
-                    let (players, other) = format_room_state(players, num_unassigned_clients, self.last_world);
+                SessionState::Room { view: RoomView::Normal, wrong_file_hash: None, ref players, num_unassigned_clients, maintenance, ref completions, triforce_goal, ref triforce_pieces, start, frozen, can_moderate, not_all_worlds_claimed, countdown_too_long, .. } => {
+                    let (players, other) = format_room_state(players, num_unassigned_clients, self.bridge.world());
                     let mut col = Column::new();
                     if let Some((start, duration)) = maintenance {
                         col = col.push(Text::new(format!(
@@ -1952,6 +2257,30 @@ impl State {
                             DurationFormatter(duration),
                         )));
                     }
+                    if frozen {
+                        col = col.push("Item delivery in this room is frozen. Items will be held until the room is unfrozen.");
+                        if can_moderate {
+                            col = col.push(Button::new("Unfreeze").on_press(Message::SetFrozen(false)));
+                        }
+                    }
+                    match start {
+                        RoomStart::Started => {}
+                        RoomStart::Waiting => {
+                            col = col.push("This room has not been started yet. Items will be held until it starts.");
+                            if can_moderate {
+                                col = col.push(Button::new("Start").on_press(Message::StartRoom));
+                            }
+                            if not_all_worlds_claimed {
+                                col = col.push("The room can't be started until all worlds have been claimed.");
+                            }
+                            if countdown_too_long {
+                                col = col.push("The start countdown was too long.");
+                            }
+                        }
+                        RoomStart::Countdown(start) => if start > Utc::now() {
+                            col = col.push(Text::new(format!("Items will start being delivered at {}.", start.with_timezone(&Local).format("%H:%M:%S"))));
+                        },
+                    }
                     col = col
                         .push(Row::new()
                             .push(Button::new("Delete Room").on_press(Message::SetRoomView(RoomView::ConfirmDeletion)))
@@ -1959,8 +2288,12 @@ impl State {
                             .spacing(8)
                         )
                         .push(Column::with_children(players.into_iter().map(|(player_id, player)| Row::new()
-                            .push(Text::new(player))
-                            .push(if self.last_world.map_or(false, |my_id| my_id == player_id) {
+                            .push(Text::new(if let Some(time) = completions.get(&player_id) {
+                                format!("{player} (finished at {})", time.with_timezone(&Local).format("%H:%M:%S"))
+                            } else {
+                                player
+                            }))
+                            .push(if self.bridge.world().map_or(false, |my_id| my_id == player_id) {
                                 Button::new("Leave").on_press(Message::Leave)
                             } else {
                                 Button::new("Kick").on_press(Message::Kick(player_id))
@@ -1971,7 +2304,22 @@ impl State {
                     if !other.is_empty() {
                         col = col.push(Text::new(other));
                     }
-                    if self.last_world.is_none() {
+                    if !self.additional_frontends.is_empty() {
+                        col = col.push(Column::with_children(self.additional_frontends.values().map(|frontend| Text::new(frontend.status()).into()).collect_vec()));
+                    }
+                    if triforce_goal.is_some() || !triforce_pieces.is_empty() {
+                        let total = triforce_pieces.values().sum::<u16>();
+                        let mut text = if let Some(triforce_goal) = triforce_goal {
+                            format!("Triforce pieces: {total}/{triforce_goal}")
+                        } else {
+                            format!("Triforce pieces: {total}")
+                        };
+                        if !triforce_pieces.is_empty() {
+                            text.push_str(&format!(" ({})", triforce_pieces.iter().map(|(world, count)| format!("{count} from world {world}")).join(", ")));
+                        }
+                        col = col.push(Text::new(text));
+                    }
+                    if self.bridge.world().is_none() {
                         col = col.push(Button::new("Leave").on_press(Message::Leave));
                     }
                     col.spacing(8)
@@ -1999,10 +2347,22 @@ impl State {
                 col = col.push(rule::horizontal(1)); //TODO hide if main_view is empty
             }
             UpdateState::UpToDate => {}
-            #[cfg(target_os = "macos")] UpdateState::Available(ref new_ver) => {
+            #[cfg(any(target_os = "linux", target_os = "windows"))] UpdateState::Paused(ref new_ver, ref notes) => {
+                col = col.push(Text::new(format!("An update is available ({} → {new_ver}). Automatic updates are paused because you rolled back to a previous version.", env!("CARGO_PKG_VERSION"))));
+                col = col.push(markdown::view(notes, &wheel::gui::theme().unwrap_or_default()).map(|url| Message::OpenReleaseNotesLink(url.to_string())));
+                col = col.push(Button::new("Update").on_press(Message::Update));
+                col = col.push(rule::horizontal(1));
+            }
+            UpdateState::Available(ref new_ver, ref notes) => {
                 col = col.push(Text::new(format!("An update is available ({} → {new_ver})", env!("CARGO_PKG_VERSION"))));
-                col = col.push("Please quit this app and run the following command in the Terminal app:");
-                col = col.push("brew update && brew upgrade"); //TODO automate
+                col = col.push(markdown::view(notes, &wheel::gui::theme().unwrap_or_default()).map(|url| Message::OpenReleaseNotesLink(url.to_string())));
+                #[cfg(any(target_os = "linux", target_os = "windows"))] {
+                    col = col.push(Button::new("Update").on_press(Message::Update));
+                }
+                #[cfg(target_os = "macos")] {
+                    col = col.push("Please quit this app and run the following command in the Terminal app:");
+                    col = col.push("brew update && brew upgrade"); //TODO automate
+                }
                 col = col.push(rule::horizontal(1));
             }
             UpdateState::Error { ref e, expanded } => {
@@ -2044,17 +2404,30 @@ impl State {
         }));
         if !matches!(self.update_state, UpdateState::Pending) {
             match self.frontend.kind {
-                Frontend::Dummy => {}
-                Frontend::EverDrive => subscriptions.push(subscription::from_recipe(LoggingSubscription { log: self.log, context: "from EverDrive", inner: everdrive::Subscription { log: self.log } })),
+                Frontend::Dummy => if self.frontend.dummy.script.is_some() || self.frontend.dummy.record.is_some() {
+                    subscriptions.push(subscription::from_recipe(LoggingSubscription { log: self.log, context: "from dummy frontend", inner: dummy::Subscription { script: self.frontend.dummy.script.clone(), record: self.frontend.dummy.record.clone(), connection_id: self.frontend_connection_id } }));
+                },
+                Frontend::EverDrive => subscriptions.push(subscription::from_recipe(LoggingSubscription { log: self.log, context: "from EverDrive", inner: subscriptions::EverDrive { log: self.log, port: self.frontend.everdrive_port.clone() } })),
                 #[cfg(any(target_os = "linux", target_os = "windows"))] Frontend::BizHawk => if let Some(BizHawkState { port, .. }) = self.frontend.bizhawk {
                     subscriptions.push(subscription::from_recipe(LoggingSubscription { log: self.log, context: "from BizHawk", inner: subscriptions::Connection { port, frontend: self.frontend.kind, log: self.log, connection_id: self.frontend_connection_id } }));
                 },
                 #[cfg(not(any(target_os = "linux", target_os = "windows")))] Frontend::BizHawk => unreachable!("no BizHawk support on this platform"),
                 Frontend::Pj64V3 => subscriptions.push(subscription::from_recipe(LoggingSubscription { log: self.log, context: "from Project64", inner: subscriptions::Listener { frontend: self.frontend.kind, log: self.log, connection_id: self.frontend_connection_id } })),
-                Frontend::Pj64V4 => subscriptions.push(subscription::from_recipe(LoggingSubscription { log: self.log, context: "from Project64", inner: subscriptions::Connection { port: frontend::PORT, frontend: self.frontend.kind, log: self.log, connection_id: self.frontend_connection_id } })), //TODO allow Project64 to specify port via command-line arg
+                Frontend::Pj64V4 => subscriptions.push(subscription::from_recipe(LoggingSubscription { log: self.log, context: "from Project64", inner: subscriptions::Connection { port: self.frontend.pj64v4_port.unwrap_or(frontend::PORT), frontend: self.frontend.kind, log: self.log, connection_id: self.frontend_connection_id } })),
+                Frontend::RetroArch => subscriptions.push(subscription::from_recipe(LoggingSubscription { log: self.log, context: "from RetroArch", inner: subscriptions::RetroArch { log: self.log, port: self.frontend.retroarch_port, connection_id: self.frontend_connection_id } })),
+            }
+            for (id, frontend) in self.extra_frontends.iter().enumerate() {
+                subscriptions.push(subscription::from_recipe(LoggingSubscription { log: self.log, context: "from extra frontend", inner: subscriptions::Extra { frontend: frontend.clone(), log: self.log, id } }));
             }
             if !matches!(self.server_connection, SessionState::Error { .. } | SessionState::Closed { .. }) {
-                subscriptions.push(subscription::from_recipe(LoggingSubscription { log: self.log, context: "from server", inner: subscriptions::Client { log: self.log, websocket_url: self.websocket_url.clone() } }));
+                subscriptions.push(subscription::from_recipe(LoggingSubscription { log: self.log, context: "from server", inner: subscriptions::Client { log: self.log, capture: self.capture, websocket_url: self.websocket_url.clone() } }));
+            }
+            if let SessionState::Room { room_id, .. } = self.server_connection {
+                for (&id, frontend) in &self.additional_frontends {
+                    if frontend.wants_server_connection() {
+                        subscriptions.push(subscription::from_recipe(LoggingSubscription { log: self.log, context: "from server (additional frontend)", inner: frontend.server_subscription(id, self.log, room_id, self.websocket_url.clone()) }));
+                    }
+                }
             }
             if let SessionState::Lobby { view: LobbyView::Login { provider, no_midos_house_account: false }, .. } = self.server_connection {
                 subscriptions.push(subscription::from_recipe(LoggingSubscription { log: self.log, context: "from login handler", inner: login::Subscription(provider) }));
@@ -2098,8 +2471,19 @@ fn error_view<'a>(context: impl Into<Cow<'a, str>>, e: &impl ToString, update: b
 #[clap(rename_all = "lower")]
 enum FrontendArgs {
     #[clap(name = "dummy-frontend")]
-    Dummy,
-    EverDrive,
+    Dummy {
+        /// A JSON file of frontend messages to send, in the format described in `multiworld::script`
+        #[clap(long)]
+        script: Option<PathBuf>,
+        /// A file to record the messages sent to the frontend to, in the JSON Lines format described in `multiworld::script`
+        #[clap(long)]
+        record: Option<PathBuf>,
+    },
+    EverDrive {
+        /// A serial port to connect to instead of scanning for EverDrives, e.g. the pseudo-terminal created by `multiworld-everdrive-sim`
+        #[clap(long)]
+        port: Option<String>,
+    },
     BizHawk {
         path: PathBuf,
         pid: Pid,
@@ -2107,18 +2491,36 @@ enum FrontendArgs {
         port: u16,
     },
     Pj64V3,
-    Pj64V4,
+    Pj64V4 {
+        /// The port the Project64 script listens on
+        #[clap(long)]
+        port: Option<u16>,
+    },
+    RetroArch {
+        /// The port of RetroArch's network command interface (`network_cmd_port` in retroarch.cfg)
+        #[clap(long, default_value_t = retroarch::DEFAULT_PORT)]
+        port: u16,
+    },
 }
 
 #[derive(clap::Parser)]
 #[clap(version)]
 struct CliArgs {
+    /// The name of a server profile from the config, or the ws:// or wss:// URL of a server, to connect to instead of the configured one
+    #[clap(long)]
+    server: Option<String>,
+    /// A room link (mhmw://join/… or https://midos.house/mw/join/…) to join on startup
+    #[clap(long, global = true)]
+    join: Option<DeepLink>,
+    /// Another frontend to connect to in addition to the selected one, in one of the formats `bizhawk:<port>`, `everdrive[:<serial port>]`, `pj64v4:<port>`, or `retroarch[:<port>]`. Can be given multiple times.
+    #[clap(long, global = true)]
+    extra_frontend: Vec<additional::ExtraFrontend>,
     #[clap(subcommand)]
     frontend: Option<FrontendArgs>,
 }
 
 #[wheel::main]
-fn main(CliArgs { frontend }: CliArgs) -> iced::Result {
+fn main(CliArgs { server, join, extra_frontend, frontend }: CliArgs) -> iced::Result {
     fn theme(_: &State) -> Option<Theme> { wheel::gui::theme() }
 
     let _ = rustls::crypto::ring::default_provider().install_default();
@@ -2128,7 +2530,7 @@ fn main(CliArgs { frontend }: CliArgs) -> iced::Result {
         Err(e) => (None, RefCell::new(Some(e))),
     };
     iced::application(move || (
-        State::new(icon_error.borrow_mut().take(), Config::blocking_load(), PersistentState::blocking_load(), frontend.clone()),
+        State::new(icon_error.borrow_mut().take(), Config::blocking_load(), PersistentState::blocking_load(), frontend.clone(), server.clone(), join.clone(), extra_frontend.clone()),
         cmd(future::ok(Message::CheckForUpdates)),
     ), State::update, State::view)
         .title(State::title)
@@ -3,15 +3,12 @@ use {
         any::TypeId,
         hash::Hash as _,
         io::prelude::*,
+        iter,
         net::Ipv4Addr,
-        pin::{
-            Pin,
-            pin,
-        },
+        pin::Pin,
         sync::Arc,
         time::Duration,
     },
-    async_proto::Protocol,
     chrono::prelude::*,
     futures::{
         future::{
@@ -20,7 +17,8 @@ use {
         },
         stream::{
             self,
-            SplitSink,
+            BoxStream,
+            SelectAll,
             Stream,
             StreamExt as _,
             TryStreamExt as _,
@@ -39,30 +37,28 @@ use {
         net::{
             TcpListener,
             TcpStream,
+            tcp::OwnedWriteHalf,
         },
         select,
-        time::{
-            Instant,
-            interval_at,
-            timeout,
-        },
+        time::sleep,
     },
-    tokio_tungstenite::tungstenite,
     url::Url,
     multiworld::{
+        capture::Capture,
+        everdrive,
         frontend::{
             self,
             Kind as Frontend,
         },
-        user_agent,
-        ws::latest as ws,
+        retroarch,
     },
     crate::{
         Error,
         FrontendWriter,
         LoggingReader,
-        LoggingStream,
         Message,
+        additional,
+        log_line,
     },
 };
 
@@ -91,8 +87,6 @@ impl<T: Recipe<Output = Message> + 'static> Recipe for LoggingSubscription<T> {
     }
 }
 
-pub(crate) type WsSink = SplitSink<tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<TcpStream>>, tungstenite::Message>;
-
 pub(crate) struct Connection {
     pub(crate) port: u16,
     pub(crate) frontend: Frontend,
@@ -113,15 +107,11 @@ impl Recipe for Connection {
         let log = self.log;
         stream::once(TcpStream::connect((Ipv4Addr::LOCALHOST, self.port)).err_into())
             .and_then(move |mut tcp_stream| async move {
-                frontend::PROTOCOL_VERSION.write(&mut tcp_stream).await?;
-                let client_version = u8::read(&mut tcp_stream).await?;
-                if client_version != frontend::PROTOCOL_VERSION {
-                    return Err(Error::VersionMismatch { version: client_version, frontend })
-                }
+                let negotiated = frontend::handshake_app(&mut tcp_stream, frontend::Capabilities::all()).await.map_err(|source| Error::FrontendHandshake { frontend, source })?;
                 let (reader, writer) = tcp_stream.into_split();
                 let reader = LoggingReader { context: "from frontend", inner: reader, log };
                 Ok(
-                    stream::once(future::ok(Message::FrontendConnected(FrontendWriter::Tcp(Arc::new(Mutex::new(writer))))))
+                    stream::once(future::ok(Message::FrontendConnected(FrontendWriter::Tcp(Arc::new(Mutex::new(writer)), negotiated.capabilities))))
                         .chain(stream::try_unfold(reader, |mut reader| async move {
                             Ok(Some((Message::Plugin(Box::new(reader.read::<frontend::ClientMessage>().await?)), reader)))
                         }))
@@ -134,12 +124,36 @@ impl Recipe for Connection {
     }
 }
 
+/// Accepts any number of frontend connections on [`frontend::PORT`].
+///
+/// The first frontend to connect while no other frontend is connected is handled by the main session, all others are reported as [`Message::AdditionalFrontend`].
 pub(crate) struct Listener {
     pub(crate) frontend: Frontend,
     pub(crate) log: bool,
     pub(crate) connection_id: u8,
 }
 
+enum ListenerEvent {
+    Connected(OwnedWriteHalf, frontend::Capabilities),
+    Plugin(frontend::ClientMessage),
+}
+
+/// Performs the handshake with a single frontend connected to a [`Listener`] and reads its messages.
+fn listener_connection(frontend: Frontend, log: bool, mut tcp_stream: TcpStream) -> impl Stream<Item = Result<ListenerEvent, Error>> + Send {
+    stream::once(async move {
+        let negotiated = frontend::handshake_app(&mut tcp_stream, frontend::Capabilities::all()).await.map_err(|source| Error::FrontendHandshake { frontend, source })?;
+        let (reader, writer) = tcp_stream.into_split();
+        let reader = LoggingReader { context: "from frontend", inner: reader, log };
+        Ok(
+            stream::once(future::ok(ListenerEvent::Connected(writer, negotiated.capabilities)))
+                .chain(stream::try_unfold(reader, |mut reader| async move {
+                    Ok(Some((ListenerEvent::Plugin(reader.read::<frontend::ClientMessage>().await?), reader)))
+                }))
+        )
+    })
+        .try_flatten()
+}
+
 impl Recipe for Listener {
     type Output = Message;
 
@@ -152,33 +166,175 @@ impl Recipe for Listener {
         let frontend = self.frontend;
         let log = self.log;
         stream::once(TcpListener::bind((Ipv4Addr::LOCALHOST, frontend::PORT)))
-            .and_then(move |listener| future::ok(stream::try_unfold(listener, move |listener| async move {
-                let (mut tcp_stream, _) = listener.accept().await?;
-                frontend::PROTOCOL_VERSION.write(&mut tcp_stream).await?;
-                let client_version = u8::read(&mut tcp_stream).await?;
-                if client_version != frontend::PROTOCOL_VERSION {
-                    return Err(Error::VersionMismatch { version: client_version, frontend })
-                }
-                let (reader, writer) = tcp_stream.into_split();
-                let reader = LoggingReader { context: "from frontend", inner: reader, log };
-                Ok(Some((
-                    stream::once(future::ok(Message::FrontendConnected(FrontendWriter::Tcp(Arc::new(Mutex::new(writer))))))
-                    .chain(stream::try_unfold(reader, |mut reader| async move {
-                        Ok(Some((Message::Plugin(Box::new(reader.read::<frontend::ClientMessage>().await?)), reader)))
-                    })),
-                    listener,
-                )))
-            })))
-            .try_flatten()
-            .try_flatten()
-            .map(|res| res.unwrap_or_else(|e| Message::FrontendSubscriptionError(Arc::new(e))))
+            .flat_map(move |res| match res {
+                Ok(listener) => stream::unfold(Some((listener, SelectAll::<BoxStream<'static, (u8, Result<ListenerEvent, Error>)>>::new(), None::<u8>, 0u8)), move |state| async move {
+                    let (listener, mut connections, mut main_id, mut next_id) = state?;
+                    let msg = loop {
+                        select! {
+                            res = listener.accept() => match res {
+                                Ok((tcp_stream, _)) => {
+                                    let id = next_id;
+                                    next_id = next_id.wrapping_add(1);
+                                    main_id.get_or_insert(id);
+                                    connections.push(listener_connection(frontend, log, tcp_stream).map(move |res| (id, res)).boxed());
+                                }
+                                Err(e) => return Some((Message::FrontendSubscriptionError(Arc::new(e.into())), None)),
+                            },
+                            Some((id, res)) = connections.next() => {
+                                let is_main = main_id == Some(id);
+                                break match res {
+                                    Ok(ListenerEvent::Connected(writer, capabilities)) => {
+                                        let writer = FrontendWriter::Tcp(Arc::new(Mutex::new(writer)), capabilities);
+                                        if is_main {
+                                            Message::FrontendConnected(writer)
+                                        } else {
+                                            Message::AdditionalFrontend(additional::FrontendId::Listener(id), additional::Event::FrontendConnected(writer))
+                                        }
+                                    }
+                                    Ok(ListenerEvent::Plugin(msg)) => if is_main {
+                                        Message::Plugin(Box::new(msg))
+                                    } else {
+                                        Message::AdditionalFrontend(additional::FrontendId::Listener(id), additional::Event::Plugin(Box::new(msg)))
+                                    },
+                                    Err(e) => if is_main {
+                                        main_id = None;
+                                        Message::FrontendSubscriptionError(Arc::new(e))
+                                    } else {
+                                        Message::AdditionalFrontend(additional::FrontendId::Listener(id), additional::Event::FrontendDisconnected(Arc::new(e)))
+                                    },
+                                }
+                            }
+                        }
+                    };
+                    Some((msg, Some((listener, connections, main_id, next_id))))
+                }).boxed(),
+                Err(e) => stream::once(future::ready(Message::FrontendSubscriptionError(Arc::new(e.into())))).boxed(),
+            })
+            .chain(stream::pending())
+            .boxed()
+    }
+}
+
+/// Connects to a frontend given using `--extra-frontend`, reporting everything as [`Message::AdditionalFrontend`].
+///
+/// Unlike the subscriptions for the main session's frontend, this retries connecting to TCP frontends, since their connection errors aren't shown to the user directly.
+pub(crate) struct Extra {
+    pub(crate) id: usize,
+    pub(crate) frontend: additional::ExtraFrontend,
+    pub(crate) log: bool,
+}
+
+impl Recipe for Extra {
+    type Output = Message;
+
+    fn hash(&self, state: &mut subscription::Hasher) {
+        TypeId::of::<Self>().hash(state);
+        self.id.hash(state);
+        self.frontend.hash(state);
+    }
+
+    fn stream(self: Box<Self>, _: EventStream) -> Pin<Box<dyn Stream<Item = Message> + Send>> {
+        let Self { id, frontend, log } = *self;
+        let id = additional::FrontendId::Extra(id);
+        match frontend {
+            additional::ExtraFrontend::BizHawk(port) | additional::ExtraFrontend::Pj64V4(port) => {
+                let kind = frontend.kind();
+                stream::iter(iter::once(false).chain(iter::repeat(true)))
+                    .flat_map(move |is_retry| stream::once(async move {
+                        if is_retry {
+                            sleep(Duration::from_secs(5)).await;
+                        }
+                        TcpStream::connect((Ipv4Addr::LOCALHOST, port)).await.map_err(Error::from)
+                    })
+                        .map_ok(move |tcp_stream| listener_connection(kind, log, tcp_stream))
+                        .try_flatten()
+                    )
+                    .map(move |res| Message::AdditionalFrontend(id, match res {
+                        Ok(ListenerEvent::Connected(writer, capabilities)) => additional::Event::FrontendConnected(FrontendWriter::Tcp(Arc::new(Mutex::new(writer)), capabilities)),
+                        Ok(ListenerEvent::Plugin(msg)) => additional::Event::Plugin(Box::new(msg)),
+                        Err(e) => additional::Event::FrontendDisconnected(Arc::new(e)),
+                    }))
+                    .boxed()
+            }
+            additional::ExtraFrontend::EverDrive(port) => everdrive::connect(port, log.then_some(log_line as frontend::Logger))
+                .filter_map(move |res| future::ready(match res {
+                    Ok(everdrive::Event::Connected(tx)) => Some(additional::Event::FrontendConnected(FrontendWriter::Mpsc(tx))),
+                    Ok(everdrive::Event::Message(msg)) => Some(additional::Event::Plugin(Box::new(msg))),
+                    Ok(everdrive::Event::ScanFailed(_)) => None, // keep waiting, the scan is retried
+                    Ok(everdrive::Event::Timeout) => Some(additional::Event::FrontendDisconnected(Arc::new(Error::EverDriveTimeout))),
+                    Err(e) => Some(additional::Event::FrontendDisconnected(Arc::new(e.into()))),
+                }.map(|event| Message::AdditionalFrontend(id, event))))
+                .chain(stream::pending())
+                .boxed(),
+            additional::ExtraFrontend::RetroArch(port) => retroarch::connect(port, log.then_some(log_line as frontend::Logger))
+                .map(move |event| Message::AdditionalFrontend(id, match event {
+                    retroarch::Event::Connected(tx) => additional::Event::FrontendConnected(FrontendWriter::Mpsc(tx)),
+                    retroarch::Event::Message(msg) => additional::Event::Plugin(Box::new(msg)),
+                    retroarch::Event::Error(e) => additional::Event::FrontendDisconnected(Arc::new(Error::RetroArch(e))),
+                }))
+                .boxed(),
+        }
+    }
+}
+
+/// Scans for an EverDrive, or connects to the given serial port, using [`multiworld::everdrive`].
+pub(crate) struct EverDrive {
+    pub(crate) log: bool,
+    pub(crate) port: Option<String>,
+}
+
+impl Recipe for EverDrive {
+    type Output = Message;
+
+    fn hash(&self, state: &mut subscription::Hasher) {
+        TypeId::of::<Self>().hash(state);
+        self.port.hash(state);
+    }
+
+    fn stream(self: Box<Self>, _: EventStream) -> Pin<Box<dyn Stream<Item = Message> + Send>> {
+        everdrive::connect(self.port, self.log.then_some(log_line as frontend::Logger))
+            .map(|res| match res {
+                Ok(everdrive::Event::Connected(tx)) => Message::FrontendConnected(FrontendWriter::Mpsc(tx)),
+                Ok(everdrive::Event::Message(msg)) => Message::Plugin(Box::new(msg)),
+                Ok(everdrive::Event::ScanFailed(errors)) => Message::EverDriveScanFailed(errors),
+                Ok(everdrive::Event::Timeout) => Message::EverDriveTimeout,
+                Err(e) => Message::FrontendSubscriptionError(Arc::new(e.into())),
+            })
             .chain(stream::pending())
             .boxed()
     }
 }
 
+/// Connects to RetroArch's network command interface using [`multiworld::retroarch`].
+pub(crate) struct RetroArch {
+    pub(crate) log: bool,
+    pub(crate) port: u16,
+    pub(crate) connection_id: u8,
+}
+
+impl Recipe for RetroArch {
+    type Output = Message;
+
+    fn hash(&self, state: &mut subscription::Hasher) {
+        TypeId::of::<Self>().hash(state);
+        self.port.hash(state);
+        self.connection_id.hash(state);
+    }
+
+    fn stream(self: Box<Self>, _: EventStream) -> Pin<Box<dyn Stream<Item = Message> + Send>> {
+        retroarch::connect(self.port, self.log.then_some(log_line as frontend::Logger))
+            .map(|event| match event {
+                retroarch::Event::Connected(tx) => Message::FrontendConnected(FrontendWriter::Mpsc(tx)),
+                retroarch::Event::Message(msg) => Message::Plugin(Box::new(msg)),
+                retroarch::Event::Error(e) => Message::RetroArchError(e),
+            })
+            .boxed()
+    }
+}
+
 pub(crate) struct Client {
     pub(crate) log: bool,
+    pub(crate) capture: bool,
     pub(crate) websocket_url: Url,
 }
 
@@ -187,31 +343,26 @@ impl Recipe for Client {
 
     fn hash(&self, state: &mut subscription::Hasher) {
         TypeId::of::<Self>().hash(state);
+        self.websocket_url.hash(state);
+        self.capture.hash(state);
     }
 
     fn stream(self: Box<Self>, _: EventStream) -> Pin<Box<dyn Stream<Item = Message> + Send>> {
         let log = self.log;
         stream::once(async move {
-            let request = tungstenite::ClientRequestBuilder::new(self.websocket_url.to_string().try_into()?)
-                .with_header(tungstenite::http::header::USER_AGENT.to_string(), user_agent());
-            let (websocket, _) = tokio_tungstenite::connect_async(request).await?;
-            let (sink, stream) = websocket.split();
-            let stream = LoggingStream { context: "from server", inner: stream, log };
-            let sink = Arc::new(Mutex::new(sink));
-            let interval = interval_at(Instant::now() + Duration::from_secs(30), Duration::from_secs(30));
+            let (client, receiver) = if self.capture {
+                multiworld::client::connect_capturing(&self.websocket_url, Capture::open_rotating(crate::capture_path()?, crate::MAX_CAPTURE_LEN)?).await?
+            } else {
+                multiworld::client::connect(&self.websocket_url).await?
+            };
             Ok::<_, Error>(
-                stream::once(future::ok(Message::ServerConnected(sink.clone())))
-                .chain(stream::try_unfold((stream, sink, interval), |(stream, sink, mut interval)| async move {
-                    let mut read = pin!(timeout(Duration::from_secs(60), stream.read_owned()));
-                    Ok(loop {
-                        select! {
-                            res = &mut read => {
-                                let (stream, msg) = res??;
-                                break Some((Message::Server(msg), (stream, sink, interval)))
-                            },
-                            _ = interval.tick() => lock!(sink = sink; ws::ClientMessage::Ping.write_ws024(&mut *sink).await)?,
-                        }
-                    })
+                stream::once(future::ok(Message::ServerConnected(client)))
+                .chain(receiver.into_stream().err_into().and_then(move |change| async move {
+                    if log {
+                        lock!(log = crate::LOG; writeln!(&*log, "{} from server: {:?}", Utc::now().format("%Y-%m-%d %H:%M:%S"), change.msg))?;
+                    }
+                    // the main window's session state also tracks UI state like form inputs, so it applies the message itself rather than adopting change.state
+                    Ok(Message::Server(change.msg))
                 }))
             )
         })
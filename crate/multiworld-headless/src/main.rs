@@ -0,0 +1,427 @@
+//! Connects a frontend to a multiworld room without a GUI, e.g. on a Steam Deck in game mode, on a Raspberry Pi, or in automated tests.
+//!
+//! Everything is logged to stdout. Connection problems with the server are retried with exponential backoff, other errors end the process.
+
+use {
+    std::{
+        convert::Infallible as Never,
+        mem,
+        net::Ipv4Addr,
+        path::PathBuf,
+        pin::pin,
+        sync::Arc,
+        time::Duration,
+    },
+    async_proto::Protocol as _,
+    chrono::prelude::*,
+    futures::stream::StreamExt as _,
+    tokio::{
+        net::{
+            TcpListener,
+            TcpStream,
+        },
+        select,
+        sync::mpsc,
+        time::{
+            Instant,
+            sleep,
+        },
+    },
+    url::Url,
+    wheel::traits::IsNetworkError,
+    multiworld::{
+        IdentityProvider,
+        SessionState,
+        bridge::{
+            self,
+            Bridge,
+            Effect,
+            Room,
+            RunError,
+        },
+        capture::Capture,
+        client::Client,
+        config::Config,
+        everdrive,
+        frontend,
+        persistent_state::{
+            self,
+            PersistentState,
+        },
+        retroarch,
+        ws::{
+            ServerError,
+            latest::{
+                ClientMessage,
+                ServerMessage,
+            },
+        },
+    },
+};
+
+macro_rules! log {
+    ($($arg:tt)*) => {
+        println!("{} {}", Local::now().format("%Y-%m-%d %H:%M:%S"), format_args!($($arg)*))
+    };
+}
+
+#[derive(clap::Parser)]
+#[clap(version)]
+struct Args {
+    /// The emulator or console to connect to. Use `dummy` to join the room without a frontend.
+    #[clap(value_enum)]
+    frontend: frontend::Kind,
+    /// The name of the room to join.
+    room: String,
+    /// The room password.
+    #[clap(short, long)]
+    password: Option<String>,
+    /// Create the room if it doesn't exist yet.
+    #[clap(long)]
+    create: bool,
+    /// The name of a server profile from the config, or a ws:// or wss:// URL. Defaults to the server selected in the app's settings.
+    #[clap(long)]
+    server: Option<String>,
+    /// The local TCP port of the frontend connection.
+    ///
+    /// For Project64 3, this is the port to listen on and defaults to 24818, the only port the Project64 3 script connects to.
+    /// For BizHawk and Project64 4, this is the port the script listens on and is required. These scripts listen on a port assigned by the operating system and normally pass it to the GUI when they start it, so it has to be passed here. Headless mode does not discover it automatically.
+    /// For RetroArch, this is the port of RetroArch's network commands and defaults to 55355.
+    #[clap(long)]
+    port: Option<u16>,
+    /// The serial port the EverDrive is connected to. If omitted, all serial ports are scanned.
+    #[clap(long)]
+    serial_port: Option<String>,
+    /// Don't sign in, even if the config contains a login token for the server.
+    #[clap(long)]
+    anonymous: bool,
+    /// Record all messages exchanged with the server in this file, for replaying with `multiworld-replay`. Appends to the file if it exists.
+    #[clap(long)]
+    capture: Option<PathBuf>,
+}
+
+#[derive(Debug, thiserror::Error)]
+enum Error {
+    #[error(transparent)] Client(#[from] multiworld::client::Error),
+    #[error(transparent)] Config(#[from] multiworld::config::Error),
+    #[error(transparent)] EverDrive(#[from] everdrive::Error),
+    #[error(transparent)] Io(#[from] tokio::io::Error),
+    #[error(transparent)] PersistentState(#[from] persistent_state::Error),
+    #[error(transparent)] Read(#[from] async_proto::ReadError),
+    #[error(transparent)] RetroArch(Arc<retroarch::Error>),
+    #[error(transparent)] Write(#[from] async_proto::WriteError),
+    #[error("the server is going down")]
+    Goodbye,
+    #[error("there is no room named {0:?} on this server, pass --create to create it")]
+    NoSuchRoom(String),
+    #[error("the room {0:?} has a password, pass it using --password")]
+    PasswordRequired(String),
+    #[error("the {0} script listens on a port chosen by the operating system, pass it using --port")]
+    PortRequired(frontend::Kind),
+    #[error("{0}")]
+    Session(String),
+    #[error("the EverDrive stopped responding")]
+    EverDriveTimeout,
+    #[error("handshake with {frontend} plugin failed: {source}")]
+    FrontendHandshake {
+        frontend: frontend::Kind,
+        source: frontend::HandshakeError,
+    },
+    #[error("wrong room password")]
+    WrongPassword,
+}
+
+impl IsNetworkError for Error {
+    fn is_network_error(&self) -> bool {
+        match self {
+            Self::EverDriveTimeout | Self::Goodbye => true,
+            Self::Config(_) | Self::EverDrive(_) | Self::PersistentState(_) | Self::NoSuchRoom(_) | Self::PasswordRequired(_) | Self::PortRequired(_) | Self::Session(_) | Self::WrongPassword => false,
+            Self::Client(e) => e.is_network_error(),
+            Self::FrontendHandshake { source, .. } => source.is_network_error(),
+            Self::Io(e) => e.is_network_error(),
+            Self::Read(e) => e.is_network_error(),
+            Self::RetroArch(e) => matches!(**e, retroarch::Error::Io(_) | retroarch::Error::Timeout),
+            Self::Write(e) => e.is_network_error(),
+        }
+    }
+}
+
+enum FrontendEvent {
+    Connected(mpsc::Sender<frontend::ServerMessage>, frontend::Capabilities),
+    Message(frontend::ClientMessage),
+    Disconnected(Error),
+}
+
+async fn connect_frontend(kind: frontend::Kind, listener: Option<&TcpListener>, port: u16, tx: &mpsc::Sender<FrontendEvent>) -> Result<(), Error> {
+    let mut tcp_stream = if let Some(listener) = listener {
+        listener.accept().await?.0
+    } else {
+        TcpStream::connect((Ipv4Addr::LOCALHOST, port)).await?
+    };
+    let negotiated = frontend::handshake_app(&mut tcp_stream, frontend::Capabilities::all()).await.map_err(|source| Error::FrontendHandshake { frontend: kind, source })?;
+    let (mut reader, mut writer) = tcp_stream.into_split();
+    let (writer_tx, mut writer_rx) = mpsc::channel::<frontend::ServerMessage>(256);
+    tokio::spawn(async move {
+        while let Some(msg) = writer_rx.recv().await {
+            if let Err(e) = msg.write(&mut writer).await {
+                log!("error writing to frontend: {e}");
+                break
+            }
+        }
+    });
+    if tx.send(FrontendEvent::Connected(writer_tx, negotiated.capabilities)).await.is_err() { return Ok(()) }
+    loop {
+        let msg = frontend::ClientMessage::read(&mut reader).await?;
+        if tx.send(FrontendEvent::Message(msg)).await.is_err() { return Ok(()) }
+    }
+}
+
+/// Keeps a frontend connected for as long as the receiving end of `tx` is alive, reconnecting whenever the connection is lost.
+async fn frontend_connections(kind: frontend::Kind, port: u16, tx: mpsc::Sender<FrontendEvent>) -> Result<(), Error> {
+    let listener = if let frontend::Kind::Pj64V3 = kind {
+        Some(TcpListener::bind((Ipv4Addr::LOCALHOST, port)).await?)
+    } else {
+        None
+    };
+    while !tx.is_closed() {
+        if let Err(e) = connect_frontend(kind, listener.as_ref(), port, &tx).await {
+            if tx.send(FrontendEvent::Disconnected(e)).await.is_err() { break }
+            if listener.is_none() {
+                sleep(Duration::from_secs(5)).await;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Keeps looking for an EverDrive for as long as the receiving end of `tx` is alive.
+async fn everdrive_connections(port: Option<String>, tx: mpsc::Sender<FrontendEvent>) {
+    while !tx.is_closed() {
+        let mut events = pin!(everdrive::connect(port.clone(), None));
+        let mut scan_failure_logged = false;
+        while let Some(res) = events.next().await {
+            let event = match res {
+                Ok(everdrive::Event::Connected(writer)) => {
+                    scan_failure_logged = false;
+                    FrontendEvent::Connected(writer, frontend::Capabilities::all())
+                }
+                Ok(everdrive::Event::Message(msg)) => FrontendEvent::Message(msg),
+                Ok(everdrive::Event::ScanFailed(errors)) => {
+                    if !mem::replace(&mut scan_failure_logged, true) {
+                        if errors.is_empty() {
+                            log!("no serial ports found, make sure the EverDrive is connected, retrying every 5 seconds");
+                        } else {
+                            log!("no EverDrive found, make sure the console is in game, retrying every 5 seconds");
+                            for (port, e) in &*errors {
+                                log!("{}: {e}", port.port_name);
+                            }
+                        }
+                    }
+                    continue
+                }
+                Ok(everdrive::Event::Timeout) => FrontendEvent::Disconnected(Error::EverDriveTimeout),
+                Err(e) => FrontendEvent::Disconnected(e.into()),
+            };
+            if tx.send(event).await.is_err() { return }
+        }
+        sleep(Duration::from_secs(5)).await;
+    }
+}
+
+/// Keeps RetroArch connected for as long as the receiving end of `tx` is alive.
+async fn retroarch_connections(port: u16, tx: mpsc::Sender<FrontendEvent>) {
+    let mut events = pin!(retroarch::connect(port, None));
+    while let Some(event) = events.next().await {
+        let event = match event {
+            retroarch::Event::Connected(writer) => FrontendEvent::Connected(writer, frontend::Capabilities::all()),
+            retroarch::Event::Message(msg) => FrontendEvent::Message(msg),
+            retroarch::Event::Error(e) => FrontendEvent::Disconnected(Error::RetroArch(e)),
+        };
+        if tx.send(event).await.is_err() { break }
+    }
+}
+
+/// The state which has to survive reconnecting to the server.
+struct State {
+    bridge: Bridge,
+    frontend_writer: Option<mpsc::Sender<frontend::ServerMessage>>,
+    persistent_state: PersistentState,
+}
+
+impl State {
+    async fn run(&mut self, server: &Client, effects: Vec<Effect>) -> Result<(), Error> {
+        for effect in &effects {
+            match effect {
+                Effect::HoldItem(item) => log!("not in a room, holding item {} for world {} until the room is rejoined", item.kind, item.target_world),
+                Effect::Server(ClientMessage::SaveDataError { debug, .. }) => log!("failed to read save data: {debug}"),
+                _ => {}
+            }
+        }
+        match bridge::run(effects, Some(server), self.frontend_writer.as_ref(), &self.persistent_state).await {
+            Ok(()) => Ok(()),
+            Err(RunError::PersistentState(e)) => Err(e.into()),
+            Err(RunError::Server(e)) => Err(e.into()),
+            Err(RunError::Frontend(e)) => {
+                log!("frontend disconnected: {e}");
+                self.frontend_writer = None;
+                self.bridge.frontend_disconnected();
+                Ok(())
+            }
+        }
+    }
+
+    async fn handle_frontend_event(&mut self, websocket_url: &Url, session_state: &SessionState<Never>, server: &Client, event: FrontendEvent) -> Result<(), Error> {
+        let room = Room::of(session_state);
+        let effects = match event {
+            FrontendEvent::Connected(writer, capabilities) => {
+                log!("frontend connected");
+                self.frontend_writer = Some(writer);
+                self.bridge.frontend_connected(capabilities, room)
+            }
+            FrontendEvent::Disconnected(e) => {
+                if self.frontend_writer.take().is_some() {
+                    log!("frontend disconnected: {e}");
+                    self.bridge.frontend_disconnected();
+                } else if !e.is_network_error() {
+                    log!("error connecting to frontend: {e}");
+                }
+                return Ok(())
+            }
+            FrontendEvent::Message(msg) => {
+                match msg {
+                    frontend::ClientMessage::PlayerId(world) => log!("frontend: world {world}"),
+                    frontend::ClientMessage::PlayerName(name) => log!("frontend: player name {name}"),
+                    frontend::ClientMessage::ResetPlayerId => log!("frontend: no world"),
+                    _ => {}
+                }
+                match self.bridge.frontend_message(websocket_url, room, msg) {
+                    Ok(effects) => effects,
+                    Err(e) => {
+                        log!("error handling message from frontend: {e}");
+                        return Ok(())
+                    }
+                }
+            }
+        };
+        self.run(server, effects).await
+    }
+
+    async fn handle_server_message(&mut self, args: &Args, websocket_url: &Url, login_token: Option<&(IdentityProvider, String)>, session_state: &SessionState<Never>, server: &Client, msg: ServerMessage) -> Result<(), Error> {
+        match session_state {
+            SessionState::Error { e, .. } => return Err(Error::Session(e.to_string())),
+            SessionState::Lobby { wrong_password: true, .. } => return Err(Error::WrongPassword),
+            SessionState::Closed { .. } => return Err(Error::Goodbye),
+            _ => {}
+        }
+        match msg {
+            ServerMessage::StructuredError(ServerError::SessionExpiredDiscord | ServerError::SessionExpiredRaceTime) => log!("login token expired, sign in again in the app to refresh it"),
+            ServerMessage::StructuredError(ServerError::NoMidosHouseAccountDiscord | ServerError::NoMidosHouseAccountRaceTime) => log!("the login token in the config isn't associated with a Mido's House account"),
+            ServerMessage::StructuredError(ServerError::NotAllWorldsClaimed) => log!("not all worlds in the room have been claimed yet"),
+            ServerMessage::StructuredError(ServerError::AlreadyStarted) => log!("the room has already started"),
+            ServerMessage::StructuredError(ServerError::CountdownTooLong) => log!("the start countdown is too long"),
+            ServerMessage::StructuredError(ServerError::ConflictingItemKinds) => log!("the server received conflicting item kinds, check that everyone in the room is playing the same seed"),
+            ServerMessage::EnterLobby { ref rooms } => {
+                if let (Some((provider, bearer_token)), SessionState::Lobby { login_state: None, .. }) = (login_token, session_state) {
+                    server.login(*provider, bearer_token.clone()).await?;
+                }
+                if let Some((&id, (_, password_required))) = rooms.iter().find(|(_, (name, _))| *name == args.room) {
+                    if *password_required && args.password.is_none() {
+                        return Err(Error::PasswordRequired(args.room.clone()))
+                    }
+                    log!("joining room {:?}", args.room);
+                    server.send(ClientMessage::JoinRoom { id, password: args.password.clone() }).await?;
+                } else if args.create {
+                    log!("creating room {:?}", args.room);
+                    server.send(ClientMessage::CreateRoom { name: args.room.clone(), password: args.password.clone().unwrap_or_default() }).await?;
+                } else {
+                    return Err(Error::NoSuchRoom(args.room.clone()))
+                }
+            }
+            ServerMessage::LoginSuccess => log!("signed in"),
+            ServerMessage::EnterRoom { .. } => log!("entered room {:?}", args.room),
+            ServerMessage::PlayerId(world) => log!("world {world} claimed"),
+            ServerMessage::ResetPlayerId(world) => log!("world {world} unclaimed"),
+            ServerMessage::PlayerDisconnected(world) => log!("world {world} disconnected"),
+            ServerMessage::WrongFileHash { .. } => log!("this file doesn't match the seed of the room, items won't be sent or received"),
+            ServerMessage::WorldTaken(world) => log!("world {world} is already taken by another player, items won't be sent or received"),
+            ServerMessage::WorldFreed => log!("world is no longer taken"),
+            ServerMessage::MaintenanceNotice { start, duration } => log!("the server will be down for maintenance starting {start} for {}", multiworld::DurationFormatter(duration)),
+            ServerMessage::GetItem(item) => if Room::of(session_state).is_some_and(|room| room.active) {
+                log!("received item {item}");
+            },
+            _ => {}
+        }
+        let effects = self.bridge.server_message(websocket_url, Room::of(session_state), msg);
+        self.run(server, effects).await
+    }
+}
+
+/// Connects to the server and bridges messages until the connection fails.
+async fn session(args: &Args, websocket_url: &Url, login_token: Option<&(IdentityProvider, String)>, state: &mut State, frontend_rx: &mut mpsc::Receiver<FrontendEvent>) -> Result<Never, Error> {
+    let (server, mut receiver) = if let Some(ref capture) = args.capture {
+        multiworld::client::connect_capturing(websocket_url, Capture::open(capture)?).await?
+    } else {
+        multiworld::client::connect(websocket_url).await?
+    };
+    log!("connected to {websocket_url}");
+    loop {
+        select! {
+            res = receiver.recv() => state.handle_server_message(args, websocket_url, login_token, receiver.state(), &server, res?).await?,
+            Some(event) = frontend_rx.recv() => state.handle_frontend_event(websocket_url, receiver.state(), &server, event).await?,
+        }
+    }
+}
+
+#[wheel::main]
+async fn main(args: Args) -> Result<(), Error> {
+    let config = Config::load().await?;
+    let persistent_state = PersistentState::blocking_load()?;
+    let websocket_url = if let Some(ref server) = args.server {
+        config.server_arg_url(server)?
+    } else {
+        config.websocket_url()?
+    };
+    // only send login tokens to the server they were obtained for
+    let login_token = if args.anonymous { None } else { config.login_token_for(&websocket_url) };
+    let (frontend_tx, mut frontend_rx) = mpsc::channel(256);
+    match args.frontend {
+        frontend::Kind::Dummy => {}
+        frontend::Kind::BizHawk | frontend::Kind::Pj64V3 | frontend::Kind::Pj64V4 => {
+            let kind = args.frontend;
+            let port = match (kind, args.port) {
+                (_, Some(port)) => port,
+                (frontend::Kind::Pj64V3, None) => frontend::PORT,
+                (_, None) => return Err(Error::PortRequired(kind)),
+            };
+            tokio::spawn(async move {
+                if let Err(e) = frontend_connections(kind, port, frontend_tx).await {
+                    log!("error listening for frontend connections: {e}");
+                }
+            });
+        }
+        frontend::Kind::EverDrive => {
+            tokio::spawn(everdrive_connections(args.serial_port.clone(), frontend_tx));
+        }
+        frontend::Kind::RetroArch => {
+            tokio::spawn(retroarch_connections(args.port.unwrap_or(retroarch::DEFAULT_PORT), frontend_tx));
+        }
+    }
+    let mut state = State {
+        bridge: Bridge::default(),
+        frontend_writer: None,
+        persistent_state,
+    };
+    let mut wait_time = Duration::from_secs(1);
+    loop {
+        let start = Instant::now();
+        let Err(e) = session(&args, &websocket_url, login_token.as_ref(), &mut state, &mut frontend_rx).await;
+        if !e.is_network_error() { return Err(e) }
+        if start.elapsed() >= Duration::from_secs(60 * 60 * 24) {
+            wait_time = Duration::from_secs(1); // reset wait time after no error for a day
+        } else {
+            wait_time *= 2; // exponential backoff
+        }
+        log!("lost connection to the server ({e}), reconnecting in {}", multiworld::DurationFormatter(wait_time));
+        sleep(wait_time).await;
+    }
+}
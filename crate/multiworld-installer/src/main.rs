@@ -87,6 +87,52 @@ use {
 #[cfg(all(target_os = "linux", target_arch = "x86_64"))] const BIZHAWK_PLATFORM_SUFFIX: &str = "-linux-x64.tar.gz";
 #[cfg(all(target_os = "windows", target_arch = "x86_64"))] const BIZHAWK_PLATFORM_SUFFIX: &str = "-win-x64.zip";
 
+/// Escapes a command-line argument for use inside double quotes in the `Exec` key of a desktop entry.
+///
+/// The desktop entry spec applies the escape rules for string values before the quoting rules, so characters which need a backslash inside quotes end up with two, and a literal backslash needs four. `%` starts a field code and is escaped as `%%`.
+#[cfg(target_os = "linux")]
+fn desktop_entry_quoted_arg(arg: &str) -> String {
+    let mut escaped = String::with_capacity(arg.len());
+    for c in arg.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\\\\\"),
+            '"' | '`' | '$' => {
+                escaped.push_str("\\\\");
+                escaped.push(c);
+            }
+            '%' => escaped.push_str("%%"),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Registers the multiworld app as the handler for `mhmw://` room links.
+#[cfg(target_os = "linux")]
+async fn register_deep_link_handler(multiworld_path: &Path) -> Result<(), Error> {
+    let desktop_file_path = BaseDirectories::new().place_data_file("applications/midos-house-multiworld.desktop")?;
+    let exec = desktop_entry_quoted_arg(multiworld_path.to_str().ok_or(Error::NonUtf8Path)?);
+    fs::write(&desktop_file_path, format!("[Desktop Entry]
+Type=Application
+Name=Mido's House Multiworld
+Exec=\"{exec}\" --join %u
+Terminal=false
+NoDisplay=true
+MimeType=x-scheme-handler/mhmw;
+")).await?;
+    if which("xdg-mime").is_ok() {
+        tokio::process::Command::new("xdg-mime")
+            .arg("default")
+            .arg("midos-house-multiworld.desktop")
+            .arg("x-scheme-handler/mhmw")
+            .check("xdg-mime").await?;
+    }
+    Ok(())
+}
+
 #[derive(Debug, thiserror::Error)]
 enum Error {
     #[error(transparent)] Config(#[from] multiworld::config::Error),
@@ -781,6 +827,7 @@ impl State {
                             #[cfg(all(target_os = "linux", not(debug_assertions)))] fs::write(&multiworld_path, include_bytes!("../../../target/release/multiworld-gui")).await?;
                             #[cfg(all(target_os = "windows", debug_assertions))] fs::write(&multiworld_path, include_bytes!("../../../target/debug/multiworld-gui.exe")).await?;
                             #[cfg(all(target_os = "windows", not(debug_assertions)))] fs::write(&multiworld_path, include_bytes!("../../../target/release/multiworld-gui.exe")).await?;
+                            #[cfg(target_os = "linux")] register_deep_link_handler(&multiworld_path).await?;
                             #[cfg(target_os = "windows")] {
                                 let base_dirs = BaseDirs::new().ok_or(Error::MissingHomeDir)?;
                                 ShellLink::new(&multiworld_path)?
@@ -1218,3 +1265,16 @@ fn main(Args { mut emulator }: Args) -> iced::Result {
         .theme(theme)
         .run()
 }
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn desktop_entry_quoting() {
+        assert_eq!(desktop_entry_quoted_arg("/home/user/bin/multiworld"), "/home/user/bin/multiworld");
+        assert_eq!(desktop_entry_quoted_arg("/home/user/100% multiworld"), "/home/user/100%% multiworld");
+        assert_eq!(desktop_entry_quoted_arg(r#"/home/"$user"/`x`"#), r#"/home/\\"\\$user\\"/\\`x\\`"#);
+        assert_eq!(desktop_entry_quoted_arg(r"/home/user/a\b"), r"/home/user/a\\\\b");
+    }
+}
@@ -5,12 +5,16 @@ use {
         env,
         fmt,
         io::prelude::*,
-        path::Path,
+        path::{
+            Path,
+            PathBuf,
+        },
         pin::pin,
         process::{
             self,
             Stdio,
         },
+        sync::Arc,
         time::Duration,
     },
     async_proto::{
@@ -37,6 +41,7 @@ use {
     gres::Task,
     itertools::Itertools as _,
     lazy_regex::regex_captures,
+    log_lock::*,
     semver::Version,
     tempfile::NamedTempFile,
     tokio::{
@@ -65,7 +70,10 @@ use {
         WaitUntilInactiveMessage,
         frontend,
         github::{
+            MANIFEST_NAME,
+            MANIFEST_SIGNATURE_NAME,
             Release,
+            ReleaseManifest,
             Repo,
         },
     },
@@ -81,6 +89,58 @@ mod version;
 
 const MACOS_ADDR: &str = "192.168.178.115";
 
+/// Where release assets are uploaded to.
+#[derive(Clone)]
+enum ReleaseTarget {
+    /// A GitHub release which has been created as a draft.
+    GitHub(Release),
+    /// A local output directory, used by the `local` subcommand.
+    Local(PathBuf),
+}
+
+/// The target for the release assets, along with the manifest of the assets attached to it so far.
+#[derive(Clone)]
+struct DraftRelease {
+    target: ReleaseTarget,
+    manifest: Arc<Mutex<ReleaseManifest>>,
+}
+
+impl DraftRelease {
+    async fn attach(&self, client: &reqwest::Client, repo: &Repo, name: &str, content_type: &'static str, data: Vec<u8>) -> wheel::Result {
+        lock!(manifest = self.manifest; manifest.add_asset(name, &data));
+        match self.target {
+            ReleaseTarget::GitHub(ref release) => repo.release_attach(client, release, name, content_type, data).await,
+            ReleaseTarget::Local(ref dir) => fs::write(dir.join(name), data).await,
+        }
+    }
+
+    /// Signs the manifest of all assets attached so far and attaches it to the release.
+    async fn attach_manifest(&self, client: &reqwest::Client, repo: &Repo, private_key: &[u8]) -> Result<(), Error> {
+        let (manifest, signature) = lock!(manifest = self.manifest; manifest.sign(private_key))?;
+        match self.target {
+            ReleaseTarget::GitHub(ref release) => {
+                repo.release_attach(client, release, MANIFEST_NAME, "application/json", manifest).await?;
+                repo.release_attach(client, release, MANIFEST_SIGNATURE_NAME, "application/octet-stream", signature).await?;
+            }
+            ReleaseTarget::Local(ref dir) => {
+                fs::write(dir.join(MANIFEST_NAME), manifest).await?;
+                fs::write(dir.join(MANIFEST_SIGNATURE_NAME), signature).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the manifest of all assets attached so far without signing it.
+    ///
+    /// Used for dry runs, which must never use the release signing key since their assets aren't published.
+    async fn write_unsigned_manifest(&self) -> Result<(), Error> {
+        let ReleaseTarget::Local(ref dir) = self.target else { unreachable!("unsigned manifests are only written for local builds") };
+        let manifest = lock!(manifest = self.manifest; manifest.to_json())?;
+        fs::write(dir.join(MANIFEST_NAME), manifest).await?;
+        Ok(())
+    }
+}
+
 #[derive(Clone)] struct WindowsUpdaterNotification;
 #[derive(Clone)] struct LinuxGuiNotification;
 #[derive(Clone)] struct WindowsGuiNotification;
@@ -198,14 +258,14 @@ impl Task<Result<(reqwest::Client, Repo, Version), Error>> for Setup {
 }
 
 enum CreateRelease {
-    CreateNotesFile(Repo, reqwest::Client, broadcast::Sender<Release>, Args),
-    EditNotes(Repo, reqwest::Client, broadcast::Sender<Release>, Args, NamedTempFile),
-    ReadNotes(Repo, reqwest::Client, broadcast::Sender<Release>, NamedTempFile),
-    Create(Repo, reqwest::Client, broadcast::Sender<Release>, String),
+    CreateNotesFile(Repo, reqwest::Client, broadcast::Sender<DraftRelease>, Args),
+    EditNotes(Repo, reqwest::Client, broadcast::Sender<DraftRelease>, Args, NamedTempFile),
+    ReadNotes(Repo, reqwest::Client, broadcast::Sender<DraftRelease>, NamedTempFile),
+    Create(Repo, reqwest::Client, broadcast::Sender<DraftRelease>, String),
 }
 
 impl CreateRelease {
-    fn new(repo: Repo, client: reqwest::Client, tx: broadcast::Sender<Release>, args: Args) -> Self {
+    fn new(repo: Repo, client: reqwest::Client, tx: broadcast::Sender<DraftRelease>, args: Args) -> Self {
         Self::CreateNotesFile(repo, client, tx, args)
     }
 }
@@ -233,8 +293,8 @@ impl GetPriority for CreateRelease {
 }
 
 #[async_trait]
-impl Task<Result<Release, Error>> for CreateRelease {
-    async fn run(self) -> Result<Result<Release, Error>, Self> {
+impl Task<Result<DraftRelease, Error>> for CreateRelease {
+    async fn run(self) -> Result<Result<DraftRelease, Error>, Self> {
         match self {
             Self::CreateNotesFile(repo, client, tx, args) => gres::transpose(async move {
                 let notes_file = tokio::task::spawn_blocking(|| {
@@ -273,6 +333,10 @@ impl Task<Result<Release, Error>> for CreateRelease {
             }).await,
             Self::Create(repo, client, tx, notes) => gres::transpose(async move {
                 let release = repo.create_release(&client, format!("Mido's House Multiworld {}", version::version().await), format!("v{}", version::version().await), notes).await?;
+                let release = DraftRelease {
+                    target: ReleaseTarget::GitHub(release),
+                    manifest: Arc::new(Mutex::new(ReleaseManifest::new(version::version().await))),
+                };
                 let _ = tx.send(release.clone());
                 Ok(Ok(release))
             }).await,
@@ -328,15 +392,15 @@ impl Task<Result<(), Error>> for BuildUpdater {
 }
 
 enum BuildGui {
-    Updater(bool, reqwest::Client, Repo, broadcast::Receiver<WindowsUpdaterNotification>, broadcast::Receiver<Release>, broadcast::Sender<WindowsGuiNotification>),
-    Glow(bool, reqwest::Client, Repo, broadcast::Receiver<Release>, broadcast::Sender<WindowsGuiNotification>),
-    Read(bool, reqwest::Client, Repo, broadcast::Receiver<Release>),
-    WaitRelease(bool, reqwest::Client, Repo, broadcast::Receiver<Release>, Vec<u8>),
-    Upload(bool, reqwest::Client, Repo, Release, Vec<u8>),
+    Updater(bool, reqwest::Client, Repo, broadcast::Receiver<WindowsUpdaterNotification>, broadcast::Receiver<DraftRelease>, broadcast::Sender<WindowsGuiNotification>),
+    Glow(bool, reqwest::Client, Repo, broadcast::Receiver<DraftRelease>, broadcast::Sender<WindowsGuiNotification>),
+    Read(bool, reqwest::Client, Repo, broadcast::Receiver<DraftRelease>),
+    WaitRelease(bool, reqwest::Client, Repo, broadcast::Receiver<DraftRelease>, Vec<u8>),
+    Upload(bool, reqwest::Client, Repo, DraftRelease, Vec<u8>),
 }
 
 impl BuildGui {
-    fn new(debug: bool, client: reqwest::Client, repo: Repo, updater_rx: broadcast::Receiver<WindowsUpdaterNotification>, release_rx: broadcast::Receiver<Release>, gui_tx: broadcast::Sender<WindowsGuiNotification>) -> Self {
+    fn new(debug: bool, client: reqwest::Client, repo: Repo, updater_rx: broadcast::Receiver<WindowsUpdaterNotification>, release_rx: broadcast::Receiver<DraftRelease>, gui_tx: broadcast::Sender<WindowsGuiNotification>) -> Self {
         Self::Updater(debug, client, repo, updater_rx, release_rx, gui_tx)
     }
 }
@@ -401,7 +465,7 @@ impl Task<Result<(), Error>> for BuildGui {
                 Ok(Err(Self::Upload(debug, client, repo, release, data)))
             }).await,
             Self::Upload(debug, client, repo, release, data) => gres::transpose(async move {
-                repo.release_attach(&client, &release, if debug { "multiworld-gui-debug.exe" } else { "multiworld-pj64.exe" }, "application/vnd.microsoft.portable-executable", data).await?;
+                release.attach(&client, &repo, if debug { "multiworld-gui-debug.exe" } else { "multiworld-pj64.exe" }, "application/vnd.microsoft.portable-executable", data).await?;
                 Ok(Ok(()))
             }).await,
         }
@@ -409,18 +473,23 @@ impl Task<Result<(), Error>> for BuildGui {
 }
 
 enum BuildGuiLinux {
-    Sync(reqwest::Client, Repo, broadcast::Receiver<Release>, broadcast::Sender<LinuxGuiNotification>),
-    Updater(reqwest::Client, Repo, broadcast::Receiver<Release>, broadcast::Sender<LinuxGuiNotification>),
-    Gui(reqwest::Client, Repo, broadcast::Receiver<Release>, broadcast::Sender<LinuxGuiNotification>),
-    Copy(reqwest::Client, Repo, broadcast::Receiver<Release>),
-    Read(reqwest::Client, Repo, broadcast::Receiver<Release>),
-    WaitRelease(reqwest::Client, Repo, broadcast::Receiver<Release>, Vec<u8>),
-    Upload(reqwest::Client, Repo, Release, Vec<u8>),
+    Sync(reqwest::Client, Repo, broadcast::Receiver<DraftRelease>, broadcast::Sender<LinuxGuiNotification>),
+    Updater(bool, reqwest::Client, Repo, broadcast::Receiver<DraftRelease>, broadcast::Sender<LinuxGuiNotification>),
+    Gui(bool, reqwest::Client, Repo, broadcast::Receiver<DraftRelease>, broadcast::Sender<LinuxGuiNotification>),
+    Copy(reqwest::Client, Repo, broadcast::Receiver<DraftRelease>),
+    Read(bool, reqwest::Client, Repo, broadcast::Receiver<DraftRelease>),
+    WaitRelease(reqwest::Client, Repo, broadcast::Receiver<DraftRelease>, Vec<u8>),
+    Upload(reqwest::Client, Repo, DraftRelease, Vec<u8>),
 }
 
 impl BuildGuiLinux {
-    fn new(client: reqwest::Client, repo: Repo, release_rx: broadcast::Receiver<Release>, gui_tx: broadcast::Sender<LinuxGuiNotification>) -> Self {
-        Self::Sync(client, repo, release_rx, gui_tx)
+    /// If `local` is true, builds on this machine instead of in WSL.
+    fn new(local: bool, client: reqwest::Client, repo: Repo, release_rx: broadcast::Receiver<DraftRelease>, gui_tx: broadcast::Sender<LinuxGuiNotification>) -> Self {
+        if local {
+            Self::Updater(true, client, repo, release_rx, gui_tx)
+        } else {
+            Self::Sync(client, repo, release_rx, gui_tx)
+        }
     }
 }
 
@@ -458,24 +527,24 @@ impl Task<Result<(), Error>> for BuildGuiLinux {
         match self {
             Self::Sync(client, repo, release_rx, gui_tx) => gres::transpose(async move {
                 Command::new("wsl").arg("-d").arg("ubuntu-m2").arg("rsync").arg("--mkpath").arg("--delete").arg("-av").arg("/mnt/c/Users/fenhl/git/github.com/midoshouse/ootr-multiworld/stage/").arg("/home/fenhl/wslgit/github.com/midoshouse/ootr-multiworld/").arg("--exclude").arg(".cargo/config.toml").arg("--exclude").arg("target").arg("--exclude").arg("crate/multiworld-bizhawk/OotrMultiworld/BizHawk").arg("--exclude").arg("crate/multiworld-bizhawk/OotrMultiworld/src/bin").arg("--exclude").arg("crate/multiworld-bizhawk/OotrMultiworld/src/obj").arg("--exclude").arg("crate/multiworld-bizhawk/OotrMultiworld/src/multiworld.dll").check("wsl rsync").await?;
-                Ok(Err(Self::Updater(client, repo, release_rx, gui_tx)))
+                Ok(Err(Self::Updater(false, client, repo, release_rx, gui_tx)))
             }).await,
-            Self::Updater(client, repo, release_rx, gui_tx) => gres::transpose(async move {
-                Command::new("wsl").arg("-d").arg("ubuntu-m2").arg("env").arg("-C").arg("/home/fenhl/wslgit/github.com/midoshouse/ootr-multiworld").arg("/home/fenhl/.cargo/bin/cargo").arg("build").arg("--release").arg("--package=multiworld-updater").arg("--features=require-user-agent-salt").check("wsl cargo build --package=multiworld-updater").await?;
-                Ok(Err(Self::Gui(client, repo, release_rx, gui_tx)))
+            Self::Updater(local, client, repo, release_rx, gui_tx) => gres::transpose(async move {
+                linux_cargo_build(local, "multiworld-updater").await?;
+                Ok(Err(Self::Gui(local, client, repo, release_rx, gui_tx)))
             }).await,
-            Self::Gui(client, repo, release_rx, gui_tx) => gres::transpose(async move {
-                Command::new("wsl").arg("-d").arg("ubuntu-m2").arg("env").arg("-C").arg("/home/fenhl/wslgit/github.com/midoshouse/ootr-multiworld").arg("/home/fenhl/.cargo/bin/cargo").arg("build").arg("--release").arg("--package=multiworld-gui").arg("--features=require-user-agent-salt").check("wsl cargo build --package=multiworld-gui").await?;
+            Self::Gui(local, client, repo, release_rx, gui_tx) => gres::transpose(async move {
+                linux_cargo_build(local, "multiworld-gui").await?;
                 let _ = gui_tx.send(LinuxGuiNotification);
-                Ok(Err(Self::Copy(client, repo, release_rx)))
+                Ok(Err(if local { Self::Read(true, client, repo, release_rx) } else { Self::Copy(client, repo, release_rx) }))
             }).await,
             Self::Copy(client, repo, release_rx) => gres::transpose(async move {
                 fs::create_dir_all("target/wsl/release").await?;
                 Command::new("wsl").arg("-d").arg("ubuntu-m2").arg("cp").arg("/home/fenhl/wslgit/github.com/midoshouse/ootr-multiworld/target/release/multiworld-gui").arg("/mnt/c/Users/fenhl/git/github.com/midoshouse/ootr-multiworld/stage/target/wsl/release/multiworld-gui").check("wsl cp").await?;
-                Ok(Err(Self::Read(client, repo, release_rx)))
+                Ok(Err(Self::Read(false, client, repo, release_rx)))
             }).await,
-            Self::Read(client, repo, release_rx) => gres::transpose(async move {
-                let data = fs::read("target/wsl/release/multiworld-gui").await?;
+            Self::Read(local, client, repo, release_rx) => gres::transpose(async move {
+                let data = fs::read(if local { "target/release/multiworld-gui" } else { "target/wsl/release/multiworld-gui" }).await?;
                 Ok(Err(Self::WaitRelease(client, repo, release_rx, data)))
             }).await,
             Self::WaitRelease(client, repo, mut release_rx, data) => gres::transpose(async move {
@@ -483,24 +552,36 @@ impl Task<Result<(), Error>> for BuildGuiLinux {
                 Ok(Err(Self::Upload(client, repo, release, data)))
             }).await,
             Self::Upload(client, repo, release, data) => gres::transpose(async move {
-                repo.release_attach(&client, &release, "multiworld-gui-linux", "application/x-executable", data).await?;
+                release.attach(&client, &repo, "multiworld-gui-linux", "application/x-executable", data).await?;
                 Ok(Ok(()))
             }).await,
         }
     }
 }
 
+/// Builds the given package for Linux in release mode, either in WSL or, if `local` is true, on this machine.
+///
+/// Local builds don't require the user agent salt, so they can be made without access to it.
+async fn linux_cargo_build(local: bool, package: &str) -> wheel::Result {
+    if local {
+        Command::new("cargo").arg("build").arg("--release").arg(format!("--package={package}")).check("cargo build").await?;
+    } else {
+        Command::new("wsl").arg("-d").arg("ubuntu-m2").arg("env").arg("-C").arg("/home/fenhl/wslgit/github.com/midoshouse/ootr-multiworld").arg("/home/fenhl/.cargo/bin/cargo").arg("build").arg("--release").arg(format!("--package={package}")).arg("--features=require-user-agent-salt").check("wsl cargo build").await?;
+    }
+    Ok(())
+}
+
 enum BuildBizHawk {
-    Gui(bool, reqwest::Client, Repo, broadcast::Receiver<WindowsGuiNotification>, broadcast::Receiver<Release>, Version, broadcast::Sender<WindowsBizHawkNotification>),
-    CSharp(bool, reqwest::Client, Repo, broadcast::Receiver<Release>, Version, broadcast::Sender<WindowsBizHawkNotification>),
-    BizHawk(bool, reqwest::Client, Repo, broadcast::Receiver<Release>, Version, broadcast::Sender<WindowsBizHawkNotification>),
-    Zip(reqwest::Client, Repo, broadcast::Receiver<Release>, Version),
-    WaitRelease(reqwest::Client, Repo, broadcast::Receiver<Release>, Vec<u8>),
-    Upload(reqwest::Client, Repo, Release, Vec<u8>),
+    Gui(bool, reqwest::Client, Repo, broadcast::Receiver<WindowsGuiNotification>, broadcast::Receiver<DraftRelease>, Version, broadcast::Sender<WindowsBizHawkNotification>),
+    CSharp(bool, reqwest::Client, Repo, broadcast::Receiver<DraftRelease>, Version, broadcast::Sender<WindowsBizHawkNotification>),
+    BizHawk(bool, reqwest::Client, Repo, broadcast::Receiver<DraftRelease>, Version, broadcast::Sender<WindowsBizHawkNotification>),
+    Zip(reqwest::Client, Repo, broadcast::Receiver<DraftRelease>, Version),
+    WaitRelease(reqwest::Client, Repo, broadcast::Receiver<DraftRelease>, Vec<u8>),
+    Upload(reqwest::Client, Repo, DraftRelease, Vec<u8>),
 }
 
 impl BuildBizHawk {
-    fn new(debug: bool, client: reqwest::Client, repo: Repo, gui_rx: broadcast::Receiver<WindowsGuiNotification>, release_rx: broadcast::Receiver<Release>, version: Version, bizhawk_tx: broadcast::Sender<WindowsBizHawkNotification>) -> Self {
+    fn new(debug: bool, client: reqwest::Client, repo: Repo, gui_rx: broadcast::Receiver<WindowsGuiNotification>, release_rx: broadcast::Receiver<DraftRelease>, version: Version, bizhawk_tx: broadcast::Sender<WindowsBizHawkNotification>) -> Self {
         Self::Gui(debug, client, repo, gui_rx, release_rx, version, bizhawk_tx)
     }
 }
@@ -583,7 +664,7 @@ impl Task<Result<(), Error>> for BuildBizHawk {
                 Ok(Err(Self::Upload(client, repo, release, zip_data)))
             }).await,
             Self::Upload(client, repo, release, zip_data) => gres::transpose(async move {
-                repo.release_attach(&client, &release, "multiworld-bizhawk.zip", "application/zip", zip_data).await?;
+                release.attach(&client, &repo, "multiworld-bizhawk.zip", "application/zip", zip_data).await?;
                 Ok(Ok(()))
             }).await,
         }
@@ -591,18 +672,19 @@ impl Task<Result<(), Error>> for BuildBizHawk {
 }
 
 enum BuildBizHawkLinux {
-    Gui(reqwest::Client, Repo, broadcast::Receiver<LinuxGuiNotification>, broadcast::Receiver<Release>, Version, broadcast::Sender<LinuxBizHawkNotification>),
-    CSharp(reqwest::Client, Repo, broadcast::Receiver<Release>, Version, broadcast::Sender<LinuxBizHawkNotification>),
-    BizHawk(reqwest::Client, Repo, broadcast::Receiver<Release>, Version, broadcast::Sender<LinuxBizHawkNotification>),
-    Copy(reqwest::Client, Repo, broadcast::Receiver<Release>, Version),
-    Zip(reqwest::Client, Repo, broadcast::Receiver<Release>, Version),
-    WaitRelease(reqwest::Client, Repo, broadcast::Receiver<Release>, Vec<u8>),
-    Upload(reqwest::Client, Repo, Release, Vec<u8>),
+    Gui(bool, reqwest::Client, Repo, broadcast::Receiver<LinuxGuiNotification>, broadcast::Receiver<DraftRelease>, Version, broadcast::Sender<LinuxBizHawkNotification>),
+    CSharp(bool, reqwest::Client, Repo, broadcast::Receiver<DraftRelease>, Version, broadcast::Sender<LinuxBizHawkNotification>),
+    BizHawk(bool, reqwest::Client, Repo, broadcast::Receiver<DraftRelease>, Version, broadcast::Sender<LinuxBizHawkNotification>),
+    Copy(reqwest::Client, Repo, broadcast::Receiver<DraftRelease>, Version),
+    Zip(bool, reqwest::Client, Repo, broadcast::Receiver<DraftRelease>, Version),
+    WaitRelease(reqwest::Client, Repo, broadcast::Receiver<DraftRelease>, Vec<u8>),
+    Upload(reqwest::Client, Repo, DraftRelease, Vec<u8>),
 }
 
 impl BuildBizHawkLinux {
-    fn new(client: reqwest::Client, repo: Repo, gui_rx: broadcast::Receiver<LinuxGuiNotification>, release_rx: broadcast::Receiver<Release>, version: Version, bizhawk_tx: broadcast::Sender<LinuxBizHawkNotification>) -> Self {
-        Self::Gui(client, repo, gui_rx, release_rx, version, bizhawk_tx)
+    /// If `local` is true, builds on this machine instead of in WSL.
+    fn new(local: bool, client: reqwest::Client, repo: Repo, gui_rx: broadcast::Receiver<LinuxGuiNotification>, release_rx: broadcast::Receiver<DraftRelease>, version: Version, bizhawk_tx: broadcast::Sender<LinuxBizHawkNotification>) -> Self {
+        Self::Gui(local, client, repo, gui_rx, release_rx, version, bizhawk_tx)
     }
 }
 
@@ -638,31 +720,36 @@ impl GetPriority for BuildBizHawkLinux {
 impl Task<Result<(), Error>> for BuildBizHawkLinux {
     async fn run(self) -> Result<Result<(), Error>, Self> {
         match self {
-            Self::Gui(client, repo, mut gui_rx, release_rx, version, bizhawk_tx) => gres::transpose(async move {
+            Self::Gui(local, client, repo, mut gui_rx, release_rx, version, bizhawk_tx) => gres::transpose(async move {
                 let LinuxGuiNotification = gui_rx.recv().await?;
-                Ok(Err(Self::CSharp(client, repo, release_rx, version, bizhawk_tx)))
+                Ok(Err(Self::CSharp(local, client, repo, release_rx, version, bizhawk_tx)))
             }).await,
-            Self::CSharp(client, repo, release_rx, version, bizhawk_tx) => gres::transpose(async move {
-                Command::new("wsl").arg("-d").arg("ubuntu-m2").arg("env").arg("-C").arg("/home/fenhl/wslgit/github.com/midoshouse/ootr-multiworld").arg("/home/fenhl/.cargo/bin/cargo").arg("build").arg("--release").arg("--package=multiworld-csharp").arg("--features=require-user-agent-salt").check("wsl cargo build --package=multiworld-csharp").await?;
-                Ok(Err(Self::BizHawk(client, repo, release_rx, version, bizhawk_tx)))
+            Self::CSharp(local, client, repo, release_rx, version, bizhawk_tx) => gres::transpose(async move {
+                linux_cargo_build(local, "multiworld-csharp").await?;
+                Ok(Err(Self::BizHawk(local, client, repo, release_rx, version, bizhawk_tx)))
             }).await,
-            Self::BizHawk(client, repo, release_rx, version, bizhawk_tx) => gres::transpose(async move {
-                Command::new("wsl").arg("-d").arg("ubuntu-m2").arg("env").arg("-C").arg("/home/fenhl/wslgit/github.com/midoshouse/ootr-multiworld").arg("/home/fenhl/.cargo/bin/cargo").arg("build").arg("--release").arg("--package=multiworld-bizhawk").arg("--features=require-user-agent-salt").check("wsl cargo build --package=multiworld-bizhawk").await?;
+            Self::BizHawk(local, client, repo, release_rx, version, bizhawk_tx) => gres::transpose(async move {
+                linux_cargo_build(local, "multiworld-bizhawk").await?;
                 let _ = bizhawk_tx.send(LinuxBizHawkNotification);
-                Ok(Err(Self::Copy(client, repo, release_rx, version)))
+                Ok(Err(if local { Self::Zip(true, client, repo, release_rx, version) } else { Self::Copy(client, repo, release_rx, version) }))
             }).await,
             Self::Copy(client, repo, release_rx, version) => gres::transpose(async move {
                 fs::create_dir_all("target/wsl/release").await?;
                 Command::new("wsl").arg("-d").arg("ubuntu-m2").arg("cp").arg("/home/fenhl/wslgit/github.com/midoshouse/ootr-multiworld/crate/multiworld-bizhawk/OotrMultiworld/BizHawk/dll/libmultiworld.so").arg("/mnt/c/Users/fenhl/git/github.com/midoshouse/ootr-multiworld/stage/target/wsl/release/libmultiworld.so").check("wsl cp").await?;
                 Command::new("wsl").arg("-d").arg("ubuntu-m2").arg("cp").arg("/home/fenhl/wslgit/github.com/midoshouse/ootr-multiworld/crate/multiworld-bizhawk/OotrMultiworld/BizHawk/ExternalTools/OotrMultiworld.dll").arg("/mnt/c/Users/fenhl/git/github.com/midoshouse/ootr-multiworld/stage/target/wsl/release/OotrMultiworld.dll").check("wsl cp").await?;
-                Ok(Err(Self::Zip(client, repo, release_rx, version)))
+                Ok(Err(Self::Zip(false, client, repo, release_rx, version)))
             }).await,
-            Self::Zip(client, repo, release_rx, version) => gres::transpose(async move {
+            Self::Zip(local, client, repo, release_rx, version) => gres::transpose(async move {
+                let (dll_path, so_path) = if local {
+                    ("crate/multiworld-bizhawk/OotrMultiworld/BizHawk/ExternalTools/OotrMultiworld.dll", "crate/multiworld-bizhawk/OotrMultiworld/BizHawk/dll/libmultiworld.so")
+                } else {
+                    ("target/wsl/release/OotrMultiworld.dll", "target/wsl/release/libmultiworld.so")
+                };
                 let zip_data = {
                     let mut zip = ZipFileWriter::with_tokio(Vec::default());
                     zip.write_entry_whole(ZipEntryBuilder::new("README.txt".into(), Compression::Deflate), format!(include_str!("../../../assets/bizhawk-readme-linux.txt"), version).as_ref()).await?;
-                    zip.write_entry_whole(ZipEntryBuilder::new("OotrMultiworld.dll".into(), Compression::Deflate), &fs::read("target/wsl/release/OotrMultiworld.dll").await?).await?;
-                    zip.write_entry_whole(ZipEntryBuilder::new("libmultiworld.so".into(), Compression::Deflate), &fs::read("target/wsl/release/libmultiworld.so").await?).await?;
+                    zip.write_entry_whole(ZipEntryBuilder::new("OotrMultiworld.dll".into(), Compression::Deflate), &fs::read(dll_path).await?).await?;
+                    zip.write_entry_whole(ZipEntryBuilder::new("libmultiworld.so".into(), Compression::Deflate), &fs::read(so_path).await?).await?;
                     zip.close().await?.into_inner()
                 };
                 Ok(Err(Self::WaitRelease(client, repo, release_rx, zip_data)))
@@ -672,7 +759,7 @@ impl Task<Result<(), Error>> for BuildBizHawkLinux {
                 Ok(Err(Self::Upload(client, repo, release, zip_data)))
             }).await,
             Self::Upload(client, repo, release, zip_data) => gres::transpose(async move {
-                repo.release_attach(&client, &release, "multiworld-bizhawk-linux.zip", "application/zip", zip_data).await?;
+                release.attach(&client, &repo, "multiworld-bizhawk-linux.zip", "application/zip", zip_data).await?;
                 Ok(Ok(()))
             }).await,
         }
@@ -680,13 +767,13 @@ impl Task<Result<(), Error>> for BuildBizHawkLinux {
 }
 
 enum BuildPj64 {
-    ReadJs(reqwest::Client, Repo, broadcast::Receiver<Release>),
-    WaitRelease(reqwest::Client, Repo, broadcast::Receiver<Release>, Vec<u8>),
-    UploadJs(reqwest::Client, Repo, Release, Vec<u8>),
+    ReadJs(reqwest::Client, Repo, broadcast::Receiver<DraftRelease>),
+    WaitRelease(reqwest::Client, Repo, broadcast::Receiver<DraftRelease>, Vec<u8>),
+    UploadJs(reqwest::Client, Repo, DraftRelease, Vec<u8>),
 }
 
 impl BuildPj64 {
-    fn new(client: reqwest::Client, repo: Repo, release_rx: broadcast::Receiver<Release>) -> Self {
+    fn new(client: reqwest::Client, repo: Repo, release_rx: broadcast::Receiver<DraftRelease>) -> Self {
         Self::ReadJs(client, repo, release_rx)
     }
 }
@@ -724,7 +811,7 @@ impl Task<Result<(), Error>> for BuildPj64 {
                 Ok(Err(Self::UploadJs(client, repo, release, data)))
             }).await,
             Self::UploadJs(client, repo, release, data) => gres::transpose(async move {
-                repo.release_attach(&client, &release, "ootrmw-pj64.js", "text/javascript", data).await?;
+                release.attach(&client, &repo, "ootrmw-pj64.js", "text/javascript", data).await?;
                 Ok(Ok(()))
             }).await,
         }
@@ -732,15 +819,15 @@ impl Task<Result<(), Error>> for BuildPj64 {
 }
 
 enum BuildInstaller {
-    Deps(bool, reqwest::Client, Repo, broadcast::Receiver<WindowsBizHawkNotification>, broadcast::Receiver<WindowsGuiNotification>, broadcast::Receiver<Release>),
-    Glow(bool, reqwest::Client, Repo, broadcast::Receiver<Release>),
-    Read(bool, reqwest::Client, Repo, broadcast::Receiver<Release>),
-    WaitRelease(bool, reqwest::Client, Repo, broadcast::Receiver<Release>, Vec<u8>),
-    Upload(bool, reqwest::Client, Repo, Release, Vec<u8>),
+    Deps(bool, reqwest::Client, Repo, broadcast::Receiver<WindowsBizHawkNotification>, broadcast::Receiver<WindowsGuiNotification>, broadcast::Receiver<DraftRelease>),
+    Glow(bool, reqwest::Client, Repo, broadcast::Receiver<DraftRelease>),
+    Read(bool, reqwest::Client, Repo, broadcast::Receiver<DraftRelease>),
+    WaitRelease(bool, reqwest::Client, Repo, broadcast::Receiver<DraftRelease>, Vec<u8>),
+    Upload(bool, reqwest::Client, Repo, DraftRelease, Vec<u8>),
 }
 
 impl BuildInstaller {
-    fn new(debug: bool, client: reqwest::Client, repo: Repo, bizhawk_rx: broadcast::Receiver<WindowsBizHawkNotification>, gui_rx: broadcast::Receiver<WindowsGuiNotification>, release_rx: broadcast::Receiver<Release>) -> Self {
+    fn new(debug: bool, client: reqwest::Client, repo: Repo, bizhawk_rx: broadcast::Receiver<WindowsBizHawkNotification>, gui_rx: broadcast::Receiver<WindowsGuiNotification>, release_rx: broadcast::Receiver<DraftRelease>) -> Self {
         Self::Deps(debug, client, repo, bizhawk_rx, gui_rx, release_rx)
     }
 }
@@ -805,7 +892,7 @@ impl Task<Result<(), Error>> for BuildInstaller {
                 Ok(Err(Self::Upload(debug, client, repo, release, data)))
             }).await,
             Self::Upload(debug, client, repo, release, data) => gres::transpose(async move {
-                repo.release_attach(&client, &release, if debug { "multiworld-installer-debug.exe" } else { "multiworld-installer.exe" }, "application/vnd.microsoft.portable-executable", data).await?;
+                release.attach(&client, &repo, if debug { "multiworld-installer-debug.exe" } else { "multiworld-installer.exe" }, "application/vnd.microsoft.portable-executable", data).await?;
                 Ok(Ok(()))
             }).await,
         }
@@ -813,16 +900,16 @@ impl Task<Result<(), Error>> for BuildInstaller {
 }
 
 enum BuildInstallerLinux {
-    Deps(reqwest::Client, Repo, broadcast::Receiver<LinuxBizHawkNotification>, broadcast::Receiver<Release>),
-    Glow(reqwest::Client, Repo, broadcast::Receiver<Release>),
-    Copy(reqwest::Client, Repo, broadcast::Receiver<Release>),
-    Read(reqwest::Client, Repo, broadcast::Receiver<Release>),
-    WaitRelease(reqwest::Client, Repo, broadcast::Receiver<Release>, Vec<u8>),
-    Upload(reqwest::Client, Repo, Release, Vec<u8>),
+    Deps(reqwest::Client, Repo, broadcast::Receiver<LinuxBizHawkNotification>, broadcast::Receiver<DraftRelease>),
+    Glow(reqwest::Client, Repo, broadcast::Receiver<DraftRelease>),
+    Copy(reqwest::Client, Repo, broadcast::Receiver<DraftRelease>),
+    Read(reqwest::Client, Repo, broadcast::Receiver<DraftRelease>),
+    WaitRelease(reqwest::Client, Repo, broadcast::Receiver<DraftRelease>, Vec<u8>),
+    Upload(reqwest::Client, Repo, DraftRelease, Vec<u8>),
 }
 
 impl BuildInstallerLinux {
-    fn new(client: reqwest::Client, repo: Repo, bizhawk_rx: broadcast::Receiver<LinuxBizHawkNotification>, release_rx: broadcast::Receiver<Release>) -> Self {
+    fn new(client: reqwest::Client, repo: Repo, bizhawk_rx: broadcast::Receiver<LinuxBizHawkNotification>, release_rx: broadcast::Receiver<DraftRelease>) -> Self {
         Self::Deps(client, repo, bizhawk_rx, release_rx)
     }
 }
@@ -879,7 +966,7 @@ impl Task<Result<(), Error>> for BuildInstallerLinux {
                 Ok(Err(Self::Upload(client, repo, release, data)))
             }).await,
             Self::Upload(client, repo, release, data) => gres::transpose(async move {
-                repo.release_attach(&client, &release, "multiworld-installer-linux", "application/x-executable", data).await?;
+                release.attach(&client, &repo, "multiworld-installer-linux", "application/x-executable", data).await?;
                 Ok(Ok(()))
             }).await,
         }
@@ -1004,19 +1091,19 @@ impl Task<Result<(), Error>> for BuildServer {
 }
 
 enum BuildMacOs {
-    Pull(reqwest::Client, Repo, broadcast::Receiver<Release>),
-    Build(reqwest::Client, Repo, broadcast::Receiver<Release>),
-    Connect(reqwest::Client, Repo, broadcast::Receiver<Release>),
-    Remote(String, reqwest::Client, Repo, broadcast::Receiver<Release>, Child, ChildStdout),
-    Disconnect(reqwest::Client, Repo, broadcast::Receiver<Release>, Child),
-    Download(reqwest::Client, Repo, broadcast::Receiver<Release>),
-    Read(reqwest::Client, Repo, broadcast::Receiver<Release>),
-    WaitRelease(reqwest::Client, Repo, broadcast::Receiver<Release>, Vec<u8>),
-    Upload(reqwest::Client, Repo, Release, Vec<u8>),
+    Pull(reqwest::Client, Repo, broadcast::Receiver<DraftRelease>),
+    Build(reqwest::Client, Repo, broadcast::Receiver<DraftRelease>),
+    Connect(reqwest::Client, Repo, broadcast::Receiver<DraftRelease>),
+    Remote(String, reqwest::Client, Repo, broadcast::Receiver<DraftRelease>, Child, ChildStdout),
+    Disconnect(reqwest::Client, Repo, broadcast::Receiver<DraftRelease>, Child),
+    Download(reqwest::Client, Repo, broadcast::Receiver<DraftRelease>),
+    Read(reqwest::Client, Repo, broadcast::Receiver<DraftRelease>),
+    WaitRelease(reqwest::Client, Repo, broadcast::Receiver<DraftRelease>, Vec<u8>),
+    Upload(reqwest::Client, Repo, DraftRelease, Vec<u8>),
 }
 
 impl BuildMacOs {
-    fn new(client: reqwest::Client, repo: Repo, release_rx: broadcast::Receiver<Release>) -> Self {
+    fn new(client: reqwest::Client, repo: Repo, release_rx: broadcast::Receiver<DraftRelease>) -> Self {
         Self::Pull(client, repo, release_rx)
     }
 }
@@ -1098,7 +1185,7 @@ impl Task<Result<(), Error>> for BuildMacOs {
                 Ok(Err(Self::Upload(client, repo, release, data)))
             }).await,
             Self::Upload(client, repo, release, data) => gres::transpose(async move {
-                repo.release_attach(&client, &release, "multiworld-gui.dmg", "application/x-apple-diskimage", data).await?;
+                release.attach(&client, &repo, "multiworld-gui.dmg", "application/x-apple-diskimage", data).await?;
                 Ok(Ok(()))
             }).await,
         }
@@ -1119,6 +1206,9 @@ enum CliArgs {
         /// Don't pass `--wait` to the release notes editor
         #[clap(short = 'W', long)]
         no_wait: bool,
+        /// The PKCS#8-encoded Ed25519 private key used to sign the release manifest, see assets/doc/release-signing.md
+        #[clap(long)]
+        signing_key: PathBuf,
     },
     /// Only update the client
     Client {
@@ -1128,6 +1218,9 @@ enum CliArgs {
         /// Don't pass `--wait` to the release notes editor
         #[clap(short = 'W', long)]
         no_wait: bool,
+        /// The PKCS#8-encoded Ed25519 private key used to sign the release manifest, see assets/doc/release-signing.md
+        #[clap(long)]
+        signing_key: PathBuf,
     },
     /// Only update the server
     Server {
@@ -1135,38 +1228,64 @@ enum CliArgs {
         #[clap(long)]
         force: bool,
     },
+    /// Build the Linux and Project64 assets locally without creating a release
+    ///
+    /// This makes no GitHub or SSH calls. The assets and their manifest are written to the output directory instead. The manifest is not signed, so the release signing key is never needed for this.
+    #[clap(alias = "dry-run")]
+    Local {
+        /// The directory to write the release assets to
+        #[clap(long, default_value = "target/local-release")]
+        output: PathBuf,
+    },
 }
 
 #[derive(Clone)]
 struct Args {
     force: bool,
+    local: Option<PathBuf>,
     no_publish: bool,
     no_server: bool,
     no_wait: bool,
     server_only: bool,
+    signing_key: Option<PathBuf>,
 }
 
 impl From<CliArgs> for Args {
     fn from(args: CliArgs) -> Self {
         match args {
-            CliArgs::Both { force, no_publish, no_wait } => Self {
+            CliArgs::Both { force, no_publish, no_wait, signing_key } => Self {
+                local: None,
                 no_server: false,
                 server_only: false,
+                signing_key: Some(signing_key),
                 force, no_publish, no_wait,
             },
-            CliArgs::Client { no_publish, no_wait } => Self {
+            CliArgs::Client { no_publish, no_wait, signing_key } => Self {
                 force: false,
+                local: None,
                 no_server: true,
                 server_only: false,
+                signing_key: Some(signing_key),
                 no_publish, no_wait,
             },
             CliArgs::Server { force } => Self {
+                local: None,
                 no_publish: false,
                 no_server: false,
                 no_wait: false,
                 server_only: true,
+                signing_key: None,
                 force,
             },
+            CliArgs::Local { output } => Self {
+                force: false,
+                local: Some(output),
+                no_publish: true,
+                no_server: true,
+                no_wait: false,
+                server_only: false,
+                signing_key: None,
+            },
         }
     }
 }
@@ -1180,6 +1299,7 @@ enum Error {
     #[error(transparent)] GitHubAppAuth(#[from] github_app_auth::AuthError),
     #[error(transparent)] InvalidHeaderValue(#[from] reqwest::header::InvalidHeaderValue),
     #[error(transparent)] Io(#[from] io::Error),
+    #[error(transparent)] Manifest(#[from] multiworld::github::ManifestError),
     #[error(transparent)] ParseInt(#[from] std::num::ParseIntError),
     #[error(transparent)] Read(#[from] ReadError),
     #[error(transparent)] Reqwest(#[from] reqwest::Error),
@@ -1239,8 +1359,50 @@ impl wheel::CustomExit for Error {
     }
 }
 
+/// Builds the Linux and Project64 assets into a local directory, for testing the release process without publishing anything.
+async fn local_main(cli: &Cli, output: PathBuf) -> Result<(), Error> {
+    let [major, minor, patch, _] = multiworld_bizhawk::bizhawk_version();
+    let bizhawk_version = Version::new(major.into(), minor.into(), patch.into());
+    fs::create_dir_all(&output).await?;
+    // the local tasks never use these, but the task types are shared with the regular release process
+    let client = reqwest::Client::builder()
+        .user_agent(concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")))
+        .build()?;
+    let repo = Repo::new("midoshouse", "ootr-multiworld");
+    let (release_tx, release_rx_gui_linux) = broadcast::channel(1);
+    let release_rx_bizhawk_linux = release_tx.subscribe();
+    let release_rx_pj64 = release_tx.subscribe();
+    let (linux_gui_tx, linux_gui_rx) = broadcast::channel(1);
+    let (linux_bizhawk_tx, _) = broadcast::channel(1);
+    let release = DraftRelease {
+        target: ReleaseTarget::Local(output.clone()),
+        manifest: Arc::new(Mutex::new(ReleaseManifest::new(version::version().await))),
+    };
+    let _ = release_tx.send(release.clone());
+    tokio::try_join!(
+        { let client = client.clone(); let repo = repo.clone(); async move { cli.run(BuildGuiLinux::new(true, client, repo, release_rx_gui_linux, linux_gui_tx), "GUI (Linux)").await? } },
+        { let client = client.clone(); let repo = repo.clone(); async move { cli.run(BuildBizHawkLinux::new(true, client, repo, linux_gui_rx, release_rx_bizhawk_linux, bizhawk_version, linux_bizhawk_tx), "BizHawk (Linux)").await? } },
+        { let client = client.clone(); let repo = repo.clone(); async move { cli.run(BuildPj64::new(client, repo, release_rx_pj64), "Project64").await? } },
+    )?;
+    let line = cli.new_line("writing manifest").await?;
+    release.write_unsigned_manifest().await?;
+    line.replace(format!("release assets written to {}", output.display())).await?;
+    Ok(())
+}
+
 /// Separate function to ensure CLI is dropped before exit
 async fn cli_main(cli: &Cli, args: Args) -> Result<(), Error> {
+    if let Some(output) = args.local {
+        return local_main(cli, output).await
+    }
+    // read and check the signing key before building anything so a wrong key doesn't waste a full build
+    let private_key = if let Some(ref signing_key) = args.signing_key {
+        let private_key = fs::read(signing_key).await?;
+        ReleaseManifest::check_signing_key(&private_key)?;
+        Some(private_key)
+    } else {
+        None
+    };
     let (client, repo, bizhawk_version) = cli.run(Setup::new(args.server_only), "pre-release checks").await??; // don't show release notes editor if version check could still fail
     if args.server_only {
         cli.run(BuildServer::new(!args.force), "server").await??;
@@ -1289,10 +1451,10 @@ async fn cli_main(cli: &Cli, args: Args) -> Result<(), Error> {
             async move { cli.run(BuildUpdater::new(false, updater_tx), "updater").await? },
             { let client = client.clone(); let repo = repo.clone(); async move { cli.run(BuildGui::new(true, client, repo, debug_updater_rx, release_rx_gui_debug, debug_gui_tx), "GUI (Windows, debug)").await? } },
             { let client = client.clone(); let repo = repo.clone(); async move { cli.run(BuildGui::new(false, client, repo, updater_rx, release_rx_gui, gui_tx), "GUI (Windows)").await? } },
-            { let client = client.clone(); let repo = repo.clone(); async move { cli.run(BuildGuiLinux::new(client, repo, release_rx_gui_linux, linux_gui_tx), "GUI (Linux)").await? } },
+            { let client = client.clone(); let repo = repo.clone(); async move { cli.run(BuildGuiLinux::new(false, client, repo, release_rx_gui_linux, linux_gui_tx), "GUI (Linux)").await? } },
             { let client = client.clone(); let repo = repo.clone(); async move { cli.run(BuildBizHawk::new(true, client, repo, debug_gui_rx, release_rx_bizhawk_debug, bizhawk_version_debug, debug_bizhawk_tx), "BizHawk (Windows, debug)").await? } },
             { let client = client.clone(); let repo = repo.clone(); async move { cli.run(BuildBizHawk::new(false, client, repo, gui_rx, release_rx_bizhawk, bizhawk_version, bizhawk_tx), "BizHawk (Windows)").await? } },
-            { let client = client.clone(); let repo = repo.clone(); async move { cli.run(BuildBizHawkLinux::new(client, repo, linux_gui_rx, release_rx_bizhawk_linux, bizhawk_version_linux, linux_bizhawk_tx), "BizHawk (Linux)").await? } },
+            { let client = client.clone(); let repo = repo.clone(); async move { cli.run(BuildBizHawkLinux::new(false, client, repo, linux_gui_rx, release_rx_bizhawk_linux, bizhawk_version_linux, linux_bizhawk_tx), "BizHawk (Linux)").await? } },
             { let client = client.clone(); let repo = repo.clone(); async move { cli.run(BuildPj64::new(client, repo, release_rx_pj64), "Project64").await? } },
             { let client = client.clone(); let repo = repo.clone(); async move { cli.run(BuildInstaller::new(true, client, repo, debug_bizhawk_rx, debug_gui_rx_installer, release_rx_installer_debug), "installer (Windows, debug)").await? } },
             { let client = client.clone(); let repo = repo.clone(); async move { cli.run(BuildInstaller::new(false, client, repo, bizhawk_rx, gui_rx_installer, release_rx_installer), "installer (Windows)").await? } },
@@ -1300,8 +1462,12 @@ async fn cli_main(cli: &Cli, args: Args) -> Result<(), Error> {
             { let client = client.clone(); let repo = repo.clone(); async move { cli.run(BuildMacOs::new(client, repo, release_rx_macos), "macOS").await? } },
             if args.no_server { future::ok(()).boxed() } else { async move { cli.run(BuildServer::new(!args.force), "server").await? }.boxed() },
         ]?;
+        let line = cli.new_line("signing release").await?;
+        release.attach_manifest(&client, &repo, private_key.as_deref().expect("client releases always have a signing key")).await?;
+        line.replace("release signed").await?;
         if !args.no_publish {
             let line = cli.new_line("publishing release").await?;
+            let ReleaseTarget::GitHub(release) = release.target else { unreachable!("CreateRelease always creates a GitHub release") };
             repo.publish_release(&client, release).await?;
             line.replace("release published").await?;
             let line = cli.new_line("updating Homebrew tap").await?;
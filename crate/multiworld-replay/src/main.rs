@@ -0,0 +1,287 @@
+//! Replays capture files recorded by the multiworld app (see [`multiworld::capture`]) against a local `ootrmwd` instance, to reproduce desyncs and reports like `ConflictingItemKinds`.
+//!
+//! All connections in all given files are replayed concurrently with their original relative timing. Room IDs are translated using the room names from the capture, and rooms which don't exist on the target server yet are created.
+//! Once the replay is done, the last room state of each connection is compared against the captured one.
+
+use {
+    std::{
+        collections::HashMap,
+        convert::Infallible as Never,
+        num::NonZeroU8,
+        path::PathBuf,
+        pin::pin,
+        time::Duration,
+    },
+    chrono::prelude::*,
+    futures::future,
+    tokio::{
+        select,
+        time::{
+            Instant,
+            sleep_until,
+        },
+    },
+    url::Url,
+    multiworld::{
+        Filename,
+        SessionState,
+        capture::{
+            self,
+            Entry,
+        },
+        client::Receiver,
+        ws::latest::{
+            ClientMessage,
+            ServerMessage,
+        },
+    },
+};
+
+macro_rules! log {
+    ($($arg:tt)*) => {
+        println!("{} {}", Local::now().format("%Y-%m-%d %H:%M:%S"), format_args!($($arg)*))
+    };
+}
+
+#[derive(clap::Parser)]
+#[clap(version)]
+struct Args {
+    /// The capture files to replay.
+    #[clap(required = true)]
+    captures: Vec<PathBuf>,
+    /// The WebSocket URL of the server to replay against. Defaults to a local ootrmwd on its default port.
+    #[clap(long)]
+    url: Option<Url>,
+    /// Replay this many times faster than the capture.
+    #[clap(long, default_value_t = 1.0)]
+    speed: f64,
+    /// The password to use for rooms, since room passwords aren't recorded in captures.
+    #[clap(long, default_value = "replay")]
+    password: String,
+    /// How many seconds to keep receiving messages after the last message of a connection has been sent, before comparing states.
+    #[clap(long, default_value_t = 5)]
+    settle: u64,
+    /// Replay captures recorded with a different major version of the app, which may use a different protocol.
+    #[clap(long)]
+    ignore_version: bool,
+}
+
+#[derive(Debug, thiserror::Error)]
+enum Error {
+    #[error(transparent)] Client(#[from] multiworld::client::Error),
+    #[error(transparent)] Read(#[from] async_proto::ReadError),
+    #[error(transparent)] Url(#[from] url::ParseError),
+    #[error(transparent)] Wheel(#[from] wheel::Error),
+    #[error("{0} of the replayed connections didn't match the capture")]
+    Mismatch(usize),
+    #[error("--speed must be a positive number")]
+    Speed,
+    #[error("{label} was recorded with version {captured} of the app, which may use a different protocol than this version ({}); pass --ignore-version to replay it anyway", multiworld::version())]
+    Version {
+        label: String,
+        captured: String,
+    },
+}
+
+/// The parts of a [`SessionState::Room`] which are compared between the capture and the replay.
+#[derive(PartialEq, Eq)]
+struct RoomSnapshot {
+    name: String,
+    players: Vec<(NonZeroU8, Filename)>,
+    item_queue: Vec<u16>,
+    conflicting_item_kinds: bool,
+}
+
+impl RoomSnapshot {
+    fn new(state: &SessionState<Never>) -> Option<Self> {
+        if let SessionState::Room { room_name, players, item_queue, conflicting_item_kinds, .. } = state {
+            let mut players = players.iter().map(|player| (player.world, player.name)).collect::<Vec<_>>();
+            players.sort_by_key(|&(world, _)| world);
+            Some(Self {
+                name: room_name.clone(),
+                item_queue: item_queue.clone(),
+                conflicting_item_kinds: *conflicting_item_kinds,
+                players,
+            })
+        } else {
+            None
+        }
+    }
+
+    fn compare(&self, label: &str, replayed: &Self) -> bool {
+        if self == replayed {
+            log!("{label}: final state of room {:?} matches the capture ({} players, {} items in queue)", self.name, self.players.len(), self.item_queue.len());
+            return true
+        }
+        if self.name != replayed.name {
+            log!("{label}: ended up in room {:?} instead of {:?}", replayed.name, self.name);
+        }
+        if self.players != replayed.players {
+            log!("{label}: players differ");
+            log!("{label}:   captured: {}", format_players(&self.players));
+            log!("{label}:   replayed: {}", format_players(&replayed.players));
+        }
+        if self.item_queue != replayed.item_queue {
+            log!("{label}: item queue differs");
+            log!("{label}:   captured: {:?}", self.item_queue);
+            log!("{label}:   replayed: {:?}", replayed.item_queue);
+        }
+        if self.conflicting_item_kinds != replayed.conflicting_item_kinds {
+            log!("{label}: ConflictingItemKinds was {}reported in the capture but {}reported in the replay", if self.conflicting_item_kinds { "" } else { "not " }, if replayed.conflicting_item_kinds { "" } else { "not " });
+        }
+        false
+    }
+}
+
+fn format_players(players: &[(NonZeroU8, Filename)]) -> String {
+    players.iter().map(|(world, name)| format!("{world}: {name}")).collect::<Vec<_>>().join(", ")
+}
+
+/// Waits for messages from the server until `deadline`, logging them and keeping track of the last room state.
+async fn receive_until(label: &str, receiver: &mut Receiver, last_room: &mut Option<RoomSnapshot>, deadline: Instant) -> Result<(), Error> {
+    let mut deadline = pin!(sleep_until(deadline));
+    loop {
+        select! {
+            () = &mut deadline => break Ok(()),
+            res = receiver.recv() => {
+                let msg = res?;
+                if !matches!(msg, ServerMessage::Ping) {
+                    log!("{label}: {msg:?}");
+                }
+                if let Some(room) = RoomSnapshot::new(receiver.state()) {
+                    *last_room = Some(room);
+                }
+            }
+        }
+    }
+}
+
+/// Adapts a captured client message to the target server, or returns `None` if it should be skipped.
+fn translate(args: &Args, label: &str, room_names: &HashMap<u64, String>, state: &SessionState<Never>, msg: ClientMessage) -> Option<ClientMessage> {
+    let join_or_create = |name: &str| {
+        if let SessionState::Lobby { rooms, .. } = state {
+            if let Some((&id, _)) = rooms.iter().find(|(_, (room_name, _))| room_name == name) {
+                return ClientMessage::JoinRoom { id, password: Some(args.password.clone()) }
+            }
+        }
+        ClientMessage::CreateRoom { name: name.to_owned(), password: args.password.clone() }
+    };
+    match msg {
+        // the client sends its own pings
+        ClientMessage::Ping => None,
+        ClientMessage::LoginApiKey { .. } | ClientMessage::LoginDiscord { .. } | ClientMessage::LoginRaceTime { .. } => {
+            log!("{label}: skipping sign-in");
+            None
+        }
+        ClientMessage::Track { .. } | ClientMessage::RevokeItem { .. } | ClientMessage::GrantItem { .. } | ClientMessage::MoveItem { .. } => {
+            log!("{label}: skipping admin-only message {msg:?}");
+            None
+        }
+        ClientMessage::JoinRoom { id, .. } => if let Some(name) = room_names.get(&id) {
+            Some(join_or_create(name))
+        } else {
+            log!("{label}: skipping JoinRoom for room {id} since its name isn't in the capture");
+            None
+        },
+        ClientMessage::CreateRoom { name, .. } => Some(join_or_create(&name)),
+        msg => Some(msg),
+    }
+}
+
+/// Replays a single captured connection. Returns whether the last room state matches the capture.
+async fn replay(args: &Args, websocket_url: &Url, origin: DateTime<Utc>, start: Instant, label: &str, entries: Vec<Entry>) -> Result<bool, Error> {
+    let deadline = |time: DateTime<Utc>| start + (time - origin).to_std().unwrap_or_default().div_f64(args.speed);
+    let mut room_names = HashMap::default();
+    let mut captured_state = SessionState::<Never>::default();
+    let mut captured_room = None;
+    for entry in &entries {
+        if let Entry::Server { msg, .. } = entry {
+            match msg {
+                ServerMessage::EnterLobby { rooms } => room_names.extend(rooms.iter().map(|(&id, (name, _))| (id, name.clone()))),
+                ServerMessage::NewRoom { id, name, .. } => { room_names.insert(*id, name.clone()); }
+                _ => {}
+            }
+            captured_state.apply(msg.clone());
+            if let Some(room) = RoomSnapshot::new(&captured_state) {
+                captured_room = Some(room);
+            }
+        }
+    }
+    let Some(first) = entries.first() else { return Ok(true) };
+    sleep_until(deadline(first.time())).await;
+    let (client, mut receiver) = multiworld::client::connect(websocket_url).await?;
+    log!("{label}: connected");
+    let mut replayed_room = None;
+    for entry in entries {
+        if let Entry::Client { time, msg } = entry {
+            receive_until(label, &mut receiver, &mut replayed_room, deadline(time)).await?;
+            if let Some(msg) = translate(args, label, &room_names, receiver.state(), msg) {
+                client.send(msg).await?;
+            }
+        }
+    }
+    receive_until(label, &mut receiver, &mut replayed_room, Instant::now() + Duration::from_secs(args.settle)).await?;
+    client.close(format!("replay finished")).await?;
+    Ok(match (captured_room, replayed_room) {
+        (Some(captured), Some(replayed)) => captured.compare(label, &replayed),
+        (Some(captured), None) => {
+            log!("{label}: never entered a room, but the capture ended in room {:?}", captured.name);
+            false
+        }
+        (None, _) => {
+            log!("{label}: the capture never entered a room, nothing to compare");
+            true
+        }
+    })
+}
+
+#[wheel::main]
+async fn main(args: Args) -> Result<(), Error> {
+    if args.speed.is_nan() || args.speed <= 0.0 { return Err(Error::Speed) }
+    let websocket_url = if let Some(ref url) = args.url {
+        url.clone()
+    } else {
+        Url::parse(&format!("ws://localhost:24819/v{}", multiworld::version().major))?
+    };
+    let mut connections = Vec::default();
+    for path in &args.captures {
+        for (idx, entries) in capture::read(&wheel::fs::read(path).await?)?.into_iter().enumerate() {
+            connections.push((format!("{}#{}", path.display(), idx + 1), entries));
+        }
+    }
+    let version = multiworld::version();
+    for (label, entries) in &connections {
+        if let Some(Entry::Connect { app_version, .. }) = entries.first() {
+            if *app_version != version {
+                if app_version.major != version.major && !args.ignore_version {
+                    return Err(Error::Version { label: label.clone(), captured: app_version.to_string() })
+                }
+                log!("{label}: recorded with version {app_version} of the app, replaying with version {version}");
+            }
+        }
+    }
+    let Some(origin) = connections.iter().filter_map(|(_, entries)| entries.first()).map(Entry::time).min() else {
+        log!("no connections found in the given capture files");
+        return Ok(())
+    };
+    log!("replaying {} connections against {websocket_url}", connections.len());
+    let start = Instant::now();
+    let results = future::join_all(connections.into_iter().map(|(label, entries)| {
+        let args = &args;
+        let websocket_url = &websocket_url;
+        async move {
+            let res = replay(args, websocket_url, origin, start, &label, entries).await;
+            if let Err(ref e) = res {
+                log!("{label}: replay failed: {e} ({e:?})");
+            }
+            res
+        }
+    })).await;
+    let mismatches = results.into_iter().filter(|res| !matches!(res, Ok(true))).count();
+    if mismatches > 0 {
+        Err(Error::Mismatch(mismatches))
+    } else {
+        log!("all connections match the capture");
+        Ok(())
+    }
+}
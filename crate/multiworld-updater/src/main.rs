@@ -6,6 +6,7 @@ use {
         cmp::Ordering::*,
         convert::Infallible as Never,
         env,
+        ffi::OsStr,
         io::prelude::*,
         path::{
             Path,
@@ -16,12 +17,9 @@ use {
     },
     bytes::Bytes,
     chrono::prelude::*,
-    futures::{
-        future::{
-            self,
-            Future,
-        },
-        stream::TryStreamExt as _,
+    futures::future::{
+        self,
+        Future,
     },
     iced::{
         Element,
@@ -52,14 +50,12 @@ use {
         },
         time::sleep,
     },
-    tokio_util::io::StreamReader,
     url::Url,
     wheel::{
         fs::{
             self,
             File,
         },
-        io_error_from_reqwest,
         traits::{
             IoResultExt as _,
             ResultNeverErrExt as _,
@@ -69,7 +65,11 @@ use {
     multiworld::{
         config::Config,
         github::{
+            FIRST_SIGNED_VERSION,
+            MANIFEST_NAME,
+            MANIFEST_SIGNATURE_NAME,
             ReleaseAsset,
+            ReleaseManifest,
             Repo,
         },
     },
@@ -91,6 +91,7 @@ mod util;
 enum Error {
     #[error(transparent)] Config(#[from] multiworld::config::Error),
     #[error(transparent)] Icon(#[from] icon::Error),
+    #[error(transparent)] Manifest(#[from] multiworld::github::ManifestError),
     #[error(transparent)] Reqwest(#[from] reqwest::Error),
     #[error(transparent)] SemVer(#[from] semver::Error),
     #[error(transparent)] Task(#[from] tokio::task::JoinError),
@@ -105,16 +106,27 @@ enum Error {
     CopyDebugInfo,
     #[error("latest release does not have a download for this platform")]
     MissingAsset,
+    #[error("latest release is not signed")]
+    MissingManifest,
+    #[error("there is no previous version to roll back to")]
+    NoPreviousVersion,
     #[error("the file README.md is missing from the download")]
     MissingReadme,
     #[error("there are no released versions")]
     NoReleases,
+    #[error("version {0} was released before updates were signed, so it can't be verified. Download it manually from GitHub if you want to install it.")]
+    UnsignedRelease(Version),
     #[error("failed to update Project64 script")]
     Pj64Script {
         temp_path: PathBuf,
         script_path: PathBuf,
         source: wheel::Error,
     },
+    #[error("failed to roll back Project64 script")]
+    Pj64ScriptRollback {
+        script_path: PathBuf,
+        source: wheel::Error,
+    },
     #[error("failed to locate Program Files folder")]
     ProgramFiles,
     #[error("could not find expected BizHawk version in README.md")]
@@ -124,12 +136,12 @@ enum Error {
 }
 
 impl Error {
-    fn to_markdown(&self) -> String {
+    fn to_markdown(&self, new_version: Option<&Version>) -> String {
         MessageBuilder::default()
-            .push_line(format!("error while trying to update Mido's House Multiworld from version {}{}:", env!("CARGO_PKG_VERSION"), {
+            .push_line(format!("error while trying to update Mido's House Multiworld from version {}{}{}:", env!("CARGO_PKG_VERSION"), {
                 #[cfg(debug_assertions)] { " (debug)" }
                 #[cfg(not(debug_assertions))] { "" }
-            })) //TODO also show new version
+            }, if let Some(new_version) = new_version { format!(" to version {new_version}") } else { String::default() }))
             .push_line_safe(self.to_string())
             .push_codeblock_safe(format!("{self:?}"), Some("rust"))
             .build()
@@ -141,8 +153,10 @@ enum Message {
     Error(Arc<Error>),
     CopyDebugInfo,
     Exited,
-    MultiworldReleaseAssets(reqwest::Client, ReleaseAsset, Option<ReleaseAsset>),
-    MultiworldResponse(reqwest::Client, reqwest::Response),
+    MultiworldReleaseAssets(reqwest::Client, ReleaseManifest, ReleaseAsset, Option<ReleaseAsset>),
+    MultiworldResponse(reqwest::Client, ReleaseManifest, String, reqwest::Response),
+    ReleaseInfo(Version, Vec<markdown::Item>),
+    OpenReleaseNotesLink(String),
     WaitDownload(File),
     AskBizHawkUpdate(reqwest::Client, Version),
     UpdateBizHawk(reqwest::Client, Version),
@@ -163,7 +177,9 @@ impl Clone for Message {
             Self::Error(e) => Self::Error(e.clone()),
             Self::CopyDebugInfo => Self::CopyDebugInfo,
             Self::Exited => Self::Exited,
-            Self::MultiworldReleaseAssets(client, asset, script) => Self::MultiworldReleaseAssets(client.clone(), asset.clone(), script.clone()),
+            Self::MultiworldReleaseAssets(client, manifest, asset, script) => Self::MultiworldReleaseAssets(client.clone(), manifest.clone(), asset.clone(), script.clone()),
+            Self::ReleaseInfo(version, notes) => Self::ReleaseInfo(version.clone(), notes.clone()),
+            Self::OpenReleaseNotesLink(url) => Self::OpenReleaseNotesLink(url.clone()),
             Self::AskBizHawkUpdate(client, version) => Self::AskBizHawkUpdate(client.clone(), version.clone()),
             Self::UpdateBizHawk(client, version) => Self::UpdateBizHawk(client.clone(), version.clone()),
             Self::BizHawkReleaseAsset(client, asset) => Self::BizHawkReleaseAsset(client.clone(), asset.clone()),
@@ -174,7 +190,7 @@ impl Clone for Message {
             Self::DiscordChannel => Self::DiscordChannel,
             Self::NewIssue => Self::NewIssue,
             Self::Cloned(debug) => Self::Cloned(debug.clone()),
-            | Self::MultiworldResponse(_, _)
+            | Self::MultiworldResponse(..)
             | Self::WaitDownload(_)
             | Self::BizHawkResponse(_)
                 => Self::Cloned(format!("{self:?}")),
@@ -193,6 +209,7 @@ fn cmd(future: impl Future<Output = Result<Message, Error>> + Send + 'static) ->
 
 enum State {
     WaitExit,
+    Rollback,
     GetMultiworldRelease,
     DownloadMultiworld,
     ExtractMultiworld,
@@ -210,33 +227,46 @@ enum State {
 
 struct App {
     args: EmuArgs,
+    rollback: bool,
+    /// The version being updated to and its release notes, once known.
+    target: Option<(Version, Vec<markdown::Item>)>,
     state: State,
 }
 
 impl App {
-    fn new(icon_error: Option<icon::Error>, args: EmuArgs) -> Self {
+    fn new(icon_error: Option<icon::Error>, args: EmuArgs, rollback: bool) -> Self {
         Self {
             state: if let Some(e) = icon_error {
                 State::Error(Arc::new(e.into()), false)
             } else {
                 State::WaitExit
             },
-            args,
+            target: None,
+            args, rollback,
         }
     }
 
-    fn title(&self) -> String { format!("updating Mido's House Multiworld…") }
+    fn title(&self) -> String {
+        if self.rollback {
+            format!("rolling back Mido's House Multiworld…")
+        } else {
+            format!("updating Mido's House Multiworld…")
+        }
+    }
 
     fn update(&mut self, msg: Message) -> Task<Message> {
         match msg {
             Message::Error(e) => self.state = State::Error(e, false),
             Message::CopyDebugInfo => if let State::Error(ref e, ref mut debug_info_copied) = self.state {
                 *debug_info_copied = true;
-                return clipboard::write(e.to_markdown())
+                return clipboard::write(e.to_markdown(self.target.as_ref().map(|(version, _)| version)))
             } else {
                 self.state = State::Error(Arc::new(Error::CopyDebugInfo), false);
             },
-            Message::Exited => {
+            Message::Exited => if self.rollback {
+                self.state = State::Rollback;
+                return cmd(rollback(self.args.clone()))
+            } else {
                 self.state = State::GetMultiworldRelease;
                 let (asset_name, script_name) = match self.args {
                     EmuArgs::EverDrive { .. } => {
@@ -250,39 +280,44 @@ impl App {
                     EmuArgs::Pj64 { .. } => ("multiworld-pj64.exe", Some("ootrmw-pj64.js")),
                 };
                 return cmd(async move {
-                    let http_client = reqwest::Client::builder()
-                        .user_agent(concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")))
-                        .use_rustls_tls()
-                        .https_only(true)
-                        .http2_prior_knowledge()
-                        .build()?;
-                    let release = Repo::new("midoshouse", "ootr-multiworld").latest_release(&http_client).await?.ok_or(Error::NoReleases)?;
+                    let http_client = http_client()?;
+                    let config = Config::load().await?;
+                    let release = Repo::new("midoshouse", "ootr-multiworld").release_for_channel(&http_client, &config.update_channel).await?.ok_or(Error::NoReleases)?;
+                    let version = release.version()?;
+                    // releases before the first signed version have no manifest, so they can't be verified and must be installed manually
+                    if version < FIRST_SIGNED_VERSION { return Err(Error::UnsignedRelease(version)) }
                     let mut asset = None;
                     let mut script = None;
+                    let mut manifest = None;
+                    let mut signature = None;
                     for iter_asset in release.assets {
                         if iter_asset.name == asset_name {
                             asset = Some(iter_asset);
                         } else if Some(&*iter_asset.name) == script_name {
                             script = Some(iter_asset);
+                        } else if iter_asset.name == MANIFEST_NAME {
+                            manifest = Some(iter_asset);
+                        } else if iter_asset.name == MANIFEST_SIGNATURE_NAME {
+                            signature = Some(iter_asset);
                         }
                     }
                     if script_name.is_some() && script.is_none() { return Err(Error::MissingAsset) }
-                    Ok(Message::MultiworldReleaseAssets(http_client, asset.ok_or(Error::MissingAsset)?, script))
+                    let (Some(manifest), Some(signature)) = (manifest, signature) else { return Err(Error::MissingManifest) };
+                    let manifest = http_client.get(manifest.browser_download_url).send().await?.error_for_status()?.bytes().await?;
+                    let signature = http_client.get(signature.browser_download_url).send().await?.error_for_status()?.bytes().await?;
+                    let manifest = ReleaseManifest::verify(&manifest, &signature, &version)?;
+                    Ok(Message::MultiworldReleaseAssets(http_client, manifest, asset.ok_or(Error::MissingAsset)?, script))
                 })
-            }
-            Message::MultiworldReleaseAssets(http_client, asset, script) => {
+            },
+            Message::MultiworldReleaseAssets(http_client, manifest, asset, script) => {
                 self.state = State::DownloadMultiworld;
                 return cmd(async move {
+                    let config = Config::load().await?;
                     if let Some(script) = script {
-                        let config = Config::load().await?;
-                        let script_path = if let Some(ref script_path) = config.pj64_script_path {
-                            script_path.clone()
-                        } else {
-                            let program_files = env::var_os("ProgramFiles(x86)").or_else(|| env::var_os("ProgramFiles")).ok_or(Error::ProgramFiles)?;
-                            PathBuf::from(program_files).join("Project64 3.0").join("Scripts").join("ootrmw.js")
-                        };
+                        let script_path = pj64_script_path(&config)?;
                         let old_script = fs::read(&script_path).await?;
                         let new_script = http_client.get(script.browser_download_url).send().await?.error_for_status()?.bytes().await?;
+                        manifest.check_asset(&script.name, &new_script)?;
                         if old_script != new_script {
                             let temp_path = tokio::task::spawn_blocking(|| tempfile::Builder::default().prefix("ootrmw-pj64").suffix(".js").tempfile()).await?.at_unknown()?;
                             io::copy_buf(&mut &*new_script, &mut tokio::fs::File::from_std(temp_path.reopen().at(&temp_path)?)).await.at(&temp_path)?;
@@ -298,16 +333,22 @@ impl App {
                             }).await??;
                         }
                     }
-                    Ok(Message::MultiworldResponse(http_client.clone(), http_client.get(asset.browser_download_url).send().await?.error_for_status()?))
+                    Ok(Message::MultiworldResponse(http_client.clone(), manifest, asset.name, http_client.get(asset.browser_download_url).send().await?.error_for_status()?))
                 })
             }
-            Message::MultiworldResponse(http_client, response) => match self.args {
+            Message::ReleaseInfo(version, notes) => self.target = Some((version, notes)),
+            Message::OpenReleaseNotesLink(url) => if let Err(e) = open(url) {
+                self.state = State::Error(Arc::new(Err::<Never, _>(e).at_unknown().never_unwrap_err().into()), false);
+            },
+            Message::MultiworldResponse(http_client, manifest, asset_name, response) => match self.args {
                 EmuArgs::BizHawk { ref path, ref local_bizhawk_version, .. } => {
                     self.state = State::ExtractMultiworld;
                     let path = path.clone();
                     let local_bizhawk_version = local_bizhawk_version.clone();
                     return cmd(async move {
-                        let mut zip_file = StreamReader::new(response.bytes_stream().map_err(io_error_from_reqwest));
+                        let data = response.bytes().await?;
+                        manifest.check_asset(&asset_name, &data)?;
+                        let mut zip_file = &*data;
                         let mut zip_file = async_zip::base::read::stream::ZipFileReader::with_tokio(&mut zip_file);
                         let mut required_bizhawk_version = None;
                         while let Some(mut entry) = zip_file.next_with_entry().await? {
@@ -330,27 +371,34 @@ impl App {
                                     fs::create_dir_all(&external_tools).await?;
                                     let mut buf = Vec::default();
                                     entry.reader_mut().read_to_end_checked(&mut buf).await?;
-                                    fs::write(external_tools.join("OotrMultiworld.dll"), &buf).await?;
+                                    let dll_path = external_tools.join("OotrMultiworld.dll");
+                                    keep_previous_version(&dll_path).await?;
+                                    fs::write(dll_path, &buf).await?;
                                 }
                                 #[cfg(target_os = "linux")] "libmultiworld.so" => {
                                     let dlls = path.join("dll");
                                     fs::create_dir_all(&dlls).await?;
                                     let mut buf = Vec::default();
                                     entry.reader_mut().read_to_end_checked(&mut buf).await?;
-                                    fs::write(dlls.join("libmultiworld.so"), &buf).await?;
+                                    let dll_path = dlls.join("libmultiworld.so");
+                                    keep_previous_version(&dll_path).await?;
+                                    fs::write(dll_path, &buf).await?;
                                 }
                                 #[cfg(target_os = "windows")] "multiworld.dll" => {
                                     let external_tools = path.join("ExternalTools"); //TODO test if placing in `dll` works, use that and clean up `ExternalTools` if it does
                                     fs::create_dir_all(&external_tools).await?;
                                     let mut buf = Vec::default();
                                     entry.reader_mut().read_to_end_checked(&mut buf).await?;
-                                    fs::write(external_tools.join("multiworld.dll"), &buf).await?;
+                                    let dll_path = external_tools.join("multiworld.dll");
+                                    keep_previous_version(&dll_path).await?;
+                                    fs::write(dll_path, &buf).await?;
                                 }
                                 _ => return Err(Error::UnexpectedZipEntry),
                             }
                             zip_file = entry.done().await?;
                         }
                         let required_bizhawk_version = required_bizhawk_version.ok_or(Error::MissingReadme)?;
+                        record_previous_version().await?;
                         match local_bizhawk_version.cmp(&required_bizhawk_version) {
                             Less => Ok(Message::AskBizHawkUpdate(http_client, required_bizhawk_version)),
                             Equal => Ok(Message::Launch),
@@ -362,11 +410,11 @@ impl App {
                     self.state = State::Replace;
                     let path = path.clone();
                     return cmd(async move {
-                        let mut data = response.bytes_stream();
+                        let data = response.bytes().await?;
+                        manifest.check_asset(&asset_name, &data)?;
+                        keep_previous_version(&path).await?;
                         let mut exe_file = File::create(&path).await?;
-                        while let Some(chunk) = data.try_next().await? {
-                            exe_file.write_all(chunk.as_ref()).await.at(&path)?;
-                        }
+                        exe_file.write_all(&data).await.at(&path)?;
                         Ok(Message::WaitDownload(exe_file))
                     })
                 }
@@ -375,6 +423,7 @@ impl App {
                 self.state = State::WaitDownload;
                 return cmd(async move {
                     exe_file.sync_all().await?;
+                    record_previous_version().await?;
                     Ok(Message::Launch)
                 })
             }
@@ -468,7 +517,7 @@ impl App {
                     Ok(issue_url) => issue_url,
                     Err(e) => return cmd(future::err(e.into())),
                 };
-                issue_url.query_pairs_mut().append_pair("body", &e.to_markdown());
+                issue_url.query_pairs_mut().append_pair("body", &e.to_markdown(self.target.as_ref().map(|(version, _)| version)));
                 if let Err(e) = open(issue_url.to_string()) {
                     self.state = State::Error(Arc::new(Err::<Never, _>(e).at_unknown().never_unwrap_err().into()), false);
                 }
@@ -482,30 +531,45 @@ impl App {
 
     fn view(&self) -> Element<'_, Message> {
         match self.state {
-            State::WaitExit => match self.args {
-                EmuArgs::BizHawk { .. } => Column::new()
-                    .push("An update for Mido's House Multiworld for BizHawk is available.")
-                    .push("Please close BizHawk to start the update.")
-                    .push(Space::default().height(Length::Fill))
-                    .push(Text::new(format!("old version: {}{}", env!("CARGO_PKG_VERSION"), {
-                        #[cfg(debug_assertions)] { " (debug)" }
-                        #[cfg(not(debug_assertions))] { "" }
-                    }))) //TODO also show new version
-                    .spacing(8)
-                    .padding(8)
-                    .into(),
-                EmuArgs::EverDrive { .. } | EmuArgs::Pj64 { .. } => Column::new()
-                    .push("An update for Mido's House Multiworld is available.")
-                    .push("Waiting to make sure the old version has exited…")
-                    .push(Space::default().height(Length::Fill))
+            State::WaitExit if self.rollback => Column::new()
+                .push("Rolling back Mido's House Multiworld to the previous version.")
+                .push(match self.args {
+                    EmuArgs::BizHawk { .. } => "Please close BizHawk to start the rollback.",
+                    EmuArgs::EverDrive { .. } | EmuArgs::Pj64 { .. } => "Waiting to make sure the current version has exited…",
+                })
+                .push(Space::default().height(Length::Fill))
+                .push(Text::new(format!("current version: {}{}", env!("CARGO_PKG_VERSION"), {
+                    #[cfg(debug_assertions)] { " (debug)" }
+                    #[cfg(not(debug_assertions))] { "" }
+                })))
+                .spacing(8)
+                .padding(8)
+                .into(),
+            State::WaitExit => {
+                let mut col = match self.args {
+                    EmuArgs::BizHawk { .. } => Column::new()
+                        .push("An update for Mido's House Multiworld for BizHawk is available.")
+                        .push("Please close BizHawk to start the update."),
+                    EmuArgs::EverDrive { .. } | EmuArgs::Pj64 { .. } => Column::new()
+                        .push("An update for Mido's House Multiworld is available.")
+                        .push("Waiting to make sure the old version has exited…"),
+                };
+                col = if let Some((_, ref notes)) = self.target {
+                    col.push(Scrollable::new(markdown::view(notes, &wheel::gui::theme().unwrap_or_default()).map(|url| Message::OpenReleaseNotesLink(url.to_string()))).height(Length::Fill))
+                } else {
+                    col.push(Space::default().height(Length::Fill))
+                };
+                col
                     .push(Text::new(format!("old version: {}{}", env!("CARGO_PKG_VERSION"), {
                         #[cfg(debug_assertions)] { " (debug)" }
                         #[cfg(not(debug_assertions))] { "" }
-                    }))) //TODO also show new version
+                    })))
+                    .push(Text::new(if let Some((ref version, _)) = self.target { format!("new version: {version}") } else { format!("new version: checking…") }))
                     .spacing(8)
                     .padding(8)
-                    .into(),
-            },
+                    .into()
+            }
+            State::Rollback => Column::new().push("Restoring previous version…").spacing(8).padding(8).into(),
             State::GetMultiworldRelease => Column::new().push("Checking latest release…").spacing(8).padding(8).into(),
             State::DownloadMultiworld => Column::new().push("Starting download…").spacing(8).padding(8).into(),
             State::ExtractMultiworld => Column::new().push("Downloading and extracting multiworld…").spacing(8).padding(8).into(),
@@ -560,7 +624,106 @@ impl App {
     }
 }
 
+fn http_client() -> reqwest::Result<reqwest::Client> {
+    reqwest::Client::builder()
+        .user_agent(concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")))
+        .use_rustls_tls()
+        .https_only(true)
+        .http2_prior_knowledge()
+        .build()
+}
+
+/// Looks up the version the update will install so it can be shown along with its release notes while waiting for the old version to exit.
+async fn release_info() -> Result<Message, Error> {
+    let http_client = http_client()?;
+    let config = Config::load().await?;
+    let repo = Repo::new("midoshouse", "ootr-multiworld");
+    let release = repo.release_for_channel(&http_client, &config.update_channel).await?.ok_or(Error::NoReleases)?;
+    let version = release.version()?;
+    // the release notes are only informational, so failing to load them shouldn't prevent the update
+    let notes = match repo.release_notes(&http_client, &Version::parse(env!("CARGO_PKG_VERSION"))?, &version).await {
+        Ok(notes) => markdown::parse(&notes).collect(),
+        Err(e) => markdown::parse(&format!("*The release notes could not be loaded: {e}*")).collect(),
+    };
+    Ok(Message::ReleaseInfo(version, notes))
+}
+
+fn pj64_script_path(config: &Config) -> Result<PathBuf, Error> {
+    Ok(if let Some(ref script_path) = config.pj64_script_path {
+        script_path.clone()
+    } else {
+        let program_files = env::var_os("ProgramFiles(x86)").or_else(|| env::var_os("ProgramFiles")).ok_or(Error::ProgramFiles)?;
+        PathBuf::from(program_files).join("Project64 3.0").join("Scripts").join("ootrmw.js")
+    })
+}
+
+/// The path where the previous version of the given file is kept for rollbacks.
+fn previous_version_path(path: &Path) -> PathBuf {
+    let mut file_name = path.file_name().map(OsStr::to_owned).unwrap_or_default();
+    file_name.push(".previous");
+    path.with_file_name(file_name)
+}
+
+async fn keep_previous_version(path: &Path) -> wheel::Result {
+    if fs::exists(path).await? {
+        fs::copy(path, previous_version_path(path)).await?;
+    }
+    Ok(())
+}
+
+async fn restore_previous_version(path: &Path) -> Result<(), Error> {
+    let previous_path = previous_version_path(path);
+    if !fs::exists(&previous_path).await? { return Err(Error::NoPreviousVersion) }
+    fs::copy(previous_path, path).await?;
+    Ok(())
+}
+
+/// Records the running version as the one to roll back to and resumes automatic updates.
+///
+/// Only called once all files of the new version are in place, so that a failed update doesn't offer a rollback to a version whose files weren't kept.
+async fn record_previous_version() -> Result<(), Error> {
+    let mut config = Config::load().await?;
+    config.previous_version = Some(env!("CARGO_PKG_VERSION").parse()?);
+    config.auto_update_paused = false;
+    config.save().await?;
+    Ok(())
+}
+
+/// Restores the files kept by the last update and pauses automatic updates until the user updates manually.
+///
+/// Note that BizHawk itself is not rolled back if the update also updated BizHawk.
+async fn rollback(args: EmuArgs) -> Result<Message, Error> {
+    let mut config = Config::load().await?;
+    match args {
+        EmuArgs::EverDrive { path, .. } => restore_previous_version(&path).await?,
+        EmuArgs::BizHawk { path, .. } => {
+            restore_previous_version(&path.join("ExternalTools").join("OotrMultiworld.dll")).await?;
+            #[cfg(target_os = "linux")] restore_previous_version(&path.join("dll").join("libmultiworld.so")).await?;
+            #[cfg(target_os = "windows")] restore_previous_version(&path.join("ExternalTools").join("multiworld.dll")).await?;
+        }
+        EmuArgs::Pj64 { path, .. } => {
+            restore_previous_version(&path).await?;
+            let script_path = pj64_script_path(&config)?;
+            if fs::exists(previous_version_path(&script_path)).await? {
+                tokio::task::spawn_blocking(move || {
+                    if let Err(source) = runas::Command::new(env::current_exe().at_unknown()?).arg("pj64scriptrollback").arg(&script_path).gui(true).status().at_command("runas")?.check("runas") {
+                        return Err(Error::Pj64ScriptRollback { script_path, source })
+                    }
+                    Ok(())
+                }).await??;
+            }
+        }
+    }
+    config.previous_version = None;
+    config.auto_update_paused = true;
+    config.save().await?;
+    Ok(Message::Launch)
+}
+
 fn pj64script(src: &Path, dst: &Path) -> wheel::Result {
+    if dst.exists() {
+        std::fs::copy(dst, previous_version_path(dst)).at2(dst, previous_version_path(dst))?;
+    }
     let is_same_drive = {
         #[cfg(windows)] {
             src.components().find_map(|component| if let std::path::Component::Prefix(prefix) = component { Some(prefix) } else { None })
@@ -577,6 +740,12 @@ fn pj64script(src: &Path, dst: &Path) -> wheel::Result {
     Ok(())
 }
 
+fn pj64script_rollback(path: &Path) -> wheel::Result {
+    let previous_path = previous_version_path(path);
+    std::fs::copy(&previous_path, path).at2(&previous_path, path)?;
+    Ok(())
+}
+
 #[derive(Clone, clap::Subcommand)]
 #[clap(rename_all = "lower")]
 enum EmuArgs {
@@ -605,6 +774,14 @@ enum Args {
         src: PathBuf,
         dst: PathBuf,
     },
+    Pj64ScriptRollback {
+        path: PathBuf,
+    },
+    /// Restore the version that was installed before the last update
+    Rollback {
+        #[clap(subcommand)]
+        emu: EmuArgs,
+    },
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -617,63 +794,77 @@ enum MainError {
     MissingHomeDir,
 }
 
-#[wheel::main]
-fn main(args: Args) -> Result<(), MainError> {
+fn gui(args: EmuArgs, rollback: bool) -> iced::Result {
     fn theme(_: &App) -> Option<Theme> { wheel::gui::theme() }
 
-    let _ = rustls::crypto::ring::default_provider().install_default();
-    match args {
-        Args::Emu(args) => {
-            // RefCell as workaround for https://github.com/iced-rs/iced/issues/3080
-            let (icon, icon_error) = match icon::from_file_data(include_bytes!("../../../assets/icon.ico"), Some(ImageFormat::Ico)) {
-                Ok(icon) => (Some(icon), RefCell::new(None)),
-                Err(e) => (None, RefCell::new(Some(e))),
-            };
-            iced::application(move || {
-                let cmd_args = args.clone();
-                (
-                    App::new(icon_error.borrow_mut().take(), args.clone()),
-                    cmd(async move {
-                        let mut system = sysinfo::System::default();
-                        match cmd_args {
-                            EmuArgs::BizHawk { mw_pid, bizhawk_pid, .. } => {
-                                while system.refresh_processes_specifics(ProcessesToUpdate::Some(&[mw_pid, bizhawk_pid]), true, ProcessRefreshKind::default()) > 0 {
-                                    sleep(Duration::from_secs(1)).await;
-                                }
-                            }
-                            EmuArgs::EverDrive { pid, .. } | EmuArgs::Pj64 { pid, .. } => {
-                                while system.refresh_processes_specifics(ProcessesToUpdate::Some(&[pid]), true, ProcessRefreshKind::default()) > 0 {
-                                    sleep(Duration::from_secs(1)).await;
-                                }
-                            }
-                        }
-                        Ok(Message::Exited)
-                    }),
-                )
-            }, App::update, App::view)
-                .title(App::title)
-                .window(window::Settings {
-                    size: Size { width: 320.0, height: 240.0 },
-                    icon,
-                    ..window::Settings::default()
-                })
-                .theme(theme)
-                .run()?;
-        }
-        Args::Pj64Script { src, dst } => if let Err(e) = pj64script(&src, &dst) {
-            if Config::blocking_load()?.log {
-                let path = {
-                    #[cfg(unix)] {
-                        BaseDirectories::new().place_data_file("midos-house/multiworld-updater.log").expect("failed to create log dir")
+    // RefCell as workaround for https://github.com/iced-rs/iced/issues/3080
+    let (icon, icon_error) = match icon::from_file_data(include_bytes!("../../../assets/icon.ico"), Some(ImageFormat::Ico)) {
+        Ok(icon) => (Some(icon), RefCell::new(None)),
+        Err(e) => (None, RefCell::new(Some(e))),
+    };
+    iced::application(move || {
+        let cmd_args = args.clone();
+        let wait_exit = cmd(async move {
+            let mut system = sysinfo::System::default();
+            match cmd_args {
+                EmuArgs::BizHawk { mw_pid, bizhawk_pid, .. } => {
+                    while system.refresh_processes_specifics(ProcessesToUpdate::Some(&[mw_pid, bizhawk_pid]), true, ProcessRefreshKind::default()) > 0 {
+                        sleep(Duration::from_secs(1)).await;
                     }
-                    #[cfg(windows)] {
-                        let project_dirs = ProjectDirs::from("net", "Fenhl", "OoTR Multiworld").ok_or(MainError::MissingHomeDir)?;
-                        std::fs::create_dir_all(project_dirs.data_dir()).at(project_dirs.data_dir())?;
-                        project_dirs.data_dir().join("updater.log")
+                }
+                EmuArgs::EverDrive { pid, .. } | EmuArgs::Pj64 { pid, .. } => {
+                    while system.refresh_processes_specifics(ProcessesToUpdate::Some(&[pid]), true, ProcessRefreshKind::default()) > 0 {
+                        sleep(Duration::from_secs(1)).await;
                     }
-                };
-                write!(std::fs::File::create(&path).at(&path)?, "{} error in pj64script subcommand: {e}\ndebug info: {e:?}", Utc::now().format("%Y-%m-%d %H:%M:%S")).at(path)?;
+                }
+            }
+            Ok(Message::Exited)
+        });
+        (
+            App::new(icon_error.borrow_mut().take(), args.clone(), rollback),
+            if rollback { wait_exit } else { Task::batch([wait_exit, cmd(release_info())]) },
+        )
+    }, App::update, App::view)
+        .title(App::title)
+        .window(window::Settings {
+            size: Size { width: 400.0, height: 360.0 },
+            icon,
+            ..window::Settings::default()
+        })
+        .theme(theme)
+        .run()
+}
+
+/// The elevated subcommands have no GUI, so errors are written to the log file if logging is enabled.
+fn log_subcommand_error(subcommand: &str, e: &wheel::Error) -> Result<(), MainError> {
+    if Config::blocking_load()?.log {
+        let path = {
+            #[cfg(unix)] {
+                BaseDirectories::new().place_data_file("midos-house/multiworld-updater.log").expect("failed to create log dir")
+            }
+            #[cfg(windows)] {
+                let project_dirs = ProjectDirs::from("net", "Fenhl", "OoTR Multiworld").ok_or(MainError::MissingHomeDir)?;
+                std::fs::create_dir_all(project_dirs.data_dir()).at(project_dirs.data_dir())?;
+                project_dirs.data_dir().join("updater.log")
             }
+        };
+        write!(std::fs::File::create(&path).at(&path)?, "{} error in {subcommand} subcommand: {e}\ndebug info: {e:?}", Utc::now().format("%Y-%m-%d %H:%M:%S")).at(path)?;
+    }
+    Ok(())
+}
+
+#[wheel::main]
+fn main(args: Args) -> Result<(), MainError> {
+    let _ = rustls::crypto::ring::default_provider().install_default();
+    match args {
+        Args::Emu(args) => gui(args, false)?,
+        Args::Rollback { emu } => gui(emu, true)?,
+        Args::Pj64Script { src, dst } => if let Err(e) = pj64script(&src, &dst) {
+            log_subcommand_error("pj64script", &e)?;
+            return Err(MainError::Wheel(e))
+        },
+        Args::Pj64ScriptRollback { path } => if let Err(e) = pj64script_rollback(&path) {
+            log_subcommand_error("pj64scriptrollback", &e)?;
             return Err(MainError::Wheel(e))
         },
     }
@@ -0,0 +1,512 @@
+//! The logic connecting a frontend to a room, shared by the GUI (including its additional frontends) and `multiworld-headless`.
+//!
+//! A [`Bridge`] keeps track of what the frontend has reported and decides which messages to send in response to each message from the frontend or the server.
+//! It doesn't do any I/O itself. Instead, it returns [`Effect`]s which are carried out in order using [`run`].
+
+use {
+    std::{
+        collections::HashMap,
+        num::NonZeroU8,
+    },
+    async_trait::async_trait,
+    ootr::model::{
+        DungeonReward,
+        Medallion,
+        Stone,
+    },
+    ootr_utils::spoiler::HashIcon,
+    tokio::sync::mpsc,
+    url::Url,
+    crate::{
+        Filename,
+        HintArea,
+        Player,
+        SessionState,
+        client::{
+            self,
+            Client,
+        },
+        config::server_key,
+        frontend,
+        persistent_state::{
+            self,
+            PendingItem,
+            PersistentState,
+        },
+        save_diff,
+        ws::{
+            ServerError,
+            latest::{
+                ClientMessage,
+                ServerMessage,
+            },
+        },
+    },
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error(transparent)] SaveDiff(#[from] save_diff::Error),
+    #[error("the frontend sent a save data diff without sending save data first")]
+    SaveDataDiffWithoutBase,
+}
+
+/// Something to be done in response to a message, returned by the methods of [`Bridge`].
+#[derive(Debug)]
+pub enum Effect {
+    Server(ClientMessage),
+    Frontend(frontend::ServerMessage),
+    /// The frontend sent an item while not in a room. It's kept in the persistent state until the room is entered again.
+    HoldItem(PendingItem),
+    /// The frontend reported save data, so the items it sent that are held so far were sent before the last save.
+    Saved {
+        world: Option<NonZeroU8>,
+        hash: Option<Option<[HashIcon; 5]>>,
+    },
+    /// A room was entered. The items held for it are taken from the persistent state and sent to the server, those held since the last save after the messages in `between`.
+    SendHeldItems {
+        server: String,
+        room_id: u64,
+        world: Option<NonZeroU8>,
+        hash: Option<Option<[HashIcon; 5]>>,
+        between: Vec<ClientMessage>,
+    },
+}
+
+/// The room a [`Bridge`]'s server connection is in.
+#[derive(Debug, Clone, Copy)]
+pub struct Room<'a> {
+    pub id: u64,
+    pub name: &'a str,
+    pub players: &'a [Player],
+    pub item_queue: &'a [u16],
+    /// Whether items are exchanged with the frontend, i.e. the file hash matches the room and the world isn't taken by another player.
+    pub active: bool,
+}
+
+impl<'a> Room<'a> {
+    pub fn of<E>(session_state: &'a SessionState<E>) -> Option<Self> {
+        if let SessionState::Room { room_id, ref room_name, ref players, ref item_queue, wrong_file_hash, world_taken, .. } = *session_state {
+            Some(Self {
+                id: room_id,
+                name: room_name,
+                active: wrong_file_hash.is_none() && world_taken.is_none(),
+                players, item_queue,
+            })
+        } else {
+            None
+        }
+    }
+}
+
+/// What a frontend has reported, which has to survive reconnecting to the frontend or the server.
+#[derive(Default)]
+pub struct Bridge {
+    /// The protocol features supported by the frontend, or `None` while no frontend is connected.
+    frontend_capabilities: Option<frontend::Capabilities>,
+    last_world: Option<NonZeroU8>,
+    last_name: Filename,
+    last_hash: Option<Option<[HashIcon; 5]>>,
+    last_save: Option<oottracker::Save>,
+    /// The raw save data last received from the frontend, which [`frontend::ClientMessage::SaveDataDiff`] applies to.
+    frontend_save_data: Option<Box<[u8; oottracker::save::SIZE]>>,
+    /// The raw save data last sent to the server in the current room session, which [`ClientMessage::SaveDataDiff`] applies to.
+    server_save_data: Option<Box<[u8; oottracker::save::SIZE]>>,
+    last_dungeon_reward_locations: HashMap<DungeonReward, (NonZeroU8, HintArea)>,
+    /// The ID and name of the room that was entered last, recorded with items held while not in a room.
+    last_room: Option<(u64, String)>,
+}
+
+impl Bridge {
+    pub fn world(&self) -> Option<NonZeroU8> {
+        self.last_world
+    }
+
+    pub fn name(&self) -> Filename {
+        self.last_name
+    }
+
+    /// `Some(None)` if the frontend reported that the file hash is unknown.
+    pub fn hash(&self) -> Option<Option<[HashIcon; 5]>> {
+        self.last_hash
+    }
+
+    /// Messages to the frontend are only returned while one is connected.
+    fn to_frontend(&self, effects: &mut Vec<Effect>, msg: frontend::ServerMessage) {
+        if self.frontend_capabilities.is_some() {
+            effects.push(Effect::Frontend(msg));
+        }
+    }
+
+    fn player_names(&self, effects: &mut Vec<Effect>, players: &[Player]) {
+        for player in players {
+            self.to_frontend(effects, frontend::ServerMessage::PlayerName(player.world, if player.name == Filename::default() {
+                Filename::fallback(player.world)
+            } else {
+                player.name
+            }));
+        }
+    }
+
+    pub fn frontend_connected(&mut self, capabilities: frontend::Capabilities, room: Option<Room<'_>>) -> Vec<Effect> {
+        self.frontend_capabilities = Some(capabilities);
+        self.frontend_save_data = None;
+        let mut effects = Vec::default();
+        if let Some(room) = room {
+            self.player_names(&mut effects, room.players);
+            if room.active {
+                self.to_frontend(&mut effects, frontend::ServerMessage::ItemQueue(room.item_queue.to_owned()));
+            }
+        }
+        effects
+    }
+
+    pub fn frontend_disconnected(&mut self) {
+        self.frontend_capabilities = None;
+    }
+
+    fn save_data(&mut self, in_room: bool, save_data: Box<[u8; oottracker::save::SIZE]>) -> Vec<Effect> {
+        let mut effects = Vec::default();
+        match oottracker::Save::from_save_data(&*save_data) {
+            Ok(save) => {
+                self.last_save = Some(save.clone());
+                effects.push(Effect::Saved { world: self.last_world, hash: self.last_hash });
+                if in_room {
+                    match self.server_save_data.replace(save_data.clone()) {
+                        Some(old_save_data) => {
+                            let diff = save_diff::diff(&old_save_data, &save_data);
+                            if !diff.is_empty() {
+                                effects.push(Effect::Server(ClientMessage::SaveDataDiff(diff)));
+                            }
+                        }
+                        None => effects.push(Effect::Server(ClientMessage::SaveData(save))),
+                    }
+                }
+            }
+            Err(e) => effects.push(Effect::Server(ClientMessage::SaveDataError { debug: format!("{e:?}"), version: crate::version() })),
+        }
+        self.frontend_save_data = Some(save_data);
+        effects
+    }
+
+    /// `websocket_url` is the URL of the server the items sent while not in a room are held for.
+    pub fn frontend_message(&mut self, websocket_url: &Url, room: Option<Room<'_>>, msg: frontend::ClientMessage) -> Result<Vec<Effect>, Error> {
+        let in_room = room.is_some();
+        let mut effects = Vec::default();
+        match msg {
+            frontend::ClientMessage::PlayerId(new_player_id) => {
+                let is_new = self.last_world.replace(new_player_id).is_none();
+                if in_room {
+                    effects.push(Effect::Server(ClientMessage::PlayerId(new_player_id)));
+                    if is_new {
+                        if self.last_name != Filename::default() {
+                            effects.push(Effect::Server(ClientMessage::PlayerName(self.last_name)));
+                        }
+                        if let Some(hash) = self.last_hash {
+                            effects.push(Effect::Server(ClientMessage::FileHash(hash)));
+                        }
+                    }
+                }
+            }
+            frontend::ClientMessage::PlayerName(new_player_name) => {
+                self.last_name = new_player_name;
+                if in_room && self.last_world.is_some() {
+                    effects.push(Effect::Server(ClientMessage::PlayerName(new_player_name)));
+                }
+            }
+            frontend::ClientMessage::SendItem { key, kind, target_world } => if in_room {
+                effects.push(Effect::Server(ClientMessage::SendItem { key, kind, target_world }));
+            } else if let Some(hash) = self.last_hash {
+                effects.push(Effect::HoldItem(PendingItem {
+                    server: Some(server_key(websocket_url)),
+                    room: self.last_room.clone(),
+                    world: self.last_world,
+                    hash, key, kind, target_world,
+                }));
+            },
+            frontend::ClientMessage::SaveData(save_data) => effects = self.save_data(in_room, Box::new(save_data)),
+            frontend::ClientMessage::SaveDataDiff(diff) => {
+                let mut save_data = self.frontend_save_data.clone().ok_or(Error::SaveDataDiffWithoutBase)?;
+                save_diff::apply(&mut save_data, &diff)?;
+                effects = self.save_data(in_room, save_data);
+            }
+            frontend::ClientMessage::FileHash(new_hash) => {
+                self.last_hash = Some(new_hash);
+                if in_room && self.last_world.is_some() {
+                    effects.push(Effect::Server(ClientMessage::FileHash(new_hash)));
+                }
+            }
+            frontend::ClientMessage::ResetPlayerId => {
+                self.last_world = None;
+                if in_room {
+                    effects.push(Effect::Server(ClientMessage::ResetPlayerId));
+                }
+            }
+            frontend::ClientMessage::DungeonRewardInfo { emerald, ruby, sapphire, light, forest, fire, water, shadow, spirit } => for (reward, location) in [
+                (DungeonReward::Stone(Stone::KokiriEmerald), emerald),
+                (DungeonReward::Stone(Stone::GoronRuby), ruby),
+                (DungeonReward::Stone(Stone::ZoraSapphire), sapphire),
+                (DungeonReward::Medallion(Medallion::Light), light),
+                (DungeonReward::Medallion(Medallion::Forest), forest),
+                (DungeonReward::Medallion(Medallion::Fire), fire),
+                (DungeonReward::Medallion(Medallion::Water), water),
+                (DungeonReward::Medallion(Medallion::Shadow), shadow),
+                (DungeonReward::Medallion(Medallion::Spirit), spirit),
+            ] {
+                if let Some((world, area)) = location {
+                    if self.last_dungeon_reward_locations.insert(reward, (world, area)) != Some((world, area)) && in_room {
+                        effects.push(Effect::Server(ClientMessage::DungeonRewardInfo { reward, world, area }));
+                    }
+                }
+            },
+            frontend::ClientMessage::CurrentScene(scene) => if in_room {
+                effects.push(Effect::Server(ClientMessage::CurrentScene(scene)));
+            },
+        }
+        Ok(effects)
+    }
+
+    /// Must be called after `msg` has been applied to the session state `room` was taken from.
+    ///
+    /// Logging in and joining a room is left to the caller since it differs between the GUI, its additional frontends, and `multiworld-headless`.
+    pub fn server_message(&mut self, websocket_url: &Url, room: Option<Room<'_>>, msg: ServerMessage) -> Vec<Effect> {
+        let mut effects = Vec::default();
+        match msg {
+            ServerMessage::EnterLobby { .. } => self.to_frontend(&mut effects, frontend::ServerMessage::ItemQueue(Vec::default())),
+            ServerMessage::EnterRoom { room_id, players, .. } => {
+                self.last_room = Some((room_id, room.map(|room| room.name.to_owned()).unwrap_or_default()));
+                self.server_save_data = None;
+                if let Some(world) = self.last_world {
+                    effects.push(Effect::Server(ClientMessage::PlayerId(world)));
+                    if self.last_name != Filename::default() {
+                        effects.push(Effect::Server(ClientMessage::PlayerName(self.last_name)));
+                    }
+                    if let Some(hash) = self.last_hash {
+                        effects.push(Effect::Server(ClientMessage::FileHash(hash)));
+                    }
+                }
+                let mut between = Vec::default();
+                if let Some(ref save) = self.last_save {
+                    between.push(ClientMessage::SaveData(save.clone()));
+                }
+                for (&reward, &(world, area)) in &self.last_dungeon_reward_locations {
+                    between.push(ClientMessage::DungeonRewardInfo { reward, world, area });
+                }
+                effects.push(Effect::SendHeldItems {
+                    server: server_key(websocket_url),
+                    world: self.last_world,
+                    hash: self.last_hash,
+                    room_id, between,
+                });
+                self.player_names(&mut effects, &players);
+            }
+            ServerMessage::StructuredError(ServerError::SaveDataDiffFailed) => if room.is_some() {
+                // the server discarded the save data our diffs apply to, so send it in full until it has a new base
+                self.server_save_data = None;
+                if let Some(ref save) = self.last_save {
+                    effects.push(Effect::Server(ClientMessage::SaveData(save.clone())));
+                }
+            },
+            ServerMessage::PlayerName(world, name) => self.to_frontend(&mut effects, frontend::ServerMessage::PlayerName(world, name)),
+            ServerMessage::ItemQueue(queue) => if room.is_some_and(|room| room.active) {
+                self.to_frontend(&mut effects, frontend::ServerMessage::ItemQueue(queue));
+            },
+            ServerMessage::GetItem(item) => if let Some(room) = room.filter(|room| room.active) {
+                if self.frontend_capabilities.is_some_and(|capabilities| capabilities.contains(frontend::Capabilities::DELTA_QUEUES)) {
+                    self.to_frontend(&mut effects, frontend::ServerMessage::GetItem(item));
+                } else {
+                    self.to_frontend(&mut effects, frontend::ServerMessage::ItemQueue(room.item_queue.to_owned()));
+                }
+            },
+            ServerMessage::ProgressiveItems { world, state } => if room.is_some_and(|room| room.active) && self.frontend_capabilities.is_some_and(|capabilities| capabilities.contains(frontend::Capabilities::PROGRESSIVE_ITEMS)) {
+                self.to_frontend(&mut effects, frontend::ServerMessage::ProgressiveItems(world, state));
+            },
+            _ => {}
+        }
+        effects
+    }
+}
+
+/// A connection to the server which [`Effect::Server`] messages can be sent on.
+#[async_trait]
+pub trait ServerSink: Sync {
+    type Error: Send;
+
+    async fn send_server(&self, msg: ClientMessage) -> Result<(), Self::Error>;
+}
+
+#[async_trait]
+impl ServerSink for Client {
+    type Error = client::Error;
+
+    async fn send_server(&self, msg: ClientMessage) -> Result<(), client::Error> {
+        self.send(msg).await
+    }
+}
+
+/// A connection to a frontend which [`Effect::Frontend`] messages can be sent on.
+#[async_trait]
+pub trait FrontendSink: Sync {
+    type Error: Send;
+
+    async fn send_frontend(&self, msg: frontend::ServerMessage) -> Result<(), Self::Error>;
+}
+
+/// Frontends which are built into the app, like [`crate::retroarch`], receive messages through a channel.
+#[async_trait]
+impl FrontendSink for mpsc::Sender<frontend::ServerMessage> {
+    type Error = mpsc::error::SendError<frontend::ServerMessage>;
+
+    async fn send_frontend(&self, msg: frontend::ServerMessage) -> Result<(), Self::Error> {
+        self.send(msg).await
+    }
+}
+
+/// Which part of [`run`] failed, so that frontend errors don't end the server session and vice versa.
+#[derive(Debug, thiserror::Error)]
+pub enum RunError<S, F> {
+    #[error(transparent)] PersistentState(#[from] persistent_state::Error),
+    #[error(transparent)] Server(S),
+    #[error(transparent)] Frontend(F),
+}
+
+/// Carries out the given effects in order.
+///
+/// Messages for the server or the frontend are dropped if the respective connection is `None`. Held items are only taken from the persistent state while connected to the server.
+pub async fn run<S: ServerSink + ?Sized, F: FrontendSink + ?Sized>(effects: Vec<Effect>, server: Option<&S>, frontend: Option<&F>, persistent_state: &PersistentState) -> Result<(), RunError<S::Error, F::Error>> {
+    for effect in effects {
+        match effect {
+            Effect::Server(msg) => if let Some(server) = server {
+                server.send_server(msg).await.map_err(RunError::Server)?;
+            },
+            Effect::Frontend(msg) => if let Some(frontend) = frontend {
+                frontend.send_frontend(msg).await.map_err(RunError::Frontend)?;
+            },
+            Effect::HoldItem(item) => persistent_state.edit(move |state| state.pending_items_after_save.push(item)).await?,
+            Effect::Saved { world, hash } => persistent_state.edit(|state| state.saved(world, hash)).await?,
+            Effect::SendHeldItems { server: server_key, room_id, world, hash, between } => if let Some(server) = server {
+                let (before_save, after_save) = persistent_state.edit(|state| state.take_for_room(&server_key, room_id, world, hash)).await?;
+                for PendingItem { key, kind, target_world, .. } in before_save {
+                    server.send_server(ClientMessage::SendItem { key, kind, target_world }).await.map_err(RunError::Server)?;
+                }
+                for msg in between {
+                    server.send_server(msg).await.map_err(RunError::Server)?;
+                }
+                for PendingItem { key, kind, target_world, .. } in after_save {
+                    server.send_server(ClientMessage::SendItem { key, kind, target_world }).await.map_err(RunError::Server)?;
+                }
+            },
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn world(world: u8) -> NonZeroU8 {
+        NonZeroU8::new(world).expect("world numbers are nonzero")
+    }
+
+    fn url() -> Url {
+        Url::parse("wss://mw.example.com/v18").expect("valid URL")
+    }
+
+    fn room<'a>(players: &'a [Player], item_queue: &'a [u16]) -> Room<'a> {
+        Room { id: 1, name: "test", active: true, players, item_queue }
+    }
+
+    fn frontend_message(bridge: &mut Bridge, room: Option<Room<'_>>, msg: frontend::ClientMessage) -> Vec<Effect> {
+        bridge.frontend_message(&url(), room, msg).expect("frontend message rejected")
+    }
+
+    const HASH: [HashIcon; 5] = [HashIcon::DekuStick, HashIcon::DekuNut, HashIcon::Bow, HashIcon::Slingshot, HashIcon::FairyOcarina];
+
+    #[test]
+    fn hold_items_outside_room() {
+        let mut bridge = Bridge::default();
+        assert!(frontend_message(&mut bridge, None, frontend::ClientMessage::PlayerId(world(2))).is_empty());
+        assert!(frontend_message(&mut bridge, None, frontend::ClientMessage::FileHash(Some(HASH))).is_empty());
+        let effects = frontend_message(&mut bridge, None, frontend::ClientMessage::SendItem { key: 42, kind: 0x0001, target_world: world(3) });
+        assert!(matches!(&effects[..], [Effect::HoldItem(PendingItem { server: Some(server), room: None, world: Some(sender), hash: Some(hash), key: 42, kind: 0x0001, target_world })] if server == "mw.example.com" && *sender == world(2) && *hash == HASH && *target_world == world(3)));
+        let effects = frontend_message(&mut bridge, Some(room(&[], &[])), frontend::ClientMessage::SendItem { key: 43, kind: 0x0001, target_world: world(3) });
+        assert!(matches!(&effects[..], [Effect::Server(ClientMessage::SendItem { key: 43, .. })]));
+    }
+
+    #[test]
+    fn enter_room() {
+        let mut bridge = Bridge::default();
+        bridge.frontend_connected(frontend::Capabilities::all(), None);
+        frontend_message(&mut bridge, None, frontend::ClientMessage::PlayerId(world(2)));
+        frontend_message(&mut bridge, None, frontend::ClientMessage::FileHash(Some(HASH)));
+        let players = [Player::new(world(1))];
+        let effects = bridge.server_message(&url(), Some(room(&players, &[])), ServerMessage::EnterRoom {
+            room_id: 1,
+            players: players.to_vec(),
+            num_unassigned_clients: 0,
+            autodelete_delta: std::time::Duration::from_secs(60 * 60 * 24),
+            allow_send_all: true,
+            completions: Default::default(),
+            triforce_goal: None,
+            triforce_pieces: Default::default(),
+            start: crate::RoomStart::Started,
+            frozen: false,
+            can_moderate: false,
+        });
+        assert!(matches!(&effects[..], [
+            Effect::Server(ClientMessage::PlayerId(player_id)),
+            Effect::Server(ClientMessage::FileHash(Some(hash))),
+            Effect::SendHeldItems { server, room_id: 1, world: Some(held_world), hash: Some(Some(held_hash)), between },
+            Effect::Frontend(frontend::ServerMessage::PlayerName(name_world, name)),
+        ] if *player_id == world(2) && *hash == HASH && server == "mw.example.com" && *held_world == world(2) && *held_hash == HASH && between.is_empty() && *name_world == world(1) && *name == Filename::fallback(world(1))));
+    }
+
+    #[test]
+    fn current_scene_only_in_room() {
+        let mut bridge = Bridge::default();
+        assert!(frontend_message(&mut bridge, None, frontend::ClientMessage::CurrentScene(0x51)).is_empty());
+        let effects = frontend_message(&mut bridge, Some(room(&[], &[])), frontend::ClientMessage::CurrentScene(0x51));
+        assert!(matches!(&effects[..], [Effect::Server(ClientMessage::CurrentScene(0x51))]));
+    }
+
+    #[test]
+    fn get_item_without_delta_queues() {
+        let mut bridge = Bridge::default();
+        let queue = [0x0001, 0x0002];
+        assert!(bridge.server_message(&url(), Some(room(&[], &queue)), ServerMessage::GetItem(0x0002)).is_empty(), "messages for the frontend must not be returned while no frontend is connected");
+        bridge.frontend_connected(frontend::Capabilities::empty(), None);
+        let effects = bridge.server_message(&url(), Some(room(&[], &queue)), ServerMessage::GetItem(0x0002));
+        assert!(matches!(&effects[..], [Effect::Frontend(frontend::ServerMessage::ItemQueue(items))] if *items == queue));
+        bridge.frontend_connected(frontend::Capabilities::DELTA_QUEUES, None);
+        let effects = bridge.server_message(&url(), Some(room(&[], &queue)), ServerMessage::GetItem(0x0002));
+        assert!(matches!(&effects[..], [Effect::Frontend(frontend::ServerMessage::GetItem(0x0002))]));
+        let effects = bridge.server_message(&url(), Some(Room { active: false, ..room(&[], &queue) }), ServerMessage::GetItem(0x0002));
+        assert!(effects.is_empty(), "items must not be passed to the frontend while its world is taken or its file hash doesn't match");
+    }
+
+    #[test]
+    fn resend_save_data_after_diff_failed() {
+        let mut bridge = Bridge::default();
+        let mut save = oottracker::Save::default();
+        let effects = frontend_message(&mut bridge, Some(room(&[], &[])), frontend::ClientMessage::SaveData(*save_diff::encode(&save).expect("failed to encode save data")));
+        assert!(matches!(&effects[..], [Effect::Saved { .. }, Effect::Server(ClientMessage::SaveData(_))]));
+        save.inv_amounts.num_received_mw_items = 1;
+        let effects = frontend_message(&mut bridge, Some(room(&[], &[])), frontend::ClientMessage::SaveData(*save_diff::encode(&save).expect("failed to encode save data")));
+        assert!(matches!(&effects[..], [Effect::Saved { .. }, Effect::Server(ClientMessage::SaveDataDiff(_))]));
+        let effects = bridge.server_message(&url(), Some(room(&[], &[])), ServerMessage::StructuredError(ServerError::SaveDataDiffFailed));
+        assert!(matches!(&effects[..], [Effect::Server(ClientMessage::SaveData(resent))] if resent.inv_amounts.num_received_mw_items == 1));
+        save.inv_amounts.num_received_mw_items = 2;
+        let effects = frontend_message(&mut bridge, Some(room(&[], &[])), frontend::ClientMessage::SaveData(*save_diff::encode(&save).expect("failed to encode save data")));
+        assert!(matches!(&effects[..], [Effect::Saved { .. }, Effect::Server(ClientMessage::SaveData(_))]), "save data must be sent in full after the server failed to apply a diff");
+    }
+
+    #[test]
+    fn save_data_diff_without_base() {
+        let mut bridge = Bridge::default();
+        assert!(matches!(
+            bridge.frontend_message(&url(), None, frontend::ClientMessage::SaveDataDiff(Vec::default())),
+            Err(Error::SaveDataDiffWithoutBase),
+        ));
+    }
+}
@@ -0,0 +1,217 @@
+//! A file format for recording the exact messages exchanged with the server, so a session can be replayed against a local `ootrmwd` using `multiworld-replay`.
+//!
+//! A capture file is a concatenation of [`Entry`] values encoded using [`Protocol`]. Each connection starts with an [`Entry::Connect`], so several sessions can be appended to the same file.
+//! Login tokens, API keys, and room passwords are redacted before they are written.
+
+use {
+    std::{
+        fmt,
+        fs::{
+            self,
+            File,
+            OpenOptions,
+        },
+        io::{
+            self,
+            prelude::*,
+        },
+        path::{
+            Path,
+            PathBuf,
+        },
+        sync::{
+            Arc,
+            Mutex,
+        },
+    },
+    async_proto::Protocol,
+    chrono::prelude::*,
+    semver::Version,
+    crate::{
+        client,
+        ws::latest::{
+            ClientMessage,
+            ServerMessage,
+        },
+    },
+};
+
+#[derive(Debug, Protocol)]
+pub enum Entry {
+    Connect {
+        time: DateTime<Utc>,
+        app_version: Version,
+        websocket_url: String,
+    },
+    Client {
+        time: DateTime<Utc>,
+        msg: ClientMessage,
+    },
+    Server {
+        time: DateTime<Utc>,
+        msg: ServerMessage,
+    },
+}
+
+impl Entry {
+    pub fn time(&self) -> DateTime<Utc> {
+        match *self {
+            Self::Connect { time, .. } | Self::Client { time, .. } | Self::Server { time, .. } => time,
+        }
+    }
+}
+
+/// Replaces credentials in a client message with empty strings.
+pub fn redact(msg: ClientMessage) -> ClientMessage {
+    match msg {
+        ClientMessage::JoinRoom { id, password } => ClientMessage::JoinRoom { id, password: password.map(|_| String::default()) },
+        ClientMessage::CreateRoom { name, password: _ } => ClientMessage::CreateRoom { name, password: String::default() },
+        ClientMessage::LoginApiKey { api_key: _ } => ClientMessage::LoginApiKey { api_key: String::default() },
+        ClientMessage::LoginDiscord { bearer_token: _ } => ClientMessage::LoginDiscord { bearer_token: String::default() },
+        ClientMessage::LoginRaceTime { bearer_token: _ } => ClientMessage::LoginRaceTime { bearer_token: String::default() },
+        msg => msg,
+    }
+}
+
+/// Reads a capture file and splits it into connections, each starting with its [`Entry::Connect`].
+///
+/// Entries before the first `Connect` are discarded.
+pub fn read(buf: &[u8]) -> Result<Vec<Vec<Entry>>, async_proto::ReadError> {
+    let mut connections = Vec::<Vec<Entry>>::default();
+    for entry in read_concatenated::<Entry>(buf)? {
+        if let Entry::Connect { .. } = entry {
+            connections.push(vec![entry]);
+        } else if let Some(connection) = connections.last_mut() {
+            connection.push(entry);
+        }
+    }
+    Ok(connections)
+}
+
+fn read_concatenated<T: Protocol>(mut buf: &[u8]) -> Result<Vec<T>, async_proto::ReadError> {
+    let mut entries = Vec::default();
+    while !buf.is_empty() {
+        entries.push(T::read_sync(&mut buf)?);
+    }
+    Ok(entries)
+}
+
+/// A handle to an open capture file. Cloning it is cheap and all clones append to the same file.
+///
+/// Entries are written synchronously so that recording a message doesn't introduce an await point, which would make [`client::Receiver::recv`] cancel unsafe.
+#[derive(Clone)]
+pub struct Capture {
+    inner: Arc<Mutex<Inner>>,
+}
+
+struct Inner {
+    file: File,
+    len: u64,
+    rotation: Option<Rotation>,
+    /// The app version and WebSocket URL of the most recent [`Entry::Connect`], used to start the new file after rotating in the middle of a connection.
+    last_connect: Option<(Version, String)>,
+}
+
+struct Rotation {
+    path: PathBuf,
+    max_len: u64,
+}
+
+impl Inner {
+    fn write(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.file.write_all(buf)?;
+        self.file.flush()?;
+        self.len += buf.len() as u64;
+        Ok(())
+    }
+}
+
+impl Capture {
+    /// Opens the capture file at the given path, appending to it if it already exists.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::open_inner(path.as_ref(), None)
+    }
+
+    /// Like [`Capture::open`], but keeps the file below roughly `max_len` bytes.
+    ///
+    /// When an entry would make the file longer than that, the current contents are moved to [`rotated_path`] (replacing any previous rotated file) and the file starts over.
+    /// If this happens in the middle of a connection, the new file starts with a copy of that connection's [`Entry::Connect`], so both files remain readable using [`read`].
+    pub fn open_rotating(path: impl AsRef<Path>, max_len: u64) -> io::Result<Self> {
+        let path = path.as_ref();
+        Self::open_inner(path, Some(Rotation { path: path.to_owned(), max_len }))
+    }
+
+    fn open_inner(path: &Path, rotation: Option<Rotation>) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let len = file.metadata()?.len();
+        Ok(Self {
+            inner: Arc::new(Mutex::new(Inner { file, len, rotation, last_connect: None })),
+        })
+    }
+
+    pub(crate) fn record(&self, entry: Entry) -> Result<(), client::Error> {
+        let mut buf = Vec::default();
+        entry.write_sync(&mut buf)?;
+        let mut inner = self.inner.lock().expect("capture file lock poisoned");
+        if let Entry::Connect { ref app_version, ref websocket_url, .. } = entry {
+            inner.last_connect = Some((app_version.clone(), websocket_url.clone()));
+        }
+        let len = inner.len;
+        if let Some(path) = inner.rotation.as_ref().filter(|Rotation { max_len, .. }| len > 0 && len + buf.len() as u64 > *max_len).map(|Rotation { path, .. }| path.clone()) {
+            // copy and truncate rather than rename since the file is still open, which would make renaming fail on Windows
+            fs::copy(&path, rotated_path(&path))?;
+            inner.file.set_len(0)?;
+            inner.len = 0;
+            if !matches!(entry, Entry::Connect { .. }) {
+                if let Some((app_version, websocket_url)) = inner.last_connect.clone() {
+                    let mut connect = Vec::default();
+                    Entry::Connect { time: Utc::now(), app_version, websocket_url }.write_sync(&mut connect)?;
+                    inner.write(&connect)?;
+                }
+            }
+        }
+        inner.write(&buf)?;
+        Ok(())
+    }
+}
+
+/// Where [`Capture::open_rotating`] moves the previous contents of the capture file at the given path: the same path with `.old` appended.
+pub fn rotated_path(path: &Path) -> PathBuf {
+    let mut rotated = path.as_os_str().to_owned();
+    rotated.push(".old");
+    PathBuf::from(rotated)
+}
+
+impl fmt::Debug for Capture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Capture").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotate() {
+        let path = std::env::temp_dir().join(format!("multiworld-capture-test-{}.capture", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let _ = fs::remove_file(rotated_path(&path));
+        let capture = Capture::open_rotating(&path, 256).expect("failed to open capture file");
+        capture.record(Entry::Connect { time: Utc::now(), app_version: crate::version(), websocket_url: "wss://mw.example.com/v18".to_owned() }).expect("failed to record");
+        for _ in 0..64 {
+            capture.record(Entry::Client { time: Utc::now(), msg: ClientMessage::Ping }).expect("failed to record");
+        }
+        let current = fs::read(&path).expect("failed to read capture file");
+        let rotated = fs::read(rotated_path(&path)).expect("failed to read rotated capture file");
+        assert!(current.len() <= 256);
+        assert!(rotated.len() <= 256);
+        for buf in [current, rotated] {
+            let connections = read(&buf).expect("failed to parse capture file");
+            assert_eq!(connections.len(), 1);
+            assert!(connections[0].len() > 1);
+        }
+        fs::remove_file(&path).expect("failed to clean up capture file");
+        fs::remove_file(rotated_path(&path)).expect("failed to clean up rotated capture file");
+    }
+}
@@ -0,0 +1,241 @@
+//! A typed async client for the WebSocket protocol spoken by `ootrmwd`, for the GUI as well as bots and other tools.
+//!
+//! [`connect`] returns a [`Client`] for sending messages and a [`Receiver`] for reading them.
+//! The receiver keeps a [`SessionState`] up to date and sends the pings the server expects, so it should be polled continuously for as long as the connection is in use.
+//! Use [`connect_capturing`] instead to also record all messages in a [`Capture`] file.
+
+use {
+    std::{
+        convert::Infallible as Never,
+        fmt,
+        num::NonZero,
+        pin::pin,
+        sync::Arc,
+        time::Duration,
+    },
+    async_proto::Protocol as _,
+    chrono::prelude::*,
+    futures::{
+        sink::SinkExt as _,
+        stream::{
+            self,
+            SplitSink,
+            SplitStream,
+            Stream,
+            StreamExt as _,
+        },
+    },
+    log_lock::{
+        Mutex,
+        lock,
+    },
+    tokio::{
+        net::TcpStream,
+        select,
+        time::{
+            Instant,
+            Interval,
+            interval_at,
+            timeout,
+        },
+    },
+    tokio_tungstenite024::{
+        MaybeTlsStream,
+        WebSocketStream,
+        tungstenite,
+    },
+    url::Url,
+    wheel::traits::IsNetworkError,
+    crate::{
+        IdentityProvider,
+        SessionState,
+        capture::{
+            self,
+            Capture,
+        },
+        user_agent,
+        version,
+        ws::latest::{
+            ClientMessage,
+            ServerMessage,
+        },
+    },
+};
+
+type WsStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error(transparent)] Elapsed(#[from] tokio::time::error::Elapsed),
+    #[error(transparent)] Http(#[from] tungstenite::http::Error),
+    #[error(transparent)] InvalidUri(#[from] tungstenite::http::uri::InvalidUri),
+    #[error(transparent)] Io(#[from] std::io::Error),
+    #[error(transparent)] Read(#[from] async_proto::ReadError),
+    #[error(transparent)] WebSocket(#[from] tungstenite::Error),
+    #[error(transparent)] Write(#[from] async_proto::WriteError),
+}
+
+impl IsNetworkError for Error {
+    fn is_network_error(&self) -> bool {
+        match self {
+            Self::Elapsed(_) => true,
+            Self::Http(_) | Self::InvalidUri(_) | Self::Io(_) => false,
+            Self::Read(e) => e.is_network_error(),
+            Self::WebSocket(e) => e.is_network_error(),
+            Self::Write(e) => e.is_network_error(),
+        }
+    }
+}
+
+/// Connects to the server at the given WebSocket URL.
+pub async fn connect(websocket_url: &Url) -> Result<(Client, Receiver), Error> {
+    connect_inner(websocket_url, None).await
+}
+
+/// Connects to the server at the given WebSocket URL and records all messages sent and received on this connection in the given capture file.
+pub async fn connect_capturing(websocket_url: &Url, capture: Capture) -> Result<(Client, Receiver), Error> {
+    connect_inner(websocket_url, Some(capture)).await
+}
+
+async fn connect_inner(websocket_url: &Url, capture: Option<Capture>) -> Result<(Client, Receiver), Error> {
+    let request = tungstenite::ClientRequestBuilder::new(websocket_url.as_str().try_into()?)
+        .with_header(tungstenite::http::header::USER_AGENT.to_string(), user_agent());
+    let (websocket, _) = tokio_tungstenite024::connect_async(request).await?;
+    let (sink, stream) = websocket.split();
+    if let Some(ref capture) = capture {
+        capture.record(capture::Entry::Connect {
+            time: Utc::now(),
+            app_version: version(),
+            websocket_url: websocket_url.to_string(),
+        })?;
+    }
+    let client = Client { sink: Arc::new(Mutex::new(sink)), capture };
+    Ok((client.clone(), Receiver {
+        ping: interval_at(Instant::now() + Duration::from_secs(30), Duration::from_secs(30)),
+        state: SessionState::default(),
+        client, stream,
+    }))
+}
+
+/// The sending half of a server connection. Cloning it is cheap and all clones send on the same connection.
+#[derive(Clone)]
+pub struct Client {
+    sink: Arc<Mutex<SplitSink<WsStream, tungstenite::Message>>>,
+    capture: Option<Capture>,
+}
+
+impl Client {
+    /// Sends an arbitrary message. Prefer the more specific methods where they exist.
+    pub async fn send(&self, msg: ClientMessage) -> Result<(), Error> {
+        lock!(sink = self.sink; msg.write_ws024(&mut *sink).await)?;
+        if let Some(ref capture) = self.capture {
+            capture.record(capture::Entry::Client { time: Utc::now(), msg: capture::redact(msg) })?;
+        }
+        Ok(())
+    }
+
+    /// Signs in using a bearer token from one of the identity providers supported by Mido's House.
+    pub async fn login(&self, provider: IdentityProvider, bearer_token: String) -> Result<(), Error> {
+        self.send(match provider {
+            IdentityProvider::RaceTime => ClientMessage::LoginRaceTime { bearer_token },
+            IdentityProvider::Discord => ClientMessage::LoginDiscord { bearer_token },
+        }).await
+    }
+
+    /// Signs in using a Mido's House API key.
+    pub async fn login_api_key(&self, api_key: String) -> Result<(), Error> {
+        self.send(ClientMessage::LoginApiKey { api_key }).await
+    }
+
+    pub async fn join_room(&self, id: u64, password: Option<String>) -> Result<(), Error> {
+        self.send(ClientMessage::JoinRoom { id, password }).await
+    }
+
+    pub async fn create_room(&self, name: String, password: String) -> Result<(), Error> {
+        self.send(ClientMessage::CreateRoom { name, password }).await
+    }
+
+    pub async fn leave_room(&self) -> Result<(), Error> {
+        self.send(ClientMessage::LeaveRoom).await
+    }
+
+    /// Claims the given world in the current room.
+    pub async fn claim_world(&self, world: NonZero<u8>) -> Result<(), Error> {
+        self.send(ClientMessage::PlayerId(world)).await
+    }
+
+    /// Gives up the world claimed in the current room.
+    pub async fn unclaim_world(&self) -> Result<(), Error> {
+        self.send(ClientMessage::ResetPlayerId).await
+    }
+
+    pub async fn send_item(&self, key: u64, kind: u16, target_world: NonZero<u8>) -> Result<(), Error> {
+        self.send(ClientMessage::SendItem { key, kind, target_world }).await
+    }
+
+    /// Tells the server that this client is going away and closes the connection.
+    pub async fn close(&self, reason: String) -> Result<(), Error> {
+        lock!(sink = self.sink; {
+            sink.send(tungstenite::Message::Close(Some(tungstenite::protocol::CloseFrame {
+                code: tungstenite::protocol::frame::coding::CloseCode::Away,
+                reason: reason.into(),
+            }))).await?;
+            sink.close().await?;
+        });
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Client {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Client").finish_non_exhaustive()
+    }
+}
+
+/// A message received from the server, along with the session state after applying it.
+#[derive(Debug, Clone)]
+pub struct StateChange {
+    pub msg: ServerMessage,
+    pub state: SessionState<Never>,
+}
+
+/// The receiving half of a server connection.
+pub struct Receiver {
+    client: Client,
+    stream: SplitStream<WsStream>,
+    ping: Interval,
+    state: SessionState<Never>,
+}
+
+impl Receiver {
+    /// The state of the session as of the last message returned by [`Receiver::recv`].
+    pub fn state(&self) -> &SessionState<Never> { &self.state }
+
+    /// Waits for the next message from the server and applies it to [`Receiver::state`].
+    ///
+    /// Sends pings while waiting and fails if the server hasn't sent anything in a minute.
+    /// This is cancel safe, so it can be used in `select!`.
+    pub async fn recv(&mut self) -> Result<ServerMessage, Error> {
+        let mut read = pin!(timeout(Duration::from_secs(60), ServerMessage::read_ws024(&mut self.stream)));
+        let msg = loop {
+            select! {
+                res = &mut read => break res??,
+                _ = self.ping.tick() => self.client.send(ClientMessage::Ping).await?,
+            }
+        };
+        if let Some(ref capture) = self.client.capture {
+            capture.record(capture::Entry::Server { time: Utc::now(), msg: msg.clone() })?;
+        }
+        self.state.apply(msg.clone());
+        Ok(msg)
+    }
+
+    /// Converts this receiver into a stream of the changes to the [`SessionState`] caused by each message from the server.
+    pub fn into_stream(self) -> impl Stream<Item = Result<StateChange, Error>> + Send {
+        stream::try_unfold(self, |mut receiver| async move {
+            let msg = receiver.recv().await?;
+            let change = StateChange { msg, state: receiver.state.clone() };
+            Ok(Some((change, receiver)))
+        })
+    }
+}
@@ -5,101 +5,270 @@ use {
         path::PathBuf,
     },
     chrono::prelude::*,
-    if_chain::if_chain,
+    semver::Version,
     serde::{
         Deserialize,
         Serialize,
     },
     url::Url,
-    crate::frontend::Kind as Frontend,
+    crate::{
+        frontend::Kind as Frontend,
+        github::FIRST_SIGNED_VERSION,
+    },
 };
 #[cfg(unix)] use xdg::BaseDirectories;
 #[cfg(windows)] use directories::ProjectDirs;
 
+/// The version of the config format written by this version of the app.
+///
+/// This should be bumped whenever the meaning or format of an existing field changes, along with adding a migration to [`Config::from_json`].
+/// Adding new fields with defaults doesn't require a bump since older versions keep fields they don't know about.
+pub const SCHEMA_VERSION: u64 = 1;
+
 fn default_websocket_hostname() -> String { format!("mw.midos.house") }
 
+/// Identifies a server by the host and port of its WebSocket URL, for scoping data like login tokens or pending items to a server.
+pub fn server_key(websocket_url: &Url) -> String {
+    websocket_url[url::Position::BeforeHost..url::Position::AfterPort].to_owned()
+}
+
+/// Returns the login token from `login_tokens` which may be sent to the server at the given URL without asking the user, i.e. one that was obtained for that server according to `login_token_servers`.
+pub fn login_token_for(login_tokens: &BTreeMap<crate::IdentityProvider, String>, login_token_servers: &BTreeMap<crate::IdentityProvider, String>, websocket_url: &Url) -> Option<(crate::IdentityProvider, String)> {
+    let server = server_key(websocket_url);
+    login_tokens.iter()
+        .find(|(provider, _)| login_token_servers.get(provider).is_some_and(|token_server| *token_server == server))
+        .map(|(&provider, bearer_token)| (provider, bearer_token.clone()))
+}
+
+/// Which releases the GUI and the updater consider when checking for updates.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UpdateChannel {
+    /// The latest release which is not marked as a prerelease.
+    #[default]
+    Stable,
+    /// The latest release, including prereleases.
+    Beta,
+    /// Exactly the given version, which may also be older than the installed version.
+    ///
+    /// Must be at least [`FIRST_SIGNED_VERSION`], since the updater can't verify older releases.
+    Pinned(Version),
+}
+
+impl UpdateChannel {
+    /// Whether a release with version `available` should replace the installed version.
+    pub fn should_update(&self, available: &Version) -> bool {
+        let installed = crate::version();
+        match self {
+            Self::Stable | Self::Beta => *available > installed,
+            Self::Pinned(pinned) => available == pinned && *available != installed,
+        }
+    }
+}
+
+/// A server other than the default Mido's House server, such as a development server or a self-hosted instance.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ServerProfile {
+    /// Either `wss` or, for servers without TLS such as on a local network, `ws`.
+    pub scheme: String,
+    pub host: String,
+    pub port: Option<u16>,
+    /// Defaults to `/v{major}` using the major version of this app, which is where `ootrmwd` listens.
+    pub path: Option<String>,
+}
+
+impl ServerProfile {
+    /// Parses a WebSocket URL into a server profile. Returns `None` if the URL isn't a `ws://` or `wss://` URL with a host.
+    pub fn from_websocket_url(url: &Url) -> Option<Self> {
+        if !matches!(url.scheme(), "ws" | "wss") { return None }
+        Some(Self {
+            scheme: url.scheme().to_owned(),
+            host: url.host_str()?.to_owned(),
+            port: url.port(),
+            path: (!matches!(url.path(), "" | "/")).then(|| url.path().to_owned()),
+        })
+    }
+
+    pub fn websocket_url(&self) -> Result<Url, url::ParseError> {
+        let port = self.port.map(|port| format!(":{port}")).unwrap_or_default();
+        let path = self.path.clone().unwrap_or_else(|| format!("/v{}", crate::version().major));
+        Url::parse(&format!("{}://{}{port}{path}", self.scheme, self.host))
+    }
+}
+
 #[derive(Clone, Deserialize, Serialize)]
 pub struct Config {
+    /// The version of the config format. Missing in config files written before the format was versioned.
+    #[serde(default)]
+    pub schema_version: u64,
+    /// Set when rolling back to a previous version. While this is set, the GUI doesn't automatically install updates.
+    #[serde(default)]
+    pub auto_update_paused: bool,
+    /// Record all messages exchanged with the server in a capture file, for replaying with `multiworld-replay`.
+    #[serde(default)]
+    pub capture: bool,
     pub default_frontend: Option<Frontend>,
     pub dismiss_maintenance_notices_until: Option<DateTime<Utc>>,
     #[serde(default)]
     pub log: bool,
     #[serde(default)]
     pub login_tokens: BTreeMap<crate::IdentityProvider, String>,
+    /// The [`server_key`] of the server each entry in `login_tokens` was obtained for. Login tokens are only sent to this server automatically.
+    #[serde(default)]
+    pub login_token_servers: BTreeMap<crate::IdentityProvider, String>,
     #[serde(default)]
     pub refresh_tokens: BTreeMap<crate::IdentityProvider, String>,
     pub pj64_script_path: Option<PathBuf>,
+    /// The version the updater replaced most recently, whose files are kept for rollbacks.
+    pub previous_version: Option<Version>,
+    /// The name of the entry in `server_profiles` to connect to. If this is `None`, the server at `websocket_hostname` is used.
+    pub server_profile: Option<String>,
+    #[serde(default)]
+    pub server_profiles: BTreeMap<String, ServerProfile>,
+    #[serde(default)]
+    pub update_channel: UpdateChannel,
     #[serde(default = "default_websocket_hostname")]
     pub websocket_hostname: String,
+    /// Fields written by newer versions of the app, kept so they aren't lost when this version saves the config.
+    #[serde(flatten)]
+    pub unknown_fields: serde_json::Map<String, serde_json::Value>,
 }
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error(transparent)] Io(#[from] std::io::Error),
     #[error(transparent)] Json(#[from] serde_json::Error),
+    #[error(transparent)] Url(#[from] url::ParseError),
     #[error(transparent)] Wheel(#[from] wheel::Error),
+    #[error("the config file was written by a newer version of Mido's House Multiworld (config format version {version}) and could not be read by this version: {source}")]
+    NewerSchema {
+        version: u64,
+        source: serde_json::Error,
+    },
     #[cfg(windows)]
     #[error("failed to find project folder")]
     ProjectDirs,
+    #[error("no server profile named {0:?} in the config, and it's not a ws:// or wss:// URL either")]
+    UnknownServerArg(String),
+    #[error("no server profile named {0:?}")]
+    UnknownServerProfile(String),
+    #[error("the update channel is pinned to version {0}, but only versions {FIRST_SIGNED_VERSION} and later can be installed automatically")]
+    UnsignedPin(Version),
 }
 
 impl Config {
-    pub fn blocking_load() -> Result<Self, Error> { //TODO update callers of this function to handle broken config files interactively (delete/edit/retry)
-        let path = {
-            #[cfg(unix)] {
-                BaseDirectories::new().find_config_file("midos-house/multiworld.json")
-            }
-            #[cfg(windows)] {
-                Some(ProjectDirs::from("net", "Fenhl", "OoTR Multiworld").ok_or(Error::ProjectDirs)?.config_dir().join("config.json"))
-            }
-        };
-        Ok(if_chain! {
-            if let Some(path) = path;
-            if path.exists();
-            then {
-                serde_json::from_str(&fs::read_to_string(path)?)?
-            } else {
-                Self::default()
+    /// The path of the config file, if it might exist.
+    fn existing_path() -> Result<Option<PathBuf>, Error> {
+        #[cfg(unix)] {
+            Ok(BaseDirectories::new().find_config_file("midos-house/multiworld.json"))
+        }
+        #[cfg(windows)] {
+            Ok(Some(ProjectDirs::from("net", "Fenhl", "OoTR Multiworld").ok_or(Error::ProjectDirs)?.config_dir().join("config.json")))
+        }
+    }
+
+    /// The path of the config file, creating its parent directory if necessary.
+    pub async fn path() -> Result<PathBuf, Error> {
+        #[cfg(unix)] {
+            Ok(BaseDirectories::new().place_config_file("midos-house/multiworld.json")?)
+        }
+        #[cfg(windows)] {
+            let project_dirs = ProjectDirs::from("net", "Fenhl", "OoTR Multiworld").ok_or(Error::ProjectDirs)?;
+            wheel::fs::create_dir_all(project_dirs.config_dir()).await?;
+            Ok(project_dirs.config_dir().join("config.json"))
+        }
+    }
+
+    /// Parses a config file, migrating it from older versions of the config format.
+    fn from_json(value: serde_json::Value) -> Result<Self, Error> {
+        let version = value.get("schema_version").and_then(serde_json::Value::as_u64).unwrap_or(0);
+        // no migrations yet, version 1 only added the schema_version field itself
+        match serde_json::from_value::<Self>(value) {
+            Ok(mut config) => {
+                if let UpdateChannel::Pinned(ref pinned) = config.update_channel {
+                    if *pinned < FIRST_SIGNED_VERSION { return Err(Error::UnsignedPin(pinned.clone())) }
+                }
+                // login tokens stored before they were scoped to a server were only ever sent to the server at websocket_hostname
+                if let Ok(default_websocket_url) = config.default_websocket_url() {
+                    for &provider in config.login_tokens.keys() {
+                        config.login_token_servers.entry(provider).or_insert_with(|| server_key(&default_websocket_url));
+                    }
+                }
+                // don't downgrade the version of files written by newer versions, since their unknown fields are kept
+                config.schema_version = config.schema_version.max(SCHEMA_VERSION);
+                Ok(config)
             }
-        })
+            Err(source) if version > SCHEMA_VERSION => Err(Error::NewerSchema { version, source }),
+            Err(e) => Err(e.into()),
+        }
     }
 
-    pub async fn load() -> Result<Self, Error> { //TODO update callers of this function to handle broken config files interactively (delete/edit/retry)
-        let path = {
-            #[cfg(unix)] {
-                BaseDirectories::new().find_config_file("midos-house/multiworld.json")
+    pub fn blocking_load() -> Result<Self, Error> {
+        if let Some(path) = Self::existing_path()? {
+            if path.exists() {
+                return Self::from_json(serde_json::from_str(&fs::read_to_string(path)?)?)
             }
-            #[cfg(windows)] {
-                Some(ProjectDirs::from("net", "Fenhl", "OoTR Multiworld").ok_or(Error::ProjectDirs)?.config_dir().join("config.json"))
+        }
+        Ok(Self::default())
+    }
+
+    pub async fn load() -> Result<Self, Error> {
+        if let Some(path) = Self::existing_path()? {
+            if wheel::fs::exists(&path).await? {
+                return Self::from_json(wheel::fs::read_json(path).await?)
             }
+        }
+        Ok(Self::default())
+    }
+
+    /// Replaces a config file that couldn't be loaded with the default config.
+    ///
+    /// Returns the path of a backup of the previous file, if there was one.
+    pub async fn reset() -> Result<Option<PathBuf>, Error> {
+        let backup_path = if let Some(path) = Self::existing_path()?.filter(|path| path.exists()) {
+            let mut file_name = path.file_name().unwrap_or_default().to_owned();
+            file_name.push(format!(".{}.broken", Utc::now().format("%Y%m%d%H%M%S")));
+            let backup_path = path.with_file_name(file_name);
+            wheel::fs::copy(&path, &backup_path).await?;
+            Some(backup_path)
+        } else {
+            None
         };
-        Ok(if_chain! {
-            if let Some(path) = path;
-            if wheel::fs::exists(&path).await?;
-            then {
-                wheel::fs::read_json(path).await?
-            } else {
-                Self::default()
-            }
-        })
+        Self::default().save().await?;
+        Ok(backup_path)
     }
 
     pub async fn save(&self) -> Result<(), Error> {
-        let path = {
-            #[cfg(unix)] {
-                BaseDirectories::new().place_config_file("midos-house/multiworld.json")?
-            }
-            #[cfg(windows)] {
-                let project_dirs = ProjectDirs::from("net", "Fenhl", "OoTR Multiworld").ok_or(Error::ProjectDirs)?;
-                wheel::fs::create_dir_all(project_dirs.config_dir()).await?;
-                project_dirs.config_dir().join("config.json")
-            }
-        };
-        wheel::fs::write_json(path, self).await?;
+        wheel::fs::write_json(Self::path().await?, self).await?;
         Ok(())
     }
 
-    pub fn websocket_url(&self) -> Result<Url, url::ParseError> {
+    pub fn websocket_url(&self) -> Result<Url, Error> {
+        if let Some(ref name) = self.server_profile {
+            Ok(self.server_profiles.get(name).ok_or_else(|| Error::UnknownServerProfile(name.clone()))?.websocket_url()?)
+        } else {
+            Ok(self.default_websocket_url()?)
+        }
+    }
+
+    /// Resolves a `--server` command-line argument, which is either the name of a server profile or a WebSocket URL.
+    pub fn server_arg_url(&self, server: &str) -> Result<Url, Error> {
+        if let Some(profile) = self.server_profiles.get(server) {
+            Ok(profile.websocket_url()?)
+        } else if let Some(profile) = Url::parse(server).ok().as_ref().and_then(ServerProfile::from_websocket_url) {
+            Ok(profile.websocket_url()?)
+        } else {
+            Err(Error::UnknownServerArg(server.to_owned()))
+        }
+    }
+
+    /// Returns a stored login token which may be sent to the server at the given URL without asking the user, i.e. one that was obtained for that server.
+    pub fn login_token_for(&self, websocket_url: &Url) -> Option<(crate::IdentityProvider, String)> {
+        login_token_for(&self.login_tokens, &self.login_token_servers, websocket_url)
+    }
+
+    /// The URL of the server at `websocket_hostname`, which is used if no server profile is selected.
+    pub fn default_websocket_url(&self) -> Result<Url, url::ParseError> {
         Url::parse(&format!("wss://{}/v{}", self.websocket_hostname, crate::version().major))
     }
 }
@@ -107,13 +276,75 @@ impl Config {
 impl Default for Config {
     fn default() -> Self {
         Self {
+            schema_version: SCHEMA_VERSION,
+            auto_update_paused: false,
+            capture: false,
             default_frontend: None,
             dismiss_maintenance_notices_until: None,
             log: false,
             login_tokens: BTreeMap::default(),
+            login_token_servers: BTreeMap::default(),
             refresh_tokens: BTreeMap::default(),
             pj64_script_path: None,
+            previous_version: None,
+            server_profile: None,
+            server_profiles: BTreeMap::default(),
+            update_channel: UpdateChannel::default(),
             websocket_hostname: default_websocket_hostname(),
+            unknown_fields: serde_json::Map::default(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use {
+        serde_json::json,
+        super::*,
+    };
+
+    #[test]
+    fn pinned_version() {
+        let config = Config::from_json(json!({"update_channel": {"pinned": "18.0.0"}})).expect("pin to a signed version should be accepted");
+        assert_eq!(config.update_channel, UpdateChannel::Pinned(Version::new(18, 0, 0)));
+        assert!(matches!(Config::from_json(json!({"update_channel": {"pinned": "17.0.0"}})), Err(Error::UnsignedPin(version)) if version == Version::new(17, 0, 0)));
+    }
+
+    #[test]
+    fn legacy_login_tokens() {
+        let config = Config::from_json(json!({"login_tokens": {"racetime": "token"}})).expect("config with login token should be accepted");
+        let default_websocket_url = config.default_websocket_url().expect("failed to parse default WebSocket URL");
+        assert_eq!(config.login_token_for(&default_websocket_url), Some((crate::IdentityProvider::RaceTime, format!("token"))));
+        assert_eq!(config.login_token_for(&Url::parse("wss://example.com/v18").unwrap()), None);
+    }
+
+    #[test]
+    fn scoped_login_tokens() {
+        let config = Config::from_json(json!({
+            "login_tokens": {"discord": "token"},
+            "login_token_servers": {"discord": "example.com"},
+        })).expect("config with login token should be accepted");
+        assert_eq!(config.login_token_for(&Url::parse("wss://example.com/v18").unwrap()), Some((crate::IdentityProvider::Discord, format!("token"))));
+        assert_eq!(config.login_token_for(&config.default_websocket_url().unwrap()), None);
+    }
+
+    #[test]
+    fn unknown_server_profile() {
+        let config = Config::from_json(json!({"server_profile": "missing"})).expect("config should be accepted");
+        assert!(matches!(config.websocket_url(), Err(Error::UnknownServerProfile(name)) if name == "missing"));
+    }
+
+    #[test]
+    fn server_arg() {
+        let config = Config::from_json(json!({"server_profiles": {"dev": {"scheme": "ws", "host": "localhost", "port": 24819, "path": null}}})).expect("config should be accepted");
+        assert_eq!(config.server_arg_url("dev").expect("profile should be found").as_str(), format!("ws://localhost:24819/v{}", crate::version().major));
+        assert_eq!(config.server_arg_url("wss://mw.example.com/v18").expect("URL should be accepted").as_str(), "wss://mw.example.com/v18");
+        assert!(matches!(config.server_arg_url("missing"), Err(Error::UnknownServerArg(server)) if server == "missing"));
+    }
+
+    #[test]
+    fn default_channel() {
+        let config = Config::from_json(json!({})).expect("empty config should be accepted");
+        assert_eq!(config.update_channel, UpdateChannel::Stable);
+    }
+}
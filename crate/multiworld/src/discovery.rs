@@ -0,0 +1,89 @@
+//! Lets several independent instances of the app (or of `multiworld-headless`) run side by side on the same computer.
+//!
+//! Instances which talk to consoles over serial ports (EverDrive) claim each port with a `serial-<port>.lock` file in [`dir`] before opening it, so they don't steal each other's consoles.
+//! Claim files are kept locked by the process which created them, so files left behind by a crashed process can be recognized as stale.
+//!
+//! TCP frontends don't need this: the BizHawk tool and the Project64 4.x script listen on an OS-selected port and pass it to the app they launch,
+//! and the Project64 3.x script can only connect to [`frontend::PORT`](crate::frontend::PORT) since its API can't read environment variables or detect failed connections,
+//! so additional Project64 3.x instances are handled by the instance listening on that port instead.
+
+use std::{
+    fs::{
+        File,
+        OpenOptions,
+        TryLockError,
+    },
+    io,
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+#[cfg(windows)] use std::fs;
+#[cfg(unix)] use xdg::BaseDirectories;
+#[cfg(windows)] use directories::ProjectDirs;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error(transparent)] Io(#[from] io::Error),
+    #[cfg(windows)]
+    #[error("failed to find project folder")]
+    ProjectDirs,
+}
+
+/// The directory containing serial port claims, creating it if necessary.
+///
+/// This is `$XDG_RUNTIME_DIR/midos-house/multiworld-ports` on Unix (falling back to the cache directory if there is no runtime directory, e.g. on macOS) and `%LOCALAPPDATA%\Fenhl\OoTR Multiworld\data\ports` on Windows.
+pub fn dir() -> Result<PathBuf, Error> {
+    #[cfg(unix)] {
+        let dirs = BaseDirectories::new();
+        Ok(if dirs.has_runtime_directory() {
+            dirs.create_runtime_directory("midos-house/multiworld-ports")?
+        } else {
+            dirs.create_cache_directory("midos-house/multiworld-ports")?
+        })
+    }
+    #[cfg(windows)] {
+        let path = ProjectDirs::from("net", "Fenhl", "OoTR Multiworld").ok_or(Error::ProjectDirs)?.data_local_dir().join("ports");
+        fs::create_dir_all(&path)?;
+        Ok(path)
+    }
+}
+
+/// Opens and locks the file at the given path, or returns `None` if another process holds the lock.
+fn try_lock(path: &Path) -> Result<Option<File>, Error> {
+    let file = OpenOptions::new().create(true).write(true).truncate(false).open(path)?;
+    match file.try_lock() {
+        Ok(()) => Ok(Some(file)),
+        Err(TryLockError::WouldBlock) => Ok(None),
+        Err(TryLockError::Error(e)) => Err(e.into()),
+    }
+}
+
+/// A claim on a serial port created by [`claim_serial_port`], released when this is dropped.
+///
+/// Claim files aren't removed when the claim is released, since another process may already be waiting to lock them.
+#[derive(Debug)]
+pub struct SerialClaim {
+    _file: File,
+}
+
+/// Claims the serial port at the given path for this process. Returns `None` if another instance has already claimed it.
+pub fn claim_serial_port(port_path: &str) -> Result<Option<SerialClaim>, Error> {
+    let name = port_path.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect::<String>();
+    Ok(try_lock(&dir()?.join(format!("serial-{name}.lock")))?.map(|file| SerialClaim { _file: file }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn claim_serial_port_once() {
+        let port_path = format!("/dev/multiworld-test-{}", std::process::id());
+        let claim = claim_serial_port(&port_path).expect("failed to claim serial port").expect("serial port already claimed");
+        assert!(claim_serial_port(&port_path).expect("failed to check serial port claim").is_none(), "a claimed serial port must not be claimed again");
+        drop(claim);
+        assert!(claim_serial_port(&port_path).expect("failed to claim serial port").is_some(), "a released serial port must be claimable again");
+    }
+}
@@ -0,0 +1,462 @@
+//! The EverDrive frontend, which talks to a console running the randomizer via an EverDrive's USB port.
+//!
+//! Ports are scanned for a console which is in game, unless a specific port is given. The console side of the protocol is simulated by `multiworld-everdrive-sim` for testing.
+
+use {
+    std::{
+        cmp::Ordering::*,
+        collections::HashMap,
+        mem,
+        num::NonZeroU8,
+        pin::Pin,
+        sync::Arc,
+        time::Duration,
+    },
+    arrayref::{
+        array_mut_ref,
+        array_ref,
+    },
+    enum_iterator::all,
+    futures::{
+        future::{
+            Future,
+            FutureExt as _,
+        },
+        stream::{
+            self,
+            Stream,
+            StreamExt as _,
+            TryStreamExt as _,
+        },
+    },
+    num_traits::FromPrimitive as _,
+    ootr_utils::spoiler::HashIcon,
+    tokio::{
+        io::{
+            self,
+            AsyncReadExt,
+            AsyncWriteExt,
+            ReadHalf,
+            WriteHalf,
+        },
+        select,
+        sync::mpsc,
+        time::sleep,
+    },
+    tokio_io_timeout::TimeoutStream,
+    tokio_serial::{
+        SerialPortBuilderExt as _,
+        SerialStream,
+    },
+    crate::{
+        Filename,
+        HintArea,
+        OptHintArea,
+        discovery::SerialClaim,
+        frontend,
+    },
+};
+#[cfg(unix)] use std::{
+    ffi::OsString,
+    path::Path,
+};
+
+const TEST_TIMEOUT: Duration = Duration::from_millis(200); // 200ms in the sample code
+const REGULAR_TIMEOUT: Duration = Duration::from_secs(10); // twice the ping interval
+
+/// The version of the protocol between the app and the EverDrive, sent in both directions during the handshake.
+pub const PROTOCOL_VERSION: u8 = 1;
+/// The command which starts the handshake. A console which is in game replies with `OoTR` and the protocol version.
+pub const HANDSHAKE: [u8; 16] = *b"cmdt\0\0\0\0\0\0\0\0\0\0\0\0";
+
+#[derive(Debug)]
+struct HandshakeResponse {
+    claim: SerialClaim,
+    port: Pin<Box<TimeoutStream<SerialStream>>>,
+    version: ootr_utils::Version,
+    player_id: NonZeroU8,
+    file_hash: [HashIcon; 5],
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ConnectError {
+    #[error(transparent)] Discovery(#[from] crate::discovery::Error),
+    #[error(transparent)] Io(#[from] io::Error),
+    #[error(transparent)] SerialPort(#[from] tokio_serial::Error),
+    #[error("unknown branch identifier: 0x{0:02x}")]
+    Branch(u8),
+    #[error("port is in use by another instance of Mido's House Multiworld")]
+    Claimed,
+    #[error("failed to decode hash icon")]
+    HashIcon,
+    #[error("connected to EverDrive main menu")]
+    MainMenu,
+    #[cfg(unix)]
+    #[error("non-UTF-8 string: {}", .0.to_string_lossy())]
+    OsString(OsString),
+    #[error("N64 reported as world 0")]
+    PlayerId,
+    #[cfg(unix)]
+    #[error("found USB port at file system root")]
+    PortAtRoot,
+    #[error("unexpected handshake reply: {0:x?}")]
+    UnknownReply([u8; 4]),
+}
+
+#[cfg(unix)]
+impl From<OsString> for ConnectError {
+    fn from(s: OsString) -> Self {
+        Self::OsString(s)
+    }
+}
+
+/// The path to open for a port returned by [`tokio_serial::available_ports`].
+fn scanned_port_path(port_info: &tokio_serial::SerialPortInfo) -> Result<String, ConnectError> {
+    #[cfg(unix)] { Ok(Path::new("/dev").join(Path::new(&port_info.port_name).file_name().ok_or(ConnectError::PortAtRoot)?).into_os_string().into_string()?) }
+    #[cfg(windows)] { Ok(port_info.port_name.clone()) }
+}
+
+async fn connect_to_port(port_path: &str, log: Option<frontend::Logger>) -> Result<HandshakeResponse, ConnectError> {
+    let claim = crate::discovery::claim_serial_port(port_path)?.ok_or(ConnectError::Claimed)?;
+    if let Some(log) = log {
+        log(format_args!("EverDrive: opening port at {port_path:?}"));
+    }
+    let mut port = TimeoutStream::new(tokio_serial::new(port_path, 9_600).open_native_async()?);
+    port.set_read_timeout(Some(TEST_TIMEOUT));
+    port.set_write_timeout(Some(TEST_TIMEOUT));
+    let mut port = Box::pin(port);
+    if let Some(log) = log {
+        log(format_args!("EverDrive: sending cmdt to {port:?}"));
+    }
+    AsyncWriteExt::write_all(&mut port, &HANDSHAKE).await?;
+    AsyncWriteExt::flush(&mut port).await?;
+    if let Some(log) = log {
+        log(format_args!("EverDrive: reading from {port:?}"));
+    }
+    let mut cmd = [0; 16];
+    AsyncReadExt::read_exact(&mut port, &mut cmd).await?;
+    match cmd {
+        [b'O', b'o', b'T', b'R', PROTOCOL_VERSION, major, minor, patch, branch, supplementary, player_id, hash1, hash2, hash3, hash4, hash5] => {
+            if let Some(log) = log {
+                log(format_args!("EverDrive: port is in game"));
+            }
+            port.as_mut().set_read_timeout_pinned(Some(REGULAR_TIMEOUT));
+            port.as_mut().set_write_timeout_pinned(Some(REGULAR_TIMEOUT));
+            let mut buf = [0; 16];
+            buf[0] = b'M';
+            buf[1] = b'W';
+            buf[2] = PROTOCOL_VERSION;
+            buf[3] = 1; // enable MW_SEND_OWN_ITEMS
+            buf[4] = 1; // enable MW_PROGRESSIVE_ITEMS_ENABLE
+            AsyncWriteExt::write_all(&mut port, &buf).await?;
+            Ok(HandshakeResponse {
+                version: ootr_utils::Version::from_bytes([major, minor, patch, branch, supplementary]).ok_or_else(|| ConnectError::Branch(branch))?,
+                player_id: NonZeroU8::new(player_id).ok_or(ConnectError::PlayerId)?,
+                file_hash: [
+                    all().nth(hash1.into()).ok_or(ConnectError::HashIcon)?,
+                    all().nth(hash2.into()).ok_or(ConnectError::HashIcon)?,
+                    all().nth(hash3.into()).ok_or(ConnectError::HashIcon)?,
+                    all().nth(hash4.into()).ok_or(ConnectError::HashIcon)?,
+                    all().nth(hash5.into()).ok_or(ConnectError::HashIcon)?,
+                ],
+                claim, port,
+            })
+        }
+        [b'c', b'm', b'd', b'r', ..] => {
+            if let Some(log) = log {
+                log(format_args!("EverDrive: port is in main menu (cmdr)"));
+            }
+            Err(ConnectError::MainMenu)
+        }
+        [b'c', b'm', b'd', b'k', ..] => {
+            if let Some(log) = log {
+                log(format_args!("EverDrive: port is in main menu (cmdk)"));
+            }
+            Err(ConnectError::MainMenu) // older versions of EverDrive OS
+        }
+        _ => {
+            if let Some(log) = log {
+                log(format_args!("EverDrive: unknown reply from port: {:?}", array_ref![cmd, 0, 4]));
+            }
+            Err(ConnectError::UnknownReply(*array_ref![cmd, 0, 4]))
+        }
+    }
+}
+
+async fn read_from_port(mut port: ReadHalf<Pin<Box<TimeoutStream<SerialStream>>>>) -> io::Result<(ReadHalf<Pin<Box<TimeoutStream<SerialStream>>>>, [u8; 16])> {
+    let mut buf = [0; 16];
+    port.read_exact(&mut buf).await?;
+    Ok((port, buf))
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error(transparent)] Io(#[from] io::Error),
+    #[error(transparent)] SerialPort(#[from] tokio_serial::Error),
+    #[error(transparent)] Task(#[from] tokio::task::JoinError),
+    #[error("received save data segment with out-of-range index")]
+    SaveDataSegment(u8),
+    #[error("received unknown message {0} from EverDrive")]
+    UnknownCommand(u8),
+    #[error("received item for world 0")]
+    PlayerId,
+}
+
+#[derive(Debug)]
+pub enum Event {
+    /// A console was found. Messages for it can be sent using the given channel until the stream yields an error or [`Event::Timeout`].
+    Connected(mpsc::Sender<frontend::ServerMessage>),
+    Message(frontend::ClientMessage),
+    /// No console which is in game was found. Scanning is retried after 5 seconds.
+    ScanFailed(Arc<Vec<(tokio_serial::SerialPortInfo, ConnectError)>>),
+    /// The connected console stopped responding. Scanning is retried after 5 seconds.
+    Timeout,
+}
+
+/// Scans for a console, or connects to the given serial port, and reports everything it sends.
+///
+/// The stream ends after the first error, other than those which are retried.
+pub fn connect(port: Option<String>, log: Option<frontend::Logger>) -> impl Stream<Item = Result<Event, Error>> + Send {
+    enum SubscriptionState {
+        Init {
+            is_retry: bool,
+            port: Option<String>,
+        },
+        Connected {
+            /// Held for as long as the connection is open, so other instances of the app don't try to connect to this EverDrive.
+            claim: SerialClaim,
+            port: Option<String>,
+            session: SessionState,
+            read: Pin<Box<dyn Future<Output = io::Result<(ReadHalf<Pin<Box<TimeoutStream<SerialStream>>>>, [u8; 16])>> + Send>>,
+            writer: WriteHalf<Pin<Box<TimeoutStream<SerialStream>>>>,
+            rx: mpsc::Receiver<frontend::ServerMessage>,
+            version: ootr_utils::Version,
+            player_data: HashMap<NonZeroU8, (Filename, u32)>,
+            save_data: [u8; oottracker::save::SIZE],
+            queue: Vec<u16>,
+        },
+    }
+
+    enum SessionState {
+        Handshake,
+        FileSelect,
+        InGame {
+            internal_count: u16,
+            item_pending: bool,
+        },
+    }
+
+    stream::try_unfold(SubscriptionState::Init { is_retry: false, port }, move |state| async move {
+        let (messages, new_state) = match state {
+            SubscriptionState::Init { is_retry, port } => {
+                if is_retry {
+                    if let Some(log) = log {
+                        log(format_args!("EverDrive: waiting 5 seconds before next scan"));
+                    }
+                    sleep(Duration::from_secs(5)).await;
+                }
+                let mut response = None;
+                let mut errors = Vec::default();
+                let ports = if let Some(ref port) = port {
+                    vec![tokio_serial::SerialPortInfo { port_name: port.clone(), port_type: tokio_serial::SerialPortType::Unknown }]
+                } else {
+                    tokio_serial::available_ports()?
+                };
+                for port_info in ports {
+                    if let Some(log) = log {
+                        log(format_args!("EverDrive: attempting to connect to {port_info:?}"));
+                    }
+                    let port_path = if port.is_some() { Ok(port_info.port_name.clone()) } else { scanned_port_path(&port_info) };
+                    let res = match port_path {
+                        Ok(port_path) => connect_to_port(&port_path, log).await,
+                        Err(e) => Err(e),
+                    };
+                    match res {
+                        Ok(resp) => {
+                            if let Some(log) = log {
+                                log(format_args!("EverDrive: connection successful: {resp:?}"));
+                            }
+                            response = Some(resp);
+                            break
+                        }
+                        Err(e) => {
+                            if let Some(log) = log {
+                                log(format_args!("EverDrive: connection failed: {e:?}"));
+                            }
+                            errors.push((port_info, e));
+                        }
+                    }
+                }
+                if let Some(HandshakeResponse { claim, port, version, player_id, file_hash }) = response {
+                    let (reader, writer) = io::split(port);
+                    let (tx, rx) = mpsc::channel(1_024);
+                    (vec![
+                        Event::Connected(tx),
+                        Event::Message(frontend::ClientMessage::PlayerId(player_id)),
+                        Event::Message(frontend::ClientMessage::FileHash(Some(file_hash))),
+                    ], SubscriptionState::Connected {
+                        session: SessionState::Handshake,
+                        read: read_from_port(reader).boxed(),
+                        player_data: HashMap::default(),
+                        save_data: [0; oottracker::save::SIZE],
+                        queue: Vec::default(),
+                        claim, port, writer, rx, version,
+                    })
+                } else {
+                    (vec![Event::ScanFailed(Arc::new(errors))], SubscriptionState::Init { is_retry: true, port })
+                }
+            }
+            SubscriptionState::Connected { claim, port, mut session, mut read, mut writer, mut rx, version, mut player_data, mut save_data, mut queue } => {
+                async fn send_player_data(port: &mut WriteHalf<Pin<Box<TimeoutStream<SerialStream>>>>, world: NonZeroU8, name: Filename, progressive_items: u32) -> io::Result<()> {
+                    let mut buf = [0; 16];
+                    buf[0] = 0x01; // Player Data
+                    buf[1] = world.get();
+                    *array_mut_ref![buf, 2, 8] = name.0;
+                    *array_mut_ref![buf, 10, 4] = progressive_items.to_be_bytes();
+                    port.write_all(&buf).await?;
+                    Ok(())
+                }
+
+                async fn get_item(port: &mut WriteHalf<Pin<Box<TimeoutStream<SerialStream>>>>, queue: &[u16], internal_count: &mut u16) -> io::Result<bool> {
+                    Ok(if let Some(item) = queue.get(usize::from(*internal_count)) {
+                        let mut buf = [0; 16];
+                        buf[0] = 0x02; // Get Item
+                        *array_mut_ref![buf, 1, 2] = item.to_be_bytes();
+                        port.write_all(&buf).await?;
+                        *internal_count += 1;
+                        true
+                    } else {
+                        false
+                    })
+
+                }
+
+                select! {
+                    Some(msg) = rx.recv() => {
+                        match msg {
+                            frontend::ServerMessage::ItemQueue(new_queue) => {
+                                queue = new_queue;
+                                if let SessionState::InGame { ref mut internal_count, ref mut item_pending } = session {
+                                    if !*item_pending && get_item(&mut writer, &queue, internal_count).await? {
+                                        *item_pending = true;
+                                    }
+                                }
+                            }
+                            frontend::ServerMessage::GetItem(item_id) => {
+                                queue.push(item_id);
+                                if let SessionState::InGame { ref mut internal_count, ref mut item_pending } = session {
+                                    if !*item_pending && get_item(&mut writer, &queue, internal_count).await? {
+                                        *item_pending = true;
+                                    }
+                                }
+                            }
+                            frontend::ServerMessage::PlayerName(world, new_name) => {
+                                let (name, progressive_items) = player_data.entry(world).or_default();
+                                *name = new_name;
+                                send_player_data(&mut writer, world, *name, *progressive_items).await?;
+                            }
+                            frontend::ServerMessage::ProgressiveItems(world, new_progressive_items) => {
+                                let (name, progressive_items) = player_data.entry(world).or_default();
+                                *progressive_items = new_progressive_items;
+                                send_player_data(&mut writer, world, *name, *progressive_items).await?;
+                            }
+                        }
+                        (Vec::default(), SubscriptionState::Connected { claim, port, session, read, writer, rx, version, player_data, save_data, queue })
+                    }
+                    res = &mut read => match res {
+                        Ok((mut reader, buf)) => (
+                            match buf[0] {
+                                0x00 => Vec::default(), // Ping
+                                0x01 => { // State: File Select
+                                    session = SessionState::FileSelect;
+                                    vec![Event::Message(frontend::ClientMessage::PlayerName(Filename(*array_ref![buf, 1, 8])))]
+                                }
+                                0x02 => { // State: In Game
+                                    if version.branch() == ootr_utils::Branch::DevFenhl && version.base().cmp(&semver::Version::new(8, 3, 68)).then_with(|| version.supplementary().cmp(&Some(2))) == Less {
+                                        // older iteration of this packet without the full save data
+                                        let mut internal_count = u16::from_be_bytes(*array_ref![buf, 1, 2]);
+                                        let item_pending = if let SessionState::InGame { item_pending, .. } = session {
+                                            item_pending
+                                        } else {
+                                            for (world, (name, progressive_items)) in mem::take(&mut player_data) {
+                                                send_player_data(&mut writer, world, name, progressive_items).await?;
+                                            }
+                                            get_item(&mut writer, &queue, &mut internal_count).await?
+                                        };
+                                        session = SessionState::InGame { internal_count, item_pending };
+                                    } else {
+                                        *array_mut_ref![save_data, 0, 15] = *array_ref![buf, 1, 15];
+                                        reader.read_exact(array_mut_ref![save_data, 15, 200 - 15]).await?;
+                                    }
+                                    Vec::default()
+                                }
+                                0x03 => vec![Event::Message(frontend::ClientMessage::SendItem {
+                                    key: u64::from_be_bytes(*array_ref![buf, 1, 8]),
+                                    kind: u16::from_be_bytes(*array_ref![buf, 9, 2]),
+                                    target_world: NonZeroU8::new(buf[11]).ok_or(Error::PlayerId)?,
+                                })],
+                                0x04 => { // Item Received
+                                    if let SessionState::InGame { ref mut internal_count, ref mut item_pending } = session {
+                                        if !get_item(&mut writer, &queue, internal_count).await? {
+                                            *item_pending = false;
+                                        }
+                                    }
+                                    Vec::default()
+                                }
+                                0x05 => { // Dungeon Reward Locations
+                                    let mut rest = [0; 3];
+                                    reader.read_exact(&mut rest).await?;
+                                    let [_, emerald_world, emerald_area, ruby_world, ruby_area, sapphire_world, sapphire_area, light_world, light_area, forest_world, forest_area, fire_world, fire_area, water_world, water_area, shadow_world] = buf;
+                                    let [shadow_area, spirit_world, spirit_area] = rest;
+                                    vec![Event::Message(frontend::ClientMessage::DungeonRewardInfo {
+                                        emerald: if let (Some(world), Some(area)) = (NonZeroU8::new(emerald_world), OptHintArea::from_u8(emerald_area).and_then(|area| HintArea::try_from(area).ok())) { Some((world, area)) } else { None },
+                                        ruby: if let (Some(world), Some(area)) = (NonZeroU8::new(ruby_world), OptHintArea::from_u8(ruby_area).and_then(|area| HintArea::try_from(area).ok())) { Some((world, area)) } else { None },
+                                        sapphire: if let (Some(world), Some(area)) = (NonZeroU8::new(sapphire_world), OptHintArea::from_u8(sapphire_area).and_then(|area| HintArea::try_from(area).ok())) { Some((world, area)) } else { None },
+                                        light: if let (Some(world), Some(area)) = (NonZeroU8::new(light_world), OptHintArea::from_u8(light_area).and_then(|area| HintArea::try_from(area).ok())) { Some((world, area)) } else { None },
+                                        forest: if let (Some(world), Some(area)) = (NonZeroU8::new(forest_world), OptHintArea::from_u8(forest_area).and_then(|area| HintArea::try_from(area).ok())) { Some((world, area)) } else { None },
+                                        fire: if let (Some(world), Some(area)) = (NonZeroU8::new(fire_world), OptHintArea::from_u8(fire_area).and_then(|area| HintArea::try_from(area).ok())) { Some((world, area)) } else { None },
+                                        water: if let (Some(world), Some(area)) = (NonZeroU8::new(water_world), OptHintArea::from_u8(water_area).and_then(|area| HintArea::try_from(area).ok())) { Some((world, area)) } else { None },
+                                        shadow: if let (Some(world), Some(area)) = (NonZeroU8::new(shadow_world), OptHintArea::from_u8(shadow_area).and_then(|area| HintArea::try_from(area).ok())) { Some((world, area)) } else { None },
+                                        spirit: if let (Some(world), Some(area)) = (NonZeroU8::new(spirit_world), OptHintArea::from_u8(spirit_area).and_then(|area| HintArea::try_from(area).ok())) { Some((world, area)) } else { None },
+                                    })]
+                                }
+                                0x06 => { // Save Data Segment
+                                    let segment_idx = buf[1];
+                                    if segment_idx >= 10 { return Err(Error::SaveDataSegment(segment_idx)) }
+                                    *array_mut_ref![save_data, 500 * usize::from(segment_idx) + 200, 14] = *array_ref![buf, 2, 14];
+                                    reader.read_exact(array_mut_ref![save_data, 500 * usize::from(segment_idx) + 214, 500 - 14]).await?;
+                                    if segment_idx == 9 {
+                                        let mut internal_count = u16::from_be_bytes(*array_ref![save_data, 0x90, 2]);
+                                        let item_pending = if let SessionState::InGame { item_pending, .. } = session {
+                                            item_pending
+                                        } else {
+                                            for (world, (name, progressive_items)) in mem::take(&mut player_data) {
+                                                send_player_data(&mut writer, world, name, progressive_items).await?;
+                                            }
+                                            get_item(&mut writer, &queue, &mut internal_count).await?
+                                        };
+                                        session = SessionState::InGame { internal_count, item_pending };
+                                    }
+                                    vec![Event::Message(frontend::ClientMessage::SaveData(save_data))]
+                                }
+                                cmd => return Err(Error::UnknownCommand(cmd)),
+                            },
+                            SubscriptionState::Connected {
+                                read: read_from_port(reader).boxed(),
+                                claim, port, session, writer, rx, version, player_data, save_data, queue,
+                            },
+                        ),
+                        Err(e) => match e.kind() {
+                            io::ErrorKind::TimedOut => (
+                                vec![Event::Timeout],
+                                SubscriptionState::Init { is_retry: true, port },
+                            ),
+                            _ => return Err(e.into()),
+                        },
+                    },
+                }
+            }
+        };
+        Ok::<_, Error>(Some((stream::iter(messages).map(Ok::<_, Error>), new_state)))
+    }).try_flatten()
+}
@@ -1,26 +1,160 @@
 use {
     std::{
         fmt,
+        io::prelude::*,
         num::NonZeroU8,
     },
     async_proto::Protocol,
+    bitflags::bitflags,
     enum_iterator::Sequence,
     ootr_utils::spoiler::HashIcon,
     serde::{
         Deserialize,
         Serialize,
     },
+    tokio::io::{
+        AsyncRead,
+        AsyncWrite,
+    },
+    wheel::traits::IsNetworkError,
     crate::{
         Filename,
         HintArea,
+        save_diff,
     },
 };
 
 /// The default port used for connections between client and frontend.
 ///
+/// The Project64 3.x script always connects to this port, see [`crate::discovery`] for how other frontends avoid collisions.
 /// If possible, prefer having the OS select an unused port dynamically, to allow multiple instances of multiworld to run in parallel on the same computer.
 pub const PORT: u16 = 24818;
-pub const PROTOCOL_VERSION: u8 = 8;
+/// The newest frontend protocol version supported by this version of multiworld.
+pub const PROTOCOL_VERSION: u8 = 9;
+/// The oldest frontend protocol version supported by this version of multiworld.
+///
+/// Versions from this one up to but excluding [`NEGOTIATION_VERSION`] can't negotiate capabilities, so this is only a version range check: such frontends are assumed to support exactly [`Capabilities::LEGACY`].
+pub const MIN_PROTOCOL_VERSION: u8 = 8;
+/// The first protocol version which exchanges a [`Handshake`] after the version byte. Older versions only exchange a version byte and require it to match exactly.
+const NEGOTIATION_VERSION: u8 = 9;
+
+/// Writes a line to the debug log. Frontends which are built into the app, like [`crate::everdrive`], use this to report connection details.
+pub type Logger = fn(fmt::Arguments<'_>);
+
+bitflags! {
+    /// Optional features of the frontend protocol, negotiated during the handshake.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Capabilities: u32 {
+        /// The frontend appends items received via [`ServerMessage::GetItem`] to its queue. Without this, the whole queue is resent using [`ServerMessage::ItemQueue`] instead.
+        const DELTA_QUEUES = 0x0000_0001;
+        /// The frontend reports the scene the player is in using [`ClientMessage::CurrentScene`].
+        const SCENE_REPORTING = 0x0000_0002;
+        /// The frontend reports dungeon reward locations using [`ClientMessage::DungeonRewardInfo`].
+        const DUNGEON_REWARD_INFO = 0x0000_0004;
+        /// The frontend handles [`ServerMessage::ProgressiveItems`]. Without this, those messages aren't sent.
+        const PROGRESSIVE_ITEMS = 0x0000_0008;
+        /// The frontend may send [`ClientMessage::SaveDataDiff`] instead of [`ClientMessage::SaveData`].
+        const SAVE_DATA_DIFFS = 0x0000_0010;
+    }
+}
+
+impl Capabilities {
+    /// The capabilities of frontends speaking a protocol version older than [`NEGOTIATION_VERSION`].
+    ///
+    /// These are the features which were part of protocol version 8, so every frontend accepted by [`MIN_PROTOCOL_VERSION`] supports all of them.
+    pub const LEGACY: Self = Self::DELTA_QUEUES.union(Self::SCENE_REPORTING).union(Self::DUNGEON_REWARD_INFO).union(Self::PROGRESSIVE_ITEMS);
+}
+
+/// Sent by both sides after the version byte, if both sides support protocol version 9 or newer.
+#[derive(Debug, Protocol)]
+struct Handshake {
+    min_version: u8,
+    max_version: u8,
+    capabilities: u32,
+}
+
+impl Handshake {
+    fn new(capabilities: Capabilities) -> Self {
+        Self {
+            min_version: MIN_PROTOCOL_VERSION,
+            max_version: PROTOCOL_VERSION,
+            capabilities: capabilities.bits(),
+        }
+    }
+
+    fn negotiate(&self, peer: &Self) -> Result<Negotiated, HandshakeError> {
+        let version = self.max_version.min(peer.max_version);
+        if version < self.min_version.max(peer.min_version) {
+            return Err(HandshakeError::VersionMismatch { min: peer.min_version, max: peer.max_version })
+        }
+        Ok(Negotiated {
+            capabilities: Capabilities::from_bits_truncate(self.capabilities & peer.capabilities),
+            version,
+        })
+    }
+}
+
+/// The result of a successful handshake.
+#[derive(Debug, Clone, Copy)]
+pub struct Negotiated {
+    pub version: u8,
+    /// The capabilities supported by both sides.
+    pub capabilities: Capabilities,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum HandshakeError {
+    #[error(transparent)] Read(#[from] async_proto::ReadError),
+    #[error(transparent)] Write(#[from] async_proto::WriteError),
+    #[error("protocol version mismatch: the other side supports versions {min} to {max} but we support versions {MIN_PROTOCOL_VERSION} to {PROTOCOL_VERSION}")]
+    VersionMismatch {
+        min: u8,
+        max: u8,
+    },
+}
+
+impl IsNetworkError for HandshakeError {
+    fn is_network_error(&self) -> bool {
+        match self {
+            Self::Read(e) => e.is_network_error(),
+            Self::Write(e) => e.is_network_error(),
+            Self::VersionMismatch { .. } => false,
+        }
+    }
+}
+
+/// Performs the handshake on the app's side of a frontend connection.
+///
+/// The frontend sends its newest supported version first. Frontends older than [`NEGOTIATION_VERSION`] expect the same version byte back and nothing else, so in that case the version byte is echoed and the result is `capabilities` limited to [`Capabilities::LEGACY`].
+/// Otherwise, the app replies with its own newest version and both sides send a [`Handshake`]. Frontends may send their handshake without waiting for the app's version byte.
+pub async fn handshake_app(stream: &mut (impl AsyncRead + AsyncWrite + Unpin + Send), capabilities: Capabilities) -> Result<Negotiated, HandshakeError> {
+    let frontend_version = u8::read(stream).await?;
+    if frontend_version < MIN_PROTOCOL_VERSION {
+        PROTOCOL_VERSION.write(stream).await?;
+        Err(HandshakeError::VersionMismatch { min: frontend_version, max: frontend_version })
+    } else if frontend_version < NEGOTIATION_VERSION {
+        frontend_version.write(stream).await?;
+        Ok(Negotiated { version: frontend_version, capabilities: capabilities & Capabilities::LEGACY })
+    } else {
+        let handshake = Handshake::new(capabilities);
+        PROTOCOL_VERSION.write(stream).await?;
+        handshake.write(stream).await?;
+        handshake.negotiate(&Handshake::read(stream).await?)
+    }
+}
+
+/// Performs the handshake on the frontend's side of a connection, see [`handshake_app`].
+pub fn handshake_frontend_sync(stream: &mut (impl Read + Write), capabilities: Capabilities) -> Result<Negotiated, HandshakeError> {
+    let handshake = Handshake::new(capabilities);
+    PROTOCOL_VERSION.write_sync(stream)?;
+    handshake.write_sync(stream)?;
+    let app_version = u8::read_sync(stream)?;
+    if app_version < NEGOTIATION_VERSION {
+        // apps older than the negotiation version always send their own version first and require an exact match, so this connection can't work
+        return Err(HandshakeError::VersionMismatch { min: app_version, max: app_version })
+    }
+    handshake.negotiate(&Handshake::read_sync(stream)?)
+}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Sequence, Deserialize, Serialize, clap::ValueEnum)]
 #[clap(rename_all = "lower")]
@@ -30,6 +164,7 @@ pub enum Kind {
     BizHawk,
     Pj64V3,
     Pj64V4,
+    RetroArch,
 }
 
 impl Kind {
@@ -40,6 +175,7 @@ impl Kind {
             Self::BizHawk => cfg!(any(target_os = "linux", target_os = "windows")),
             Self::Pj64V3 => cfg!(target_os = "windows"),
             Self::Pj64V4 => false, // hide until Project64 version 4 is released
+            Self::RetroArch => true,
         }
     }
 }
@@ -51,6 +187,7 @@ impl fmt::Display for Kind {
             Self::EverDrive => write!(f, "EverDrive"),
             Self::BizHawk => write!(f, "BizHawk"),
             Self::Pj64V3 | Self::Pj64V4 => write!(f, "Project64"),
+            Self::RetroArch => write!(f, "RetroArch"),
         }
     }
 }
@@ -87,4 +224,85 @@ pub enum ClientMessage {
         spirit: Option<(NonZeroU8, HintArea)>,
     },
     CurrentScene(u8),
+    /// Changes to the save data since the last [`ClientMessage::SaveData`] or `SaveDataDiff` on this connection. Like `SaveData`, this means that the player has loaded the save.
+    SaveDataDiff(Vec<save_diff::Range>),
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        std::collections::HashMap,
+        super::*,
+    };
+
+    #[tokio::test]
+    async fn handshake_v8_frontend() {
+        let (mut app, mut frontend) = tokio::io::duplex(64);
+        8u8.write(&mut frontend).await.expect("failed to write frontend version");
+        let negotiated = handshake_app(&mut app, Capabilities::all()).await.expect("handshake failed");
+        assert_eq!(negotiated.version, 8);
+        assert_eq!(negotiated.capabilities, Capabilities::LEGACY);
+        assert_eq!(u8::read(&mut frontend).await.expect("failed to read app version"), 8);
+    }
+
+    #[tokio::test]
+    async fn handshake_v9_frontend_with_fewer_capabilities() {
+        let (mut app, mut frontend) = tokio::io::duplex(64);
+        9u8.write(&mut frontend).await.expect("failed to write frontend version");
+        Handshake { min_version: 9, max_version: 9, capabilities: Capabilities::DELTA_QUEUES.bits() }.write(&mut frontend).await.expect("failed to write frontend handshake");
+        let negotiated = handshake_app(&mut app, Capabilities::all()).await.expect("handshake failed");
+        assert_eq!(negotiated.version, 9);
+        assert_eq!(negotiated.capabilities, Capabilities::DELTA_QUEUES);
+        assert_eq!(u8::read(&mut frontend).await.expect("failed to read app version"), PROTOCOL_VERSION);
+        let handshake = Handshake::read(&mut frontend).await.expect("failed to read app handshake");
+        assert_eq!(handshake.min_version, MIN_PROTOCOL_VERSION);
+        assert_eq!(handshake.max_version, PROTOCOL_VERSION);
+        assert_eq!(handshake.capabilities, Capabilities::all().bits());
+    }
+
+    #[tokio::test]
+    async fn handshake_newer_frontend() {
+        let (mut app, mut frontend) = tokio::io::duplex(64);
+        (PROTOCOL_VERSION + 1).write(&mut frontend).await.expect("failed to write frontend version");
+        Handshake { min_version: PROTOCOL_VERSION, max_version: PROTOCOL_VERSION + 1, capabilities: u32::MAX }.write(&mut frontend).await.expect("failed to write frontend handshake");
+        let negotiated = handshake_app(&mut app, Capabilities::LEGACY).await.expect("handshake failed");
+        assert_eq!(negotiated.version, PROTOCOL_VERSION);
+        assert_eq!(negotiated.capabilities, Capabilities::LEGACY);
+    }
+
+    #[tokio::test]
+    async fn handshake_outdated_frontend() {
+        let (mut app, mut frontend) = tokio::io::duplex(64);
+        (MIN_PROTOCOL_VERSION - 1).write(&mut frontend).await.expect("failed to write frontend version");
+        assert!(matches!(handshake_app(&mut app, Capabilities::all()).await, Err(HandshakeError::VersionMismatch { .. })));
+        assert_eq!(u8::read(&mut frontend).await.expect("failed to read app version"), PROTOCOL_VERSION);
+    }
+
+    fn js_value(constants: &HashMap<&str, &str>, name: &str) -> u32 {
+        constants.get(name).unwrap_or_else(|| panic!("missing constant {name} in Project64 script")).split(" | ").map(|term| if let Some(hex) = term.strip_prefix("0x") {
+            u32::from_str_radix(hex, 16).expect("invalid hex literal in Project64 script")
+        } else if let Ok(value) = term.parse() {
+            value
+        } else {
+            js_value(constants, term)
+        }).fold(0, |acc, value| acc | value)
+    }
+
+    /// The Project64 script can't use this module, so it keeps its own copies of the protocol constants.
+    #[test]
+    fn pj64_script_constants() {
+        let constants = include_str!("../../../assets/ootrmw-pj64.js").lines()
+            .filter_map(|line| line.strip_prefix("const ")?.strip_suffix(';')?.split_once(" = "))
+            .collect::<HashMap<_, _>>();
+        assert_eq!(js_value(&constants, "MW_FRONTEND_PROTO_VERSION"), u32::from(PROTOCOL_VERSION));
+        assert!(js_value(&constants, "MW_FRONTEND_MIN_PROTO_VERSION") >= u32::from(NEGOTIATION_VERSION));
+        assert_eq!(js_value(&constants, "MW_FRONTEND_NEGOTIATION_VERSION"), u32::from(NEGOTIATION_VERSION));
+        for name in constants.keys() {
+            if let Some(flag) = name.strip_prefix("MW_FRONTEND_CAPABILITY_") {
+                let capability = Capabilities::from_name(flag).unwrap_or_else(|| panic!("unknown capability {flag} in Project64 script"));
+                assert_eq!(js_value(&constants, name), capability.bits(), "wrong value for capability {flag} in Project64 script");
+            }
+        }
+        assert!(Capabilities::from_bits(js_value(&constants, "MW_FRONTEND_CAPABILITIES")).is_some());
+    }
 }
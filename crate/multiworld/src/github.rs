@@ -1,20 +1,166 @@
 use {
-    std::future::Future,
+    std::{
+        collections::BTreeMap,
+        future::Future,
+    },
+    itertools::Itertools as _,
     reqwest::{
         Body,
         Client,
         StatusCode,
     },
+    ring::{
+        digest::{
+            SHA256,
+            digest,
+        },
+        signature::{
+            ED25519,
+            Ed25519KeyPair,
+            KeyPair as _,
+            UnparsedPublicKey,
+        },
+    },
     semver::Version,
-    serde::Deserialize,
+    serde::{
+        Deserialize,
+        Serialize,
+    },
     serde_json::json,
     url::Url,
     wheel::traits::{
         RequestBuilderExt as _,
         ReqwestResponseExt as _,
     },
+    crate::config::UpdateChannel,
 };
 
+/// The name of the release asset listing the SHA-256 hashes of all other assets.
+pub const MANIFEST_NAME: &str = "manifest.json";
+/// The name of the release asset containing the Ed25519 signature of the manifest.
+pub const MANIFEST_SIGNATURE_NAME: &str = "manifest.json.sig";
+
+/// The first version whose releases include a signed manifest. Older releases can't be verified, so the updater doesn't install them.
+pub const FIRST_SIGNED_VERSION: Version = Version::new(18, 0, 0);
+
+/// The Ed25519 public keys trusted to sign release manifests.
+///
+/// Only public keys belong here. The private key is kept offline by the maintainers and passed to `multiworld-release` using `--signing-key`.
+/// See `assets/doc/release-signing.md` for how it's generated, stored, and rotated.
+const RELEASE_SIGNING_KEYS: &[[u8; 32]] = &[
+    [
+        0xef, 0x6e, 0xcb, 0x07, 0x8e, 0xf2, 0x82, 0x4b, 0xf6, 0x02, 0xc4, 0xb1,
+        0xc7, 0x2e, 0xb0, 0x61, 0xf4, 0xbf, 0x22, 0xf0, 0xf3, 0x0d, 0x71, 0xcf,
+        0x9e, 0x3c, 0xf0, 0xe4, 0xcc, 0xbf, 0x3e, 0x46,
+    ],
+];
+
+#[derive(Debug, thiserror::Error)]
+pub enum ManifestError {
+    #[error(transparent)] Json(#[from] serde_json::Error),
+    #[error(transparent)] KeyRejected(#[from] ring::error::KeyRejected),
+    #[error("release asset {0} is not listed in the release manifest")]
+    MissingAsset(String),
+    #[error("release asset {0} does not match the hash in the release manifest")]
+    HashMismatch(String),
+    #[error("the release manifest has an invalid signature")]
+    Signature,
+    #[error("the release signing key is not among the keys trusted by this version")]
+    UntrustedKey,
+    #[error("the release manifest is for version {manifest} but the release is version {release}")]
+    VersionMismatch {
+        manifest: Version,
+        release: Version,
+    },
+}
+
+/// A list of the assets of a release along with their SHA-256 hashes, signed by `multiworld-release`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReleaseManifest {
+    pub version: Version,
+    /// Maps asset names to lowercase hex-encoded SHA-256 hashes.
+    pub assets: BTreeMap<String, String>,
+}
+
+impl ReleaseManifest {
+    pub fn new(version: Version) -> Self {
+        Self {
+            version,
+            assets: BTreeMap::default(),
+        }
+    }
+
+    pub fn add_asset(&mut self, name: impl ToString, data: &[u8]) {
+        self.assets.insert(name.to_string(), sha256_hex(data));
+    }
+
+    pub fn to_json(&self) -> Result<Vec<u8>, ManifestError> {
+        Ok(serde_json::to_vec_pretty(self)?)
+    }
+
+    /// Checks that the given PKCS#8-encoded Ed25519 private key can be used to sign releases, i.e. that its public key is trusted by this version.
+    pub fn check_signing_key(private_key: &[u8]) -> Result<(), ManifestError> {
+        trusted_key_pair(private_key, RELEASE_SIGNING_KEYS)?;
+        Ok(())
+    }
+
+    /// Serializes the manifest and signs it with the given PKCS#8-encoded Ed25519 private key, which must be trusted by this version.
+    ///
+    /// Returns the serialized manifest and its signature, to be uploaded as [`MANIFEST_NAME`] and [`MANIFEST_SIGNATURE_NAME`].
+    pub fn sign(&self, private_key: &[u8]) -> Result<(Vec<u8>, Vec<u8>), ManifestError> {
+        self.sign_with_keys(private_key, RELEASE_SIGNING_KEYS)
+    }
+
+    fn sign_with_keys(&self, private_key: &[u8], trusted_keys: &[[u8; 32]]) -> Result<(Vec<u8>, Vec<u8>), ManifestError> {
+        let key_pair = trusted_key_pair(private_key, trusted_keys)?;
+        let manifest = self.to_json()?;
+        let signature = key_pair.sign(&manifest).as_ref().to_vec();
+        Ok((manifest, signature))
+    }
+
+    /// Parses a manifest after checking its signature against the embedded release signing keys.
+    pub fn verify(manifest: &[u8], signature: &[u8], release_version: &Version) -> Result<Self, ManifestError> {
+        Self::verify_with_keys(manifest, signature, release_version, RELEASE_SIGNING_KEYS)
+    }
+
+    fn verify_with_keys(manifest: &[u8], signature: &[u8], release_version: &Version, trusted_keys: &[[u8; 32]]) -> Result<Self, ManifestError> {
+        if !trusted_keys.iter().any(|key| UnparsedPublicKey::new(&ED25519, key).verify(manifest, signature).is_ok()) {
+            return Err(ManifestError::Signature)
+        }
+        let manifest = serde_json::from_slice::<Self>(manifest)?;
+        if manifest.version != *release_version {
+            return Err(ManifestError::VersionMismatch {
+                manifest: manifest.version,
+                release: release_version.clone(),
+            })
+        }
+        Ok(manifest)
+    }
+
+    /// Checks that the given asset data matches the hash listed in the manifest.
+    pub fn check_asset(&self, name: &str, data: &[u8]) -> Result<(), ManifestError> {
+        let expected = self.assets.get(name).ok_or_else(|| ManifestError::MissingAsset(name.to_owned()))?;
+        if *expected == sha256_hex(data) {
+            Ok(())
+        } else {
+            Err(ManifestError::HashMismatch(name.to_owned()))
+        }
+    }
+}
+
+fn trusted_key_pair(private_key: &[u8], trusted_keys: &[[u8; 32]]) -> Result<Ed25519KeyPair, ManifestError> {
+    let key_pair = Ed25519KeyPair::from_pkcs8_maybe_unchecked(private_key)?;
+    if trusted_keys.iter().any(|key| key[..] == *key_pair.public_key().as_ref()) {
+        Ok(key_pair)
+    } else {
+        Err(ManifestError::UntrustedKey)
+    }
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    format!("{:02x}", digest(&SHA256, data).as_ref().iter().format(""))
+}
+
 #[cfg(feature = "github-app-auth")]
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
@@ -53,7 +199,9 @@ pub struct Label {
 #[derive(Debug, Clone, Deserialize)]
 pub struct Release {
     pub assets: Vec<ReleaseAsset>,
+    pub body: Option<String>,
     id: u64,
+    pub prerelease: bool,
     pub tag_name: String,
     upload_url: String,
 }
@@ -120,6 +268,45 @@ impl Repo {
         ))
     }
 
+    /// Returns the most recent releases, newest first. Draft releases are only included if the client is authorized to see them.
+    pub async fn releases(&self, client: &Client) -> wheel::Result<Vec<Release>> {
+        Ok(client.get(&format!("https://api.github.com/repos/{}/{}/releases", self.user, self.name))
+            .query(&[("per_page", "100")])
+            .send_github(false).await?
+            .detailed_error_for_status().await?
+            .json_with_text_in_error().await?)
+    }
+
+    /// Returns the release that should be installed according to the given update channel.
+    pub async fn release_for_channel(&self, client: &Client, channel: &UpdateChannel) -> wheel::Result<Option<Release>> {
+        match channel {
+            UpdateChannel::Stable => self.latest_release(client).await,
+            UpdateChannel::Beta => Ok(
+                self.releases(client).await?
+                    .into_iter()
+                    .filter_map(|release| Some((release.version().ok()?, release)))
+                    .max_by(|(version1, _), (version2, _)| version1.cmp(version2))
+                    .map(|(_, release)| release)
+            ),
+            UpdateChannel::Pinned(version) => self.release_by_tag(client, &format!("v{version}")).await,
+        }
+    }
+
+    /// Returns the release notes for updating from `installed` to `target`, as Markdown.
+    ///
+    /// This includes the notes of all releases newer than `installed` up to and including `target`, newest first. If `target` is older than `installed`, only its own notes are included.
+    pub async fn release_notes(&self, client: &Client, installed: &Version, target: &Version) -> wheel::Result<String> {
+        let mut releases = self.releases(client).await?
+            .into_iter()
+            .filter_map(|release| {
+                let version = release.version().ok()?;
+                (version == *target || version > *installed && version < *target).then(|| (version, release.body.unwrap_or_default()))
+            })
+            .collect_vec();
+        releases.sort_by(|(version1, _), (version2, _)| version2.cmp(version1));
+        Ok(releases.into_iter().map(|(version, body)| format!("## {version}\n\n{}\n", body.trim())).join("\n"))
+    }
+
     pub async fn release_by_tag(&self, client: &Client, tag: &str) -> wheel::Result<Option<Release>> {
         let response = client.get(&format!("https://api.github.com/repos/{}/{}/releases/tags/{tag}", self.user, self.name))
             .send_github(false).await?;
@@ -170,3 +357,68 @@ impl Repo {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use {
+        ring::rand::SystemRandom,
+        super::*,
+    };
+
+    fn test_key() -> (Vec<u8>, [u8; 32]) {
+        let private_key = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).expect("failed to generate test key");
+        let key_pair = Ed25519KeyPair::from_pkcs8(private_key.as_ref()).expect("generated test key is invalid");
+        let public_key = key_pair.public_key().as_ref().try_into().expect("Ed25519 public keys are 32 bytes");
+        (private_key.as_ref().to_vec(), public_key)
+    }
+
+    fn test_manifest() -> ReleaseManifest {
+        let mut manifest = ReleaseManifest::new(Version::new(18, 0, 0));
+        manifest.add_asset("multiworld-pj64.exe", b"exe");
+        manifest
+    }
+
+    #[test]
+    fn sign_and_verify() {
+        let (private_key, public_key) = test_key();
+        let (manifest, signature) = test_manifest().sign_with_keys(&private_key, &[public_key]).expect("failed to sign manifest");
+        let manifest = ReleaseManifest::verify_with_keys(&manifest, &signature, &Version::new(18, 0, 0), &[public_key]).expect("failed to verify manifest");
+        manifest.check_asset("multiworld-pj64.exe", b"exe").expect("asset should match manifest");
+        assert!(matches!(manifest.check_asset("multiworld-pj64.exe", b"tampered"), Err(ManifestError::HashMismatch(_))));
+        assert!(matches!(manifest.check_asset("multiworld-gui-linux", b"exe"), Err(ManifestError::MissingAsset(_))));
+    }
+
+    /// The fixture was signed with the private key belonging to the first entry of [`RELEASE_SIGNING_KEYS`], see `assets/doc/release-signing.md`.
+    #[test]
+    fn verify_release_signing_key() {
+        let manifest = ReleaseManifest::verify(
+            include_bytes!("../../../assets/test/release-manifest.json"),
+            include_bytes!("../../../assets/test/release-manifest.json.sig"),
+            &Version::new(18, 0, 0),
+        ).expect("manifest signed with the release signing key should verify");
+        manifest.check_asset("multiworld-pj64.exe", b"exe").expect("asset should match manifest");
+    }
+
+    #[test]
+    fn untrusted_key() {
+        let (private_key, _) = test_key();
+        let (_, other_public_key) = test_key();
+        assert!(matches!(test_manifest().sign_with_keys(&private_key, &[other_public_key]), Err(ManifestError::UntrustedKey)));
+        assert!(matches!(test_manifest().sign_with_keys(&private_key, &[]), Err(ManifestError::UntrustedKey)));
+    }
+
+    #[test]
+    fn verify_rejects_other_key() {
+        let (private_key, public_key) = test_key();
+        let (_, other_public_key) = test_key();
+        let (manifest, signature) = test_manifest().sign_with_keys(&private_key, &[public_key]).expect("failed to sign manifest");
+        assert!(matches!(ReleaseManifest::verify_with_keys(&manifest, &signature, &Version::new(18, 0, 0), &[other_public_key]), Err(ManifestError::Signature)));
+    }
+
+    #[test]
+    fn verify_rejects_other_version() {
+        let (private_key, public_key) = test_key();
+        let (manifest, signature) = test_manifest().sign_with_keys(&private_key, &[public_key]).expect("failed to sign manifest");
+        assert!(matches!(ReleaseManifest::verify_with_keys(&manifest, &signature, &Version::new(18, 0, 1), &[public_key]), Err(ManifestError::VersionMismatch { .. })));
+    }
+}
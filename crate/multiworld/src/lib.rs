@@ -3,11 +3,13 @@ use {
         collections::{
             BTreeMap,
             HashSet,
+            btree_map,
             hash_map::{
                 self,
                 HashMap,
             },
         },
+        convert::Infallible,
         fmt,
         hash::Hash,
         mem,
@@ -100,15 +102,27 @@ use {
     types::Json,
 };
 
+#[cfg(feature = "client")] pub mod bridge;
+#[cfg(feature = "client")] pub mod capture;
+#[cfg(feature = "client")] pub mod client;
 pub mod config;
+pub mod discovery;
+#[cfg(feature = "everdrive")] pub mod everdrive;
 pub mod frontend;
 pub mod github;
+#[cfg(feature = "client")] pub mod persistent_state;
+#[cfg(feature = "client")] pub mod retroarch;
+pub mod save_diff;
+pub mod script;
 pub mod ws;
 
 pub const DEFAULT_TCP_PORT: u16 = 24809; //TODO use for LAN support (https://github.com/midoshouse/ootr-multiworld/issues/3)
 
 pub const CREDENTIAL_LEN: usize = ring::digest::SHA512_OUTPUT_LEN;
 
+/// The longest countdown accepted by [`Room::start`].
+pub const MAX_START_COUNTDOWN: Duration = Duration::from_secs(60 * 60);
+
 pub fn version() -> Version { Version::parse(env!("CARGO_PKG_VERSION")).expect("failed to parse package version") }
 
 pub fn user_agent_hash(version: &str) -> Option<[u8; CREDENTIAL_LEN]> {
@@ -580,11 +594,13 @@ pub struct Client<C: ClientKind> {
     pub pending_items: Vec<(u64, u16, NonZero<u8>)>,
     pub tracker_state: oottracker::ModelState,
     pub adjusted_save: oottracker::Save,
+    /// The save data last received from this client in this room session, encoded using [`save_diff::encode`], which [`unversioned::ClientMessage::SaveDataDiff`] applies to.
+    pub save_data: Option<Box<[u8; oottracker::save::SIZE]>>,
 }
 
 impl<C: ClientKind> fmt::Debug for Client<C> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let Self { version, writer: _, end_tx, player, pending_world, pending_name, pending_hash, pending_items, tracker_state, adjusted_save } = self;
+        let Self { version, writer: _, end_tx, player, pending_world, pending_name, pending_hash, pending_items, tracker_state, adjusted_save, save_data } = self;
         f.debug_struct("Client")
             .field("version", version)
             .field("writer", &format_args!("_"))
@@ -596,6 +612,7 @@ impl<C: ClientKind> fmt::Debug for Client<C> {
             .field("pending_items", pending_items)
             .field("tracker_state", tracker_state)
             .field("adjusted_save", adjusted_save)
+            .field("save_data", save_data)
             .finish()
     }
 }
@@ -693,7 +710,8 @@ pub struct Room<C: ClientKind> {
     pub autodelete_tx: broadcast::Sender<(u64, DateTime<Utc>)>,
     #[cfg(feature = "sqlx")]
     pub db_pool: PgPool,
-    pub tracker_state: Option<(String, tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<TcpStream>>)>,
+    /// The name and world count of the oottracker.fenhl.net room tracking this room, and the connection to oottracker.fenhl.net.
+    pub tracker_state: Option<(String, NonZero<u8>, tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<TcpStream>>)>,
     pub metadata: RoomMetadata,
 }
 
@@ -703,6 +721,105 @@ pub struct RoomMetadata {
     #[serde(default)]
     #[serde_as(as = "Seq<(_, _)>")]
     pub item_sources: HashMap<(NonZero<u8>, NonZero<u8>, u64), String>,
+    /// The number of Triforce pieces required to beat the game, if this room is for a Triforce Hunt seed.
+    #[serde(default)]
+    pub triforce_goal: Option<u16>,
+    /// The time at which each world's save data first showed the goal as completed.
+    #[serde(default)]
+    #[serde_as(as = "Seq<(_, _)>")]
+    pub completions: BTreeMap<NonZero<u8>, DateTime<Utc>>,
+    /// The number of worlds this room is expected to have, if known.
+    #[serde(default)]
+    pub world_count: Option<NonZero<u8>>,
+    /// Whether items are currently being delivered.
+    #[serde(default)]
+    pub start: RoomStart,
+    /// Whether the room has been started using [`Room::start`] at some point.
+    #[serde(default)]
+    pub started: bool,
+    /// The Mido's House user ID of the user who created this room, if they were signed in.
+    #[serde(default)]
+    pub owner: Option<u64>,
+    /// Whether item delivery has been paused, e.g. while a technical issue is being resolved.
+    #[serde(default)]
+    pub frozen: bool,
+    /// Items which have been sent while the room wasn't started yet or was frozen, in the order they were sent.
+    #[serde(default)]
+    pub held_items: Vec<HeldItem>,
+    /// The highest number of items from its queue that each world's save data has reported as received.
+    #[serde(default)]
+    #[serde_as(as = "Seq<(_, _)>")]
+    pub num_received_items: BTreeMap<NonZero<u8>, u16>,
+    /// Manual edits to this room's item queues, in chronological order.
+    #[serde(default)]
+    pub admin_log: Vec<AdminLogEntry>,
+}
+
+impl RoomMetadata {
+    /// Whether a client with the given login state may start or freeze this room, i.e. whether it's signed in as an admin or as the room's owner.
+    pub fn can_moderate(&self, logged_in_as_admin: bool, midos_house_user_id: Option<u64>) -> bool {
+        logged_in_as_admin || midos_house_user_id.is_some_and(|user| self.owner == Some(user))
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Protocol, Deserialize, Serialize)]
+pub enum RoomStart {
+    /// Items are delivered as soon as they're sent.
+    #[default]
+    Started,
+    /// Items are held until the room is started by its owner or by Mido's House.
+    Waiting,
+    /// The room has been started and held items will be delivered at the given time.
+    Countdown(DateTime<Utc>),
+}
+
+impl RoomStart {
+    pub fn holds_items(&self) -> bool {
+        match *self {
+            Self::Started => false,
+            Self::Waiting => true,
+            Self::Countdown(start) => start > Utc::now(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AdminLogEntry {
+    pub time: DateTime<Utc>,
+    /// The Mido's House user ID of the admin who made this edit, if known.
+    pub admin: Option<u64>,
+    pub action: AdminAction,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub enum AdminAction {
+    /// An item was removed from a world's queue.
+    Revoke {
+        source: NonZero<u8>,
+        key: u64,
+        kind: u16,
+        target: NonZero<u8>,
+    },
+    /// An item was added to the end of a world's queue.
+    Grant {
+        key: u64,
+        kind: u16,
+        target: NonZero<u8>,
+    },
+    /// An item was moved to a different position in a world's queue.
+    Move {
+        target: NonZero<u8>,
+        from: usize,
+        to: usize,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub struct HeldItem {
+    pub source: NonZero<u8>,
+    pub key: u64,
+    pub kind: u16,
+    pub target: NonZero<u8>,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -835,6 +952,126 @@ impl ProgressiveItems {
     }
 }
 
+/// Checks whether the given save data shows the player's goal as completed, i.e. Ganon has been defeated or the Triforce Hunt goal has been reached.
+fn goal_completed(save: &oottracker::Save, triforce_goal: Option<u16>) -> bool {
+    save.event_chk_inf.contains(oottracker::save::EventChkInf::GANON_DEFEATED)
+    || triforce_goal.is_some_and(|triforce_goal| u16::from(save.triforce_pieces) >= triforce_goal)
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum QueueEditError {
+    #[error("world {world} has already received the item at position {position} of its queue")]
+    AlreadyReceived {
+        world: NonZero<u8>,
+        position: usize,
+    },
+    #[error("no such item")]
+    NoSuchItem,
+    #[error("item queue position out of range")]
+    OutOfRange,
+}
+
+/// Removes the item from the given location from the target world's queue, or from all queues if `target` is `None`.
+///
+/// Worlds without a queue of their own use the base queue, so revoking an item from a single world gives it its own queue.
+/// Nothing is changed if any of the affected worlds has already received the item, since item delivery is based on positions in the queue.
+fn revoke_queued_item(base_queue: &mut Vec<Item>, player_queues: &mut HashMap<NonZero<u8>, Vec<Item>>, num_received: &BTreeMap<NonZero<u8>, usize>, source: NonZero<u8>, key: u64, target: Option<NonZero<u8>>) -> Result<(), QueueEditError> {
+    let position = |queue: &[Item]| queue.iter().position(|item| item.source == source && item.key == key);
+    let check_received = |world: NonZero<u8>, position: usize| if position < num_received.get(&world).copied().unwrap_or_default() {
+        Err(QueueEditError::AlreadyReceived { world, position })
+    } else {
+        Ok(())
+    };
+    if let Some(target) = target {
+        let mut queue = player_queues.get(&target).unwrap_or(&*base_queue).clone();
+        let position = position(&queue).ok_or(QueueEditError::NoSuchItem)?;
+        check_received(target, position)?;
+        queue.remove(position);
+        player_queues.insert(target, queue);
+    } else {
+        let base_position = position(base_queue);
+        if let Some(base_position) = base_position {
+            for &world in num_received.keys().filter(|&world| !player_queues.contains_key(world)) {
+                check_received(world, base_position)?;
+            }
+        }
+        let player_positions = player_queues.iter().map(|(&world, queue)| (world, position(queue))).collect::<Vec<_>>();
+        for &(world, position) in &player_positions {
+            if let Some(position) = position {
+                check_received(world, position)?;
+            }
+        }
+        if base_position.is_none() && player_positions.iter().all(|&(_, position)| position.is_none()) { return Err(QueueEditError::NoSuchItem) }
+        if let Some(base_position) = base_position {
+            base_queue.remove(base_position);
+        }
+        for (world, position) in player_positions {
+            if let Some(position) = position {
+                player_queues.get_mut(&world).expect("collected from player_queues").remove(position);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Adds an item of the given kind to the end of the target world's queue and returns the key it was given.
+///
+/// The target world is used as the source, with a key that's not used by any real location, to avoid conflicts with sent items.
+/// Since Triforce pieces count toward the goal of the entire room, they're added to all worlds' queues instead, like sent pieces.
+fn grant_queued_item(base_queue: &mut Vec<Item>, player_queues: &mut HashMap<NonZero<u8>, Vec<Item>>, kind: u16, target: NonZero<u8>) -> u64 {
+    let is_used = |queue: &[Item], key: u64| queue.iter().any(|item| item.source == target && item.key == key);
+    if kind == TRIFORCE_PIECE {
+        let key = (0..=u64::MAX).rev().find(|&key| !is_used(base_queue, key) && !player_queues.values().any(|queue| is_used(queue, key))).expect("item queue full");
+        let item = Item { source: target, key, kind };
+        base_queue.push(item);
+        for queue in player_queues.values_mut() {
+            queue.push(item);
+        }
+        key
+    } else {
+        let queue = player_queues.entry(target).or_insert_with(|| base_queue.clone());
+        let key = (0..=u64::MAX).rev().find(|&key| !is_used(queue, key)).expect("item queue full");
+        queue.push(Item { source: target, key, kind });
+        key
+    }
+}
+
+/// Moves the item at position `from` in the given world's queue to position `to`.
+///
+/// Positions which the world has already received can't be edited, since item delivery is based on positions in the queue.
+fn move_queued_item(queue: &mut Vec<Item>, world: NonZero<u8>, num_received: usize, from: usize, to: usize) -> Result<(), QueueEditError> {
+    if from >= queue.len() || to >= queue.len() { return Err(QueueEditError::OutOfRange) }
+    let position = from.min(to);
+    if position < num_received { return Err(QueueEditError::AlreadyReceived { world, position }) }
+    let item = queue.remove(from);
+    queue.insert(to, item);
+    Ok(())
+}
+
+/// The time at which a start countdown of the given length beginning at `now` ends, or `None` if the countdown is longer than [`MAX_START_COUNTDOWN`].
+fn countdown_end(now: DateTime<Utc>, countdown: Duration) -> Option<DateTime<Utc>> {
+    if countdown > MAX_START_COUNTDOWN { return None }
+    now.checked_add_signed(TimeDelta::from_std(countdown).ok()?)
+}
+
+/// The item queue of each world in a room with the given number of worlds, as shown on oottracker.fenhl.net.
+fn tracker_queues(base_queue: &[Item], player_queues: &HashMap<NonZero<u8>, Vec<Item>>, world_count: NonZero<u8>) -> Vec<Vec<Item>> {
+    (1..=world_count.get()).filter_map(NonZero::new)
+        .map(|world| player_queues.get(&world).map_or(base_queue, Vec::as_slice).to_vec())
+        .collect()
+}
+
+/// Counts the Triforce pieces in the given item queue by the world in which they were found.
+fn triforce_pieces(queue: &[Item]) -> BTreeMap<NonZero<u8>, u16> {
+    let mut pieces = BTreeMap::<_, u16>::default();
+    for item in queue {
+        if item.kind == TRIFORCE_PIECE {
+            *pieces.entry(item.source).or_default() += 1;
+        }
+    }
+    pieces
+}
+
 fn format_opt_hash(hash: Option<[HashIcon; 5]>) -> String {
     if let Some(hash) = hash {
         natjoin(hash)
@@ -845,13 +1082,19 @@ fn format_opt_hash(hash: Option<[HashIcon; 5]>) -> String {
 
 #[derive(Debug, thiserror::Error)]
 pub enum RoomError {
+    #[error(transparent)] SaveDiff(#[from] save_diff::Error),
     #[error(transparent)] Wheel(#[from] wheel::Error),
+    #[error(transparent)] QueueEdit(#[from] QueueEditError),
     #[error(transparent)] Write(#[from] async_proto::WriteError),
     #[error("this room is for a different seed: server has {} but client has {}", format_opt_hash(*.server), format_opt_hash(*.client))]
     FileHash {
         server: Option<[HashIcon; 5]>,
         client: Option<[HashIcon; 5]>,
     },
+    #[error("start countdown is longer than the maximum of {MAX_START_COUNTDOWN:?}")]
+    CountdownTooLong,
+    #[error("received a save data diff without save data in this room session")]
+    SaveDataDiffWithoutBase,
 }
 
 impl IsNetworkError for RoomError {
@@ -859,7 +1102,7 @@ impl IsNetworkError for RoomError {
         match self {
             Self::Wheel(e) => e.is_network_error(),
             Self::Write(e) => e.is_network_error(),
-            Self::FileHash { .. } => false,
+            Self::QueueEdit(_) | Self::SaveDiff(_) | Self::FileHash { .. } | Self::CountdownTooLong | Self::SaveDataDiffWithoutBase => false,
         }
     }
 }
@@ -898,6 +1141,7 @@ impl<C: ClientKind> Room<C> {
             pending_items: Vec::default(),
             tracker_state: oottracker::ModelState::default(),
             adjusted_save: oottracker::Save::default(),
+            save_data: None,
             version, writer, end_tx,
         });
         Ok(())
@@ -933,7 +1177,7 @@ impl<C: ClientKind> Room<C> {
                 wheel::night_report("/games/zelda/oot/mhmw/error", Some(&format!("failed to delete room from database: {e} ({e:?})"))).await?;
             }
         }
-        if let Some((ref tracker_room_name, ref mut sock)) = self.tracker_state {
+        if let Some((ref tracker_room_name, _, ref mut sock)) = self.tracker_state {
             let _ = oottracker::websocket::ClientMessage::MwDeleteRoom { room: tracker_room_name.clone() }.write_ws021(sock).await;
         }
         Ok(())
@@ -1007,7 +1251,7 @@ impl<C: ClientKind> Room<C> {
         if !queue.is_empty() {
             self.write(client_id, unversioned::ServerMessage::ItemQueue(queue)).await?;
         }
-        if let Some((ref tracker_room_name, ref mut sock)) = self.tracker_state {
+        if let Some((ref tracker_room_name, _, ref mut sock)) = self.tracker_state {
             oottracker::websocket::ClientMessage::MwResetPlayer { room: tracker_room_name.clone(), world, save }.write_ws021(sock).await?;
         }
         Ok(true)
@@ -1053,7 +1297,21 @@ impl<C: ClientKind> Room<C> {
     }
 
     async fn queue_item_inner(&mut self, source_version: Result<Version, &'static str>, source_client: Option<C::SessionId>, source_world: NonZero<u8>, key: u64, kind: u16, target_world: NonZero<u8>, #[cfg_attr(not(feature = "sqlx"), allow(unused))] context: &str, verbose_logging: bool) -> Result<(), RoomError> {
-        if let Some((ref tracker_room_name, ref mut sock)) = self.tracker_state {
+        if self.metadata.frozen || self.metadata.start.holds_items() {
+            if verbose_logging { println!("room not started or frozen, holding item") }
+            let item = HeldItem { source: source_world, key, kind, target: target_world };
+            if !self.metadata.held_items.contains(&item) {
+                self.metadata.held_items.push(item);
+                #[cfg(feature = "sqlx")] {
+                    if let Err(e) = self.save(true).await {
+                        eprintln!("failed to save room state while trying to hold item for room {} {context} ({}): {e} ({e:?})", self.name, self.id);
+                        wheel::night_report("/games/zelda/oot/mhmw/error", Some(&format!("failed to save room state while trying to hold item for room {} {context} ({}): {e} ({e:?})", self.name, self.id))).await?;
+                    }
+                }
+            }
+            return Ok(())
+        }
+        if let Some((ref tracker_room_name, _, ref mut sock)) = self.tracker_state {
             if verbose_logging { println!("updating tracker") }
             oottracker::websocket::ClientMessage::MwQueueItem {
                 room: tracker_room_name.clone(),
@@ -1087,6 +1345,7 @@ impl<C: ClientKind> Room<C> {
                 for target_client in player_clients {
                     self.write(target_client, msg.clone()).await?;
                 }
+                self.write_triforce_pieces(source_world).await?;
             }
         } else if source_world == target_world {
             if verbose_logging { println!("is own world") }
@@ -1162,6 +1421,7 @@ impl<C: ClientKind> Room<C> {
     }
 
     pub async fn queue_item(&mut self, source_client_id: C::SessionId, key: u64, kind: u16, target_world: NonZero<u8>, verbose_logging: bool) -> Result<(), RoomError> {
+        self.start_if_due().await?;
         let source_client = self.clients.get_mut(&source_client_id).expect("tried to queue item from nonexistent client");
         if let Some(source) = source_client.player {
             if let Some(player_hash) = source.file_hash {
@@ -1192,6 +1452,7 @@ impl<C: ClientKind> Room<C> {
         } else {
             self.file_hash = Some(Some(spoiler_log.file_hash));
         }
+        self.start_if_due().await?;
         spoiler_log.version.clone_repo(false, false).await?;
         let py_modules = spoiler_log.version.py_modules("/usr/bin/python3", false)?;
         let mut items_to_queue = Vec::default();
@@ -1209,10 +1470,45 @@ impl<C: ClientKind> Room<C> {
         Ok(())
     }
 
+    /// Updates the room with the client's save data.
+    ///
+    /// If the save data can't be encoded as a base for [`Room::apply_save_data_diff`], the failure is logged and the client's next diff is rejected, but the save data is still used.
     pub async fn set_save_data(&mut self, client_id: C::SessionId, save: oottracker::Save) -> Result<(), RoomError> {
+        let client = self.clients.get_mut(&client_id).expect("tried to set save data for nonexistent client");
+        client.save_data = match save_diff::encode(&save) {
+            Ok(save_data) => Some(save_data),
+            Err(e) => {
+                eprintln!("failed to encode save data for room {} ({}): {e} ({e:?})", self.name, self.id);
+                None
+            }
+        };
+        self.update_save(client_id, save).await
+    }
+
+    /// Applies a [`save_diff`] to the save data the client sent in this room session, then updates the room like [`Room::set_save_data`]. Does nothing if the diff doesn't change the save data.
+    ///
+    /// The diff is applied to the raw save data kept for the client, so unlike with [`Room::set_save_data`], the save data doesn't have to be encoded again.
+    /// If the diff can't be applied, the client's save data is discarded, so the client should send its next save data in full.
+    pub async fn apply_save_data_diff(&mut self, client_id: C::SessionId, diff: Vec<save_diff::Range>) -> Result<(), RoomError> {
+        let client = self.clients.get_mut(&client_id).expect("tried to apply save data diff for nonexistent client");
+        let save_data = client.save_data.as_mut().ok_or(RoomError::SaveDataDiffWithoutBase)?;
+        let save = match save_diff::apply(save_data, &diff).and_then(|changed| changed.then(|| save_diff::decode(save_data)).transpose()) {
+            Ok(Some(save)) => save,
+            Ok(None) => return Ok(()),
+            Err(e) => {
+                client.save_data = None;
+                return Err(e.into())
+            }
+        };
+        self.update_save(client_id, save).await
+    }
+
+    async fn update_save(&mut self, client_id: C::SessionId, save: oottracker::Save) -> Result<(), RoomError> {
         let client = self.clients.get_mut(&client_id).expect("tried to set save data for nonexistent client");
         client.tracker_state.ram.save = save.clone();
         if let Some(Player { world, .. }) = client.player {
+            let num_received_items = self.metadata.num_received_items.entry(world).or_default();
+            *num_received_items = (*num_received_items).max(save.inv_amounts.num_received_mw_items);
             let queue = self.player_queues.get(&world).unwrap_or(&self.base_queue).iter().map(|item| item.kind).collect::<Vec<_>>();
             let mut adjusted_save = save.clone();
             if let Some(queued_items) = queue.get(adjusted_save.inv_amounts.num_received_mw_items.into()..) {
@@ -1227,11 +1523,24 @@ impl<C: ClientKind> Room<C> {
             }
             let old_progressive_items = ProgressiveItems::new(&client.adjusted_save);
             let new_progressive_items = ProgressiveItems::new(&adjusted_save);
+            let completed = goal_completed(&adjusted_save, self.metadata.triforce_goal);
             client.adjusted_save = adjusted_save;
             if old_progressive_items != new_progressive_items {
                 self.write_all(&unversioned::ServerMessage::ProgressiveItems { world, state: new_progressive_items.bits() }).await?;
             }
-            if let Some((ref tracker_room_name, ref mut sock)) = self.tracker_state {
+            if completed {
+                if let btree_map::Entry::Vacant(entry) = self.metadata.completions.entry(world) {
+                    let time = *entry.insert(Utc::now());
+                    #[cfg(feature = "sqlx")] {
+                        if let Err(e) = self.save(false).await {
+                            eprintln!("failed to save room state while trying to record completion of world {world} for room {} ({}): {e} ({e:?})", self.name, self.id);
+                            wheel::night_report("/games/zelda/oot/mhmw/error", Some(&format!("failed to save room state while trying to record completion of world {world} for room {} ({}): {e} ({e:?})", self.name, self.id))).await?;
+                        }
+                    }
+                    self.write_all(&unversioned::ServerMessage::WorldCompleted { world, time }).await?;
+                }
+            }
+            if let Some((ref tracker_room_name, _, ref mut sock)) = self.tracker_state {
                 oottracker::websocket::ClientMessage::MwResetPlayer { room: tracker_room_name.clone(), world, save }.write_ws021(sock).await?;
             }
         }
@@ -1242,7 +1551,7 @@ impl<C: ClientKind> Room<C> {
         let client = self.clients.get_mut(&client_id).expect("tried to add dungeon reward info for nonexistent client");
         client.tracker_state.knowledge.dungeon_reward_locations.insert(reward, location);
         if let Some(Player { world, .. }) = client.player {
-            if let Some((ref tracker_room_name, ref mut sock)) = self.tracker_state {
+            if let Some((ref tracker_room_name, _, ref mut sock)) = self.tracker_state {
                 oottracker::websocket::ClientMessage::MwDungeonRewardLocation { room: tracker_room_name.clone(), world, reward, location }.write_ws021(sock).await?;
             }
         }
@@ -1252,7 +1561,7 @@ impl<C: ClientKind> Room<C> {
     pub async fn set_current_scene(&mut self, client_id: C::SessionId, scene: u8) -> Result<(), async_proto::WriteError> {
         if let Some(client) = self.clients.get_mut(&client_id) {
             if let Some(Player { world, .. }) = client.player {
-                if let Some((ref tracker_room_name, ref mut sock)) = self.tracker_state {
+                if let Some((ref tracker_room_name, _, ref mut sock)) = self.tracker_state {
                     oottracker::websocket::ClientMessage::MwCurrentScene { room: tracker_room_name.clone(), world, scene }.write_ws021(sock).await?;
                 }
             }
@@ -1260,27 +1569,273 @@ impl<C: ClientKind> Room<C> {
         Ok(())
     }
 
-    pub async fn init_tracker(&mut self, tracker_room_name: String, world_count: NonZero<u8>) -> Result<(), async_proto::WriteError> {
-        let mut worlds = (1..=world_count.get())
-            .map(|player_id| (
-                oottracker::ModelState::default(),
-                self.player_queues.get(&NonZero::new(player_id).expect("range starts at 1")).unwrap_or(&self.base_queue).clone(),
-            ))
+    /// The state of each world as sent to oottracker.fenhl.net when creating the tracker room.
+    fn tracker_worlds(&self, world_count: NonZero<u8>) -> Vec<(oottracker::ModelState, Vec<Item>)> {
+        let mut worlds = tracker_queues(&self.base_queue, &self.player_queues, world_count).into_iter()
+            .map(|queue| (oottracker::ModelState::default(), queue))
             .collect::<Vec<_>>();
         for client in self.clients.values() {
             if let Some(player) = client.player {
-                worlds[usize::from(player.world.get() - 1)].0 = client.tracker_state.clone();
+                if let Some((state, _)) = worlds.get_mut(usize::from(player.world.get() - 1)) {
+                    *state = client.tracker_state.clone();
+                }
             }
         }
+        worlds
+    }
+
+    pub async fn init_tracker(&mut self, tracker_room_name: String, world_count: NonZero<u8>) -> Result<(), async_proto::WriteError> {
+        let worlds = self.tracker_worlds(world_count);
         let mut sock = tokio_tungstenite::connect_async("wss://oottracker.fenhl.net/websocket").await.map_err(|e| async_proto::WriteError {
             context: async_proto::ErrorContext::Custom(format!("multiworld::Room::init_tracker")),
             kind: e.into(),
         })?.0;
         oottracker::websocket::ClientMessage::MwCreateRoom { room: tracker_room_name.clone(), worlds }.write_ws021(&mut sock).await?;
-        self.tracker_state = Some((tracker_room_name, sock));
+        self.tracker_state = Some((tracker_room_name, world_count, sock));
         Ok(())
     }
 
+    /// Sends the current item queues to oottracker.fenhl.net after a manual edit.
+    ///
+    /// The tracker has no messages for revoking or moving queued items, so the tracker room is created again with the current state instead.
+    async fn resync_tracker(&mut self) -> Result<(), async_proto::WriteError> {
+        if let Some((_, world_count, _)) = self.tracker_state {
+            let worlds = self.tracker_worlds(world_count);
+            if let Some((ref tracker_room_name, _, ref mut sock)) = self.tracker_state {
+                oottracker::websocket::ClientMessage::MwCreateRoom { room: tracker_room_name.clone(), worlds }.write_ws021(sock).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Makes the room hold items until it's started using [`Room::start`].
+    ///
+    /// Returns `false` without changing anything if the room has already started, i.e. if it has been started using [`Room::start`] or items have already been sent.
+    pub async fn wait_for_start(&mut self) -> Result<bool, RoomError> {
+        match self.metadata.start {
+            RoomStart::Waiting => {}
+            RoomStart::Countdown(_) => return Ok(false),
+            RoomStart::Started => {
+                if self.metadata.started || !self.base_queue.is_empty() || !self.metadata.held_items.is_empty() { return Ok(false) }
+                self.metadata.start = RoomStart::Waiting;
+                #[cfg(feature = "sqlx")] {
+                    if let Err(e) = self.save(true).await {
+                        eprintln!("failed to save room state while trying to make room {} ({}) wait for start: {e} ({e:?})", self.name, self.id);
+                        wheel::night_report("/games/zelda/oot/mhmw/error", Some(&format!("failed to save room state while trying to make room {} ({}) wait for start: {e} ({e:?})", self.name, self.id))).await?;
+                    }
+                }
+                self.write_all(&unversioned::ServerMessage::StartState(RoomStart::Waiting)).await?;
+            }
+        }
+        Ok(true)
+    }
+
+    /// Starts a countdown after which held items will be delivered.
+    ///
+    /// Returns `None` if not all worlds have been claimed yet. Otherwise, returns the time at which items will start being delivered, at which point [`Room::start_if_due`] should be called.
+    /// Countdowns longer than [`MAX_START_COUNTDOWN`] are rejected with [`RoomError::CountdownTooLong`].
+    pub async fn start(&mut self, countdown: Duration) -> Result<Option<DateTime<Utc>>, RoomError> {
+        Ok(match self.metadata.start {
+            RoomStart::Started => Some(Utc::now()),
+            RoomStart::Countdown(start) => Some(start),
+            RoomStart::Waiting => {
+                let claimed_worlds = self.clients.values().filter_map(|client| client.player).map(|player| player.world).collect::<HashSet<_>>();
+                let all_claimed = if let Some(world_count) = self.metadata.world_count {
+                    (1..=world_count.get()).filter_map(NonZero::new).all(|world| claimed_worlds.contains(&world))
+                } else {
+                    !claimed_worlds.is_empty() && self.clients.values().all(|client| client.player.is_some())
+                };
+                if !all_claimed { return Ok(None) }
+                let start = countdown_end(Utc::now(), countdown).ok_or(RoomError::CountdownTooLong)?;
+                self.metadata.start = RoomStart::Countdown(start);
+                self.metadata.started = true;
+                #[cfg(feature = "sqlx")] {
+                    if let Err(e) = self.save(true).await {
+                        eprintln!("failed to save room state while trying to start room {} ({}): {e} ({e:?})", self.name, self.id);
+                        wheel::night_report("/games/zelda/oot/mhmw/error", Some(&format!("failed to save room state while trying to start room {} ({}): {e} ({e:?})", self.name, self.id))).await?;
+                    }
+                }
+                self.write_all(&unversioned::ServerMessage::StartState(RoomStart::Countdown(start))).await?;
+                Some(start)
+            }
+        })
+    }
+
+    /// If the room's start countdown has elapsed, marks the room as started and delivers any held items in the order they were sent.
+    pub async fn start_if_due(&mut self) -> Result<(), RoomError> {
+        if let RoomStart::Countdown(start) = self.metadata.start {
+            if start <= Utc::now() {
+                self.metadata.start = RoomStart::Started;
+                self.write_all(&unversioned::ServerMessage::StartState(RoomStart::Started)).await?;
+                self.release_held_items().await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Freezes or unfreezes item delivery. While frozen, sent items are held and delivered in order once the room is unfrozen.
+    pub async fn set_frozen(&mut self, frozen: bool) -> Result<(), RoomError> {
+        if self.metadata.frozen != frozen {
+            self.metadata.frozen = frozen;
+            #[cfg(feature = "sqlx")] {
+                if let Err(e) = self.save(true).await {
+                    eprintln!("failed to save room state while trying to set frozen state of room {} ({}): {e} ({e:?})", self.name, self.id);
+                    wheel::night_report("/games/zelda/oot/mhmw/error", Some(&format!("failed to save room state while trying to set frozen state of room {} ({}): {e} ({e:?})", self.name, self.id))).await?;
+                }
+            }
+            self.write_all(&unversioned::ServerMessage::Frozen(frozen)).await?;
+            if !frozen {
+                self.start_if_due().await?;
+                self.release_held_items().await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The number of items each world is known to have received from its queue.
+    ///
+    /// This is the highest number reported by the world's save data, since items are delivered based on their position in the queue and a lower number (e.g. on a new file) will be caught up again.
+    fn num_received_items(&self) -> BTreeMap<NonZero<u8>, usize> {
+        let mut num_received = self.metadata.num_received_items.iter().map(|(&world, &count)| (world, usize::from(count))).collect::<BTreeMap<_, _>>();
+        for client in self.clients.values() {
+            if let Some(Player { world, .. }) = client.player {
+                let count = num_received.entry(world).or_default();
+                *count = (*count).max(client.tracker_state.ram.save.inv_amounts.num_received_mw_items.into());
+            }
+        }
+        num_received
+    }
+
+    /// Removes the item from the given location from the target world's queue.
+    ///
+    /// Since Triforce pieces count toward the goal of the entire room, they're removed from all worlds' queues instead.
+    /// Items which a world has already received can't be revoked, since item delivery is based on positions in the queue.
+    /// Note that this means this can't take away the item from the player's game.
+    pub async fn revoke_item(&mut self, source: NonZero<u8>, key: u64, target: NonZero<u8>, admin: Option<u64>) -> Result<(), RoomError> {
+        let Item { kind, .. } = *self.player_queues.get(&target).unwrap_or(&self.base_queue).iter().find(|item| item.source == source && item.key == key).ok_or(QueueEditError::NoSuchItem)?;
+        let num_received = self.num_received_items();
+        let targets = if kind == TRIFORCE_PIECE { None } else { Some(target) };
+        revoke_queued_item(&mut self.base_queue, &mut self.player_queues, &num_received, source, key, targets)?;
+        if kind == TRIFORCE_PIECE {
+            self.metadata.item_sources.retain(|&(item_source, _, item_key), _| (item_source, item_key) != (source, key));
+        } else {
+            self.metadata.item_sources.remove(&(source, target, key));
+        }
+        self.edit_queue(targets, AdminLogEntry { time: Utc::now(), action: AdminAction::Revoke { source, key, kind, target }, admin }).await?;
+        if kind == TRIFORCE_PIECE {
+            self.write_triforce_pieces(source).await?;
+        }
+        Ok(())
+    }
+
+    /// Adds an item of the given kind to the end of the target world's queue, e.g. to replace an item that was lost due to a bug.
+    ///
+    /// Since Triforce pieces count toward the goal of the entire room, they're added to all worlds' queues instead and count as found in the target world.
+    pub async fn grant_item(&mut self, kind: u16, target: NonZero<u8>, admin: Option<u64>) -> Result<(), RoomError> {
+        let key = grant_queued_item(&mut self.base_queue, &mut self.player_queues, kind, target);
+        self.metadata.item_sources.insert((target, target, key), format!("granted by admin"));
+        let targets = if kind == TRIFORCE_PIECE { None } else { Some(target) };
+        self.edit_queue(targets, AdminLogEntry { time: Utc::now(), action: AdminAction::Grant { key, kind, target }, admin }).await?;
+        if kind == TRIFORCE_PIECE {
+            self.write_triforce_pieces(target).await?;
+        }
+        Ok(())
+    }
+
+    /// Moves the item at position `from` in the target world's queue to position `to`.
+    ///
+    /// Positions which the world has already received can't be edited, since item delivery is based on positions in the queue.
+    pub async fn move_item(&mut self, target: NonZero<u8>, from: usize, to: usize, admin: Option<u64>) -> Result<(), RoomError> {
+        let num_received = self.num_received_items().get(&target).copied().unwrap_or_default();
+        let mut queue = self.player_queues.get(&target).unwrap_or(&self.base_queue).clone();
+        move_queued_item(&mut queue, target, num_received, from, to)?;
+        self.player_queues.insert(target, queue);
+        self.edit_queue(Some(target), AdminLogEntry { time: Utc::now(), action: AdminAction::Move { target, from, to }, admin }).await?;
+        Ok(())
+    }
+
+    /// Records a manual edit to the item queue of the given world (or of all worlds if `world` is `None`) and updates the affected players' state accordingly.
+    async fn edit_queue(&mut self, world: Option<NonZero<u8>>, log_entry: AdminLogEntry) -> Result<(), RoomError> {
+        // a revoked Triforce piece may have been what completed a world's goal, other edits can't undo a completion
+        let may_undo_completion = matches!(log_entry.action, AdminAction::Revoke { kind: TRIFORCE_PIECE, .. });
+        self.metadata.admin_log.push(log_entry);
+        let mut updates = Vec::default();
+        for (&client_id, client) in &mut self.clients {
+            let Some(Player { world: client_world, .. }) = client.player else { continue };
+            if world.is_some_and(|world| world != client_world) { continue }
+            let queue = self.player_queues.get(&client_world).unwrap_or(&self.base_queue).iter().map(|item| item.kind).collect::<Vec<_>>();
+            let mut adjusted_save = client.tracker_state.ram.save.clone();
+            if let Some(queued_items) = queue.get(adjusted_save.inv_amounts.num_received_mw_items.into()..) {
+                for &item in queued_items {
+                    if let Err(()) = adjusted_save.recv_mw_item(item) {
+                        eprintln!("edit_queue: item 0x{item:04x} not supported by recv_mw_item");
+                        wheel::night_report("/games/zelda/oot/mhmw/error", Some(&format!("edit_queue: item 0x{item:04x} not supported by recv_mw_item"))).await?;
+                    }
+                }
+            } else {
+                eprintln!("save data from player {client_world} in room {} has more received items than are in their queue", self.name);
+            }
+            let old_progressive_items = ProgressiveItems::new(&client.adjusted_save);
+            let new_progressive_items = ProgressiveItems::new(&adjusted_save);
+            let completed = goal_completed(&adjusted_save, self.metadata.triforce_goal);
+            client.adjusted_save = adjusted_save;
+            updates.push((client_id, client_world, queue, (old_progressive_items != new_progressive_items).then_some(new_progressive_items), completed));
+        }
+        let mut completion_changes = Vec::default();
+        for &(_, client_world, _, _, completed) in &updates {
+            if completed {
+                if let btree_map::Entry::Vacant(entry) = self.metadata.completions.entry(client_world) {
+                    completion_changes.push(unversioned::ServerMessage::WorldCompleted { world: client_world, time: *entry.insert(Utc::now()) });
+                }
+            } else if may_undo_completion && self.metadata.completions.remove(&client_world).is_some() {
+                completion_changes.push(unversioned::ServerMessage::CompletionRevoked(client_world));
+            }
+        }
+        #[cfg(feature = "sqlx")] {
+            if let Err(e) = self.save(true).await {
+                eprintln!("failed to save room state while trying to edit item queue for room {} ({}): {e} ({e:?})", self.name, self.id);
+                wheel::night_report("/games/zelda/oot/mhmw/error", Some(&format!("failed to save room state while trying to edit item queue for room {} ({}): {e} ({e:?})", self.name, self.id))).await?;
+            }
+        }
+        self.resync_tracker().await?;
+        for (client_id, client_world, queue, new_progressive_items, _) in updates {
+            if let Some(new_progressive_items) = new_progressive_items {
+                self.write_all(&unversioned::ServerMessage::ProgressiveItems { world: client_world, state: new_progressive_items.bits() }).await?;
+            }
+            self.write(client_id, unversioned::ServerMessage::ItemQueue(queue)).await?;
+        }
+        for msg in completion_changes {
+            self.write_all(&msg).await?;
+        }
+        Ok(())
+    }
+
+    /// Delivers held items in the order they were sent, unless the room is still frozen or not yet started.
+    async fn release_held_items(&mut self) -> Result<(), RoomError> {
+        if self.metadata.frozen || self.metadata.start.holds_items() { return Ok(()) }
+        for HeldItem { source, key, kind, target } in mem::take(&mut self.metadata.held_items) {
+            self.queue_item_inner(Err("held item"), None, source, key, kind, target, "while delivering held items", false).await?;
+        }
+        #[cfg(feature = "sqlx")] {
+            if let Err(e) = self.save(true).await {
+                eprintln!("failed to save room state while trying to deliver held items for room {} ({}): {e} ({e:?})", self.name, self.id);
+                wheel::night_report("/games/zelda/oot/mhmw/error", Some(&format!("failed to save room state while trying to deliver held items for room {} ({}): {e} ({e:?})", self.name, self.id))).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The number of Triforce pieces found so far, by the world in which they were found.
+    pub fn triforce_pieces(&self) -> BTreeMap<NonZero<u8>, u16> {
+        triforce_pieces(&self.base_queue)
+    }
+
+    /// Notifies everyone in the room of the number of Triforce pieces found in the given world after it has changed.
+    async fn write_triforce_pieces(&mut self, world: NonZero<u8>) -> Result<(), RoomError> {
+        let count = self.triforce_pieces().get(&world).copied().unwrap_or_default();
+        self.write_all(&unversioned::ServerMessage::TriforcePieces { world, count }).await
+    }
+
     pub fn autodelete_at(&self) -> DateTime<Utc> {
         self.last_saved + TimeDelta::from_std(self.autodelete_delta).expect("autodelete delta too long")
     }
@@ -1363,7 +1918,7 @@ impl IsNetworkError for ClientError {
     }
 }
 
-#[derive(Debug, thiserror::Error)]
+#[derive(Debug, Clone, thiserror::Error)]
 pub enum SessionStateError<E> {
     #[error(transparent)] Connection(#[from] E),
     #[error("server error #{0}")]
@@ -1406,6 +1961,7 @@ pub enum LobbyView {
         error: Option<Arc<oauth2::basic::BasicRequestTokenError<oauth2::reqwest::HttpClientError>>>,
     },
     Settings,
+    PendingItems,
     Login {
         provider: IdentityProvider,
         no_midos_house_account: bool,
@@ -1419,7 +1975,7 @@ pub enum RoomView {
     Options,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum SessionState<E> {
     Error {
         maintenance: Option<(DateTime<Utc>, Duration)>,
@@ -1457,6 +2013,15 @@ pub enum SessionState<E> {
         item_queue: Vec<u16>,
         autodelete_delta: Duration,
         allow_send_all: bool,
+        completions: BTreeMap<NonZero<u8>, DateTime<Utc>>,
+        triforce_goal: Option<u16>,
+        triforce_pieces: BTreeMap<NonZero<u8>, u16>,
+        start: RoomStart,
+        frozen: bool,
+        can_moderate: bool,
+        not_all_worlds_claimed: bool,
+        already_started: bool,
+        countdown_too_long: bool,
         view: RoomView,
         wrong_file_hash: Option<[Option<[HashIcon; 5]>; 2]>,
         world_taken: Option<NonZero<u8>>,
@@ -1467,6 +2032,31 @@ pub enum SessionState<E> {
     },
 }
 
+impl SessionStateError<Infallible> {
+    fn into_fallible<E>(self) -> SessionStateError<E> {
+        match self {
+            Self::Connection(never) => match never {},
+            Self::Future(discrim) => SessionStateError::Future(discrim),
+            Self::Mismatch { expected, actual } => SessionStateError::Mismatch { expected, actual: Box::new(actual.into_fallible()) },
+            Self::Server(e) => SessionStateError::Server(e),
+        }
+    }
+}
+
+impl SessionState<Infallible> {
+    /// Converts a session state which can't contain connection errors, like the one tracked by `client::Receiver`, into one which can.
+    pub fn into_fallible<E>(self) -> SessionState<E> {
+        match self {
+            Self::Error { maintenance, e, auto_retry } => SessionState::Error { maintenance, e: e.into_fallible(), auto_retry },
+            Self::Init { maintenance } => SessionState::Init { maintenance },
+            Self::InitAutoRejoin { maintenance, room_id, room_password } => SessionState::InitAutoRejoin { maintenance, room_id, room_password },
+            Self::Lobby { maintenance, login_state, rooms, create_new_room, existing_room_selection, new_room_name, password, view, wrong_password } => SessionState::Lobby { maintenance, login_state, rooms, create_new_room, existing_room_selection, new_room_name, password, view, wrong_password },
+            Self::Room { maintenance, login_state, room_id, room_name, room_password, players, progressive_items, num_unassigned_clients, item_queue, autodelete_delta, allow_send_all, completions, triforce_goal, triforce_pieces, start, frozen, can_moderate, not_all_worlds_claimed, already_started, countdown_too_long, view, wrong_file_hash, world_taken, conflicting_item_kinds } => SessionState::Room { maintenance, login_state, room_id, room_name, room_password, players, progressive_items, num_unassigned_clients, item_queue, autodelete_delta, allow_send_all, completions, triforce_goal, triforce_pieces, start, frozen, can_moderate, not_all_worlds_claimed, already_started, countdown_too_long, view, wrong_file_hash, world_taken, conflicting_item_kinds },
+            Self::Closed { maintenance } => SessionState::Closed { maintenance },
+        }
+    }
+}
+
 impl<E> SessionState<E> {
     pub fn maintenance(&self) -> Option<(DateTime<Utc>, Duration)> {
         match *self {
@@ -1557,6 +2147,42 @@ impl<E> SessionState<E> {
                     auto_retry: false,
                 };
             },
+            latest::ServerMessage::StructuredError(ServerError::NotAllWorldsClaimed) => if let Self::Room { not_all_worlds_claimed, .. } = self {
+                *not_all_worlds_claimed = true;
+            } else {
+                *self = Self::Error {
+                    maintenance: self.maintenance(),
+                    e: SessionStateError::Mismatch {
+                        expected: "Room",
+                        actual: Box::new(mem::replace(self, Self::Init { maintenance: self.maintenance() })),
+                    },
+                    auto_retry: false,
+                };
+            },
+            latest::ServerMessage::StructuredError(ServerError::AlreadyStarted) => if let Self::Room { already_started, .. } = self {
+                *already_started = true;
+            } else {
+                *self = Self::Error {
+                    maintenance: self.maintenance(),
+                    e: SessionStateError::Mismatch {
+                        expected: "Room",
+                        actual: Box::new(mem::replace(self, Self::Init { maintenance: self.maintenance() })),
+                    },
+                    auto_retry: false,
+                };
+            },
+            latest::ServerMessage::StructuredError(ServerError::CountdownTooLong) => if let Self::Room { countdown_too_long, .. } = self {
+                *countdown_too_long = true;
+            } else {
+                *self = Self::Error {
+                    maintenance: self.maintenance(),
+                    e: SessionStateError::Mismatch {
+                        expected: "Room",
+                        actual: Box::new(mem::replace(self, Self::Init { maintenance: self.maintenance() })),
+                    },
+                    auto_retry: false,
+                };
+            },
             latest::ServerMessage::StructuredError(ServerError::ConflictingItemKinds) => if let Self::Room { ref mut conflicting_item_kinds, .. } = self {
                 *conflicting_item_kinds = true; //TODO update client to automatically send additional seed info when receiving this?
             } else {
@@ -1569,6 +2195,7 @@ impl<E> SessionState<E> {
                     auto_retry: false,
                 };
             },
+            latest::ServerMessage::StructuredError(ServerError::SaveDataDiffFailed) => {}
             latest::ServerMessage::StructuredError(ServerError::Future(discrim)) => if !matches!(self, Self::Error { .. }) {
                 *self = Self::Error {
                     maintenance: self.maintenance(),
@@ -1639,7 +2266,7 @@ impl<E> SessionState<E> {
                     auto_retry: false,
                 };
             },
-            latest::ServerMessage::EnterRoom { room_id, players, num_unassigned_clients, autodelete_delta, allow_send_all } => {
+            latest::ServerMessage::EnterRoom { room_id, players, num_unassigned_clients, autodelete_delta, allow_send_all, completions, triforce_goal, triforce_pieces, start, frozen, can_moderate } => {
                 let maintenance = self.maintenance();
                 if let Self::Lobby { login_state, rooms, password, new_room_name, .. } = self {
                     let room_name = if let Some((_, (room_name, _))) = rooms.iter().find(|&(&id, _)| id == room_id) {
@@ -1657,7 +2284,10 @@ impl<E> SessionState<E> {
                         wrong_file_hash: None,
                         world_taken: None,
                         conflicting_item_kinds: false,
-                        maintenance, room_id, players, num_unassigned_clients, autodelete_delta, allow_send_all,
+                        not_all_worlds_claimed: false,
+                        already_started: false,
+                        countdown_too_long: false,
+                        maintenance, room_id, players, num_unassigned_clients, autodelete_delta, allow_send_all, completions, triforce_goal, triforce_pieces, start, frozen, can_moderate,
                     };
                 } else {
                     *self = Self::Error {
@@ -1842,6 +2472,68 @@ impl<E> SessionState<E> {
                     auto_retry: false,
                 },
             },
+            latest::ServerMessage::WorldCompleted { world, time } => if let Self::Room { completions, .. } = self {
+                completions.insert(world, time);
+            } else {
+                *self = Self::Error {
+                    maintenance: self.maintenance(),
+                    e: SessionStateError::Mismatch {
+                        expected: "Room",
+                        actual: Box::new(mem::replace(self, Self::Init { maintenance: self.maintenance() })),
+                    },
+                    auto_retry: false,
+                };
+            },
+            latest::ServerMessage::CompletionRevoked(world) => if let Self::Room { completions, .. } = self {
+                completions.remove(&world);
+            } else {
+                *self = Self::Error {
+                    maintenance: self.maintenance(),
+                    e: SessionStateError::Mismatch {
+                        expected: "Room",
+                        actual: Box::new(mem::replace(self, Self::Init { maintenance: self.maintenance() })),
+                    },
+                    auto_retry: false,
+                };
+            },
+            latest::ServerMessage::TriforcePieces { world, count } => if let Self::Room { triforce_pieces, .. } = self {
+                triforce_pieces.insert(world, count);
+            } else {
+                *self = Self::Error {
+                    maintenance: self.maintenance(),
+                    e: SessionStateError::Mismatch {
+                        expected: "Room",
+                        actual: Box::new(mem::replace(self, Self::Init { maintenance: self.maintenance() })),
+                    },
+                    auto_retry: false,
+                };
+            },
+            latest::ServerMessage::StartState(new_start) => if let Self::Room { start, not_all_worlds_claimed, countdown_too_long, .. } = self {
+                *start = new_start;
+                *not_all_worlds_claimed = false;
+                *countdown_too_long = false;
+            } else {
+                *self = Self::Error {
+                    maintenance: self.maintenance(),
+                    e: SessionStateError::Mismatch {
+                        expected: "Room",
+                        actual: Box::new(mem::replace(self, Self::Init { maintenance: self.maintenance() })),
+                    },
+                    auto_retry: false,
+                };
+            },
+            latest::ServerMessage::Frozen(new_frozen) => if let Self::Room { frozen, .. } = self {
+                *frozen = new_frozen;
+            } else {
+                *self = Self::Error {
+                    maintenance: self.maintenance(),
+                    e: SessionStateError::Mismatch {
+                        expected: "Room",
+                        actual: Box::new(mem::replace(self, Self::Init { maintenance: self.maintenance() })),
+                    },
+                    auto_retry: false,
+                };
+            },
             latest::ServerMessage::MaintenanceNotice { start, duration } => match self {
                 | Self::Error { maintenance, .. }
                 | Self::Init { maintenance, .. }
@@ -1904,3 +2596,241 @@ pub fn fix_bizhawk_permissions(path: PathBuf) -> Pin<Box<dyn Future<Output = whe
         Ok(())
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn world(world: u8) -> NonZero<u8> {
+        NonZero::new(world).expect("world numbers are nonzero")
+    }
+
+    fn room_session() -> SessionState<Infallible> {
+        let mut state = SessionState::default();
+        state.apply(latest::ServerMessage::EnterLobby { rooms: BTreeMap::from_iter([(1, (format!("test"), false))]) });
+        state.apply(latest::ServerMessage::EnterRoom {
+            room_id: 1,
+            players: Vec::default(),
+            num_unassigned_clients: 0,
+            autodelete_delta: Duration::from_secs(60 * 60 * 24),
+            allow_send_all: true,
+            completions: BTreeMap::default(),
+            triforce_goal: Some(20),
+            triforce_pieces: BTreeMap::default(),
+            start: RoomStart::Started,
+            frozen: false,
+            can_moderate: false,
+        });
+        state
+    }
+
+    #[test]
+    fn goal_not_completed_on_new_file() {
+        let save = oottracker::Save::default();
+        assert!(!goal_completed(&save, None));
+        assert!(!goal_completed(&save, Some(20)));
+    }
+
+    #[test]
+    fn triforce_goal_completed() {
+        let mut save = oottracker::Save::default();
+        save.triforce_pieces = 19;
+        assert!(!goal_completed(&save, Some(20)));
+        save.triforce_pieces = 20;
+        assert!(goal_completed(&save, Some(20)));
+        assert!(!goal_completed(&save, None), "Triforce pieces should only count toward the goal in Triforce Hunt rooms");
+    }
+
+    #[test]
+    fn count_triforce_pieces_by_source_world() {
+        let queue = [
+            Item { source: world(1), key: 1, kind: TRIFORCE_PIECE },
+            Item { source: world(2), key: 2, kind: 0x0040 },
+            Item { source: world(1), key: 3, kind: TRIFORCE_PIECE },
+            Item { source: world(3), key: 4, kind: TRIFORCE_PIECE },
+        ];
+        assert_eq!(triforce_pieces(&queue), BTreeMap::from_iter([(world(1), 2), (world(3), 1)]));
+        assert!(triforce_pieces(&[]).is_empty());
+    }
+
+    fn item(source: u8, key: u64, kind: u16) -> Item {
+        Item { source: world(source), key, kind }
+    }
+
+    fn keys(queue: &[Item]) -> Vec<u64> {
+        queue.iter().map(|item| item.key).collect()
+    }
+
+    #[test]
+    fn revoke_unreceived_item() {
+        let mut base_queue = vec![item(1, 1, 0x0040), item(1, 2, 0x0041), item(2, 3, 0x0042)];
+        let mut player_queues = HashMap::default();
+        let num_received = BTreeMap::from_iter([(world(3), 1)]);
+        revoke_queued_item(&mut base_queue, &mut player_queues, &num_received, world(1), 2, Some(world(3))).expect("item not received yet");
+        assert_eq!(base_queue.len(), 3, "revoking from one world must not change the base queue");
+        assert_eq!(keys(&player_queues[&world(3)]), [1, 3]);
+    }
+
+    #[test]
+    fn revoke_received_item_rejected() {
+        let mut base_queue = vec![item(1, 1, 0x0040), item(1, 2, 0x0041)];
+        let mut player_queues = HashMap::default();
+        let num_received = BTreeMap::from_iter([(world(3), 2)]);
+        assert!(matches!(
+            revoke_queued_item(&mut base_queue, &mut player_queues, &num_received, world(1), 2, Some(world(3))),
+            Err(QueueEditError::AlreadyReceived { position: 1, .. }),
+        ));
+        assert!(player_queues.is_empty(), "a rejected edit must not give the world its own queue");
+    }
+
+    #[test]
+    fn revoke_missing_item_does_not_fork_queue() {
+        let mut base_queue = vec![item(1, 1, 0x0040)];
+        let mut player_queues = HashMap::default();
+        assert!(matches!(
+            revoke_queued_item(&mut base_queue, &mut player_queues, &BTreeMap::default(), world(1), 2, Some(world(3))),
+            Err(QueueEditError::NoSuchItem),
+        ));
+        assert!(player_queues.is_empty());
+    }
+
+    #[test]
+    fn revoke_triforce_piece_from_all_queues() {
+        let mut base_queue = vec![item(1, 1, 0x0040), item(1, 2, TRIFORCE_PIECE)];
+        let mut player_queues = HashMap::from_iter([(world(2), vec![item(1, 2, TRIFORCE_PIECE), item(3, 3, 0x0041)])]);
+        let num_received = BTreeMap::from_iter([(world(2), 0), (world(3), 1)]);
+        revoke_queued_item(&mut base_queue, &mut player_queues, &num_received, world(1), 2, None).expect("no world has received the piece yet");
+        assert_eq!(keys(&base_queue), [1]);
+        assert_eq!(keys(&player_queues[&world(2)]), [3]);
+        assert!(triforce_pieces(&base_queue).is_empty());
+    }
+
+    #[test]
+    fn revoke_triforce_piece_received_by_any_world_rejected() {
+        let mut base_queue = vec![item(1, 2, TRIFORCE_PIECE)];
+        let mut player_queues = HashMap::from_iter([(world(2), vec![item(3, 3, 0x0041), item(1, 2, TRIFORCE_PIECE)])]);
+        let num_received = BTreeMap::from_iter([(world(2), 2), (world(3), 0)]);
+        assert!(matches!(
+            revoke_queued_item(&mut base_queue, &mut player_queues, &num_received, world(1), 2, None),
+            Err(QueueEditError::AlreadyReceived { position: 1, .. }),
+        ));
+        assert_eq!(base_queue.len(), 1, "a rejected edit must not change any queue");
+        assert_eq!(player_queues[&world(2)].len(), 2);
+    }
+
+    #[test]
+    fn grant_item_to_target_queue() {
+        let mut base_queue = vec![item(1, 1, 0x0040)];
+        let mut player_queues = HashMap::default();
+        let key = grant_queued_item(&mut base_queue, &mut player_queues, 0x0041, world(2));
+        assert_eq!(keys(&base_queue), [1], "granting to one world must not change the base queue");
+        assert_eq!(keys(&player_queues[&world(2)]), [1, key]);
+        let Item { source, kind, .. } = player_queues[&world(2)][1];
+        assert_eq!((source, kind), (world(2), 0x0041));
+    }
+
+    #[test]
+    fn grant_triforce_piece_to_all_queues() {
+        let mut base_queue = vec![item(1, 1, TRIFORCE_PIECE)];
+        let mut player_queues = HashMap::from_iter([(world(1), Vec::default())]);
+        let key = grant_queued_item(&mut base_queue, &mut player_queues, TRIFORCE_PIECE, world(2));
+        assert_eq!(keys(&base_queue), [1, key]);
+        assert_eq!(keys(&player_queues[&world(1)]), [key]);
+        assert_eq!(triforce_pieces(&base_queue), BTreeMap::from_iter([(world(1), 1), (world(2), 1)]));
+    }
+
+    #[test]
+    fn tracker_queues_follow_edits() {
+        let mut base_queue = vec![item(1, 1, 0x0040)];
+        let mut player_queues = HashMap::default();
+        let key = grant_queued_item(&mut base_queue, &mut player_queues, 0x0041, world(2));
+        let tracker_keys = |base_queue: &[Item], player_queues: &HashMap<_, _>| tracker_queues(base_queue, player_queues, world(3)).iter().map(|queue| keys(queue)).collect::<Vec<_>>();
+        assert_eq!(tracker_keys(&base_queue, &player_queues), [vec![1], vec![1, key], vec![1]]);
+        revoke_queued_item(&mut base_queue, &mut player_queues, &BTreeMap::default(), world(1), 1, Some(world(2))).expect("item not received yet");
+        assert_eq!(tracker_keys(&base_queue, &player_queues), [vec![1], vec![key], vec![1]]);
+    }
+
+    #[test]
+    fn move_unreceived_items() {
+        let mut queue = vec![item(1, 1, 0x0040), item(1, 2, 0x0041), item(1, 3, 0x0042)];
+        move_queued_item(&mut queue, world(2), 1, 2, 1).expect("positions not received yet");
+        assert_eq!(keys(&queue), [1, 3, 2]);
+        assert!(matches!(move_queued_item(&mut queue, world(2), 1, 1, 0), Err(QueueEditError::AlreadyReceived { position: 0, .. })));
+        assert!(matches!(move_queued_item(&mut queue, world(2), 1, 3, 1), Err(QueueEditError::OutOfRange)));
+        assert_eq!(keys(&queue), [1, 3, 2]);
+    }
+
+    #[test]
+    fn apply_completion_revoked() {
+        let mut state = room_session();
+        state.apply(latest::ServerMessage::WorldCompleted { world: world(1), time: Utc::now() });
+        state.apply(latest::ServerMessage::CompletionRevoked(world(1)));
+        let SessionState::Room { completions, .. } = state else { panic!("expected room state") };
+        assert!(completions.is_empty());
+    }
+
+    #[test]
+    fn apply_world_completed() {
+        let mut state = room_session();
+        let time = Utc::now();
+        state.apply(latest::ServerMessage::WorldCompleted { world: world(2), time });
+        let SessionState::Room { completions, .. } = state else { panic!("expected room state") };
+        assert_eq!(completions, BTreeMap::from_iter([(world(2), time)]));
+    }
+
+    #[test]
+    fn apply_triforce_pieces() {
+        let mut state = room_session();
+        state.apply(latest::ServerMessage::TriforcePieces { world: world(1), count: 3 });
+        state.apply(latest::ServerMessage::TriforcePieces { world: world(1), count: 4 });
+        let SessionState::Room { triforce_goal, triforce_pieces, .. } = state else { panic!("expected room state") };
+        assert_eq!(triforce_goal, Some(20));
+        assert_eq!(triforce_pieces, BTreeMap::from_iter([(world(1), 4)]));
+    }
+
+    #[test]
+    fn only_admins_and_owner_can_moderate() {
+        let metadata = RoomMetadata {
+            owner: Some(42),
+            ..RoomMetadata::default()
+        };
+        assert!(metadata.can_moderate(false, Some(42)));
+        assert!(metadata.can_moderate(true, None));
+        assert!(!metadata.can_moderate(false, Some(43)));
+        assert!(!metadata.can_moderate(false, None));
+        assert!(!RoomMetadata::default().can_moderate(false, None), "rooms created without signing in have no owner");
+    }
+
+    #[test]
+    fn start_countdown_limit() {
+        let now = Utc::now();
+        assert_eq!(countdown_end(now, Duration::from_secs(10)), Some(now + TimeDelta::seconds(10)));
+        assert_eq!(countdown_end(now, MAX_START_COUNTDOWN), Some(now + TimeDelta::hours(1)));
+        assert_eq!(countdown_end(now, MAX_START_COUNTDOWN + Duration::from_secs(1)), None);
+        assert_eq!(countdown_end(now, Duration::MAX), None);
+    }
+
+    #[test]
+    fn countdown_too_long_error() {
+        let mut state = room_session();
+        state.apply(latest::ServerMessage::StructuredError(ServerError::CountdownTooLong));
+        assert!(matches!(state, SessionState::Room { countdown_too_long: true, .. }));
+        state.apply(latest::ServerMessage::StartState(RoomStart::Waiting));
+        assert!(matches!(state, SessionState::Room { countdown_too_long: false, .. }));
+    }
+
+    #[test]
+    fn already_started_error() {
+        let mut state = room_session();
+        state.apply(latest::ServerMessage::StructuredError(ServerError::AlreadyStarted));
+        assert!(matches!(state, SessionState::Room { already_started: true, .. }));
+    }
+
+    #[test]
+    fn world_completed_outside_room_is_mismatch() {
+        let mut state = SessionState::<Infallible>::default();
+        state.apply(latest::ServerMessage::EnterLobby { rooms: BTreeMap::default() });
+        state.apply(latest::ServerMessage::WorldCompleted { world: world(1), time: Utc::now() });
+        assert!(matches!(state, SessionState::Error { e: SessionStateError::Mismatch { expected: "Room", .. }, .. }));
+    }
+}
@@ -0,0 +1,336 @@
+//! State of the app which is kept across restarts, currently the items that were sent while not connected to a room.
+//!
+//! The state is shared by the GUI and `multiworld-headless`, and pending items are scoped to a server, room, and world so that each of them only picks up its own.
+
+use {
+    std::{
+        fs::{
+            self,
+            File,
+        },
+        io::prelude::*,
+        mem,
+        num::NonZeroU8,
+        path::{
+            Path,
+            PathBuf,
+        },
+    },
+    async_proto::Protocol,
+    chrono::prelude::*,
+    log_lock::*,
+    ootr_utils::spoiler::HashIcon,
+};
+#[cfg(unix)] use xdg::BaseDirectories;
+#[cfg(windows)] use directories::ProjectDirs;
+
+const VERSION: u8 = 3;
+
+/// The format used before the file hash of pending items became optional.
+///
+/// Same layout as [`v2`] except that every pending item has a file hash.
+mod v1 {
+    use {
+        std::num::NonZeroU8,
+        async_proto::Protocol,
+        ootr_utils::spoiler::HashIcon,
+    };
+
+    #[derive(Protocol)]
+    pub(super) struct PendingItem {
+        hash: [HashIcon; 5],
+        key: u64,
+        kind: u16,
+        target_world: NonZeroU8,
+    }
+
+    #[derive(Protocol)]
+    pub(super) struct Data {
+        pending_items_before_save: Vec<PendingItem>,
+        pending_items_after_save: Vec<PendingItem>,
+    }
+
+    impl From<PendingItem> for super::v2::PendingItem {
+        fn from(PendingItem { hash, key, kind, target_world }: PendingItem) -> Self {
+            Self { hash: Some(hash), key, kind, target_world }
+        }
+    }
+
+    impl From<Data> for super::v2::Data {
+        fn from(Data { pending_items_before_save, pending_items_after_save }: Data) -> Self {
+            Self {
+                pending_items_before_save: pending_items_before_save.into_iter().map(super::v2::PendingItem::from).collect(),
+                pending_items_after_save: pending_items_after_save.into_iter().map(super::v2::PendingItem::from).collect(),
+            }
+        }
+    }
+}
+
+/// The format used before pending items were scoped to a server and room.
+mod v2 {
+    use {
+        std::num::NonZeroU8,
+        async_proto::Protocol,
+        ootr_utils::spoiler::HashIcon,
+    };
+
+    #[derive(Protocol)]
+    pub(super) struct PendingItem {
+        pub(super) hash: Option<[HashIcon; 5]>,
+        pub(super) key: u64,
+        pub(super) kind: u16,
+        pub(super) target_world: NonZeroU8,
+    }
+
+    #[derive(Protocol)]
+    pub(super) struct Data {
+        pub(super) pending_items_before_save: Vec<PendingItem>,
+        pub(super) pending_items_after_save: Vec<PendingItem>,
+    }
+
+    impl From<PendingItem> for super::PendingItem {
+        fn from(PendingItem { hash, key, kind, target_world }: PendingItem) -> Self {
+            Self { server: None, room: None, world: None, hash, key, kind, target_world }
+        }
+    }
+
+    impl From<Data> for super::Data {
+        fn from(Data { pending_items_before_save, pending_items_after_save }: Data) -> Self {
+            Self {
+                pending_items_before_save: pending_items_before_save.into_iter().map(super::PendingItem::from).collect(),
+                pending_items_after_save: pending_items_after_save.into_iter().map(super::PendingItem::from).collect(),
+            }
+        }
+    }
+}
+
+/// An item that was sent while not connected to a room, to be sent once the room is rejoined.
+#[derive(Debug, Clone, PartialEq, Eq, Protocol)]
+pub struct PendingItem {
+    /// The server the item was meant for. `None` for items saved by versions of the app that didn't record this.
+    pub server: Option<String>,
+    /// The ID and name of the room the player was last in when the item was sent, if any.
+    pub room: Option<(u64, String)>,
+    /// The world of the player who sent the item, if known.
+    pub world: Option<NonZeroU8>,
+    pub hash: Option<[HashIcon; 5]>,
+    pub key: u64,
+    pub kind: u16,
+    pub target_world: NonZeroU8,
+}
+
+impl PendingItem {
+    /// Whether this item should be sent to the given room when it's entered.
+    ///
+    /// Fields that weren't recorded for this item aren't checked.
+    fn belongs_to(&self, server: &str, room_id: u64, world: Option<NonZeroU8>, hash: Option<Option<[HashIcon; 5]>>) -> bool {
+        self.server.as_ref().is_none_or(|item_server| item_server == server)
+        && self.room.as_ref().is_none_or(|&(item_room_id, _)| item_room_id == room_id)
+        && self.sent_by(world, hash)
+    }
+
+    /// Whether this item was sent by the given world, as far as it's known.
+    fn sent_by(&self, world: Option<NonZeroU8>, hash: Option<Option<[HashIcon; 5]>>) -> bool {
+        self.world.zip(world).is_none_or(|(item_world, world)| item_world == world)
+        && hash.is_none_or(|hash| self.hash == hash)
+    }
+}
+
+#[derive(Default, Protocol)]
+pub struct Data {
+    pub pending_items_before_save: Vec<PendingItem>,
+    pub pending_items_after_save: Vec<PendingItem>,
+}
+
+impl Data {
+    /// Removes the pending items which belong to the given room and returns them, split into those from before and after the last save.
+    ///
+    /// Items belonging to other servers, rooms, worlds, or seeds are kept so they can be inspected and discarded by the user.
+    pub fn take_for_room(&mut self, server: &str, room_id: u64, world: Option<NonZeroU8>, hash: Option<Option<[HashIcon; 5]>>) -> (Vec<PendingItem>, Vec<PendingItem>) {
+        let (before_save, other_before_save) = mem::take(&mut self.pending_items_before_save).into_iter().partition(|item| item.belongs_to(server, room_id, world, hash));
+        let (after_save, other_after_save) = mem::take(&mut self.pending_items_after_save).into_iter().partition(|item| item.belongs_to(server, room_id, world, hash));
+        self.pending_items_before_save = other_before_save;
+        self.pending_items_after_save = other_after_save;
+        (before_save, after_save)
+    }
+
+    /// Moves the items sent by the given world to those from before the last save, since its frontend has reported save data.
+    ///
+    /// Items of other worlds are left alone, since a frontend saving says nothing about the items sent by another one connected to the same app.
+    pub fn saved(&mut self, world: Option<NonZeroU8>, hash: Option<Option<[HashIcon; 5]>>) {
+        let (saved, other_after_save) = mem::take(&mut self.pending_items_after_save).into_iter().partition::<Vec<_>, _>(|item| item.sent_by(world, hash));
+        self.pending_items_before_save.extend(saved);
+        self.pending_items_after_save = other_after_save;
+    }
+
+    /// Removes the given items, e.g. because the user has marked them as stale.
+    pub fn discard(&mut self, items: &[PendingItem]) {
+        self.pending_items_before_save.retain(|item| !items.contains(item));
+        self.pending_items_after_save.retain(|item| !items.contains(item));
+    }
+}
+
+#[derive(Default, Clone)]
+pub struct PersistentState(ArcRwLock<Data>);
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error(transparent)] Io(#[from] std::io::Error),
+    #[error(transparent)] Read(#[from] async_proto::ReadError),
+    #[error(transparent)] Wheel(#[from] wheel::Error),
+    #[error(transparent)] Write(#[from] async_proto::WriteError),
+    #[cfg(windows)]
+    #[error("failed to find project folder")]
+    ProjectDirs,
+    #[error("{source}\n\nA copy of the persistent state file has been saved to {}", .path.display())]
+    Quarantined {
+        path: PathBuf,
+        source: Box<Error>,
+    },
+    #[error("persistent state was saved by a newer version of Mido's House Multiworld (format version {0})")]
+    UnknownVersion(u8),
+}
+
+/// Reads the data in the given format version, migrating it to the current version if necessary.
+///
+/// Each old version's `Data` converts into the next version's `Data`, so loading an old file runs through the entire chain.
+fn read_data(version: u8, file: &mut impl Read) -> Result<Data, Error> {
+    Ok(match version {
+        1 => Data::from(v2::Data::from(v1::Data::read_sync(file)?)),
+        2 => v2::Data::read_sync(file)?.into(),
+        VERSION => Data::read_sync(file)?,
+        _ => return Err(Error::UnknownVersion(version)),
+    })
+}
+
+/// Copies a persistent state file that couldn't be loaded next to the original, so its pending items aren't lost when the state is next saved.
+fn quarantine(path: &Path) -> Result<PathBuf, Error> {
+    let mut file_name = path.file_name().unwrap_or_default().to_owned();
+    file_name.push(format!(".{}.broken", Utc::now().format("%Y%m%d%H%M%S")));
+    let quarantine_path = path.with_file_name(file_name);
+    fs::copy(path, &quarantine_path)?;
+    Ok(quarantine_path)
+}
+
+impl PersistentState {
+    pub fn blocking_load() -> Result<Self, Error> {
+        let path = {
+            #[cfg(unix)] {
+                BaseDirectories::new().find_data_file("midos-house/multiworld-state.asyncproto")
+            }
+            #[cfg(windows)] {
+                Some(ProjectDirs::from("net", "Fenhl", "OoTR Multiworld").ok_or(Error::ProjectDirs)?.data_local_dir().join("state.asyncproto"))
+            }
+        };
+        let Some(path) = path else { return Ok(Self::default()) };
+        if !path.exists() { return Ok(Self::default()) }
+        let load = || {
+            let mut file = File::open(&path)?;
+            let version = u8::read_sync(&mut file)?;
+            read_data(version, &mut file)
+        };
+        match load() {
+            Ok(data) => Ok(Self(ArcRwLock::new(data))),
+            Err(source) => match quarantine(&path) {
+                Ok(path) => Err(Error::Quarantined { path, source: Box::new(source) }),
+                Err(_) => Err(source),
+            },
+        }
+    }
+
+    /// Returns a copy of all pending items, for displaying them to the user.
+    pub async fn pending_items(&self) -> Vec<PendingItem> {
+        lock!(@read state = self.0; state.pending_items_before_save.iter().chain(&state.pending_items_after_save).cloned().collect())
+    }
+
+    pub async fn edit<T>(&self, f: impl FnOnce(&mut Data) -> T) -> Result<T, Error> {
+        let output = lock!(@write state = self.0; f(&mut *state));
+        let path = {
+            #[cfg(unix)] {
+                BaseDirectories::new().place_data_file("midos-house/multiworld-state.asyncproto")?
+            }
+            #[cfg(windows)] {
+                let project_dirs = ProjectDirs::from("net", "Fenhl", "OoTR Multiworld").ok_or(Error::ProjectDirs)?;
+                wheel::fs::create_dir_all(project_dirs.data_local_dir()).await?;
+                project_dirs.data_local_dir().join("state.asyncproto")
+            }
+        };
+        let mut file = wheel::fs::File::create(path).await?;
+        VERSION.write(&mut file).await?;
+        lock!(@read state = self.0; state.write(&mut file).await)?;
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A persistent state file in format version 1, with two items from before the last save and one from after.
+    const V1_FIXTURE: &[u8] = include_bytes!("../../../assets/test/persistent-state-v1.asyncproto");
+
+    #[test]
+    fn migrate_v1() {
+        let mut file = V1_FIXTURE;
+        let version = u8::read_sync(&mut file).expect("failed to read format version");
+        assert_eq!(version, 1);
+        let data = read_data(version, &mut file).expect("failed to migrate v1 state");
+        assert!(file.is_empty(), "v1 fixture was not read completely");
+        assert_eq!(data.pending_items_before_save, [
+            PendingItem {
+                server: None,
+                room: None,
+                world: None,
+                hash: Some([HashIcon::DekuStick, HashIcon::DekuNut, HashIcon::Bow, HashIcon::Slingshot, HashIcon::FairyOcarina]),
+                key: 0x0123_4567_89ab_cdef,
+                kind: 0x00ca,
+                target_world: NonZeroU8::new(2).unwrap(),
+            },
+            PendingItem {
+                server: None,
+                room: None,
+                world: None,
+                hash: Some([HashIcon::BigMagic, HashIcon::Map, HashIcon::Compass, HashIcon::BossKey, HashIcon::HeartContainer]),
+                key: 42,
+                kind: 0x0001,
+                target_world: NonZeroU8::new(3).unwrap(),
+            },
+        ]);
+        assert_eq!(data.pending_items_after_save, [
+            PendingItem {
+                server: None,
+                room: None,
+                world: None,
+                hash: Some([HashIcon::MasterSword, HashIcon::MirrorShield, HashIcon::KokiriTunic, HashIcon::HoverBoots, HashIcon::SilverGauntlets]),
+                key: 7,
+                kind: 0x0050,
+                target_world: NonZeroU8::new(1).unwrap(),
+            },
+        ]);
+    }
+
+    #[test]
+    fn saved_per_world() {
+        let item = |world, key| PendingItem {
+            server: Some(format!("mw.example.com")),
+            room: None,
+            world: NonZeroU8::new(world),
+            hash: None,
+            target_world: NonZeroU8::new(3).unwrap(),
+            kind: 0x0001,
+            key,
+        };
+        let mut data = Data {
+            pending_items_before_save: Vec::default(),
+            pending_items_after_save: vec![item(1, 1), item(2, 2), item(0, 3)],
+        };
+        data.saved(NonZeroU8::new(2), Some(None));
+        assert_eq!(data.pending_items_before_save, [item(2, 2), item(0, 3)]);
+        assert_eq!(data.pending_items_after_save, [item(1, 1)]);
+    }
+
+    #[test]
+    fn unknown_version() {
+        assert!(matches!(read_data(VERSION + 1, &mut &[][..]), Err(Error::UnknownVersion(version)) if version == VERSION + 1));
+    }
+}
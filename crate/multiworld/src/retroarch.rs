@@ -0,0 +1,789 @@
+//! The RetroArch frontend, which reads and writes the game's memory using RetroArch's network command interface instead of running code inside the emulator.
+//!
+//! This requires the Mupen64Plus-Next core and “Network Commands” enabled in RetroArch's network settings. The logic mirrors `assets/ootrmw-pj64.js`, except that the player name is read from the save context in RDRAM since SRAM may not be part of the core's memory map.
+
+use {
+    std::{
+        array,
+        net::Ipv4Addr,
+        num::{
+            NonZeroU8,
+            ParseIntError,
+        },
+        sync::Arc,
+        time::Duration,
+    },
+    enum_iterator::all,
+    futures::stream::{
+        self,
+        Stream,
+        StreamExt as _,
+    },
+    itertools::Itertools as _,
+    num_traits::FromPrimitive as _,
+    ootr_utils::spoiler::HashIcon,
+    tokio::{
+        io,
+        net::UdpSocket,
+        sync::mpsc::{
+            self,
+            error::TryRecvError,
+        },
+        time::{
+            Instant,
+            sleep,
+            timeout,
+        },
+    },
+    crate::{
+        Filename,
+        HintArea,
+        OptHintArea,
+        frontend,
+    },
+};
+
+/// The default value of RetroArch's `network_cmd_port` setting.
+pub const DEFAULT_PORT: u16 = 55355;
+/// How long to wait for RetroArch to reply to a command. RetroArch handles network commands once per frame, so this is generous.
+const TIMEOUT: Duration = Duration::from_secs(1);
+/// Same as the interval used by the Project64 4.x script.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// RetroArch reads network commands into a 4 KiB buffer, and each byte takes up 3 characters in a `WRITE_CORE_MEMORY` command.
+const MAX_CHUNK_LEN: usize = 1_024;
+/// Player names in the co-op context may go missing seemingly at random, so they're rewritten regularly, but not on every poll since each write takes a frame.
+const PLAYER_NAMES_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Mupen64Plus-Next maps RDRAM to its KSEG0 addresses, so pointers read from RDRAM can be used as is.
+const SAVE_CONTEXT: u32 = 0x8011_a5d0;
+const RANDO_CONTEXT_POINTER: u32 = 0x801c_6e90 + 0x15d4;
+const CURRENT_SCENE: u32 = 0x801c_8545;
+const STATE_LOGO: u32 = 0x8011_f200;
+const STATE_MAIN: u32 = 0x8011_b92f;
+const STATE_MENU: u32 = 0x801d_8dd5;
+const PAUSE_CONTEXT: u32 = 0x801d_8c00;
+const PAD_HELD: u32 = 0x801c_84b4;
+/// The rewards in the order of the rows of the D-down dungeon info menu. The tracker context has the hint area of each row.
+const REWARD_ROWS: [u8; 9] = [0, 1, 2, 8, 3, 4, 5, 7, 6];
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error(transparent)] Io(#[from] io::Error),
+    #[error(transparent)] ParseInt(#[from] ParseIntError),
+    #[error("RetroArch reported an error: {0}")]
+    Command(String),
+    #[error("failed to decode hash icon")]
+    HashIcon,
+    #[error("randomizer version too old (version 5.1.4 or higher required)")]
+    RandoTooOld,
+    #[error("randomizer version too new (co-op context version {0}; please tell Fenhl that Mido's House Multiworld needs to be updated)")]
+    RandoTooNew(u32),
+    #[error("unexpected reply from RetroArch: {0:?}")]
+    Reply(String),
+    #[error("item sent to world 0")]
+    PlayerId,
+    #[error("RetroArch didn't reply in time")]
+    Timeout,
+}
+
+fn swap_words(buf: &mut [u8]) {
+    for word in buf.chunks_exact_mut(4) {
+        word.reverse();
+    }
+}
+
+fn be_u32(buf: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes([buf[offset], buf[offset + 1], buf[offset + 2], buf[offset + 3]])
+}
+
+fn hint_area_from_dungeon_idx(dungeon_idx: usize) -> OptHintArea {
+    match dungeon_idx {
+        0 => OptHintArea::DekuTree,
+        1 => OptHintArea::DodongosCavern,
+        2 => OptHintArea::JabuJabusBelly,
+        3 => OptHintArea::ForestTemple,
+        4 => OptHintArea::FireTemple,
+        5 => OptHintArea::WaterTemple,
+        6 => OptHintArea::SpiritTemple,
+        7 => OptHintArea::ShadowTemple,
+        8 => OptHintArea::BottomOfTheWell,
+        9 => OptHintArea::IceCavern,
+        10 | 13 => OptHintArea::InsideGanonsCastle,
+        11 => OptHintArea::GerudoTrainingGround,
+        12 => OptHintArea::ThievesHideout,
+        _ => OptHintArea::Unknown,
+    }
+}
+
+/// Tracker context versions before 7 have the hint areas of the dungeon info menu as text.
+fn hint_area_from_name(text: &[u8]) -> OptHintArea {
+    let text = text.split(|&c| c == 0).next().unwrap_or_default();
+    match text.trim_ascii_end() {
+        b"Free" => OptHintArea::Root,
+        b"Hyrule Field" => OptHintArea::HyruleField,
+        b"Lon Lon Ranch" => OptHintArea::LonLonRanch,
+        b"Market" => OptHintArea::Market,
+        b"Temple of Time" => OptHintArea::TempleOfTime,
+        b"Hyrule Castle" => OptHintArea::HyruleCastle,
+        b"Outside Ganon's Castle" => OptHintArea::OutsideGanonsCastle,
+        b"Inside Ganon's Castle" => OptHintArea::InsideGanonsCastle,
+        b"Kokiri Forest" => OptHintArea::KokiriForest,
+        b"Deku Tree" => OptHintArea::DekuTree,
+        b"Lost Woods" => OptHintArea::LostWoods,
+        b"Sacred Forest Meadow" => OptHintArea::SacredForestMeadow,
+        b"Forest Temple" => OptHintArea::ForestTemple,
+        b"Death Mountain Trail" => OptHintArea::DeathMountainTrail,
+        b"Dodongo's Cavern" => OptHintArea::DodongosCavern,
+        b"Goron City" => OptHintArea::GoronCity,
+        b"Death Mountain Crater" => OptHintArea::DeathMountainCrater,
+        b"Fire Temple" => OptHintArea::FireTemple,
+        b"Zora's River" => OptHintArea::ZoraRiver,
+        b"Zora's Domain" => OptHintArea::ZorasDomain,
+        b"Zora's Fountain" => OptHintArea::ZorasFountain,
+        b"Jabu Jabu's Belly" => OptHintArea::JabuJabusBelly,
+        b"Ice Cavern" => OptHintArea::IceCavern,
+        b"Lake Hylia" => OptHintArea::LakeHylia,
+        b"Water Temple" => OptHintArea::WaterTemple,
+        b"Kakariko Village" => OptHintArea::KakarikoVillage,
+        b"Bottom of the Well" => OptHintArea::BottomOfTheWell,
+        b"Graveyard" => OptHintArea::Graveyard,
+        b"Shadow Temple" => OptHintArea::ShadowTemple,
+        b"Gerudo Valley" => OptHintArea::GerudoValley,
+        b"Gerudo's Fortress" => OptHintArea::GerudoFortress,
+        b"Thieves' Hideout" => OptHintArea::ThievesHideout,
+        b"Gerudo Training Ground" => OptHintArea::GerudoTrainingGround,
+        b"Haunted Wasteland" => OptHintArea::HauntedWasteland,
+        b"Desert Colossus" => OptHintArea::DesertColossus,
+        b"Spirit Temple" => OptHintArea::SpiritTemple,
+        _ => OptHintArea::Unknown,
+    }
+}
+
+/// Converts the world and [`OptHintArea`] of each reward, indexed like the tracker context, to a message. Returns `None` if no locations are known, in which case the Project64 script doesn't send anything either.
+fn dungeon_reward_info_message(info: [(u8, u8); 9]) -> Option<frontend::ClientMessage> {
+    let [emerald, ruby, sapphire, forest, fire, water, spirit, shadow, light] = info.map(|(world, area)| Some((
+        NonZeroU8::new(world)?,
+        OptHintArea::from_u8(area).and_then(|area| HintArea::try_from(area).ok())?,
+    )));
+    [emerald, ruby, sapphire, forest, fire, water, spirit, shadow, light].iter().any(Option::is_some).then_some(frontend::ClientMessage::DungeonRewardInfo {
+        emerald, ruby, sapphire, light, forest, fire, water, shadow, spirit,
+    })
+}
+
+fn decode_file_hash([hash1, hash2, hash3, hash4, hash5]: [u8; 5]) -> Result<[HashIcon; 5], Error> {
+    Ok([
+        all().nth(hash1.into()).ok_or(Error::HashIcon)?,
+        all().nth(hash2.into()).ok_or(Error::HashIcon)?,
+        all().nth(hash3.into()).ok_or(Error::HashIcon)?,
+        all().nth(hash4.into()).ok_or(Error::HashIcon)?,
+        all().nth(hash5.into()).ok_or(Error::HashIcon)?,
+    ])
+}
+
+/// Access to the emulated system's memory using the `READ_CORE_MEMORY` and `WRITE_CORE_MEMORY` network commands.
+struct CoreMemory {
+    socket: UdpSocket,
+    /// Whether the core exposes RDRAM as native-endian 32-bit words rather than in the N64's byte order. Mupen64Plus-Next does the former on little-endian hosts.
+    word_swapped: bool,
+}
+
+impl CoreMemory {
+    async fn connect(port: u16) -> Result<Self, Error> {
+        let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await?;
+        socket.connect((Ipv4Addr::LOCALHOST, port)).await?;
+        Ok(Self { socket, word_swapped: false })
+    }
+
+    /// Sends a command and returns the part of the reply after the address.
+    async fn command(&self, name: &str, addr: u32, args: &str) -> Result<String, Error> {
+        self.socket.send(format!("{name} {addr:x} {args}\n").as_bytes()).await?;
+        let mut buf = [0; 8_192];
+        timeout(TIMEOUT, async {
+            loop {
+                let len = self.socket.recv(&mut buf).await?;
+                let reply = String::from_utf8_lossy(&buf[..len]);
+                let Some(rest) = reply.trim_end().strip_prefix(name).and_then(|rest| rest.strip_prefix(' ')) else { continue };
+                let (reply_addr, rest) = rest.split_once(' ').unwrap_or((rest, ""));
+                // skip late replies to commands which timed out
+                if u32::from_str_radix(reply_addr, 16).ok() != Some(addr) { continue }
+                if let Some(msg) = rest.strip_prefix("-1") {
+                    break Err(Error::Command(msg.trim().to_owned()))
+                }
+                break Ok(rest.to_owned())
+            }
+        }).await.map_err(|_| Error::Timeout)?
+    }
+
+    async fn read_raw(&self, addr: u32, buf: &mut [u8]) -> Result<(), Error> {
+        let mut chunk_addr = addr;
+        for chunk in buf.chunks_mut(MAX_CHUNK_LEN) {
+            let reply = self.command("READ_CORE_MEMORY", chunk_addr, &chunk.len().to_string()).await?;
+            let bytes = reply.split_ascii_whitespace().map(|byte| u8::from_str_radix(byte, 16)).collect::<Result<Vec<_>, _>>()?;
+            if bytes.len() != chunk.len() { return Err(Error::Reply(reply)) }
+            chunk.copy_from_slice(&bytes);
+            chunk_addr += MAX_CHUNK_LEN as u32;
+        }
+        Ok(())
+    }
+
+    async fn write_raw(&self, addr: u32, data: &[u8]) -> Result<(), Error> {
+        let mut chunk_addr = addr;
+        for chunk in data.chunks(MAX_CHUNK_LEN) {
+            let reply = self.command("WRITE_CORE_MEMORY", chunk_addr, &chunk.iter().map(|byte| format!("{byte:02x}")).join(" ")).await?;
+            if reply.trim().parse::<usize>()? != chunk.len() { return Err(Error::Reply(reply)) }
+            chunk_addr += MAX_CHUNK_LEN as u32;
+        }
+        Ok(())
+    }
+
+    /// Checks for the magic number at the start of the save data, which is only present while a game is loaded. Also detects the byte order of the core's memory map.
+    async fn find_save_context(&mut self) -> Result<bool, Error> {
+        let mut buf = [0; 8];
+        self.read_raw(SAVE_CONTEXT + 0x1c, &mut buf).await?;
+        if buf.starts_with(b"ZELDAZ") {
+            self.word_swapped = false;
+            return Ok(true)
+        }
+        swap_words(&mut buf);
+        if buf.starts_with(b"ZELDAZ") {
+            self.word_swapped = true;
+            return Ok(true)
+        }
+        Ok(false)
+    }
+
+    /// Reads memory in the N64's byte order.
+    async fn read(&self, addr: u32, buf: &mut [u8]) -> Result<(), Error> {
+        if self.word_swapped {
+            let start = addr & !3;
+            let end = (addr + buf.len() as u32 + 3) & !3;
+            let mut words = vec![0; (end - start) as usize];
+            self.read_raw(start, &mut words).await?;
+            swap_words(&mut words);
+            buf.copy_from_slice(&words[(addr - start) as usize..][..buf.len()]);
+            Ok(())
+        } else {
+            self.read_raw(addr, buf).await
+        }
+    }
+
+    async fn read_array<const N: usize>(&self, addr: u32) -> Result<[u8; N], Error> {
+        let mut buf = [0; N];
+        self.read(addr, &mut buf).await?;
+        Ok(buf)
+    }
+
+    async fn read_u8(&self, addr: u32) -> Result<u8, Error> {
+        Ok(u8::from_be_bytes(self.read_array(addr).await?))
+    }
+
+    async fn read_u16(&self, addr: u32) -> Result<u16, Error> {
+        Ok(u16::from_be_bytes(self.read_array(addr).await?))
+    }
+
+    async fn read_u32(&self, addr: u32) -> Result<u32, Error> {
+        Ok(u32::from_be_bytes(self.read_array(addr).await?))
+    }
+
+    /// Writes memory in the N64's byte order.
+    ///
+    /// If the core's memory map is word-swapped and the range doesn't cover whole words, the surrounding bytes are read first, so they may be overwritten with stale values if the game changes them in between.
+    /// Ranges which share a word with fields of the co-op protocol must therefore be written as whole words.
+    async fn write(&self, addr: u32, data: &[u8]) -> Result<(), Error> {
+        if self.word_swapped {
+            let start = addr & !3;
+            let end = (addr + data.len() as u32 + 3) & !3;
+            let mut words = vec![0; (end - start) as usize];
+            if start != addr || end != addr + data.len() as u32 {
+                self.read(start, &mut words).await?;
+            }
+            words[(addr - start) as usize..][..data.len()].copy_from_slice(data);
+            swap_words(&mut words);
+            self.write_raw(start, &words).await
+        } else {
+            self.write_raw(addr, data).await
+        }
+    }
+
+    /// Reads the dungeon reward locations displayed on the pause screen, like `send_dungeon_reward_location_info` in `ootrmw-pj64.js`.
+    ///
+    /// Returns `None` if the dungeon info menu isn't open. Otherwise, returns the world and [`OptHintArea`] of each reward, indexed like the tracker context (emerald, ruby, sapphire, forest, fire, water, spirit, shadow, light), with world 0 for rewards whose location isn't displayed.
+    async fn dungeon_reward_info(&self, player_id: u8, cosmetics_context: u32, tracker_context: u32) -> Result<Option<[(u8, u8); 9]>, Error> {
+        if tracker_context == 0 { return Ok(None) }
+        // CAN_DRAW_DUNGEON_INFO, checking the pause state first since it's the cheapest condition
+        let pause = self.read_array::<0x46>(PAUSE_CONTEXT + 0x01d4).await?;
+        let pause_state = u16::from_be_bytes([pause[0x00], pause[0x01]]);
+        let pause_changing = u16::from_be_bytes([pause[0x10], pause[0x11]]);
+        let pause_screen_idx = u16::from_be_bytes([pause[0x14], pause[0x15]]);
+        let pause_item_cursor = i16::from_be_bytes([pause[0x44], pause[0x45]]);
+        if pause_state != 6 || pause_screen_idx != 0 || pause_changing != 0 && pause_changing != 3 { return Ok(None) }
+        let pad_held = self.read_u16(PAD_HELD).await?;
+        let d_down_held = pad_held & 0x0400 != 0;
+        let a_held = pad_held & 0x8000 != 0;
+        if !d_down_held && !a_held { return Ok(None) }
+        let tracker = self.read_array::<0x128>(tracker_context).await?;
+        let tracker_version = be_u32(&tracker, 0x00);
+        if tracker_version < 4 { return Ok(None) } // partial functionality is available in older rando versions, but supporting those is not worth the effort of checking rando version to disambiguate tracker context v3
+        if be_u32(&tracker, 0x04) == 0 { return Ok(None) } // CFG_DUNGEON_INFO_ENABLE
+        // not CAN_DRAW_TRADE_DPAD
+        match pause_item_cursor {
+            0x16 if tracker_version < 5 || tracker[0x123] != 0 => return Ok(None), // Z64_SLOT_ADULT_TRADE and CFG_ADULT_TRADE_SHUFFLE
+            0x17 if tracker_version < 5 || tracker[0x124] != 0 => return Ok(None), // Z64_SLOT_CHILD_TRADE and CFG_CHILD_TRADE_SHUFFLE
+            _ => {}
+        }
+        let dpad_dungeon_info_enable = cosmetics_context != 0
+            && self.read_u32(cosmetics_context).await? >= 0x1f07_3fd9
+            && self.read_u8(cosmetics_context + 0x0055).await? != 0;
+        if !(dpad_dungeon_info_enable && d_down_held) && !a_held { return Ok(None) }
+        let reward_enable = be_u32(&tracker, 0x10) != 0;
+        let need_compass = be_u32(&tracker, 0x14);
+        let need_altar = be_u32(&tracker, 0x18) != 0;
+        let altar_flags = self.read_u8(SAVE_CONTEXT + 0x0ef8 + 55).await?;
+        let dungeon_items = self.read_array::<14>(SAVE_CONTEXT + 0x00a8).await?;
+        let show_stones = reward_enable && (!need_altar || altar_flags & 2 != 0);
+        let show_meds = reward_enable && (!need_altar || altar_flags & 1 != 0);
+        let mut info = [(0, OptHintArea::Unknown as u8); 9];
+        if a_held && !(d_down_held && dpad_dungeon_info_enable) {
+            // A menu
+            if be_u32(&tracker, 0x1c) == 0 { return Ok(None) } // CFG_DUNGEON_INFO_REWARD_SUMMARY_ENABLE
+            for (dungeon_idx, &items) in dungeon_items.iter().enumerate() {
+                if need_compass == 0 || items & 2 != 0 {
+                    let reward = tracker[0x20 + dungeon_idx];
+                    if if reward < 3 { show_stones } else { show_meds } {
+                        if let Some(entry) = info.get_mut(usize::from(reward)) {
+                            *entry = (player_id, hint_area_from_dungeon_idx(dungeon_idx) as u8);
+                        }
+                    }
+                }
+            }
+        } else {
+            // D-down menu
+            for (row, reward) in REWARD_ROWS.into_iter().enumerate() {
+                if if row < 3 { show_stones } else { show_meds } {
+                    let display_area = match need_compass {
+                        1 => (0..8).find(|&dungeon_idx| tracker[0x20 + dungeon_idx] == reward).is_none_or(|dungeon_idx| dungeon_items[dungeon_idx] & 2 != 0),
+                        2 => row == 3 || dungeon_items[usize::from(reward)] & 2 != 0,
+                        _ => true,
+                    };
+                    if display_area {
+                        let area = if tracker_version >= 7 { // 8.3.65 Fenhl-2
+                            // 0 means unknown, which wraps around to a value that's not a valid OptHintArea
+                            tracker[0x54 + row].wrapping_sub(1)
+                        } else {
+                            hint_area_from_name(&tracker[0x54 + 0x17 * row..][..0x17]) as u8
+                        };
+                        // always the player's own world until dungeon reward worlds are added to the tracker context
+                        info[usize::from(reward)] = (player_id, area);
+                    }
+                }
+            }
+        }
+        Ok(Some(info))
+    }
+}
+
+struct Session {
+    memory: CoreMemory,
+    rx: mpsc::Receiver<frontend::ServerMessage>,
+    player_id: Option<u8>,
+    player_name: Option<Filename>,
+    /// The last file hash sent to the app, `None` if none was sent yet.
+    file_hash: Option<Option<[u8; 5]>>,
+    player_names: [Filename; 256],
+    progressive_items: [u32; 256],
+    /// When player names and progressive items were last written to the co-op context.
+    player_names_written: Option<Instant>,
+    item_queue: Vec<u16>,
+    normal_gameplay: bool,
+    last_scene: Option<u8>,
+}
+
+impl Session {
+    async fn connect(port: u16) -> Result<(Self, mpsc::Sender<frontend::ServerMessage>), Error> {
+        let mut memory = CoreMemory::connect(port).await?;
+        // make sure RetroArch is reachable and has a memory map before reporting the connection
+        memory.find_save_context().await?;
+        let (tx, rx) = mpsc::channel(1_024);
+        Ok((Self {
+            player_id: None,
+            player_name: None,
+            file_hash: None,
+            player_names: array::from_fn(|world| u8::try_from(world).ok().and_then(NonZeroU8::new).map_or(Filename::DEFAULT, Filename::fallback)),
+            progressive_items: [0; 256],
+            player_names_written: None,
+            item_queue: Vec::default(),
+            normal_gameplay: false,
+            last_scene: None,
+            memory, rx,
+        }, tx))
+    }
+
+    /// Runs one iteration of the logic that `ootrmw-pj64.js` runs every frame. Returns `None` if the app has stopped listening to this frontend.
+    async fn poll(&mut self) -> Result<Option<Vec<frontend::ClientMessage>>, Error> {
+        loop {
+            match self.rx.try_recv() {
+                Ok(frontend::ServerMessage::ItemQueue(queue)) => self.item_queue = queue,
+                Ok(frontend::ServerMessage::GetItem(item)) => self.item_queue.push(item),
+                Ok(frontend::ServerMessage::PlayerName(world, name)) => {
+                    self.player_names[usize::from(world.get())] = name;
+                    self.player_names_written = None;
+                }
+                Ok(frontend::ServerMessage::ProgressiveItems(world, progressive_items)) => {
+                    self.progressive_items[usize::from(world.get())] = progressive_items;
+                    self.player_names_written = None;
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => return Ok(None),
+            }
+        }
+        let mut messages = Vec::default();
+        let mut coop_context = None;
+        let mut progressive_items_enable = false;
+        let mut potsanity3 = false;
+        // the word containing the incoming item, MW_SEND_OWN_ITEMS, and MW_PROGRESSIVE_ITEMS_ENABLE
+        let mut incoming_item_word = [0; 4];
+        if self.memory.find_save_context().await? {
+            let rando_context = self.memory.read_u32(RANDO_CONTEXT_POINTER).await?;
+            // pointers to the co-op, cosmetics, and tracker contexts
+            let context_pointers = if rando_context >= 0x8000_0000 && rando_context != 0xffff_ffff {
+                Some(self.memory.read_array::<16>(rando_context).await?)
+            } else {
+                None
+            };
+            if let Some((addr, context_pointers)) = context_pointers.map(|pointers| (be_u32(&pointers, 0x0), pointers)).filter(|&(addr, _)| addr >= 0x8000_0000 && addr != 0xffff_ffff) {
+                let coop_context_version = self.memory.read_u32(addr).await?;
+                if coop_context_version < 2 { return Err(Error::RandoTooOld) }
+                if coop_context_version > 7 { return Err(Error::RandoTooNew(coop_context_version)) }
+                incoming_item_word = self.memory.read_array(addr + 0x0008).await?;
+                let flags = [
+                    if coop_context_version >= 3 { 1 } else { incoming_item_word[2] }, // enable MW_SEND_OWN_ITEMS for server-side tracking
+                    if coop_context_version >= 5 { 1 } else { incoming_item_word[3] }, // MW_PROGRESSIVE_ITEMS_ENABLE
+                ];
+                // The flags share a word with the incoming item, so they're only written when they're not already set (i.e. when the co-op context is first found, or after a reset) and no item is incoming.
+                // Only the app sets the incoming item, so nothing in this word changes concurrently.
+                if incoming_item_word[2..] != flags && incoming_item_word[..2] == [0, 0] {
+                    incoming_item_word = [0, 0, flags[0], flags[1]];
+                    self.memory.write(addr + 0x0008, &incoming_item_word).await?;
+                }
+                let file_hash = if coop_context_version >= 4 {
+                    Some(self.memory.read_array(addr + 0x0814).await?)
+                } else {
+                    None
+                };
+                if self.file_hash != Some(file_hash) {
+                    messages.push(frontend::ClientMessage::FileHash(file_hash.map(decode_file_hash).transpose()?));
+                    self.file_hash = Some(file_hash);
+                }
+                progressive_items_enable = coop_context_version >= 5;
+                potsanity3 = coop_context_version >= 7;
+                if self.memory.read_u32(SAVE_CONTEXT + 0x135c).await? == 0 { // game mode == gameplay
+                    if !self.normal_gameplay {
+                        messages.push(frontend::ClientMessage::SaveData(self.memory.read_array(SAVE_CONTEXT).await?));
+                        self.normal_gameplay = true;
+                    }
+                } else {
+                    self.normal_gameplay = false;
+                }
+                coop_context = Some(addr);
+                let player_id = self.memory.read_u8(addr + 0x4).await?;
+                if self.player_id != Some(player_id) {
+                    if let Some(player_id) = NonZeroU8::new(player_id) {
+                        messages.push(frontend::ClientMessage::PlayerId(player_id));
+                    }
+                    self.player_id = Some(player_id);
+                    if let Some(player_name) = self.player_name {
+                        self.player_names[usize::from(player_id)] = player_name;
+                    }
+                }
+                if self.normal_gameplay {
+                    if let Some(info) = self.memory.dungeon_reward_info(player_id, be_u32(&context_pointers, 0x4), be_u32(&context_pointers, 0xc)).await? {
+                        messages.extend(dungeon_reward_info_message(info));
+                    }
+                }
+            } else {
+                self.normal_gameplay = false;
+            }
+        } else {
+            self.normal_gameplay = false;
+        }
+        // sync player names
+        let new_player_name = if let Some(addr) = coop_context {
+            // always fill player names in co-op context (some player names may go missing seemingly at random while others stay intact, so this has to be repeated)
+            if self.player_names_written.is_none_or(|written| written.elapsed() >= PLAYER_NAMES_INTERVAL) {
+                self.memory.write(addr + 0x0014, &self.player_names.iter().flat_map(|name| name.0).collect_vec()).await?;
+                // fill progressive items of other players
+                if progressive_items_enable {
+                    self.memory.write(addr + 0x081c, &self.progressive_items.iter().flat_map(|progressive_items| progressive_items.to_be_bytes()).collect_vec()).await?;
+                }
+                self.player_names_written = Some(Instant::now());
+            }
+            Filename(self.memory.read_array(SAVE_CONTEXT + 0x0024).await?)
+        } else {
+            self.player_name.unwrap_or_default()
+        };
+        if self.player_name != Some(new_player_name) {
+            messages.push(frontend::ClientMessage::PlayerName(new_player_name));
+            self.player_name = Some(new_player_name);
+        }
+        if let (Some(player_id), Some(addr)) = (self.player_id, coop_context) {
+            // send item
+            let (outgoing_key_hi, outgoing_key_lo) = if potsanity3 {
+                (self.memory.read_u32(addr + 0x0c1c).await?, self.memory.read_u32(addr + 0x0c20).await?)
+            } else {
+                (0, self.memory.read_u32(addr + 0xc).await?)
+            };
+            if outgoing_key_hi != 0 || outgoing_key_lo != 0 {
+                let kind = self.memory.read_u16(addr + 0x10).await?;
+                let player = self.memory.read_u8(addr + 0x13).await?;
+                if outgoing_key_hi == 0 && outgoing_key_lo == 0xff05ff {
+                    // item sent via network, ignoring
+                } else {
+                    messages.push(frontend::ClientMessage::SendItem {
+                        key: u64::from(outgoing_key_hi) << 32 | u64::from(outgoing_key_lo),
+                        target_world: NonZeroU8::new(player).ok_or(Error::PlayerId)?,
+                        kind,
+                    });
+                }
+                self.memory.write(addr + 0x10, &[0; 4]).await?;
+                if potsanity3 {
+                    self.memory.write(addr + 0x0c1c, &[0; 8]).await?;
+                } else {
+                    self.memory.write(addr + 0xc, &[0; 4]).await?;
+                }
+            }
+            // send current scene
+            let current_scene = self.memory.read_u8(CURRENT_SCENE).await?;
+            if self.normal_gameplay && self.last_scene != Some(current_scene) {
+                messages.push(frontend::ClientMessage::CurrentScene(current_scene));
+                self.last_scene = Some(current_scene);
+            }
+            // receive item
+            let state_logo = self.memory.read_u32(STATE_LOGO).await?;
+            let state_main = self.memory.read_u8(STATE_MAIN).await?;
+            let state_menu = self.memory.read_u8(STATE_MENU).await?;
+            if
+                state_logo != 0x802c_5880 && state_logo != 0 && state_main != 1 && state_main != 2 && state_menu == 0
+                && !(0x2c..=0x33).contains(&current_scene) && current_scene != 0x42 && current_scene != 0x4b // don't receive items in shops to avoid a softlock when buying an item at the same time as receiving one
+                && incoming_item_word[..2] == [0, 0]
+            {
+                let internal_count = self.memory.read_u16(SAVE_CONTEXT + 0x90).await?;
+                if let Some(&item) = self.item_queue.get(usize::from(internal_count)) {
+                    let player = if item == 0xca { if player_id == 1 { 2 } else { 1 } } else { u16::from(player_id) };
+                    // The player is written first since the game may pick up the item in the next frame.
+                    // It shares a word with the player ID, which doesn't change, and the player name ID, which the game doesn't change while no item is incoming.
+                    self.memory.write(addr + 0x6, &player.to_be_bytes()).await?;
+                    let [item_hi, item_lo] = item.to_be_bytes();
+                    self.memory.write(addr + 0x8, &[item_hi, item_lo, incoming_item_word[2], incoming_item_word[3]]).await?;
+                }
+            }
+        }
+        Ok(Some(messages))
+    }
+}
+
+#[derive(Debug)]
+pub enum Event {
+    /// RetroArch is reachable. Messages for the game can be sent using the given channel until [`Event::Error`] is reported.
+    Connected(mpsc::Sender<frontend::ServerMessage>),
+    Message(frontend::ClientMessage),
+    /// Connecting to RetroArch failed or the connection was lost. Connecting is retried after 5 seconds.
+    Error(Arc<Error>),
+}
+
+/// Connects to RetroArch's network command interface on the given port and polls the game's memory for as long as the stream is alive.
+pub fn connect(port: u16, log: Option<frontend::Logger>) -> impl Stream<Item = Event> + Send {
+    enum ConnectionState {
+        Init {
+            is_retry: bool,
+        },
+        Connected(Box<Session>),
+    }
+
+    stream::unfold(ConnectionState::Init { is_retry: false }, move |state| async move {
+        let (events, new_state) = match state {
+            ConnectionState::Init { is_retry } => {
+                if is_retry {
+                    sleep(Duration::from_secs(5)).await;
+                }
+                match Session::connect(port).await {
+                    Ok((session, tx)) => (vec![Event::Connected(tx)], ConnectionState::Connected(Box::new(session))),
+                    Err(e) => {
+                        if let Some(log) = log {
+                            log(format_args!("RetroArch: connection failed: {e:?}"));
+                        }
+                        (vec![Event::Error(Arc::new(e))], ConnectionState::Init { is_retry: true })
+                    }
+                }
+            }
+            ConnectionState::Connected(mut session) => {
+                sleep(POLL_INTERVAL).await;
+                match session.poll().await {
+                    Ok(Some(messages)) => (messages.into_iter().map(Event::Message).collect(), ConnectionState::Connected(session)),
+                    Ok(None) => (Vec::default(), ConnectionState::Init { is_retry: false }),
+                    Err(e) => {
+                        if let Some(log) = log {
+                            log(format_args!("RetroArch: connection lost: {e:?}"));
+                        }
+                        (vec![Event::Error(Arc::new(e))], ConnectionState::Init { is_retry: true })
+                    }
+                }
+            }
+        };
+        Some((stream::iter(events), new_state))
+    }).flatten()
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        std::sync::Mutex,
+        super::*,
+    };
+
+    const RDRAM_START: u32 = 0x8000_0000;
+    const RDRAM_LEN: usize = 0x80_0000;
+    const RANDO_CONTEXT: u32 = 0x8040_0000;
+    const COOP_CONTEXT: u32 = 0x8040_1000;
+
+    /// A stand-in for RetroArch's network command interface, serving the given memory.
+    struct FakeRetroArch {
+        port: u16,
+        word_swapped: bool,
+        rdram: Arc<Mutex<Vec<u8>>>,
+    }
+
+    impl FakeRetroArch {
+        async fn start(word_swapped: bool) -> Self {
+            let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.expect("failed to bind socket");
+            let port = socket.local_addr().expect("failed to get socket address").port();
+            let rdram = Arc::new(Mutex::new(vec![0; RDRAM_LEN]));
+            let task_rdram = Arc::clone(&rdram);
+            tokio::spawn(async move {
+                let mut buf = [0; 8_192];
+                loop {
+                    let (len, addr) = socket.recv_from(&mut buf).await.expect("failed to receive command");
+                    let command = String::from_utf8_lossy(&buf[..len]).into_owned();
+                    let mut args = command.split_ascii_whitespace();
+                    let name = args.next().expect("empty command");
+                    let mem_addr = args.next().expect("missing address");
+                    let start = (u32::from_str_radix(mem_addr, 16).expect("invalid address") - RDRAM_START) as usize;
+                    let reply = {
+                        let mut rdram = task_rdram.lock().expect("RDRAM lock poisoned");
+                        match name {
+                            "READ_CORE_MEMORY" => {
+                                let len = args.next().expect("missing length").parse::<usize>().expect("invalid length");
+                                format!("{name} {mem_addr} {}\n", rdram[start..start + len].iter().map(|byte| format!("{byte:02x}")).join(" "))
+                            }
+                            "WRITE_CORE_MEMORY" => {
+                                let data = args.map(|byte| u8::from_str_radix(byte, 16).expect("invalid byte")).collect_vec();
+                                rdram[start..start + data.len()].copy_from_slice(&data);
+                                format!("{name} {mem_addr} {}\n", data.len())
+                            }
+                            _ => panic!("unexpected command: {command:?}"),
+                        }
+                    };
+                    socket.send_to(reply.as_bytes(), addr).await.expect("failed to send reply");
+                }
+            });
+            Self { port, word_swapped, rdram }
+        }
+
+        fn offset(&self, addr: u32) -> usize {
+            let offset = (addr - RDRAM_START) as usize;
+            if self.word_swapped { offset ^ 3 } else { offset }
+        }
+
+        /// Writes memory in the N64's byte order.
+        fn poke(&self, addr: u32, data: &[u8]) {
+            let mut rdram = self.rdram.lock().expect("RDRAM lock poisoned");
+            for (byte_addr, &byte) in (addr..).zip(data) {
+                rdram[self.offset(byte_addr)] = byte;
+            }
+        }
+
+        /// Reads memory in the N64's byte order.
+        fn peek<const N: usize>(&self, addr: u32) -> [u8; N] {
+            let rdram = self.rdram.lock().expect("RDRAM lock poisoned");
+            array::from_fn(|idx| rdram[self.offset(addr + idx as u32)])
+        }
+
+        /// Sets up a loaded save file of a seed with co-op context version 7, in a state where items can be received.
+        fn load_game(&self) {
+            self.poke(SAVE_CONTEXT + 0x1c, b"ZELDAZ");
+            self.poke(RANDO_CONTEXT_POINTER, &RANDO_CONTEXT.to_be_bytes());
+            self.poke(RANDO_CONTEXT, &COOP_CONTEXT.to_be_bytes());
+            self.poke(COOP_CONTEXT, &7u32.to_be_bytes());
+            self.poke(COOP_CONTEXT + 0x4, &[1]);
+            self.poke(COOP_CONTEXT + 0x0814, &[0, 1, 2, 3, 4]);
+            self.poke(CURRENT_SCENE, &[0x55]);
+            self.poke(STATE_LOGO, &1u32.to_be_bytes());
+        }
+    }
+
+    async fn poll_fake(word_swapped: bool) {
+        let retroarch = FakeRetroArch::start(word_swapped).await;
+        retroarch.load_game();
+        // an item from a previous connection which the game hasn't picked up yet
+        retroarch.poke(COOP_CONTEXT + 0x6, &[0, 1, 0x00, 0x42]);
+        let (mut session, tx) = Session::connect(retroarch.port).await.expect("failed to connect");
+        assert_eq!(session.memory.word_swapped, word_swapped);
+        let messages = session.poll().await.expect("failed to poll").expect("app stopped listening");
+        assert!(messages.iter().any(|msg| matches!(msg, frontend::ClientMessage::PlayerId(world) if world.get() == 1)));
+        assert!(messages.iter().any(|msg| matches!(msg, frontend::ClientMessage::FileHash(Some(_)))));
+        assert!(messages.iter().any(|msg| matches!(msg, frontend::ClientMessage::SaveData(_))));
+        // the flags aren't written while an item is incoming
+        assert_eq!(retroarch.peek::<4>(COOP_CONTEXT + 0x8), [0x00, 0x42, 0, 0]);
+        // the game picks up the item and the player sends one
+        retroarch.poke(COOP_CONTEXT + 0x6, &[0, 0, 0, 0]);
+        retroarch.poke(SAVE_CONTEXT + 0x90, &1u16.to_be_bytes());
+        retroarch.poke(COOP_CONTEXT + 0x0c20, &0x1234u32.to_be_bytes());
+        retroarch.poke(COOP_CONTEXT + 0x10, &[0x00, 0x01, 0x00, 0x02]);
+        tx.send(frontend::ServerMessage::ItemQueue(vec![0x0042, 0x0043])).await.expect("session dropped");
+        let messages = session.poll().await.expect("failed to poll").expect("app stopped listening");
+        assert!(messages.iter().any(|msg| matches!(msg, frontend::ClientMessage::SendItem { key: 0x1234, kind: 0x0001, target_world } if target_world.get() == 2)));
+        assert_eq!(retroarch.peek::<8>(COOP_CONTEXT + 0x0c1c), [0; 8]);
+        assert_eq!(retroarch.peek::<4>(COOP_CONTEXT + 0x10), [0; 4]);
+        // the next item is written along with the flags, without touching the player ID
+        assert_eq!(retroarch.peek::<6>(COOP_CONTEXT + 0x4), [1, 0, 0, 1, 0x00, 0x43]);
+        assert_eq!(retroarch.peek::<2>(COOP_CONTEXT + 0xa), [1, 1]);
+        // the flags aren't rewritten while they're set
+        retroarch.poke(COOP_CONTEXT + 0x8, &[0, 0]);
+        retroarch.poke(SAVE_CONTEXT + 0x90, &2u16.to_be_bytes());
+        assert!(session.poll().await.expect("failed to poll").expect("app stopped listening").is_empty());
+        assert_eq!(retroarch.peek::<4>(COOP_CONTEXT + 0x8), [0, 0, 1, 1]);
+    }
+
+    #[tokio::test]
+    async fn poll() {
+        poll_fake(false).await;
+    }
+
+    #[tokio::test]
+    async fn poll_word_swapped() {
+        poll_fake(true).await;
+    }
+
+    #[tokio::test]
+    async fn dungeon_reward_info() {
+        const TRACKER_CONTEXT: u32 = 0x8040_2000;
+
+        let retroarch = FakeRetroArch::start(false).await;
+        retroarch.load_game();
+        retroarch.poke(RANDO_CONTEXT + 0xc, &TRACKER_CONTEXT.to_be_bytes());
+        retroarch.poke(TRACKER_CONTEXT, &7u32.to_be_bytes());
+        retroarch.poke(TRACKER_CONTEXT + 0x04, &1u32.to_be_bytes()); // CFG_DUNGEON_INFO_ENABLE
+        retroarch.poke(TRACKER_CONTEXT + 0x10, &1u32.to_be_bytes()); // CFG_DUNGEON_INFO_REWARD_ENABLE
+        retroarch.poke(TRACKER_CONTEXT + 0x1c, &1u32.to_be_bytes()); // CFG_DUNGEON_INFO_REWARD_SUMMARY_ENABLE
+        retroarch.poke(TRACKER_CONTEXT + 0x20, &[0xff; 14]);
+        retroarch.poke(TRACKER_CONTEXT + 0x20 + 3, &[8]); // Light Medallion in Forest Temple
+        retroarch.poke(PAUSE_CONTEXT + 0x01d4, &6u16.to_be_bytes());
+        retroarch.poke(PAD_HELD, &0x8000u16.to_be_bytes()); // A
+        let (mut session, _tx) = Session::connect(retroarch.port).await.expect("failed to connect");
+        let messages = session.poll().await.expect("failed to poll").expect("app stopped listening");
+        assert!(messages.iter().any(|msg| matches!(msg, frontend::ClientMessage::DungeonRewardInfo {
+            light: Some((world, HintArea::ForestTemple)),
+            emerald: None,
+            ..
+        } if world.get() == 1)));
+    }
+}
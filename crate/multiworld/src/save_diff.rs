@@ -0,0 +1,165 @@
+//! Incremental save data updates, sent instead of the full save data when only parts of it have changed.
+//!
+//! A diff is a list of [`Range`]s of raw save data which replace the corresponding bytes of the save data the receiving side has from the same sender.
+//! The server receives parsed save data, so it applies diffs to that save data encoded using [`encode`]. This may differ from the sender's copy in bytes which aren't parsed, but those don't affect the result.
+//!
+//! Diffs are only used on two links: from the app to the server (for all frontends), and from BizHawk to the app.
+//! The EverDrive link still sends full save data since its packets are defined by the randomizer, so diffs don't reduce serial traffic. The Project64 script also sends full save data.
+//! Diffs only reduce the amount of data sent, not the work done by the server, which still parses the whole save data and updates the room as if it had received it in full.
+//! If the server can't apply a diff, it replies with [`crate::ws::ServerError::SaveDataDiffFailed`] and the app sends its save data in full again.
+
+use {
+    async_proto::Protocol,
+    oottracker::save::SIZE,
+    serde::Deserialize,
+};
+
+/// The encoded size of a [`Range`] other than its data: a 2-byte offset followed by an 8-byte length.
+const RANGE_OVERHEAD: usize = 10;
+
+/// A range of bytes in the save data which has changed.
+#[derive(Debug, Clone, PartialEq, Eq, Protocol, Deserialize)]
+pub struct Range {
+    pub offset: u16,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error(transparent)] Write(#[from] async_proto::WriteError),
+    #[error("failed to decode save data: {0}")]
+    Decode(String),
+    #[error("encoded save data was {0} bytes long")]
+    EncodedSize(usize),
+    #[error("save data diff range of {len} bytes at offset {offset} exceeds the save data")]
+    OutOfBounds {
+        offset: u16,
+        len: usize,
+    },
+}
+
+/// Returns the ranges in which `new` differs from `old`.
+///
+/// Ranges which are close together are merged if that makes the encoded diff shorter. An empty diff means that nothing has changed.
+pub fn diff(old: &[u8; SIZE], new: &[u8; SIZE]) -> Vec<Range> {
+    let mut ranges = Vec::<Range>::default();
+    let mut end = 0;
+    let mut idx = 0;
+    while idx < SIZE {
+        if old[idx] == new[idx] {
+            idx += 1;
+            continue
+        }
+        let start = idx;
+        while idx < SIZE && old[idx] != new[idx] {
+            idx += 1;
+        }
+        match ranges.last_mut() {
+            Some(last) if start - end <= RANGE_OVERHEAD => last.data.extend_from_slice(&new[end..idx]),
+            _ => ranges.push(Range {
+                offset: u16::try_from(start).expect("save data is less than 64 KiB"),
+                data: new[start..idx].to_vec(),
+            }),
+        }
+        end = idx;
+    }
+    ranges
+}
+
+/// Applies the given diff to the save data. Returns whether anything changed.
+///
+/// If any range is out of bounds, the save data is left unchanged.
+pub fn apply(save_data: &mut [u8; SIZE], diff: &[Range]) -> Result<bool, Error> {
+    if let Some(Range { offset, data }) = diff.iter().find(|Range { offset, data }| usize::from(*offset) + data.len() > SIZE) {
+        return Err(Error::OutOfBounds { offset: *offset, len: data.len() })
+    }
+    let mut changed = false;
+    for Range { offset, data } in diff {
+        let target = &mut save_data[usize::from(*offset)..][..data.len()];
+        if target != &data[..] {
+            target.copy_from_slice(data);
+            changed = true;
+        }
+    }
+    Ok(changed)
+}
+
+/// Converts parsed save data back to raw save data.
+pub fn encode(save: &oottracker::Save) -> Result<Box<[u8; SIZE]>, Error> {
+    let mut buf = Vec::with_capacity(SIZE);
+    save.write_sync(&mut buf)?;
+    let len = buf.len();
+    buf.into_boxed_slice().try_into().map_err(|_| Error::EncodedSize(len))
+}
+
+/// Parses raw save data.
+pub fn decode(save_data: &[u8; SIZE]) -> Result<oottracker::Save, Error> {
+    oottracker::Save::from_save_data(save_data).map_err(|e| Error::Decode(format!("{e:?}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn changed(changes: &[(usize, u8)]) -> Box<[u8; SIZE]> {
+        let mut save_data = Box::new([0; SIZE]);
+        for &(idx, value) in changes {
+            save_data[idx] = value;
+        }
+        save_data
+    }
+
+    #[test]
+    fn diff_apply_round_trip() {
+        let old = changed(&[(0x20, 1), (0x200, 2)]);
+        let new = changed(&[(0, 3), (0x20, 4), (0x24, 5), (0x100, 6), (SIZE - 1, 7)]);
+        let ranges = diff(&old, &new);
+        let mut save_data = old.clone();
+        assert!(apply(&mut save_data, &ranges).expect("diff out of bounds"));
+        assert_eq!(save_data, new);
+        assert!(!apply(&mut save_data, &ranges).expect("diff out of bounds"));
+        assert!(diff(&new, &new).is_empty());
+    }
+
+    #[test]
+    fn merge_close_ranges() {
+        let old = changed(&[]);
+        assert_eq!(diff(&old, &changed(&[(0x10, 1), (0x10 + RANGE_OVERHEAD, 2)])).len(), 1);
+        assert_eq!(diff(&old, &changed(&[(0x10, 1), (0x10 + RANGE_OVERHEAD + 2, 2)])).len(), 2);
+    }
+
+    #[test]
+    fn apply_out_of_bounds() {
+        let mut save_data = changed(&[]);
+        let diff = [
+            Range { offset: 0, data: vec![1] },
+            Range { offset: u16::try_from(SIZE - 1).expect("save data is less than 64 KiB"), data: vec![2, 3] },
+        ];
+        assert!(matches!(apply(&mut save_data, &diff), Err(Error::OutOfBounds { len: 2, .. })));
+        assert_eq!(save_data, changed(&[]));
+    }
+
+    #[test]
+    fn encode_matches_save_data_format() {
+        let mut save = oottracker::Save::default();
+        save.inv_amounts.num_received_mw_items = 5;
+        let save_data = encode(&save).expect("encoded save data must be exactly save::SIZE bytes");
+        let parsed = oottracker::Save::from_save_data(&*save_data).expect("failed to parse encoded save data");
+        assert_eq!(parsed.inv_amounts.num_received_mw_items, 5);
+        assert_eq!(encode(&parsed).expect("failed to encode save data"), save_data);
+    }
+
+    #[test]
+    fn encode_decode_round_trip() {
+        let old_save = oottracker::Save::default();
+        let old = encode(&old_save).expect("failed to encode save data");
+        let reencoded = encode(&decode(&old).expect("failed to decode save data")).expect("failed to encode save data");
+        assert_eq!(reencoded, old);
+        let mut new_save = old_save.clone();
+        new_save.inv_amounts.num_received_mw_items = 3;
+        let new = encode(&new_save).expect("failed to encode save data");
+        let mut save_data = old.clone();
+        assert!(apply(&mut save_data, &diff(&old, &new)).expect("diff out of bounds"));
+        assert_eq!(decode(&save_data).expect("failed to decode save data").inv_amounts.num_received_mw_items, 3);
+    }
+}
@@ -0,0 +1,259 @@
+//! File formats of the dummy frontend, which replays a script of [`frontend::ClientMessage`]s instead of talking to an emulator or console, and records the [`frontend::ServerMessage`]s it receives.
+//!
+//! Both formats are JSON so they can be written and inspected by hand, e.g. to reproduce a bug report. A script looks like this:
+//!
+//! ```json
+//! {
+//!     "version": 1,
+//!     "entries": [
+//!         {"msg": {"type": "player_id", "world": 1}},
+//!         {"msg": {"type": "player_name", "name": "Link"}},
+//!         {"msg": {"type": "file_hash", "hash": ["Deku Stick", "Deku Nut", "Bow", "Slingshot", "Fairy Ocarina"]}},
+//!         {"delay_ms": 5000, "msg": {"type": "send_item", "key": 4660, "kind": 1, "target_world": 2}}
+//!     ]
+//! }
+//! ```
+//!
+//! Hash icons are named as in spoiler logs. Save data is given as the ranges of bytes which aren't zero, see [`save_diff::diff`].
+//!
+//! A recording is a [JSON Lines](https://jsonlines.org/) file whose first line is `{"version": 1}`, followed by one line per received message, e.g. `{"elapsed_ms": 1234, "msg": {"type": "get_item", "item": 1}}`.
+
+use {
+    std::{
+        num::NonZeroU8,
+        time::Duration,
+    },
+    ootr_utils::spoiler::HashIcon,
+    serde::{
+        Deserialize,
+        Serialize,
+    },
+    serde_with::{
+        DisplayFromStr,
+        DurationMilliSeconds,
+        serde_as,
+    },
+    crate::{
+        Filename,
+        FilenameParseError,
+        frontend,
+        save_diff,
+    },
+};
+
+/// The version of the script and recording formats, incremented for incompatible changes.
+pub const VERSION: u8 = 1;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error(transparent)] Filename(#[from] FilenameParseError),
+    #[error(transparent)] Json(#[from] serde_json::Error),
+    #[error(transparent)] SaveDiff(#[from] save_diff::Error),
+    #[error("dummy frontend script has format version {0}, but this version of Mido's House Multiworld only supports version {VERSION}")]
+    Version(u8),
+}
+
+#[derive(Deserialize, Serialize)]
+struct Header {
+    version: u8,
+}
+
+#[derive(Deserialize)]
+struct Script {
+    entries: Vec<ScriptEntryJson>,
+}
+
+#[serde_as]
+#[derive(Deserialize)]
+struct ScriptEntryJson {
+    #[serde_as(as = "DurationMilliSeconds<u64>")]
+    #[serde(rename = "delay_ms", default)]
+    delay: Duration,
+    msg: ScriptMessage,
+}
+
+#[serde_as]
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ScriptMessage {
+    PlayerId {
+        world: NonZeroU8,
+    },
+    PlayerName {
+        #[serde_as(as = "DisplayFromStr")]
+        name: Filename,
+    },
+    SendItem {
+        key: u64,
+        kind: u16,
+        target_world: NonZeroU8,
+    },
+    SaveData {
+        ranges: Vec<save_diff::Range>,
+    },
+    FileHash {
+        hash: Option<[HashIcon; 5]>,
+    },
+    ResetPlayerId,
+    CurrentScene {
+        scene: u8,
+    },
+}
+
+impl TryFrom<ScriptMessage> for frontend::ClientMessage {
+    type Error = Error;
+
+    fn try_from(msg: ScriptMessage) -> Result<Self, Error> {
+        Ok(match msg {
+            ScriptMessage::PlayerId { world } => Self::PlayerId(world),
+            ScriptMessage::PlayerName { name } => Self::PlayerName(name),
+            ScriptMessage::SendItem { key, kind, target_world } => Self::SendItem { key, kind, target_world },
+            ScriptMessage::SaveData { ranges } => {
+                let mut save_data = [0; oottracker::save::SIZE];
+                save_diff::apply(&mut save_data, &ranges)?;
+                Self::SaveData(save_data)
+            }
+            ScriptMessage::FileHash { hash } => Self::FileHash(hash),
+            ScriptMessage::ResetPlayerId => Self::ResetPlayerId,
+            ScriptMessage::CurrentScene { scene } => Self::CurrentScene(scene),
+        })
+    }
+}
+
+/// A message to be sent by the dummy frontend.
+#[derive(Debug, Clone)]
+pub struct ScriptEntry {
+    /// How long to wait after the previous entry (or after connecting, for the first entry) before sending this message.
+    pub delay: Duration,
+    pub msg: frontend::ClientMessage,
+}
+
+/// Reads a dummy frontend script in the format described in the [module docs](self).
+pub fn parse(json: &str) -> Result<Vec<ScriptEntry>, Error> {
+    let Header { version } = serde_json::from_str(json)?;
+    if version != VERSION { return Err(Error::Version(version)) }
+    let Script { entries } = serde_json::from_str(json)?;
+    entries.into_iter()
+        .map(|ScriptEntryJson { delay, msg }| Ok(ScriptEntry { delay, msg: msg.try_into()? }))
+        .collect()
+}
+
+#[serde_as]
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum RecordedServerMessage<'a> {
+    ItemQueue {
+        queue: &'a [u16],
+    },
+    GetItem {
+        item: u16,
+    },
+    PlayerName {
+        world: NonZeroU8,
+        #[serde_as(as = "DisplayFromStr")]
+        name: Filename,
+    },
+    ProgressiveItems {
+        world: NonZeroU8,
+        state: u32,
+    },
+}
+
+impl<'a> From<&'a frontend::ServerMessage> for RecordedServerMessage<'a> {
+    fn from(msg: &'a frontend::ServerMessage) -> Self {
+        match *msg {
+            frontend::ServerMessage::ItemQueue(ref queue) => Self::ItemQueue { queue },
+            frontend::ServerMessage::GetItem(item) => Self::GetItem { item },
+            frontend::ServerMessage::PlayerName(world, name) => Self::PlayerName { world, name },
+            frontend::ServerMessage::ProgressiveItems(world, state) => Self::ProgressiveItems { world, state },
+        }
+    }
+}
+
+#[serde_as]
+#[derive(Serialize)]
+struct RecordedMessage<'a> {
+    #[serde_as(as = "DurationMilliSeconds<u64>")]
+    #[serde(rename = "elapsed_ms")]
+    elapsed: Duration,
+    msg: RecordedServerMessage<'a>,
+}
+
+/// The first line of a recording, including the line break.
+pub fn recording_header() -> Result<String, Error> {
+    Ok(format!("{}\n", serde_json::to_string(&Header { version: VERSION })?))
+}
+
+/// A line of a recording for a message received the given time after connecting, including the line break.
+pub fn recording_line(elapsed: Duration, msg: &frontend::ServerMessage) -> Result<String, Error> {
+    Ok(format!("{}\n", serde_json::to_string(&RecordedMessage { elapsed, msg: msg.into() })?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCRIPT: &str = r#"{
+        "version": 1,
+        "entries": [
+            {"msg": {"type": "player_id", "world": 1}},
+            {"msg": {"type": "player_name", "name": "Link"}},
+            {"msg": {"type": "file_hash", "hash": null}},
+            {"delay_ms": 1500, "msg": {"type": "send_item", "key": 4660, "kind": 1, "target_world": 2}},
+            {"msg": {"type": "save_data", "ranges": [{"offset": 4, "data": [90, 69, 76, 68, 65, 90]}]}}
+        ]
+    }"#;
+
+    #[test]
+    fn parse_script() {
+        let entries = parse(SCRIPT).expect("failed to parse script");
+        assert_eq!(entries.len(), 5);
+        assert!(matches!(entries[0], ScriptEntry { delay, msg: frontend::ClientMessage::PlayerId(world) } if delay.is_zero() && world.get() == 1));
+        assert!(matches!(entries[1].msg, frontend::ClientMessage::PlayerName(name) if name.to_string() == "Link    "));
+        assert_eq!(entries[3].delay, Duration::from_millis(1500));
+        let frontend::ClientMessage::SaveData(ref save_data) = entries[4].msg else { panic!("expected save data") };
+        assert_eq!(&save_data[..12], b"\0\0\0\0ZELDAZ\0\0");
+    }
+
+    #[cfg(feature = "client")]
+    #[test]
+    fn replay_script() {
+        use crate::{
+            bridge::{
+                Bridge,
+                Effect,
+                Room,
+            },
+            ws::latest::ClientMessage,
+        };
+
+        let url = url::Url::parse("wss://mw.example.com/v18").expect("valid URL");
+        let room = Room { id: 1, name: "test", players: &[], item_queue: &[], active: true };
+        let mut bridge = Bridge::default();
+        let mut sent = Vec::default();
+        for ScriptEntry { msg, .. } in parse(SCRIPT).expect("failed to parse script").into_iter().take(4) {
+            for effect in bridge.frontend_message(&url, Some(room), msg).expect("frontend message rejected") {
+                if let Effect::Server(msg) = effect {
+                    sent.push(msg);
+                }
+            }
+        }
+        assert!(matches!(&sent[..], [
+            ClientMessage::PlayerId(_),
+            ClientMessage::PlayerName(_),
+            ClientMessage::FileHash(None),
+            ClientMessage::SendItem { key: 4660, kind: 1, .. },
+        ]));
+    }
+
+    #[test]
+    fn unsupported_version() {
+        assert!(matches!(parse(r#"{"version": 2, "entries": []}"#), Err(Error::Version(2))));
+    }
+
+    #[test]
+    fn record() {
+        let msg = frontend::ServerMessage::GetItem(1);
+        assert_eq!(recording_line(Duration::from_millis(1234), &msg).expect("failed to record"), "{\"elapsed_ms\":1234,\"msg\":{\"type\":\"get_item\",\"item\":1}}\n");
+    }
+}
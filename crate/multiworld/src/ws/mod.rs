@@ -12,6 +12,7 @@ multiworld_derive::latest!();
 pub mod unversioned;
 pub mod v16;
 pub mod v17;
+pub mod v18;
 
 macro_rules! server_errors {
     ($($(#[$attr:meta])* $variant:ident),* $(,)?) => {
@@ -72,6 +73,18 @@ server_errors! {
     /// You sent multiple different kinds of items from the same location.
     #[error("conflicting item kinds from same location")]
     ConflictingItemKinds,
+    /// You tried to start a room before all of its worlds were claimed.
+    #[error("not all worlds in this room have been claimed")]
+    NotAllWorldsClaimed,
+    /// You tried to make a room wait for a start signal after it had already started.
+    #[error("this room has already started")]
+    AlreadyStarted,
+    /// You tried to start a room with a countdown longer than [`crate::MAX_START_COUNTDOWN`].
+    #[error("the start countdown is too long")]
+    CountdownTooLong,
+    /// Your save data diff couldn't be applied, e.g. because the server doesn't have save data from you in this room session. Send your save data in full instead.
+    #[error("failed to apply save data diff")]
+    SaveDataDiffFailed,
 }
 
 impl crate::ClientKind for WebSocket {
@@ -84,6 +97,7 @@ impl crate::ClientKind for WebSocket {
 pub enum Version {
     V16,
     V17,
+    V18,
 }
 
 pub struct VersionedReader {
@@ -97,6 +111,7 @@ impl crate::ClientReader for VersionedReader {
         match self.version {
             Version::V16 => v16::read_owned(self.inner).await.map(|(inner, msg)| (Self { version: Version::V16, inner }, msg)),
             Version::V17 => v17::read_owned(self.inner).await.map(|(inner, msg)| (Self { version: Version::V17, inner }, msg)),
+            Version::V18 => v18::read_owned(self.inner).await.map(|(inner, msg)| (Self { version: Version::V18, inner }, msg)),
         }
     }
 
@@ -104,6 +119,7 @@ impl crate::ClientReader for VersionedReader {
         match self.version {
             Version::V16 => "v16",
             Version::V17 => "v17",
+            Version::V18 => "v18",
         }
     }
 }
@@ -119,6 +135,7 @@ impl crate::ClientWriter for VersionedWriter {
         match self.version {
             Version::V16 => v16::write(&mut self.inner, msg).await,
             Version::V17 => v17::write(&mut self.inner, msg).await,
+            Version::V18 => v18::write(&mut self.inner, msg).await,
         }
     }
 }
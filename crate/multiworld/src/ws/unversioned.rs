@@ -12,6 +12,8 @@ use {
         Filename,
         HintArea,
         Player,
+        RoomStart,
+        save_diff,
         ws::{
             ServerError,
             latest::SpoilerLog,
@@ -97,6 +99,36 @@ pub enum ClientMessage {
     },
     /// Gives info on where the player currently is in the game. Only used for regional voice chat.
     CurrentScene(u8),
+    /// Makes the room hold items until it's started using [`ClientMessage::StartRoom`]. Only works after [`ServerMessage::EnterRoom`].
+    WaitForStart,
+    /// Starts the room after the given countdown if all worlds have been claimed. Only works after [`ServerMessage::EnterRoom`].
+    StartRoom {
+        countdown: Duration,
+    },
+    /// Freezes (`true`) or unfreezes (`false`) item delivery in the room. Only works after [`ServerMessage::EnterRoom`].
+    SetFrozen(bool),
+    /// Removes the item from the given location from the target world's item queue. Only works after [`ServerMessage::AdminLoginSuccess`].
+    RevokeItem {
+        mw_room: u64,
+        source_world: NonZeroU8,
+        key: u64,
+        target_world: NonZeroU8,
+    },
+    /// Adds an item of the given kind to the end of the target world's item queue. Only works after [`ServerMessage::AdminLoginSuccess`].
+    GrantItem {
+        mw_room: u64,
+        kind: u16,
+        target_world: NonZeroU8,
+    },
+    /// Moves an item to a different position in the target world's item queue. Only works after [`ServerMessage::AdminLoginSuccess`].
+    MoveItem {
+        mw_room: u64,
+        target_world: NonZeroU8,
+        from: u64,
+        to: u64,
+    },
+    /// Updates the save data sent using [`ClientMessage::SaveData`] with a [`save_diff`]. Only works after sending `SaveData` in the current room session.
+    SaveDataDiff(Vec<save_diff::Range>),
 }
 
 #[derive(Debug, Clone)]
@@ -127,6 +159,17 @@ pub enum ServerMessage {
         num_unassigned_clients: u8,
         autodelete_delta: Duration,
         allow_send_all: bool,
+        /// For each world that has already completed its goal, the time of completion.
+        completions: BTreeMap<NonZeroU8, DateTime<Utc>>,
+        /// The number of Triforce pieces required to beat the game, if this room is for a Triforce Hunt seed and the goal is known.
+        triforce_goal: Option<u16>,
+        /// For each world in which Triforce pieces have been found, the number of pieces found there.
+        triforce_pieces: BTreeMap<NonZeroU8, u16>,
+        start: RoomStart,
+        /// Whether item delivery is currently frozen.
+        frozen: bool,
+        /// Whether you may start and freeze this room, i.e. whether you're signed in as an admin or as the room's owner.
+        can_moderate: bool,
     },
     /// A previously unassigned world has been taken by a client.
     PlayerId(NonZeroU8),
@@ -180,4 +223,20 @@ pub enum ServerMessage {
         start: DateTime<Utc>,
         duration: Duration,
     },
+    /// A player has completed their goal (defeated Ganon or reached the Triforce Hunt goal), as detected from their save data.
+    WorldCompleted {
+        world: NonZeroU8,
+        time: DateTime<Utc>,
+    },
+    /// The number of Triforce pieces found in the given world has changed.
+    TriforcePieces {
+        world: NonZeroU8,
+        count: u16,
+    },
+    /// The room has started waiting for a start signal, has been started, or the start countdown has elapsed.
+    StartState(RoomStart),
+    /// Item delivery in the room has been frozen (`true`) or unfrozen (`false`).
+    Frozen(bool),
+    /// A previously reported goal completion no longer applies because an admin revoked a Triforce piece that counted toward it.
+    CompletionRevoked(NonZeroU8),
 }
@@ -224,7 +224,7 @@ impl From<unversioned::ServerMessage> for Option<ServerMessage> {
             unversioned::ServerMessage::EnterLobby { rooms } => Some(ServerMessage::EnterLobby { rooms }),
             unversioned::ServerMessage::NewRoom { id, name, password_required } => Some(ServerMessage::NewRoom { id, name, password_required }),
             unversioned::ServerMessage::DeleteRoom(id) => Some(ServerMessage::DeleteRoom(id)),
-            unversioned::ServerMessage::EnterRoom { room_id, players, num_unassigned_clients, autodelete_delta, allow_send_all } => Some(ServerMessage::EnterRoom {
+            unversioned::ServerMessage::EnterRoom { room_id, players, num_unassigned_clients, autodelete_delta, allow_send_all, completions: _, triforce_goal: _, triforce_pieces: _, start: _, frozen: _, can_moderate: _ } => Some(ServerMessage::EnterRoom {
                 players: players.into_iter().map(Player::from).collect(),
                 room_id, num_unassigned_clients, autodelete_delta, allow_send_all,
             }),
@@ -253,6 +253,11 @@ impl From<unversioned::ServerMessage> for Option<ServerMessage> {
             unversioned::ServerMessage::WorldTaken(world) => Some(ServerMessage::WorldTaken(world)),
             unversioned::ServerMessage::WorldFreed => Some(ServerMessage::WorldFreed),
             unversioned::ServerMessage::MaintenanceNotice { start, duration } => Some(ServerMessage::MaintenanceNotice { start, duration }),
+            unversioned::ServerMessage::WorldCompleted { .. } => None,
+            unversioned::ServerMessage::TriforcePieces { .. } => None,
+            unversioned::ServerMessage::StartState(_) => None,
+            unversioned::ServerMessage::Frozen(_) => None,
+            unversioned::ServerMessage::CompletionRevoked(_) => None,
         }
     }
 }
@@ -21,6 +21,7 @@ use {
         Player,
         ws::{
             ServerError,
+            latest,
             unversioned,
         },
     },
@@ -43,6 +44,21 @@ pub struct SpoilerLog {
     pub locations: Vec<BTreeMap<String, ootr_utils::spoiler::Item>>,
 }
 
+impl From<SpoilerLogSettings> for latest::SpoilerLogSettings {
+    fn from(SpoilerLogSettings { keyring_give_bk }: SpoilerLogSettings) -> Self {
+        Self { keyring_give_bk }
+    }
+}
+
+impl From<SpoilerLog> for latest::SpoilerLog {
+    fn from(SpoilerLog { file_hash, version, settings, locations }: SpoilerLog) -> Self {
+        Self {
+            settings: settings.into_iter().map(latest::SpoilerLogSettings::from).collect(),
+            file_hash, version, locations,
+        }
+    }
+}
+
 #[derive(Debug, Protocol)]
 pub enum ClientMessage {
     Ping,
@@ -200,7 +216,7 @@ impl From<unversioned::ServerMessage> for Option<ServerMessage> {
             unversioned::ServerMessage::EnterLobby { rooms } => Some(ServerMessage::EnterLobby { rooms }),
             unversioned::ServerMessage::NewRoom { id, name, password_required } => Some(ServerMessage::NewRoom { id, name, password_required }),
             unversioned::ServerMessage::DeleteRoom(id) => Some(ServerMessage::DeleteRoom(id)),
-            unversioned::ServerMessage::EnterRoom { room_id, players, num_unassigned_clients, autodelete_delta, allow_send_all } => Some(ServerMessage::EnterRoom { room_id, players, num_unassigned_clients, autodelete_delta, allow_send_all }),
+            unversioned::ServerMessage::EnterRoom { room_id, players, num_unassigned_clients, autodelete_delta, allow_send_all, completions: _, triforce_goal: _, triforce_pieces: _, start: _, frozen: _, can_moderate: _ } => Some(ServerMessage::EnterRoom { room_id, players, num_unassigned_clients, autodelete_delta, allow_send_all }),
             unversioned::ServerMessage::PlayerId(world) => Some(ServerMessage::PlayerId(world)),
             unversioned::ServerMessage::ResetPlayerId(world) => Some(ServerMessage::ResetPlayerId(world)),
             unversioned::ServerMessage::ClientConnected => Some(ServerMessage::ClientConnected),
@@ -220,6 +236,11 @@ impl From<unversioned::ServerMessage> for Option<ServerMessage> {
             unversioned::ServerMessage::WorldTaken(world) => Some(ServerMessage::WorldTaken(world)),
             unversioned::ServerMessage::WorldFreed => Some(ServerMessage::WorldFreed),
             unversioned::ServerMessage::MaintenanceNotice { start, duration } => Some(ServerMessage::MaintenanceNotice { start, duration }),
+            unversioned::ServerMessage::WorldCompleted { .. } => None,
+            unversioned::ServerMessage::TriforcePieces { .. } => None,
+            unversioned::ServerMessage::StartState(_) => None,
+            unversioned::ServerMessage::Frozen(_) => None,
+            unversioned::ServerMessage::CompletionRevoked(_) => None,
         }
     }
 }
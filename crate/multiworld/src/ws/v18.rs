@@ -0,0 +1,291 @@
+use {
+    std::{
+        collections::BTreeMap,
+        num::NonZero,
+        time::Duration,
+    },
+    async_proto::Protocol,
+    chrono::prelude::*,
+    futures::{
+        Sink,
+        stream::Stream,
+    },
+    ootr::model::DungeonReward,
+    ootr_utils::spoiler::HashIcon,
+    semver::Version,
+    serde::Deserialize,
+    tokio_tungstenite::tungstenite,
+    crate::{
+        Filename,
+        HintArea,
+        Player,
+        RoomStart,
+        save_diff,
+        ws::{
+            ServerError,
+            unversioned,
+        },
+    },
+};
+
+#[derive(Debug, Deserialize, Protocol)]
+pub struct SpoilerLogSettings {
+    #[serde(default)]
+    pub keyring_give_bk: bool,
+}
+
+#[derive(Debug, Deserialize, Protocol)]
+pub struct SpoilerLog {
+    pub file_hash: [HashIcon; 5],
+    #[serde(rename = ":version")]
+    pub version: ootr_utils::Version,
+    #[serde(deserialize_with = "ootr_utils::spoiler::deserialize_multiworld")]
+    pub settings: Vec<SpoilerLogSettings>,
+    #[serde(deserialize_with = "ootr_utils::spoiler::deserialize_multiworld")]
+    pub locations: Vec<BTreeMap<String, ootr_utils::spoiler::Item>>,
+}
+
+#[derive(Debug, Protocol)]
+pub enum ClientMessage {
+    Ping,
+    JoinRoom {
+        id: u64,
+        password: Option<String>,
+    },
+    CreateRoom {
+        name: String,
+        password: String,
+    },
+    LoginApiKey {
+        api_key: String,
+    },
+    _Unused1,
+    PlayerId(NonZero<u8>),
+    ResetPlayerId,
+    PlayerName(Filename),
+    SendItem {
+        key: u64,
+        kind: u16,
+        target_world: NonZero<u8>,
+    },
+    KickPlayer(NonZero<u8>),
+    DeleteRoom,
+    Track {
+        mw_room: u64,
+        tracker_room_name: String,
+        world_count: NonZero<u8>,
+    },
+    SaveData(oottracker::Save),
+    SendAll {
+        source_world: NonZero<u8>,
+        spoiler_log: SpoilerLog,
+    },
+    SaveDataError {
+        debug: String,
+        version: Version,
+    },
+    FileHash(Option<[HashIcon; 5]>),
+    AutoDeleteDelta(Duration),
+    _Unused2,
+    LoginDiscord {
+        bearer_token: String,
+    },
+    LoginRaceTime {
+        bearer_token: String,
+    },
+    LeaveRoom,
+    DungeonRewardInfo {
+        reward: DungeonReward,
+        world: NonZero<u8>,
+        area: HintArea,
+    },
+    CurrentScene(u8),
+    WaitForStart,
+    StartRoom {
+        countdown: Duration,
+    },
+    SetFrozen(bool),
+    RevokeItem {
+        mw_room: u64,
+        source_world: NonZero<u8>,
+        key: u64,
+        target_world: NonZero<u8>,
+    },
+    GrantItem {
+        mw_room: u64,
+        kind: u16,
+        target_world: NonZero<u8>,
+    },
+    MoveItem {
+        mw_room: u64,
+        target_world: NonZero<u8>,
+        from: u64,
+        to: u64,
+    },
+    SaveDataDiff(Vec<save_diff::Range>),
+}
+
+impl TryFrom<ClientMessage> for unversioned::ClientMessage {
+    type Error = async_proto::ReadError;
+
+    fn try_from(msg: ClientMessage) -> Result<Self, async_proto::ReadError> {
+        Ok(match msg {
+            ClientMessage::Ping => unversioned::ClientMessage::Ping,
+            ClientMessage::JoinRoom { id, password } => unversioned::ClientMessage::JoinRoom { id, password },
+            ClientMessage::CreateRoom { name, password } => unversioned::ClientMessage::CreateRoom { name, password },
+            ClientMessage::LoginApiKey { api_key } => unversioned::ClientMessage::LoginApiKey { api_key },
+            ClientMessage::_Unused1 => return Err(async_proto::ReadError {
+                context: async_proto::ErrorContext::Custom(format!("multiworld: unversioned ClientMessage from v18")),
+                kind: async_proto::ReadErrorKind::Custom(format!("Received ClientMessage::_Unused1. This is a bug in your multiworld client.")),
+            }),
+            ClientMessage::PlayerId(world) => unversioned::ClientMessage::PlayerId(world),
+            ClientMessage::ResetPlayerId => unversioned::ClientMessage::ResetPlayerId,
+            ClientMessage::PlayerName(filename) => unversioned::ClientMessage::PlayerName(filename),
+            ClientMessage::SendItem { key, kind, target_world } => unversioned::ClientMessage::SendItem { key, kind, target_world },
+            ClientMessage::KickPlayer(world) => unversioned::ClientMessage::KickPlayer(world),
+            ClientMessage::DeleteRoom => unversioned::ClientMessage::DeleteRoom,
+            ClientMessage::Track { mw_room, tracker_room_name, world_count } => unversioned::ClientMessage::Track { mw_room, tracker_room_name, world_count },
+            ClientMessage::SaveData(save) => unversioned::ClientMessage::SaveData(save),
+            ClientMessage::SendAll { source_world, spoiler_log } => unversioned::ClientMessage::SendAll { source_world, spoiler_log: spoiler_log.into() },
+            ClientMessage::SaveDataError { debug, version } => unversioned::ClientMessage::SaveDataError { debug, version },
+            ClientMessage::FileHash(hash) => unversioned::ClientMessage::FileHash(hash),
+            ClientMessage::AutoDeleteDelta(delta) => unversioned::ClientMessage::AutoDeleteDelta(delta),
+            ClientMessage::_Unused2 => return Err(async_proto::ReadError {
+                context: async_proto::ErrorContext::Custom(format!("multiworld: unversioned ClientMessage from v18")),
+                kind: async_proto::ReadErrorKind::Custom(format!("Received ClientMessage::_Unused2. This is a bug in your multiworld client.")),
+            }),
+            ClientMessage::LoginDiscord { bearer_token } => unversioned::ClientMessage::LoginDiscord { bearer_token },
+            ClientMessage::LoginRaceTime { bearer_token } => unversioned::ClientMessage::LoginRaceTime { bearer_token },
+            ClientMessage::LeaveRoom => unversioned::ClientMessage::LeaveRoom,
+            ClientMessage::DungeonRewardInfo { reward, world, area } => unversioned::ClientMessage::DungeonRewardInfo { reward, world, area },
+            ClientMessage::CurrentScene(scene) => unversioned::ClientMessage::CurrentScene(scene),
+            ClientMessage::WaitForStart => unversioned::ClientMessage::WaitForStart,
+            ClientMessage::StartRoom { countdown } => unversioned::ClientMessage::StartRoom { countdown },
+            ClientMessage::SetFrozen(frozen) => unversioned::ClientMessage::SetFrozen(frozen),
+            ClientMessage::RevokeItem { mw_room, source_world, key, target_world } => unversioned::ClientMessage::RevokeItem { mw_room, source_world, key, target_world },
+            ClientMessage::GrantItem { mw_room, kind, target_world } => unversioned::ClientMessage::GrantItem { mw_room, kind, target_world },
+            ClientMessage::MoveItem { mw_room, target_world, from, to } => unversioned::ClientMessage::MoveItem { mw_room, target_world, from, to },
+            ClientMessage::SaveDataDiff(diff) => unversioned::ClientMessage::SaveDataDiff(diff),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Protocol)]
+pub enum ServerMessage {
+    Ping,
+    StructuredError(ServerError),
+    OtherError(String),
+    EnterLobby {
+        rooms: BTreeMap<u64, (String, bool)>,
+    },
+    NewRoom {
+        id: u64,
+        name: String,
+        password_required: bool,
+    },
+    DeleteRoom(u64),
+    EnterRoom {
+        room_id: u64,
+        players: Vec<Player>,
+        num_unassigned_clients: u8,
+        autodelete_delta: Duration,
+        allow_send_all: bool,
+        completions: BTreeMap<NonZero<u8>, DateTime<Utc>>,
+        triforce_goal: Option<u16>,
+        triforce_pieces: BTreeMap<NonZero<u8>, u16>,
+        start: RoomStart,
+        frozen: bool,
+        can_moderate: bool,
+    },
+    PlayerId(NonZero<u8>),
+    ResetPlayerId(NonZero<u8>),
+    ClientConnected,
+    PlayerDisconnected(NonZero<u8>),
+    UnregisteredClientDisconnected,
+    PlayerName(NonZero<u8>, Filename),
+    ItemQueue(Vec<u16>),
+    GetItem(u16),
+    AdminLoginSuccess {
+        active_connections: BTreeMap<u64, (Vec<Player>, u8)>,
+    },
+    Goodbye,
+    PlayerFileHash(NonZero<u8>, Option<[HashIcon; 5]>),
+    AutoDeleteDelta(Duration),
+    RoomsEmpty,
+    WrongFileHash {
+        server: Option<[HashIcon; 5]>,
+        client: Option<[HashIcon; 5]>,
+    },
+    ProgressiveItems {
+        world: NonZero<u8>,
+        state: u32,
+    },
+    LoginSuccess,
+    WorldTaken(NonZero<u8>),
+    WorldFreed,
+    MaintenanceNotice {
+        start: DateTime<Utc>,
+        duration: Duration,
+    },
+    WorldCompleted {
+        world: NonZero<u8>,
+        time: DateTime<Utc>,
+    },
+    TriforcePieces {
+        world: NonZero<u8>,
+        count: u16,
+    },
+    StartState(RoomStart),
+    Frozen(bool),
+    CompletionRevoked(NonZero<u8>),
+}
+
+impl From<unversioned::ServerMessage> for Option<ServerMessage> {
+    fn from(msg: unversioned::ServerMessage) -> Self {
+        match msg {
+            unversioned::ServerMessage::Ping => Some(ServerMessage::Ping),
+            unversioned::ServerMessage::StructuredError(e) => Some(ServerMessage::StructuredError(e)),
+            unversioned::ServerMessage::OtherError(e) => Some(ServerMessage::OtherError(e)),
+            unversioned::ServerMessage::EnterLobby { rooms } => Some(ServerMessage::EnterLobby { rooms }),
+            unversioned::ServerMessage::NewRoom { id, name, password_required } => Some(ServerMessage::NewRoom { id, name, password_required }),
+            unversioned::ServerMessage::DeleteRoom(id) => Some(ServerMessage::DeleteRoom(id)),
+            unversioned::ServerMessage::EnterRoom { room_id, players, num_unassigned_clients, autodelete_delta, allow_send_all, completions, triforce_goal, triforce_pieces, start, frozen, can_moderate } => Some(ServerMessage::EnterRoom { room_id, players, num_unassigned_clients, autodelete_delta, allow_send_all, completions, triforce_goal, triforce_pieces, start, frozen, can_moderate }),
+            unversioned::ServerMessage::PlayerId(world) => Some(ServerMessage::PlayerId(world)),
+            unversioned::ServerMessage::ResetPlayerId(world) => Some(ServerMessage::ResetPlayerId(world)),
+            unversioned::ServerMessage::ClientConnected => Some(ServerMessage::ClientConnected),
+            unversioned::ServerMessage::PlayerDisconnected(world) => Some(ServerMessage::PlayerDisconnected(world)),
+            unversioned::ServerMessage::UnregisteredClientDisconnected => Some(ServerMessage::UnregisteredClientDisconnected),
+            unversioned::ServerMessage::PlayerName(world, filename) => Some(ServerMessage::PlayerName(world, filename)),
+            unversioned::ServerMessage::ItemQueue(items) => Some(ServerMessage::ItemQueue(items)),
+            unversioned::ServerMessage::GetItem(item) => Some(ServerMessage::GetItem(item)),
+            unversioned::ServerMessage::AdminLoginSuccess { active_connections } => Some(ServerMessage::AdminLoginSuccess { active_connections }),
+            unversioned::ServerMessage::Goodbye => Some(ServerMessage::Goodbye),
+            unversioned::ServerMessage::PlayerFileHash(world, hash) => Some(ServerMessage::PlayerFileHash(world, hash)),
+            unversioned::ServerMessage::AutoDeleteDelta(delta) => Some(ServerMessage::AutoDeleteDelta(delta)),
+            unversioned::ServerMessage::RoomsEmpty => Some(ServerMessage::RoomsEmpty),
+            unversioned::ServerMessage::WrongFileHash { server, client } => Some(ServerMessage::WrongFileHash { server, client }),
+            unversioned::ServerMessage::ProgressiveItems { world, state } => Some(ServerMessage::ProgressiveItems { world, state }),
+            unversioned::ServerMessage::LoginSuccess => Some(ServerMessage::LoginSuccess),
+            unversioned::ServerMessage::WorldTaken(world) => Some(ServerMessage::WorldTaken(world)),
+            unversioned::ServerMessage::WorldFreed => Some(ServerMessage::WorldFreed),
+            unversioned::ServerMessage::MaintenanceNotice { start, duration } => Some(ServerMessage::MaintenanceNotice { start, duration }),
+            unversioned::ServerMessage::WorldCompleted { world, time } => Some(ServerMessage::WorldCompleted { world, time }),
+            unversioned::ServerMessage::TriforcePieces { world, count } => Some(ServerMessage::TriforcePieces { world, count }),
+            unversioned::ServerMessage::StartState(start) => Some(ServerMessage::StartState(start)),
+            unversioned::ServerMessage::Frozen(frozen) => Some(ServerMessage::Frozen(frozen)),
+            unversioned::ServerMessage::CompletionRevoked(world) => Some(ServerMessage::CompletionRevoked(world)),
+        }
+    }
+}
+
+pub(crate) async fn read_owned<R: Stream<Item = Result<tungstenite::Message, tungstenite::Error>> + Unpin + Send + 'static>(reader: R) -> Result<(R, unversioned::ClientMessage), async_proto::ReadError> {
+    let (reader, msg) = ClientMessage::read_ws_owned021(reader).await?;
+    Ok((reader, msg.try_into()?))
+}
+
+pub(crate) async fn write(writer: &mut (impl Sink<tungstenite::Message, Error = tungstenite::Error> + Unpin + Send), msg: unversioned::ServerMessage) -> Result<(), async_proto::WriteError> {
+    if let Some(msg) = Option::<ServerMessage>::from(msg) {
+        msg.write_ws021(writer).await?;
+    }
+    Ok(())
+}
@@ -172,6 +172,7 @@ unsupported_version!("/v14", v14);
 unsupported_version!("/v15", v15);
 supported_version!("/v16", v16, V16, 16);
 supported_version!("/v17", v17, V17, 17);
+supported_version!("/v18", v18, V18, 18);
 
 #[rocket::catch(404)]
 async fn not_found() -> RawHtml<String> {
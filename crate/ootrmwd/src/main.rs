@@ -3,9 +3,12 @@
 
 use {
     std::{
-        collections::hash_map::{
-            self,
-            HashMap,
+        collections::{
+            BTreeMap,
+            hash_map::{
+                self,
+                HashMap,
+            },
         },
         mem,
         num::NonZero,
@@ -90,6 +93,7 @@ use {
         RoomAuth,
         RoomAvailability,
         RoomMetadata,
+        RoomStart,
         SendAllError,
         ws::{
             ServerError,
@@ -148,6 +152,7 @@ enum SessionError {
     #[error(transparent)] Room(#[from] multiworld::RoomError),
     #[error(transparent)] SendAll(#[from] SendAllError),
     #[error(transparent)] Sql(#[from] sqlx::Error),
+    #[error(transparent)] TryFromInt(#[from] std::num::TryFromIntError),
     #[error(transparent)] Wheel(#[from] wheel::Error),
     #[error(transparent)] Write(#[from] async_proto::WriteError),
     #[error("{0}")]
@@ -168,6 +173,7 @@ impl IsNetworkError for SessionError {
             Self::Room(e) => e.is_network_error(),
             Self::SendAll(e) => e.is_network_error(),
             Self::Sql(_) => false,
+            Self::TryFromInt(_) => false,
             Self::Wheel(e) => e.is_network_error(),
             Self::Write(e) => e.is_network_error(),
             Self::Server(_) => false,
@@ -182,7 +188,7 @@ struct Config {
     regional_vc: bool,
 }
 
-async fn client_session<C: ClientKind>(rng: &SystemRandom, db_pool: PgPool, http_client: reqwest::Client, rooms: Rooms<C>, socket_id: C::SessionId, version: Result<Version, &'static str>, reader: C::Reader, writer: Arc<Mutex<C::Writer>>, shutdown: rocket::Shutdown, maintenance: Arc<watch::Sender<Option<(DateTime<Utc>, Duration)>>>) -> Result<(), SessionError> {
+async fn client_session<C: ClientKind + 'static>(rng: &SystemRandom, db_pool: PgPool, http_client: reqwest::Client, rooms: Rooms<C>, socket_id: C::SessionId, version: Result<Version, &'static str>, reader: C::Reader, writer: Arc<Mutex<C::Writer>>, shutdown: rocket::Shutdown, maintenance: Arc<watch::Sender<Option<(DateTime<Utc>, Duration)>>>) -> Result<(), SessionError> {
     let config = sqlx::query_as!(Config, r#"SELECT verbose_logging_reason IS NOT NULL AS "verbose_logging!", regional_vc FROM mw_config"#).fetch_one(&db_pool).await?;
     let mut maintenance = maintenance.subscribe();
     let ping_writer = Arc::clone(&writer);
@@ -374,6 +380,12 @@ async fn lobby_session<C: ClientKind>(
                                     room_id: room.id,
                                     autodelete_delta: room.autodelete_delta,
                                     allow_send_all: room.allow_send_all,
+                                    completions: room.metadata.completions.clone(),
+                                    triforce_goal: room.metadata.triforce_goal,
+                                    triforce_pieces: room.triforce_pieces(),
+                                    start: room.metadata.start,
+                                    frozen: room.metadata.frozen,
+                                    can_moderate: room.metadata.can_moderate(*logged_in_as_admin, *midos_house_user_id),
                                     players, num_unassigned_clients,
                                 }).await)?;
                                 unlock!();
@@ -414,6 +426,7 @@ async fn lobby_session<C: ClientKind>(
                             writer: Arc::clone(&writer),
                             tracker_state: Default::default(),
                             adjusted_save: Default::default(),
+                            save_data: None,
                             end_tx,
                         });
                         let autodelete_delta = Duration::from_secs(60 * 60 * 24 * 7);
@@ -422,6 +435,11 @@ async fn lobby_session<C: ClientKind>(
                             if !sqlx::query_scalar!(r#"SELECT EXISTS (SELECT 1 FROM mw_rooms WHERE id = $1) AS "exists!""#, id as i64).fetch_one(&db_pool).await? { break id } //TODO save room to database in same transaction
                         };
                         let now = Utc::now();
+                        let metadata = RoomMetadata {
+                            owner: *midos_house_user_id,
+                            ..RoomMetadata::default()
+                        };
+                        let can_moderate = metadata.can_moderate(*logged_in_as_admin, *midos_house_user_id);
                         let room = Room {
                             name: name.clone(),
                             auth: RoomAuth::Password {
@@ -438,8 +456,7 @@ async fn lobby_session<C: ClientKind>(
                             autodelete_tx: lock!(rooms = rooms.0; rooms.autodelete_tx.clone()),
                             db_pool: db_pool.clone(),
                             tracker_state: None,
-                            metadata: RoomMetadata::default(),
-                            id, clients, autodelete_delta,
+                            id, clients, autodelete_delta, metadata,
                         };
                         match rooms.add(room, true).await {
                             Ok(room) => {
@@ -448,6 +465,12 @@ async fn lobby_session<C: ClientKind>(
                                     players: Vec::default(),
                                     num_unassigned_clients: 1,
                                     allow_send_all: true,
+                                    completions: BTreeMap::default(),
+                                    triforce_goal: None,
+                                    triforce_pieces: BTreeMap::default(),
+                                    start: RoomStart::default(),
+                                    frozen: false,
+                                    can_moderate,
                                     autodelete_delta,
                                 }).await)?;
                                 break (reader, room, end_rx)
@@ -551,11 +574,50 @@ async fn lobby_session<C: ClientKind>(
                     ClientMessage::KickPlayer(_) => {}
                     ClientMessage::DeleteRoom => {}
                     ClientMessage::SaveData(_) => error!("received a SaveData message, which only works in a room, but you're in the lobby"),
+                    ClientMessage::SaveDataDiff(_) => error!("received a SaveDataDiff message, which only works in a room, but you're in the lobby"),
                     ClientMessage::SendAll { .. } => error!("received a SendAll message, which only works in a room, but you're in the lobby"),
                     ClientMessage::SaveDataError { .. } => error!("received a SaveDataError message, which only works in a room, but you're in the lobby"),
                     ClientMessage::FileHash(_) => error!("received a FileHash message, which only works in a room, but you're in the lobby"),
                     ClientMessage::AutoDeleteDelta(_) => error!("received an AutoDeleteDelta message, which only works in a room, but you're in the lobby"),
                     ClientMessage::LeaveRoom => {}
+                    ClientMessage::WaitForStart => error!("received a WaitForStart message, which only works in a room, but you're in the lobby"),
+                    ClientMessage::StartRoom { .. } => error!("received a StartRoom message, which only works in a room, but you're in the lobby"),
+                    ClientMessage::SetFrozen(_) => error!("received a SetFrozen message, which only works in a room, but you're in the lobby"),
+                    ClientMessage::RevokeItem { mw_room, source_world, key, target_world } => if *logged_in_as_admin {
+                        lock!(rooms = rooms.0; if let Some(room) = rooms.list.get(&mw_room) {
+                            match lock!(@write room = room; room.revoke_item(source_world, key, target_world, *midos_house_user_id).await) {
+                                Ok(()) => {}
+                                Err(multiworld::RoomError::QueueEdit(e)) => error!("{e}"),
+                                Err(e) => return Err(e.into()),
+                            }
+                        } else {
+                            error!("no such room")
+                        });
+                    } else {
+                        error!("RevokeItem command requires admin login")
+                    },
+                    ClientMessage::GrantItem { mw_room, kind, target_world } => if *logged_in_as_admin {
+                        lock!(rooms = rooms.0; if let Some(room) = rooms.list.get(&mw_room) {
+                            lock!(@write room = room; room.grant_item(kind, target_world, *midos_house_user_id).await)?;
+                        } else {
+                            error!("no such room")
+                        });
+                    } else {
+                        error!("GrantItem command requires admin login")
+                    },
+                    ClientMessage::MoveItem { mw_room, target_world, from, to } => if *logged_in_as_admin {
+                        lock!(rooms = rooms.0; if let Some(room) = rooms.list.get(&mw_room) {
+                            match lock!(@write room = room; room.move_item(target_world, from.try_into()?, to.try_into()?, *midos_house_user_id).await) {
+                                Ok(()) => {}
+                                Err(multiworld::RoomError::QueueEdit(e)) => error!("{e}"),
+                                Err(e) => return Err(e.into()),
+                            }
+                        } else {
+                            error!("no such room")
+                        });
+                    } else {
+                        error!("MoveItem command requires admin login")
+                    },
                     ClientMessage::DungeonRewardInfo { .. } => error!("received a DungeonRewardInfo message, which only works in a room, but you're in the lobby"),
                     ClientMessage::CurrentScene(scene) => if config.regional_vc {
                         if let Some(midos_house_user_id) = *midos_house_user_id {
@@ -631,7 +693,7 @@ impl SessionResultExt for Result<(), SendAllError> {
     }
 }
 
-async fn room_session<C: ClientKind>(
+async fn room_session<C: ClientKind + 'static>(
     rooms: Rooms<C>,
     room: ArcRwLock<Room<C>>,
     socket_id: C::SessionId,
@@ -707,6 +769,14 @@ async fn room_session<C: ClientKind>(
                         rooms.remove(id).await;
                     }
                     ClientMessage::SaveData(save) => lock!(@write room = room; room.set_save_data(socket_id, save).await)?,
+                    ClientMessage::SaveDataDiff(diff) => match lock!(@write room = room; room.apply_save_data_diff(socket_id, diff).await) {
+                        Ok(()) => {}
+                        Err(e @ (multiworld::RoomError::SaveDataDiffWithoutBase | multiworld::RoomError::SaveDiff(_))) => {
+                            eprintln!("failed to apply save data diff from client: {e} ({e:?})");
+                            lock!(writer = writer; writer.write(ServerMessage::StructuredError(ServerError::SaveDataDiffFailed)).await)?;
+                        }
+                        Err(e) => return Err(e.into()),
+                    },
                     ClientMessage::SendAll { source_world, spoiler_log } => lock!(@write room = room; room.send_all(source_world, &spoiler_log, logged_in_as_admin).await)
                         .handle_wrong_file_hash::<C>(&writer).await?,
                     ClientMessage::SaveDataError { debug, version } => if version >= multiworld::version() && lock!(@read room = room; !room.allow_send_all || room.tracker_state.is_some()) { // only report for tournament rooms and tracked rooms, as these errors can also be caused by people playing with glitches
@@ -732,6 +802,31 @@ async fn room_session<C: ClientKind>(
                         }
                         lock!(@write room = room; room.set_current_scene(socket_id, scene).await)?;
                     }
+                    ClientMessage::WaitForStart => if lock!(@read room = room; room.metadata.can_moderate(logged_in_as_admin, midos_house_user_id)) {
+                        if !lock!(@write room = room; room.wait_for_start().await)? {
+                            lock!(writer = writer; writer.write(ServerMessage::StructuredError(ServerError::AlreadyStarted)).await)?;
+                        }
+                    } else {
+                        error!("WaitForStart command requires admin login or room ownership")
+                    },
+                    ClientMessage::StartRoom { countdown } => if lock!(@read room = room; room.metadata.can_moderate(logged_in_as_admin, midos_house_user_id)) {
+                        match lock!(@write room = room; room.start(countdown).await) {
+                            Ok(Some(start)) => schedule_start(room.clone(), start),
+                            Ok(None) => lock!(writer = writer; writer.write(ServerMessage::StructuredError(ServerError::NotAllWorldsClaimed)).await)?,
+                            Err(multiworld::RoomError::CountdownTooLong) => lock!(writer = writer; writer.write(ServerMessage::StructuredError(ServerError::CountdownTooLong)).await)?,
+                            Err(e) => return Err(e.into()),
+                        }
+                    } else {
+                        error!("StartRoom command requires admin login or room ownership")
+                    },
+                    ClientMessage::SetFrozen(frozen) => if lock!(@read room = room; room.metadata.can_moderate(logged_in_as_admin, midos_house_user_id)) {
+                        lock!(@write room = room; room.set_frozen(frozen).await)?;
+                    } else {
+                        error!("SetFrozen command requires admin login or room ownership")
+                    },
+                    ClientMessage::RevokeItem { .. } => error!("received a RevokeItem message, which only works in the lobby, but you're in a room"),
+                    ClientMessage::GrantItem { .. } => error!("received a GrantItem message, which only works in the lobby, but you're in a room"),
+                    ClientMessage::MoveItem { .. } => error!("received a MoveItem message, which only works in the lobby, but you're in a room"),
                 }
                 read = next_message::<C>(reader);
             },
@@ -739,6 +834,19 @@ async fn room_session<C: ClientKind>(
     })
 }
 
+/// Delivers the room's held items once its start countdown has elapsed.
+fn schedule_start<C: ClientKind + 'static>(room: ArcRwLock<Room<C>>, start: DateTime<Utc>) {
+    tokio::spawn(async move {
+        if let Ok(countdown) = (start - Utc::now()).to_std() {
+            sleep(countdown).await;
+        }
+        if let Err(e) = lock!(@write room = room; room.start_if_due().await) {
+            eprintln!("failed to start room: {e} ({e:?})");
+            let _ = wheel::night_report("/games/zelda/oot/mhmw/error", Some(&format!("failed to start room: {e} ({e:?})"))).await;
+        }
+    });
+}
+
 #[derive(Derivative)]
 #[derivative(Clone(bound = ""))]
 enum RoomListChange<C: ClientKind> {
@@ -963,6 +1071,12 @@ enum Error {
     #[error("error while creating room")]
     CreateRoom,
     #[cfg(unix)]
+    #[error("error while freezing or unfreezing room")]
+    Freeze,
+    #[cfg(unix)]
+    #[error("error while starting room")]
+    StartRoom,
+    #[cfg(unix)]
     #[error("error while waiting until inactive")]
     WaitUntilInactive,
 }
@@ -1041,6 +1155,12 @@ async fn main(Args { database, port, subcommand }: Args) -> Result<(), Error> {
                 Subcommand::CreateTournamentRoom { .. } | Subcommand::CreateEndOfSeasonRoom { .. } => if !bool::read(&mut sock).await? {
                     return Err(Error::CreateRoom)
                 },
+                Subcommand::StartRoom { .. } => if !bool::read(&mut sock).await? {
+                    return Err(Error::StartRoom)
+                },
+                Subcommand::Freeze { .. } | Subcommand::Unfreeze { .. } => if !bool::read(&mut sock).await? {
+                    return Err(Error::Freeze)
+                },
             }
             return Ok(())
         }
@@ -1096,7 +1216,9 @@ async fn main(Args { database, port, subcommand }: Args) -> Result<(), Error> {
                     tracker_state: None,
                     metadata: row.metadata.0,
                 }, false).await {
-                    Ok(_) => {}
+                    Ok(room) => if let RoomStart::Countdown(start) = lock!(@read room = room; room.metadata.start) {
+                        schedule_start(room, start);
+                    },
                     Err(AddRoomError::Sql(e)) => return Err(e.into()),
                     Err(e @ (AddRoomError::DuplicateId { .. } | AddRoomError::NameConflict { .. })) => {
                         eprintln!("deleting duplicate room {:?}: {e} ({e:?})", row.name);
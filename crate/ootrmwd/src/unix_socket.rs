@@ -20,7 +20,10 @@ use {
         self,
         TryFutureExt as _,
     },
-    log_lock::lock,
+    log_lock::{
+        ArcRwLock,
+        lock,
+    },
     ootr_utils::spoiler::HashIcon,
     rand::{
         prelude::*,
@@ -46,11 +49,13 @@ use {
         Room,
         RoomAuth,
         RoomMetadata,
+        RoomStart,
         WaitUntilInactiveMessage,
     },
     crate::{
         RoomListChange,
         Rooms,
+        schedule_start,
     },
 };
 
@@ -72,6 +77,10 @@ pub(crate) enum ClientMessage {
         players: Vec<u64>,
         #[clap(long)]
         tracker_room_name: Option<String>,
+        #[clap(long)]
+        triforce_goal: Option<u16>,
+        #[clap(long)]
+        wait_for_start: bool,
     },
     PrepareRestart {
         #[clap(long)]
@@ -88,9 +97,36 @@ pub(crate) enum ClientMessage {
         world_count: Option<NonZero<u8>>,
         #[clap(long)]
         tracker_room_name: Option<String>,
+        #[clap(long)]
+        triforce_goal: Option<u16>,
+        #[clap(long)]
+        wait_for_start: bool,
+    },
+    StartRoom {
+        name: String,
+        #[clap(long, default_value_t = 10)]
+        countdown_secs: u64,
+    },
+    Freeze {
+        name: String,
+    },
+    Unfreeze {
+        name: String,
     },
 }
 
+/// Finds a tournament or end-of-season room, i.e. a room created via this socket, by name.
+async fn find_room<C: ClientKind>(rooms: &Rooms<C>, name: &str) -> Option<ArcRwLock<Room<C>>> {
+    lock!(rooms = rooms.0; {
+        for room in rooms.list.values() {
+            if lock!(@read room = room; room.name == name && !matches!(room.auth, RoomAuth::Password { .. })) {
+                return Some(room.clone())
+            }
+        }
+        None
+    })
+}
+
 pub(crate) async fn listen<C: ClientKind + 'static>(db_pool: PgPool, rooms: Rooms<C>, mut shutdown: rocket::Shutdown, maintenance: Arc<watch::Sender<Option<(DateTime<Utc>, Duration)>>>) -> wheel::Result<()> {
     fs::remove_file(PATH).await.missing_ok()?;
     let listener = UnixListener::bind(PATH).at(PATH)?;
@@ -177,7 +213,7 @@ pub(crate) async fn listen<C: ClientKind + 'static>(db_pool: PgPool, rooms: Room
                             WaitUntilInactiveMessage::Inactive.write(&mut sock).await.expect("error writing to UNIX socket");
                             return
                         }
-                        ClientMessage::CreateTournamentRoom { name, hash1, hash2, hash3, hash4, hash5, players, tracker_room_name } => {
+                        ClientMessage::CreateTournamentRoom { name, hash1, hash2, hash3, hash4, hash5, players, tracker_room_name, triforce_goal, wait_for_start } => {
                             let id = loop {
                                 let id = rng().random::<u64>();
                                 match sqlx::query_scalar!(r#"SELECT EXISTS (SELECT 1 FROM mw_rooms WHERE id = $1) AS "exists!""#, id as i64).fetch_one(&db_pool).await {
@@ -205,7 +241,11 @@ pub(crate) async fn listen<C: ClientKind + 'static>(db_pool: PgPool, rooms: Room
                                 autodelete_tx: lock!(rooms = rooms.0; rooms.autodelete_tx.clone()),
                                 db_pool: db_pool.clone(),
                                 tracker_state: None,
-                                metadata: RoomMetadata::default(),
+                                metadata: RoomMetadata {
+                                    start: if wait_for_start { RoomStart::Waiting } else { RoomStart::Started },
+                                    triforce_goal, world_count,
+                                    ..RoomMetadata::default()
+                                },
                                 id, name,
                             };
                             rooms.add(room, true).and_then(|room| async move {
@@ -220,7 +260,7 @@ pub(crate) async fn listen<C: ClientKind + 'static>(db_pool: PgPool, rooms: Room
                                 })
                             }).await.unwrap_or_default().write(&mut sock).await.expect("error writing to UNIX socket");
                         }
-                        ClientMessage::CreateEndOfSeasonRoom { name, hash1, hash2, hash3, hash4, hash5, world_count, tracker_room_name } => {
+                        ClientMessage::CreateEndOfSeasonRoom { name, hash1, hash2, hash3, hash4, hash5, world_count, tracker_room_name, triforce_goal, wait_for_start } => {
                             let id = loop {
                                 let id = rng().random::<u64>();
                                 match sqlx::query_scalar!(r#"SELECT EXISTS (SELECT 1 FROM mw_rooms WHERE id = $1) AS "exists!""#, id as i64).fetch_one(&db_pool).await {
@@ -247,7 +287,11 @@ pub(crate) async fn listen<C: ClientKind + 'static>(db_pool: PgPool, rooms: Room
                                 autodelete_tx: lock!(rooms = rooms.0; rooms.autodelete_tx.clone()),
                                 db_pool: db_pool.clone(),
                                 tracker_state: None,
-                                metadata: RoomMetadata::default(),
+                                metadata: RoomMetadata {
+                                    start: if wait_for_start { RoomStart::Waiting } else { RoomStart::Started },
+                                    triforce_goal, world_count,
+                                    ..RoomMetadata::default()
+                                },
                                 id, name,
                             };
                             rooms.add(room, true).and_then(|room| async move {
@@ -258,6 +302,29 @@ pub(crate) async fn listen<C: ClientKind + 'static>(db_pool: PgPool, rooms: Room
                                 })
                             }).await.unwrap_or_default().write(&mut sock).await.expect("error writing to UNIX socket");
                         }
+                        ClientMessage::StartRoom { name, countdown_secs } => {
+                            let started = if let Some(room) = find_room(&rooms, &name).await {
+                                match lock!(@write room = room; room.start(Duration::from_secs(countdown_secs)).await) {
+                                    Ok(Some(start)) => {
+                                        schedule_start(room, start);
+                                        true
+                                    }
+                                    Ok(None) | Err(_) => false,
+                                }
+                            } else {
+                                false
+                            };
+                            started.write(&mut sock).await.expect("error writing to UNIX socket");
+                        }
+                        ClientMessage::Freeze { ref name } | ClientMessage::Unfreeze { ref name } => {
+                            let frozen = matches!(msg, ClientMessage::Freeze { .. });
+                            let success = if let Some(room) = find_room(&rooms, name).await {
+                                lock!(@write room = room; room.set_frozen(frozen).await).is_ok()
+                            } else {
+                                false
+                            };
+                            success.write(&mut sock).await.expect("error writing to UNIX socket");
+                        }
                         ClientMessage::PrepareRestart { async_proto: _ } => {
                             let mut deadline = Utc::now() + TimeDelta::try_days(1).expect("1-day timedelta out of bounds"); //TODO allow explicitly specifying the deadline (e.g. in case a shorter-notice but not immediate restart is needed)
                             loop {